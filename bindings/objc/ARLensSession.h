@@ -0,0 +1,71 @@
+// Objective-C wrapper around the ARLens C API for host apps that are not
+// using Swift. Errors are reported through NSError in ARLensErrorDomain.
+
+#import <Foundation/Foundation.h>
+#import <simd/simd.h>
+
+NS_ASSUME_NONNULL_BEGIN
+
+extern NSErrorDomain const ARLensErrorDomain;
+
+typedef NS_ERROR_ENUM(ARLensErrorDomain, ARLensErrorCode) {
+    ARLensErrorNotInitialized = 1,
+    ARLensErrorInvalidObject = 2,
+    ARLensErrorCancelled = 3,
+    ARLensErrorFailed = 4,
+    // A session quota (see set_quota) refused the addition
+    ARLensErrorQuotaExceeded = 5,
+};
+
+// Matches the integer object type codes of place_virtual_object
+typedef NS_ENUM(int32_t, ARLensObjectType) {
+    ARLensObjectTypeCube = 0,
+    ARLensObjectTypeSphere = 1,
+};
+
+@interface ARLensSession : NSObject
+
+// Creating a session (re)initializes the process-wide Rust session, which is
+// shared with the C and Swift APIs
+- (instancetype)init NS_DESIGNATED_INITIALIZER;
+
+@property (nonatomic, readonly) NSInteger planeCount;
+@property (nonatomic, readonly) NSInteger objectCount;
+
+- (void)updateCameraPosition:(simd_float3)position;
+
+// Adds a plane, or updates the one with the same identifier. Returns NO if the
+// plane is new and the plane quota is full
+- (BOOL)addPlaneWithIdentifier:(nullable NSString *)identifier
+                        center:(simd_float3)center
+                         width:(float)width
+                        height:(float)height
+                        normal:(simd_float3)normal
+                         error:(NSError **)error;
+
+// Returns the new object's index, or NSNotFound on failure (ARLensErrorQuotaExceeded
+// if the object quota is full)
+- (NSInteger)placeObjectOfType:(ARLensObjectType)type
+                      position:(simd_float3)position
+                      rotation:(simd_quatf)rotation
+                         error:(NSError **)error;
+
+- (BOOL)removeObjectAtIndex:(NSInteger)index error:(NSError **)error;
+
+// Returns the distance in meters, or a negative value on failure
+- (float)distanceBetweenObjectAtIndex:(NSInteger)first
+                     andObjectAtIndex:(NSInteger)second
+                                error:(NSError **)error;
+
+// Writes the session to `url` as JSON in the background. The completion handler
+// is called on the main queue. Returns a token for -cancelOperation:, or 0 if the
+// export could not be started (the handler is still called with an error)
+- (uint64_t)exportSnapshotToURL:(NSURL *)url
+                     completion:(void (^)(NSError *_Nullable error))completion;
+
+// Returns NO if the operation already finished or the token is unknown
+- (BOOL)cancelOperation:(uint64_t)token;
+
+@end
+
+NS_ASSUME_NONNULL_END
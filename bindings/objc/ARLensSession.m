@@ -0,0 +1,140 @@
+#import "ARLensSession.h"
+#import "arlens.h"
+
+NSErrorDomain const ARLensErrorDomain = @"ARLensErrorDomain";
+
+static NSError *ARLensMakeError(ARLensErrorCode code, NSString *description) {
+    return [NSError errorWithDomain:ARLensErrorDomain
+                               code:code
+                           userInfo:@{NSLocalizedDescriptionKey: description}];
+}
+
+static void ARLensSetError(NSError **error, ARLensErrorCode code, NSString *description) {
+    if (error != NULL) {
+        *error = ARLensMakeError(code, description);
+    }
+}
+
+// Receives the retained completion block as context and releases it
+static void ARLensCompletion(void *context, int32_t status, const char *payload) {
+    void (^completion)(NSError *_Nullable) = (__bridge_transfer void (^)(NSError *_Nullable))context;
+
+    NSError *error = nil;
+    if (status == AR_STATUS_CANCELLED) {
+        error = ARLensMakeError(ARLensErrorCancelled, @"The operation was cancelled.");
+    } else if (status != AR_STATUS_OK) {
+        NSString *message = payload != NULL ? @(payload) : @"The operation failed.";
+        error = ARLensMakeError(ARLensErrorFailed, message);
+    }
+
+    dispatch_async(dispatch_get_main_queue(), ^{
+        completion(error);
+    });
+}
+
+@implementation ARLensSession
+
+- (instancetype)init {
+    self = [super init];
+    if (self) {
+        ios_main();
+    }
+    return self;
+}
+
+- (NSInteger)planeCount {
+    int32_t planes = 0;
+    get_session_stats(&planes, NULL);
+    return planes;
+}
+
+- (NSInteger)objectCount {
+    int32_t objects = 0;
+    get_session_stats(NULL, &objects);
+    return objects;
+}
+
+- (void)updateCameraPosition:(simd_float3)position {
+    update_camera_position(position.x, position.y, position.z);
+}
+
+- (BOOL)addPlaneWithIdentifier:(NSString *)identifier
+                        center:(simd_float3)center
+                         width:(float)width
+                        height:(float)height
+                        normal:(simd_float3)normal
+                         error:(NSError **)error {
+    // The session exists for as long as this object does, so a refusal is the quota
+    if (!add_detected_plane(identifier.UTF8String,
+                            center.x, center.y, center.z,
+                            width, height,
+                            normal.x, normal.y, normal.z)) {
+        ARLensSetError(error, ARLensErrorQuotaExceeded, @"The plane quota is full.");
+        return NO;
+    }
+    return YES;
+}
+
+- (NSInteger)placeObjectOfType:(ARLensObjectType)type
+                      position:(simd_float3)position
+                      rotation:(simd_quatf)rotation
+                         error:(NSError **)error {
+    int32_t index = place_virtual_object(type,
+                                         position.x, position.y, position.z,
+                                         rotation.vector.x, rotation.vector.y,
+                                         rotation.vector.z, rotation.vector.w);
+    if (index == AR_QUOTA_EXCEEDED) {
+        ARLensSetError(error, ARLensErrorQuotaExceeded, @"The object quota is full.");
+        return NSNotFound;
+    }
+    if (index < 0) {
+        ARLensSetError(error, ARLensErrorNotInitialized, @"The AR session is not initialized.");
+        return NSNotFound;
+    }
+    return index;
+}
+
+- (BOOL)removeObjectAtIndex:(NSInteger)index error:(NSError **)error {
+    if (index < 0 || index > INT32_MAX || !remove_virtual_object((int32_t)index)) {
+        ARLensSetError(error, ARLensErrorInvalidObject,
+                       [NSString stringWithFormat:@"No object at index %ld.", (long)index]);
+        return NO;
+    }
+    return YES;
+}
+
+- (float)distanceBetweenObjectAtIndex:(NSInteger)first
+                     andObjectAtIndex:(NSInteger)second
+                                error:(NSError **)error {
+    float distance = -1.0f;
+    if (first >= 0 && first <= INT32_MAX && second >= 0 && second <= INT32_MAX) {
+        distance = get_object_distance((int32_t)first, (int32_t)second);
+    }
+    if (distance < 0.0f) {
+        ARLensSetError(error, ARLensErrorInvalidObject,
+                       [NSString stringWithFormat:@"No objects at indices %ld and %ld.",
+                                                  (long)first, (long)second]);
+    }
+    return distance;
+}
+
+- (uint64_t)exportSnapshotToURL:(NSURL *)url
+                     completion:(void (^)(NSError *_Nullable error))completion {
+    void *context = (__bridge_retained void *)[completion copy];
+    uint64_t token = export_snapshot_async(url.fileSystemRepresentation, ARLensCompletion, context);
+    if (token == 0) {
+        // Rust never took ownership of the block; release it and report here
+        void (^block)(NSError *_Nullable) = (__bridge_transfer void (^)(NSError *_Nullable))context;
+        NSError *error = ARLensMakeError(ARLensErrorNotInitialized, @"The AR session is not initialized.");
+        dispatch_async(dispatch_get_main_queue(), ^{
+            block(error);
+        });
+    }
+    return token;
+}
+
+- (BOOL)cancelOperation:(uint64_t)token {
+    return cancel_operation(token);
+}
+
+@end
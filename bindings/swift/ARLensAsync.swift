@@ -0,0 +1,120 @@
+import Foundation
+
+// async/await wrappers over the completion-callback C API (see src/ops.rs).
+// Requires include/arlens.h in the bridging header.
+
+enum ARLensError: Error {
+    case notInitialized
+    case failed(String)
+}
+
+// Holds the continuation until the Rust callback fires. Retained across the FFI
+// boundary and released by the callback, which Rust invokes exactly once
+private final class CompletionBox {
+    let continuation: CheckedContinuation<String?, Error>
+
+    init(_ continuation: CheckedContinuation<String?, Error>) {
+        self.continuation = continuation
+    }
+}
+
+private let arlensCompletion: ARCompletionCallback = { context, status, payload in
+    let box = Unmanaged<CompletionBox>.fromOpaque(context!).takeRetainedValue()
+    let message = payload.map { String(cString: $0) }
+
+    switch status {
+    case AR_STATUS_OK:
+        box.continuation.resume(returning: message)
+    case AR_STATUS_CANCELLED:
+        box.continuation.resume(throwing: CancellationError())
+    default:
+        box.continuation.resume(throwing: ARLensError.failed(message ?? "unknown error"))
+    }
+}
+
+// Tracks the operation token so task cancellation can reach the Rust side, even if
+// the task is cancelled before the operation has started
+private final class OperationHandle: @unchecked Sendable {
+    private let lock = NSLock()
+    private var token: UInt64 = 0
+    private var cancelled = false
+
+    func start(_ token: UInt64) {
+        lock.lock()
+        self.token = token
+        let cancelNow = cancelled
+        lock.unlock()
+        if cancelNow { cancel_operation(token) }
+    }
+
+    func cancel() {
+        lock.lock()
+        cancelled = true
+        let token = self.token
+        lock.unlock()
+        if token != 0 { cancel_operation(token) }
+    }
+}
+
+private func runOperation(_ start: (ARCompletionCallback, UnsafeMutableRawPointer) -> UInt64) async throws -> String? {
+    let handle = OperationHandle()
+    return try await withTaskCancellationHandler {
+        try await withCheckedThrowingContinuation { continuation in
+            let context = Unmanaged.passRetained(CompletionBox(continuation)).toOpaque()
+            let token = start(arlensCompletion, context)
+            if token == 0 {
+                // The callback will never fire; balance the retain ourselves
+                Unmanaged<CompletionBox>.fromOpaque(context).release()
+                continuation.resume(throwing: ARLensError.notInitialized)
+                return
+            }
+            handle.start(token)
+        }
+    } onCancel: {
+        handle.cancel()
+    }
+}
+
+enum ARLens {
+    // Write the current session to `url` as JSON
+    static func exportSnapshot(to url: URL) async throws {
+        _ = try await runOperation { callback, context in
+            url.path.withCString { export_snapshot_async($0, callback, context) }
+        }
+    }
+
+    // Return the current session as a JSON string
+    static func snapshotJSON() async throws -> String {
+        let json = try await runOperation { callback, context in
+            export_snapshot_async(nil, callback, context)
+        }
+        return json ?? ""
+    }
+
+    // Return a handoff bundle for the current session as a JSON string, carrying the
+    // archived ARWorldMap if given
+    static func handoffBundleJSON(worldMap: Data? = nil) async throws -> String {
+        let json = try await runOperation { callback, context in
+            guard let worldMap else {
+                return export_handoff_bundle_async(nil, 0, nil, callback, context)
+            }
+            return worldMap.withUnsafeBytes { bytes in
+                export_handoff_bundle_async(bytes.bindMemory(to: UInt8.self).baseAddress,
+                                            UInt32(bytes.count), nil, callback, context)
+            }
+        }
+        return json ?? ""
+    }
+
+    // Register TrueType or OpenType font data under `name` for text labels
+    static func registerFont(named name: String, data: Data) async throws {
+        _ = try await runOperation { callback, context in
+            name.withCString { name in
+                data.withUnsafeBytes { bytes in
+                    register_font_async(name, bytes.bindMemory(to: UInt8.self).baseAddress,
+                                        UInt32(bytes.count), callback, context)
+                }
+            }
+        }
+    }
+}
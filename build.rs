@@ -0,0 +1,5 @@
+fn main() {
+    // Generate the UniFFI scaffolding for the session interface
+    #[cfg(feature = "uniffi")]
+    uniffi::generate_scaffolding("src/arlens.udl").unwrap();
+}
@@ -0,0 +1,619 @@
+// C declarations for the ARLens Rust library. Import this from the Swift
+// bridging header (or directly from Objective-C).
+
+#ifndef ARLENS_H
+#define ARLENS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Session
+
+void ios_main(void);
+void update_camera_position(float x, float y, float z);
+void update_camera_pose(double timestamp,
+                        float pos_x, float pos_y, float pos_z,
+                        float rot_x, float rot_y, float rot_z, float rot_w);
+bool add_detected_plane(const char *id,
+                        float center_x, float center_y, float center_z,
+                        float width, float height,
+                        float normal_x, float normal_y, float normal_z);
+int32_t place_virtual_object(int32_t object_type,
+                             float pos_x, float pos_y, float pos_z,
+                             float rot_x, float rot_y, float rot_z, float rot_w);
+bool remove_virtual_object(int32_t object_id);
+float get_object_distance(int32_t object_a, int32_t object_b);
+bool get_object_transform(int32_t object_id, float *out_position,
+                          float *out_rotation, float *out_scale);
+void get_session_stats(int32_t *num_planes, int32_t *num_objects);
+void advance_frame(float dt);
+bool setup_metal_context(void *device);
+
+// Primitives (see src/primitives.rs). a and b are radius and height for
+// cylinders, cones and capsules, width and depth for planes, and major and minor
+// radius for tori, in meters. Meshes are y-up, counter-clockwise outward; positions
+// and normals take 3 floats per vertex.
+
+#define AR_PRIMITIVE_CYLINDER 0
+#define AR_PRIMITIVE_PLANE 1
+#define AR_PRIMITIVE_TORUS 2
+#define AR_PRIMITIVE_CONE 3
+#define AR_PRIMITIVE_CAPSULE 4
+
+int32_t place_primitive(int32_t kind, float a, float b, uint32_t segments,
+                        float pos_x, float pos_y, float pos_z,
+                        float rot_x, float rot_y, float rot_z, float rot_w);
+int32_t get_object_mesh(int32_t object_id, float *out_positions, float *out_normals,
+                        uint32_t vertex_capacity, uint32_t *out_indices,
+                        uint32_t index_capacity, uint32_t *out_index_count);
+
+// Text (see src/text_mesh.rs; needs the "text" feature). Fonts are
+// TrueType/OpenType data registered per process by name. Text is UTF-8 with '\n'
+// line breaks, centered, reading along +x and facing +z; size is meters per em.
+// Meshes come from get_object_mesh.
+
+bool register_font(const char *name, const uint8_t *data, uint32_t length);
+int32_t place_text(const char *text, const char *font_name, float size, float depth,
+                   float pos_x, float pos_y, float pos_z,
+                   float rot_x, float rot_y, float rot_z, float rot_w);
+bool set_object_text(int32_t object_id, const char *text);
+
+// Deterministic mode (see src/determinism.rs). advance_frame runs whole fixed
+// timesteps (timestep 0 for 1/60 s) and procedural effects are seeded from seed,
+// so peers and replays fed the same inputs stay bit-identical.
+
+bool set_deterministic_mode(bool enabled, uint64_t seed, float timestep);
+int64_t get_simulation_tick(void);
+
+// Session report (see src/session_report.rs). JSON with durations, seconds per
+// tracking state, plane count over time, estimated memory peaks and dropped
+// frames. Report the tracking state whenever ARKit's changes; the reason only
+// matters while limited.
+
+#define AR_TRACKING_NOT_AVAILABLE 0
+#define AR_TRACKING_LIMITED 1
+#define AR_TRACKING_NORMAL 2
+
+#define AR_TRACKING_REASON_NONE 0
+#define AR_TRACKING_REASON_INITIALIZING 1
+#define AR_TRACKING_REASON_EXCESSIVE_MOTION 2
+#define AR_TRACKING_REASON_INSUFFICIENT_FEATURES 3
+#define AR_TRACKING_REASON_RELOCALIZING 4
+
+bool set_tracking_state(int32_t state, int32_t reason);
+int32_t get_session_report(char *out_json, uint32_t capacity);
+bool write_session_report(const char *path);
+
+// Id namespaces (see src/namespaces.rs). Plane and anchor ids are "source:local";
+// ids passed without a prefix get the default source. A NULL or empty source
+// selects unprefixed ids.
+
+bool set_default_id_source(const char *source);
+int32_t get_planes_from_source(const char *source, uint32_t *out_indices, uint32_t capacity);
+int32_t get_anchors_from_source(const char *source, char *out_json, uint32_t capacity);
+
+// Session timeline (see src/clock.rs). Samples are stamped in seconds; after
+// ar_set_time_source, or the first host timestamp (update_camera_pose,
+// update_camera_stream, update_dynamic_anchor), the timeline follows the host
+// clock (ARFrame.timestamp).
+
+void ar_set_time_source(uint64_t host_time_ns);
+double get_session_time(void);
+bool get_camera_pose_at(double timestamp, float *out_position, float *out_rotation);
+int32_t get_plane_count_at(double timestamp);
+double get_point_cloud_timestamp(void);  // "reconstruction" feature
+
+// Camera streams (see src/cameras.rs). Rear-camera frames also drive the
+// session camera pose; front-camera frames feed face tracking only.
+// get_feature_camera reports which stream a feature uses.
+
+#define AR_CAMERA_REAR 0
+#define AR_CAMERA_FRONT 1
+
+#define AR_FEATURE_WORLD_TRACKING 0
+#define AR_FEATURE_DEPTH 1
+#define AR_FEATURE_PEOPLE_OCCLUSION 2
+#define AR_FEATURE_FACE_TRACKING 3
+
+bool update_camera_stream(int32_t camera, double timestamp,
+                          float pos_x, float pos_y, float pos_z,
+                          float rot_x, float rot_y, float rot_z, float rot_w,
+                          float fx, float fy, float cx, float cy,
+                          uint32_t width, uint32_t height);
+bool get_camera_stream_pose(int32_t camera, double *out_timestamp,
+                            float *out_position, float *out_rotation);
+bool get_camera_stream_intrinsics(int32_t camera, float *out_intrinsics, uint32_t *out_resolution);
+uint64_t get_camera_stream_frame_count(int32_t camera);
+int32_t get_feature_camera(int32_t feature);
+
+// Decals (see src/decals.rs). A texture name projected along direction onto the
+// first plane within depth / 2 and clipped to it. Geometry is a triangle fan of up
+// to AR_DECAL_MAX_VERTICES corners, lifted off the plane to avoid z-fighting; uvs
+// have v down. With the "reconstruction" feature a decal lands on the mesh where
+// it's nearer than any plane; get_decal_mesh returns either kind as an indexed
+// triangle list, while get_decal_geometry returns 0 for mesh decals.
+
+#define AR_DECAL_MAX_VERTICES 8
+
+int32_t add_decal(const char *texture,
+                  float pos_x, float pos_y, float pos_z,
+                  float direction_x, float direction_y, float direction_z,
+                  float width, float height, float rotation, float depth);
+bool update_decal(int32_t decal_id,
+                  float pos_x, float pos_y, float pos_z,
+                  float direction_x, float direction_y, float direction_z,
+                  float width, float height, float rotation, float depth);
+bool remove_decal(int32_t decal_id);
+int32_t get_decal_geometry(int32_t decal_id, float *out_positions, float *out_uvs, float *out_normal);
+int32_t get_decal_mesh(int32_t decal_id, float *out_positions, float *out_uvs, uint32_t vertex_capacity,
+                       uint32_t *out_indices, uint32_t index_capacity, uint32_t *out_index_count);
+
+// Color grading (see src/color_grading.rs). Submitted frames are analyzed for
+// white balance and exposure; multiply virtual content by the results.
+
+#define AR_PIXEL_FORMAT_BGRA8 0
+#define AR_PIXEL_FORMAT_RGBA8 1
+
+bool submit_camera_image(const uint8_t *pixels, uint32_t width, uint32_t height,
+                         uint32_t bytes_per_row, int32_t pixel_format);
+bool get_color_grading(float *out_white_balance, float *out_exposure_scale);
+
+// Environment capture (see src/environment.rs; needs the "environment" feature).
+// Frames from submit_camera_image are accumulated into a cube map for
+// reflections. Faces are +X, -X, +Y, -Y, +Z, -Z as sRGB RGBA; alpha 0 marks
+// texels not seen yet.
+
+void set_environment_capture(bool enabled, uint32_t face_size);
+int32_t get_environment_map_face(int32_t face, uint8_t *out_rgba, uint32_t capacity);
+float get_environment_map_status(uint64_t *out_version);
+
+// People occlusion (see src/occlusion.rs). Mattes are projected through the
+// rear camera stream, so update_camera_stream must have reported intrinsics.
+// Objects entirely behind people aren't visible and people block the gaze ray.
+
+bool submit_person_segmentation(const uint8_t *segmentation, const float *depth,
+                                uint32_t width, uint32_t height);
+void clear_person_segmentation(void);
+int32_t is_point_occluded(float x, float y, float z);
+float get_object_occlusion(int32_t object_id);
+
+// Visibility (see src/visibility.rs), from the rear camera's pose and intrinsics
+// (a default field of view if none were reported).
+
+bool is_object_visible(int32_t object_id);
+float object_screen_coverage(int32_t object_id);
+
+// Off-screen indicators: 0 = on screen, 1 = pinned to the edge, -1 = invalid.
+// Positions are normalized image coordinates (y down); angle is in radians.
+int32_t get_object_indicator(int32_t object_id, float margin,
+                             float *out_position, float *out_angle);
+int32_t get_anchor_indicator(const char *anchor_id, float margin,
+                             float *out_position, float *out_angle);
+
+// Visibility zones (see src/zones.rs). Boxes gating their content: a portal's is
+// seen only through its doorway (the local +z face), a room's only from inside.
+// Evaluated in advance_frame; zone_entered / zone_exited events report the camera.
+
+#define AR_ZONE_PORTAL 0
+#define AR_ZONE_ROOM 1
+
+#define AR_ZONE_VISIBLE 0
+#define AR_ZONE_THROUGH_PORTAL 1
+#define AR_ZONE_HIDDEN 2
+
+int32_t add_visibility_zone(int32_t kind,
+                            float center_x, float center_y, float center_z,
+                            float rot_x, float rot_y, float rot_z, float rot_w,
+                            float half_x, float half_y, float half_z);
+bool update_visibility_zone(int32_t zone_id,
+                            float center_x, float center_y, float center_z,
+                            float rot_x, float rot_y, float rot_z, float rot_w,
+                            float half_x, float half_y, float half_z);
+bool remove_visibility_zone(int32_t zone_id);
+int32_t get_object_zone_visibility(int32_t object_id, int32_t *out_zone_id);
+bool get_portal_doorway(int32_t zone_id, float *out_corners);
+
+// Gaze (see src/gaze.rs). Updated in advance_frame; focus_enter, focus_exit and
+// dwell_complete arrive through poll_session_event.
+
+bool set_object_gaze(int32_t object_id, bool enabled, float dwell_time);
+int32_t get_gaze_target(void);
+
+// Fading (see src/fading.rs). Policies are evaluated in advance_frame; the
+// opacity also goes into the render snapshot. hide_distance <= 0 never hides by
+// distance.
+
+bool set_object_fade_policy(int32_t object_id, float fade_start, float hide_distance,
+                            bool hide_inside_geometry);
+bool clear_object_fade_policy(int32_t object_id);
+float get_object_opacity(int32_t object_id);
+
+// Gestures (see src/gestures.rs). Pass recognizer state cumulative since the
+// gesture began; pan is in fractions of the view height. Committed gestures are
+// undoable.
+
+bool begin_object_gesture(int32_t object_id);
+bool update_object_gesture(float pinch_scale, float rotation, float pan_x, float pan_y);
+bool end_object_gesture(bool commit);
+int32_t undo_object_transform(void);
+int32_t redo_object_transform(void);
+void set_gesture_constraints(float min_scale, float max_scale,
+                             float rotation_snap_degrees, bool allow_translation);
+
+// Alignment guides (see src/alignment.rs). While on, panned objects snap into
+// line with nearby objects and the room's axes; get_object_transform gives the
+// snapped transform. Guides are line segments (start xyz, end xyz) as of the last
+// update_object_gesture; outputs may be NULL.
+
+#define AR_GUIDE_EDGE 0
+#define AR_GUIDE_CENTER 1
+#define AR_GUIDE_SPACING 2
+#define AR_GUIDE_AXIS 3
+
+bool set_alignment_guides(bool enabled, float snap_distance, float range);
+int32_t get_alignment_guides(int32_t *out_kinds, float *out_lines, uint32_t capacity);
+
+// Physics and joints (see src/physics.rs, src/contacts.rs, src/joints.rs; needs
+// the "physics" feature, as do the sleep, force field and material sections below).
+// Stepped in advance_frame. Spheres collide as spheres, capsules as capsules and
+// other objects as boxes.
+// Joints target an anchor when target_anchor_id is non-NULL, otherwise the
+// object target_object_id; points and axes are world space.
+
+bool set_object_physics(int32_t object_id, bool enabled, float mass);
+void set_gravity(float x, float y, float z);
+bool set_object_material(int32_t object_id, float restitution, float friction);
+bool set_object_velocity(int32_t object_id, float x, float y, float z);
+bool set_object_collider(int32_t object_id, float half_x, float half_y, float half_z);
+bool launch_object(int32_t object_id, float x, float y, float z);
+int32_t predict_trajectory(float origin_x, float origin_y, float origin_z,
+                           float velocity_x, float velocity_y, float velocity_z,
+                           float *out_points, bool *out_hit, uint32_t capacity);
+int32_t add_fixed_joint(int32_t object_id, int32_t target_object_id, const char *target_anchor_id);
+int32_t add_hinge_joint(int32_t object_id, int32_t target_object_id, const char *target_anchor_id,
+                        float pivot_x, float pivot_y, float pivot_z,
+                        float axis_x, float axis_y, float axis_z);
+int32_t add_spring_joint(int32_t object_id, int32_t target_object_id, const char *target_anchor_id,
+                         float pivot_x, float pivot_y, float pivot_z,
+                         float rest_length, float stiffness, float damping);
+bool remove_joint(int32_t joint_id);
+
+// Sleep states (see src/sleep.rs). Bodies still for enough frames stop being
+// simulated until something moves them; object_sleep / object_wake events
+// report the changes. frames = 0 disables sleeping.
+
+void set_sleep_thresholds(float linear, float angular, uint32_t frames);
+bool is_object_sleeping(int32_t object_id);
+bool wake_object(int32_t object_id);
+
+// Force fields (see src/force_fields.rs): spherical volumes pushing dynamic
+// objects. direction is only used by wind; strength is in newtons.
+
+#define AR_FORCE_FIELD_WIND 0
+#define AR_FORCE_FIELD_RADIAL 1
+#define AR_FORCE_FIELD_ATTRACTOR 2
+
+int32_t add_force_field(int32_t kind, float center_x, float center_y, float center_z, float radius,
+                        float direction_x, float direction_y, float direction_z, float strength);
+bool update_force_field(int32_t field_id, float center_x, float center_y, float center_z, float radius,
+                        float direction_x, float direction_y, float direction_z, float strength);
+bool remove_force_field(int32_t field_id);
+
+// Scan coverage (see src/coverage.rs). Plane cells in view accumulate observed
+// seconds in advance_frame. Heatmaps are row-major, columns along extent x. With
+// the "reconstruction" feature, unoccluded mesh cells in view accumulate too;
+// get_mesh_coverage writes 4 floats per cell (center xyz, seconds).
+
+int32_t get_plane_coverage(int32_t index, float *out_values, uint32_t capacity,
+                           uint32_t *out_columns, uint32_t *out_rows);
+float get_plane_coverage_fraction(int32_t index, float min_seconds);
+int32_t get_mesh_coverage(float min_x, float min_y, float min_z,
+                          float max_x, float max_y, float max_z,
+                          float *out_cells, uint32_t capacity);  // "reconstruction" feature
+
+// Plane expiry (see src/plane_expiry.rs). Planes neither updated nor in view for
+// stale_seconds are flagged (plane_stale), refreshed when seen again
+// (plane_refreshed), and removed after remove_seconds (plane_removed); removal
+// shifts later plane indices. 0 disables either; both are off by default.
+
+bool set_plane_expiry(float stale_seconds, float remove_seconds);
+bool is_plane_stale(int32_t index);
+
+// Scan quality (see src/scan_quality.rs). A scan_sufficient event is emitted
+// once coverage, feature density (points per m^2 of plane) and loop closures
+// all meet the criteria. out_signals receives those three values.
+
+void set_scan_criteria(float min_coverage, float min_observe_seconds,
+                       float min_feature_density, uint32_t min_loop_closures);
+void report_loop_closure(void);
+float get_scan_quality(float *out_signals);
+bool is_scan_sufficient(void);
+
+// Plane materials (see src/surfaces.rs; needs the "physics" feature, except
+// set_plane_classification). Planes use their classification's default friction
+// and restitution unless given their own.
+
+#define AR_PLANE_CLASS_NONE 0
+#define AR_PLANE_CLASS_FLOOR 1
+#define AR_PLANE_CLASS_WALL 2
+#define AR_PLANE_CLASS_CEILING 3
+#define AR_PLANE_CLASS_TABLE 4
+#define AR_PLANE_CLASS_SEAT 5
+#define AR_PLANE_CLASS_WINDOW 6
+#define AR_PLANE_CLASS_DOOR 7
+
+bool set_plane_classification(const char *plane_id, int32_t classification);
+bool set_plane_material(const char *plane_id, float friction, float restitution);
+bool clear_plane_material(const char *plane_id);
+bool set_classification_material(int32_t classification, float friction, float restitution);
+
+// Scattering (see src/scatter.rs). Places objects at random spots on the planes
+// inside a box, origin on the surface and up along its normal, with a random turn
+// and scale. classification_mask holds 1 << AR_PLANE_CLASS_* bits (0 for any).
+// Returns the number placed, possibly fewer than count; out_ids may be NULL.
+
+int32_t scatter_objects(int32_t object_type,
+                        float center_x, float center_y, float center_z,
+                        float half_x, float half_y, float half_z,
+                        uint32_t count, float min_spacing, float edge_margin,
+                        float max_slope_degrees, uint32_t classification_mask,
+                        float min_scale, float max_scale,
+                        int32_t *out_ids, uint32_t capacity);
+
+// Archetypes (see src/archetypes.rs). Named JSON bundles of physics, material,
+// collider, stabilization, fade, gaze, render layer and LOD settings, applied at
+// placement. "label", "dynamic_prop" and "static_decor" are built in. Layers and
+// the LOD level to draw are reported with each object in the render snapshot.
+// Without the "physics" feature the physics section is accepted but ignored.
+
+bool register_archetype(const char *name, const char *json);
+int32_t place_archetype_object(const char *name, int32_t object_type,
+                               float pos_x, float pos_y, float pos_z,
+                               float rot_x, float rot_y, float rot_z, float rot_w);
+bool apply_archetype(int32_t object_id, const char *name);
+bool set_object_layers(int32_t object_id, uint32_t layers);
+bool set_object_lod(int32_t object_id, const float *distances, uint32_t count);
+
+// World origin (see src/world_origin.rs). Normalizing moves every stored
+// position so the floor is at y=0 and emits world_origin_changed; apply the
+// same shift to ARKit's world origin before reporting further poses.
+
+bool get_floor_height(float *out_height);
+bool normalize_world_to_floor(void);
+
+// Stairs (see src/stairs.rs), detected from stacks of small horizontal planes
+// and recomputed on each query. Lowest stair first; outputs may be NULL.
+
+int32_t get_stair_count(void);
+bool get_stair_info(int32_t index, uint32_t *out_step_count, float *out_rise_run,
+                    float *out_direction, float *out_bottom, float *out_top);
+
+// Camera pose smoothing (see src/pose_filter.rs). Outputs may be NULL.
+
+bool get_camera_pose(float *out_position, float *out_rotation);
+bool get_smoothed_camera_pose(float *out_position, float *out_rotation,
+                              float *out_velocity, float *out_angular_velocity);
+void set_camera_smoothing(float alpha, float beta, float rotation_time_constant);
+int32_t get_camera_pose_history(double *out_timestamps, float *out_positions,
+                                float *out_rotations, uint32_t capacity);
+
+// Lock-free camera pose (see src/camera_slot.rs), for the render thread: never
+// waits on the session lock. Returns false if another read overlaps it, so read
+// from one thread only. Poses come from the session created by ios_main; it is
+// the origin until that session reports a camera pose. Outputs may be NULL.
+
+bool get_latest_camera_pose(float *out_position, float *out_rotation, double *out_timestamp);
+
+// Pose graph (see src/pose_graph.rs). Keyframes are taken from camera poses as
+// the camera moves. A loop closure gives to_keyframe's pose in from_keyframe's
+// frame; older keyframes, objects and planes are corrected toward it and reported
+// as object_pose_adjusted / plane_pose_adjusted events. Outputs may be NULL.
+
+int32_t get_latest_keyframe(float *out_position, float *out_rotation);
+int32_t get_keyframe_at(double timestamp);
+bool get_keyframe_pose(int32_t keyframe_id, float *out_position, float *out_rotation);
+bool add_loop_closure(int32_t from_keyframe, int32_t to_keyframe,
+                      float pos_x, float pos_y, float pos_z,
+                      float rot_x, float rot_y, float rot_z, float rot_w);
+
+// Trajectories (see src/trajectories.rs). The camera's path is always kept;
+// objects are sampled each advance_frame while tracking is on. Queries return
+// samples after since_timestamp, oldest first; outputs may be NULL.
+
+#define AR_TRAJECTORY_CAMERA -1
+
+void set_trajectory_duration(float seconds);
+bool set_object_trajectory_tracking(int32_t object_id, bool enabled);
+int32_t get_trajectory(int32_t id, double since_timestamp, double *out_timestamps,
+                       float *out_positions, float *out_rotations, uint32_t capacity);
+float get_trajectory_length(int32_t id, double since_timestamp);
+
+// Anchors and stabilization (see src/anchors.rs, src/stabilizer.rs). Attached
+// objects follow their anchor; stabilized ones ease toward it in advance_frame.
+// Dynamic anchors track a moving target and are extrapolated between updates.
+
+bool update_anchor(const char *id,
+                   float pos_x, float pos_y, float pos_z,
+                   float rot_x, float rot_y, float rot_z, float rot_w);
+bool update_dynamic_anchor(const char *id, double timestamp,
+                           float pos_x, float pos_y, float pos_z,
+                           float rot_x, float rot_y, float rot_z, float rot_w);
+bool get_anchor_velocity(const char *id, float *out_velocity, float *out_angular_velocity);
+bool remove_anchor(const char *id);
+void set_anchor_drift_threshold(float distance, float angle_degrees);
+bool attach_object_to_anchor(int32_t object_id, const char *anchor_id);
+bool detach_object_from_anchor(int32_t object_id);
+bool set_object_stabilization(int32_t object_id, bool enabled,
+                              float position_deadband, float rotation_deadband_degrees,
+                              float time_constant, float teleport_distance);
+
+// Camera attachment (see src/camera_attachment.rs). Offsets are in camera space
+// (x right, y up, -z forward); objects follow in advance_frame, lazily when
+// follow_time_constant > 0. Attaching detaches from any anchor. Unless clamping
+// is disabled, objects are pulled in toward the camera to stay out of planes and
+// the reconstruction mesh, with attachment_clamped / attachment_unclamped events
+// (plane_id is null when the mesh stopped the object).
+
+bool attach_object_to_camera(int32_t object_id,
+                             float offset_x, float offset_y, float offset_z,
+                             float follow_time_constant);
+bool detach_object_from_camera(int32_t object_id);
+bool is_object_attached_to_camera(int32_t object_id);
+bool set_camera_attachment_clamping(int32_t object_id, bool enabled);
+bool is_camera_attachment_clamped(int32_t object_id);
+
+// Point cloud (see src/pointcloud.rs; needs the "reconstruction" feature).
+// camera_transform is a column-major 4x4 matrix (16 floats); intrinsics are for
+// the depth map resolution.
+
+int32_t submit_depth_frame(const float *depth, uint32_t width, uint32_t height,
+                           float fx, float fy, float cx, float cy,
+                           const float *camera_transform);
+void set_point_cloud_voxel_size(float voxel_size);
+int32_t get_point_cloud(float *out_positions, float *out_normals, uint32_t capacity);
+bool point_cloud_uses_gpu(void);
+
+// Ingestion queues (see src/ingestion.rs). Frames submitted while another on the
+// same stream is processing wait under the stream's policy; turned-away frames
+// return early (submit_depth_frame returns AR_FRAME_DROPPED, submit_camera_image
+// false) and are counted. capacity applies to DROP_OLDEST, timeout_ms to BLOCK;
+// streams default to COALESCE.
+
+#define AR_INGEST_CAMERA_IMAGE 0
+#define AR_INGEST_DEPTH 1
+
+#define AR_QUEUE_DROP_OLDEST 0
+#define AR_QUEUE_COALESCE 1
+#define AR_QUEUE_BLOCK 2
+
+#define AR_FRAME_DROPPED (-2)
+
+bool set_ingestion_policy(int32_t stream, int32_t policy, uint32_t capacity, uint32_t timeout_ms);
+bool get_ingestion_drop_counts(int32_t stream, uint64_t *out_dropped, uint64_t *out_coalesced);
+
+// Shared buffers (see src/shared_buffers.rs). Register pooled frame memory once,
+// bracket each write with begin/end_shared_buffer_write, then submit by buffer id
+// with the generation begin returned; frames are read in place. A generation no
+// longer current is refused (AR_BUFFER_STALE for depth, false for images), and
+// begin returns 0 while Rust is still reading the buffer. Depth maps must be
+// tightly packed and 4-byte aligned.
+
+#define AR_BUFFER_STALE (-3)
+
+int32_t register_shared_buffer(const void *data, size_t length);
+int32_t register_shared_iosurface(void *surface);  // iOS builds only
+bool unregister_shared_buffer(int32_t buffer_id);
+uint64_t begin_shared_buffer_write(int32_t buffer_id);
+bool end_shared_buffer_write(int32_t buffer_id);
+// "reconstruction" feature
+int32_t submit_shared_depth_frame(int32_t buffer_id, uint64_t generation,
+                                  uint32_t width, uint32_t height,
+                                  float fx, float fy, float cx, float cy,
+                                  const float *camera_transform);
+bool submit_shared_camera_image(int32_t buffer_id, uint64_t generation,
+                                uint32_t width, uint32_t height,
+                                uint32_t bytes_per_row, int32_t pixel_format);
+
+// Plane extraction (see src/plane_extraction.rs; needs the "reconstruction"
+// feature, except get_plane_info). Derived planes are merged into the plane set;
+// get_plane_info reports 0 for ARKit planes, 1 for derived ones.
+
+int32_t extract_planes_from_point_cloud(void);
+void set_plane_extraction_params(float distance_threshold, uint32_t min_inliers);
+int32_t get_plane_info(int32_t index, float *out_center, float *out_extent, float *out_normal);
+
+// Reconstruction mesh (see src/mesh_store.rs; needs the "reconstruction" feature).
+// Submit each ARMeshAnchor's geometry as it's added or updated: vertices in anchor
+// space (3 floats each), indices three per triangle, and the column-major 4x4
+// anchor-to-world transform. Chunks farther than evict_distance from the camera are
+// dropped each frame; get_mesh_region writes 9 floats per triangle.
+
+int32_t submit_mesh_anchor(const char *id, const float *transform,
+                           const float *vertices, uint32_t vertex_count,
+                           const uint32_t *indices, uint32_t index_count);
+bool remove_mesh_anchor(const char *id);
+bool set_mesh_store_params(float chunk_size, float evict_distance);
+bool raycast_mesh(float origin_x, float origin_y, float origin_z,
+                  float dir_x, float dir_y, float dir_z, float max_distance,
+                  float *out_point, float *out_normal, float *out_distance);
+int32_t get_mesh_region(float min_x, float min_y, float min_z,
+                        float max_x, float max_y, float max_z,
+                        float *out_vertices, uint32_t capacity);
+bool get_mesh_store_stats(uint32_t *out_chunks, uint32_t *out_triangles, uint64_t *out_evicted);
+
+// Session events (see src/events.rs), delivered as JSON objects with a "type"
+// field. Returns the JSON length, 0 when the queue is empty, or -1 without a
+// session; an event that doesn't fit in capacity stays queued.
+
+int32_t poll_session_event(char *out_json, uint32_t capacity);
+uint64_t get_dropped_event_count(void);
+
+// Quotas (see src/quotas.rs). Limits on objects, planes, point-cloud points and
+// registered font bytes; 0 (the default) is unlimited. Additions over a limit are
+// refused: placement returns AR_QUOTA_EXCEEDED, add_detected_plane and
+// register_font false, and depth frames AR_QUOTA_EXCEEDED, keeping the previous
+// cloud. quota_warning and quota_exceeded events report approaching and hitting
+// a limit.
+
+#define AR_QUOTA_EXCEEDED (-4)
+
+#define AR_RESOURCE_OBJECTS 0
+#define AR_RESOURCE_PLANES 1
+#define AR_RESOURCE_POINTS 2
+#define AR_RESOURCE_ASSET_BYTES 3
+
+bool set_quota(int32_t resource, uint64_t limit);
+bool set_quota_warning_fraction(float fraction);
+bool get_quota_usage(int32_t resource, uint64_t *out_used, uint64_t *out_limit);
+
+// Analytics (see src/analytics.rs). Anonymized usage events are buffered until
+// drained as one JSON batch; disabling discards them and stops recording.
+
+void set_analytics_enabled(bool enabled);
+bool is_analytics_enabled(void);
+int32_t drain_analytics_events(char *out_json, uint32_t capacity);
+
+// Async operations (see src/ops.rs). The callback runs exactly once on a
+// background thread; payload is only valid during the call.
+
+#define AR_STATUS_OK 0
+#define AR_STATUS_CANCELLED 1
+#define AR_STATUS_FAILED 2
+
+typedef void (*ARCompletionCallback)(void *context, int32_t status, const char *payload);
+
+bool cancel_operation(uint64_t token);
+uint64_t export_snapshot_async(const char *path, ARCompletionCallback callback, void *context);
+uint64_t export_handoff_bundle_async(const uint8_t *world_map, uint32_t world_map_len,
+                                     const char *path, ARCompletionCallback callback,
+                                     void *context);
+// Needs the "text" feature
+uint64_t register_font_async(const char *name, const uint8_t *data, uint32_t length,
+                             ARCompletionCallback callback, void *context);
+
+// Session handoff (see src/handoff.rs). The bundle is JSON carrying the host's
+// archived ARWorldMap, planes, anchors, objects and preferences. Import returns
+// the AR_CAPABILITY_* flags the exporting device had that this one lacks.
+
+#define AR_HANDOFF_VERSION 1
+
+#define AR_CAPABILITY_DEPTH 1
+#define AR_CAPABILITY_PEOPLE_OCCLUSION 2
+#define AR_CAPABILITY_FRONT_CAMERA 4
+
+void set_device_capabilities(uint32_t mask);
+int32_t export_handoff_bundle(const uint8_t *world_map, uint32_t world_map_len,
+                              char *out_json, uint32_t capacity);
+int32_t import_handoff_bundle(const char *json);
+int32_t take_handoff_world_map(uint8_t *out, uint32_t capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // ARLENS_H
@@ -0,0 +1,353 @@
+// Alignment guides for dragging objects. While a gesture pans an object, its position
+// is snapped, in the horizontal plane, to line up with what's around it, and the lines
+// it snapped to are kept for the UI to draw:
+//
+// - edge and center: one of the object's edges, or its center, lines up with an edge or
+//   the center of a nearby object
+// - spacing: the object continues a row of two nearby objects at the same spacing, or
+//   sits halfway between them
+// - axis: the object stays on a room axis through where the drag started
+//
+// Everything is measured along the room's axes: the normal of the largest wall and the
+// direction across it, or world x and z before any wall is found. Footprints are
+// squares in those axes (a cube's side, or a mesh's bounding diameter), so rotated
+// objects line up by their bounds. Each axis snaps to its nearest candidate within the
+// snap distance; guides are line segments at the dragged object's height
+
+use crate::gestures::Transform;
+use crate::math::{add, cross, dot, length, normalize, scale, sub};
+use crate::render::DEFAULT_OBJECT_SIZE;
+use crate::{with_session, ARObject, ARObjectType, ARSession};
+
+pub const AR_GUIDE_EDGE: i32 = 0;
+pub const AR_GUIDE_CENTER: i32 = 1;
+pub const AR_GUIDE_SPACING: i32 = 2;
+pub const AR_GUIDE_AXIS: i32 = 3;
+
+// Planes whose normal is closer to horizontal than this (normal y) count as walls
+const MAX_WALL_NORMAL_Y: f32 = 0.2;
+
+// Candidates this close to the chosen target are drawn alongside it
+const COINCIDENT_TARGET: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GuideKind {
+    Edge,
+    Center,
+    Spacing,
+    Axis,
+}
+
+impl GuideKind {
+    fn code(self) -> i32 {
+        match self {
+            GuideKind::Edge => AR_GUIDE_EDGE,
+            GuideKind::Center => AR_GUIDE_CENTER,
+            GuideKind::Spacing => AR_GUIDE_SPACING,
+            GuideKind::Axis => AR_GUIDE_AXIS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Guide {
+    pub kind: GuideKind,
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AlignmentConfig {
+    pub enabled: bool,
+    // Meters an object is pulled to line up
+    pub snap_distance: f32,
+    // Objects farther than this from the dragged one are ignored
+    pub range: f32,
+}
+
+impl Default for AlignmentConfig {
+    fn default() -> Self {
+        AlignmentConfig { enabled: false, snap_distance: 0.02, range: 1.5 }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AlignmentState {
+    pub config: AlignmentConfig,
+    // From the last gesture update
+    pub guides: Vec<Guide>,
+}
+
+// A snap along one axis, with the guide it draws. The guide runs along `runs_along`
+// at `fixed` on the other axis, from `from` to `to`, or to the dragged object if None
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    axis: usize,
+    target: f32,
+    kind: GuideKind,
+    runs_along: usize,
+    fixed: f32,
+    from: f32,
+    to: Option<f32>,
+}
+
+// Half the side of an object's square footprint at `scale`
+fn half_width(object: &ARObject, scale: f32) -> f32 {
+    let unit = match object.object_type {
+        ARObjectType::Cube => DEFAULT_OBJECT_SIZE * 0.5,
+        _ => object.bounding_radius() / object.scale.max(1e-6),
+    };
+    unit * scale
+}
+
+struct Neighbor {
+    coords: [f32; 2],
+    half_width: f32,
+}
+
+impl ARSession {
+    // Horizontal room axes: the largest wall's normal and the direction across it
+    fn room_axes(&self) -> [[f32; 3]; 2] {
+        let wall = self.detected_planes.iter()
+            .filter(|plane| plane.normal[1].abs() < MAX_WALL_NORMAL_Y)
+            .max_by(|a, b| (a.extent[0] * a.extent[1]).total_cmp(&(b.extent[0] * b.extent[1])));
+        match wall {
+            Some(wall) => {
+                let across = normalize([wall.normal[0], 0.0, wall.normal[2]]);
+                [across, cross([0.0, 1.0, 0.0], across)]
+            }
+            None => [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    // Snap a dragged object's transform and record the guides it snapped to. `start` is
+    // where the drag began
+    pub(crate) fn align_transform(&mut self, index: usize, start: [f32; 3], transform: Transform) -> Transform {
+        self.alignment.guides.clear();
+        let config = self.alignment.config;
+        let Some(object) = self.virtual_objects.get(index).filter(|_| config.enabled) else {
+            return transform;
+        };
+
+        let axes = self.room_axes();
+        let coords = |point: [f32; 3]| [dot(point, axes[0]), dot(point, axes[1])];
+        let mine = coords(transform.position);
+        let half = half_width(object, transform.scale);
+        let neighbors: Vec<Neighbor> = self.virtual_objects.iter()
+            .enumerate()
+            .filter(|&(other, neighbor)| {
+                other != index
+                    && neighbor.camera_attachment.is_none()
+                    && length(sub(neighbor.position, transform.position)) <= config.range
+            })
+            .map(|(_, neighbor)| Neighbor { coords: coords(neighbor.position), half_width: half_width(neighbor, neighbor.scale) })
+            .collect();
+
+        let mut candidates = Vec::new();
+        for axis in 0..2 {
+            let other = 1 - axis;
+            for neighbor in &neighbors {
+                let theirs = neighbor.half_width;
+                let lines = [(0.0, 0.0, GuideKind::Center), (-half, -theirs, GuideKind::Edge), (-half, theirs, GuideKind::Edge),
+                    (half, -theirs, GuideKind::Edge), (half, theirs, GuideKind::Edge)];
+                for (my_offset, their_offset, kind) in lines {
+                    let line = neighbor.coords[axis] + their_offset;
+                    candidates.push(Candidate {
+                        axis,
+                        target: line - my_offset,
+                        kind,
+                        runs_along: other,
+                        fixed: line,
+                        from: neighbor.coords[other],
+                        to: None,
+                    });
+                }
+            }
+
+            // Rows of two neighbors lined up along this axis, with the object in line
+            for (i, a) in neighbors.iter().enumerate() {
+                for b in &neighbors[i + 1..] {
+                    let in_line = |coords: [f32; 2]| (coords[other] - a.coords[other]).abs() <= config.snap_distance;
+                    if !in_line(b.coords) || !in_line(mine) {
+                        continue;
+                    }
+                    let (near, far) = if a.coords[axis] <= b.coords[axis] { (a, b) } else { (b, a) };
+                    let gap = far.coords[axis] - near.coords[axis];
+                    let row = |target: f32, from: f32, to: Option<f32>| Candidate {
+                        axis,
+                        target,
+                        kind: GuideKind::Spacing,
+                        runs_along: axis,
+                        fixed: near.coords[other],
+                        from,
+                        to,
+                    };
+                    candidates.push(row(far.coords[axis] + gap, near.coords[axis], None));
+                    candidates.push(row(near.coords[axis] - gap, far.coords[axis], None));
+                    candidates.push(row(near.coords[axis] + gap * 0.5, near.coords[axis], Some(far.coords[axis])));
+                }
+            }
+
+            // Staying on the axis line through the drag's start, once the drag is
+            // clearly along it
+            let origin = coords(start);
+            if (mine[other] - origin[other]).abs() <= config.snap_distance {
+                continue;
+            }
+            candidates.push(Candidate {
+                axis,
+                target: origin[axis],
+                kind: GuideKind::Axis,
+                runs_along: other,
+                fixed: origin[axis],
+                from: origin[other],
+                to: None,
+            });
+        }
+
+        let mut snapped = mine;
+        let mut chosen: Vec<&Candidate> = Vec::new();
+        for axis in 0..2 {
+            let on_axis = candidates.iter().filter(|candidate| candidate.axis == axis);
+            let Some(best) = on_axis.clone()
+                .filter(|candidate| (candidate.target - mine[axis]).abs() <= config.snap_distance)
+                .min_by(|a, b| (a.target - mine[axis]).abs().total_cmp(&(b.target - mine[axis]).abs()))
+            else {
+                continue;
+            };
+            snapped[axis] = best.target;
+            chosen.extend(on_axis.filter(|candidate| (candidate.target - best.target).abs() <= COINCIDENT_TARGET));
+        }
+
+        let height = transform.position[1];
+        let point = |along: usize, fixed: f32, at: f32| {
+            let mut coords = [0.0; 2];
+            coords[along] = at;
+            coords[1 - along] = fixed;
+            add(add(scale(axes[0], coords[0]), scale(axes[1], coords[1])), [0.0, height, 0.0])
+        };
+        // Guides on the same line merge into one spanning them all
+        let mut lines: Vec<(GuideKind, usize, f32, f32, f32)> = Vec::new();
+        for candidate in chosen {
+            let end = candidate.to.unwrap_or(snapped[candidate.runs_along]);
+            let (low, high) = (candidate.from.min(end), candidate.from.max(end));
+            let same_line = |line: &&mut (GuideKind, usize, f32, f32, f32)| {
+                line.0 == candidate.kind && line.1 == candidate.runs_along && (line.2 - candidate.fixed).abs() <= COINCIDENT_TARGET
+            };
+            match lines.iter_mut().find(same_line) {
+                Some(line) => (line.3, line.4) = (line.3.min(low), line.4.max(high)),
+                None => lines.push((candidate.kind, candidate.runs_along, candidate.fixed, low, high)),
+            }
+        }
+        self.alignment.guides = lines.into_iter()
+            .filter(|&(.., low, high)| high > low)
+            .map(|(kind, along, fixed, low, high)| Guide { kind, start: point(along, fixed, low), end: point(along, fixed, high) })
+            .collect();
+
+        let offset = add(scale(axes[0], snapped[0] - mine[0]), scale(axes[1], snapped[1] - mine[1]));
+        Transform { position: add(transform.position, offset), ..transform }
+    }
+}
+
+// Snap dragged objects into line with nearby ones and the room's axes, within
+// `snap_distance` meters, considering objects within `range` meters. Off by default.
+// Returns false for a negative or non-finite distance
+#[no_mangle]
+pub extern "C" fn set_alignment_guides(enabled: bool, snap_distance: f32, range: f32) -> bool {
+    if [snap_distance, range].iter().any(|value| !value.is_finite() || *value < 0.0) {
+        return false;
+    }
+
+    with_session(|session| session.alignment.config = AlignmentConfig { enabled, snap_distance, range }).is_some()
+}
+
+// Guides the dragged object is snapped to as of the last update_object_gesture: their
+// kinds (AR_GUIDE_*) and line segments (start then end, 6 floats each). Writes up to
+// `capacity` guides (either output may be null) and returns how many there are; 0 while
+// no gesture is active, or -1 without a session
+#[no_mangle]
+pub extern "C" fn get_alignment_guides(out_kinds: *mut i32, out_lines: *mut f32, capacity: u32) -> i32 {
+    with_session(|session| {
+        if session.gestures.active_object().is_none() {
+            return 0;
+        }
+        let guides = &session.alignment.guides;
+        let count = guides.len().min(capacity as usize);
+        unsafe {
+            if !out_kinds.is_null() {
+                let kinds = std::slice::from_raw_parts_mut(out_kinds, count);
+                for (slot, guide) in kinds.iter_mut().zip(guides) {
+                    *slot = guide.kind.code();
+                }
+            }
+            if !out_lines.is_null() {
+                let lines = std::slice::from_raw_parts_mut(out_lines, count * 6);
+                for (slot, guide) in lines.chunks_exact_mut(6).zip(guides) {
+                    slot[..3].copy_from_slice(&guide.start);
+                    slot[3..].copy_from_slice(&guide.end);
+                }
+            }
+        }
+        guides.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn session_with_guides(neighbors: &[[f32; 3]]) -> ARSession {
+        let mut session = ARSession::new();
+        session.alignment.config = AlignmentConfig { enabled: true, ..Default::default() };
+        for &position in neighbors {
+            session.place_object(ARObjectType::Cube, position, IDENTITY).unwrap();
+        }
+        session
+    }
+
+    fn drag(session: &mut ARSession, position: [f32; 3]) -> [f32; 3] {
+        let index = session.place_object(ARObjectType::Cube, position, IDENTITY).unwrap();
+        let transform = Transform { position, rotation: IDENTITY, scale: 1.0 };
+        session.align_transform(index, position, transform).position
+    }
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        length(sub(a, b)) < 1e-5
+    }
+
+    #[test]
+    fn edges_snap_together_within_the_snap_distance() {
+        let mut session = session_with_guides(&[[0.0; 3]]);
+
+        // The dragged cube's left edge is 5mm from the neighbor's right edge
+        let snapped = drag(&mut session, [0.105, 0.0, 0.3]);
+        assert!(close(snapped, [0.1, 0.0, 0.3]), "snapped to {:?}", snapped);
+        assert_eq!(session.alignment.guides.len(), 1);
+        let guide = session.alignment.guides[0];
+        assert_eq!(guide.kind, GuideKind::Edge);
+        assert!(close(guide.start, [0.05, 0.0, 0.0]) && close(guide.end, [0.05, 0.0, 0.3]));
+    }
+
+    #[test]
+    fn nothing_snaps_beyond_the_snap_distance_or_while_disabled() {
+        let mut session = session_with_guides(&[[0.0; 3]]);
+        assert!(close(drag(&mut session, [0.13, 0.0, 0.3]), [0.13, 0.0, 0.3]));
+        assert!(session.alignment.guides.is_empty());
+
+        session.alignment.config.enabled = false;
+        assert!(close(drag(&mut session, [0.105, 0.0, 0.3]), [0.105, 0.0, 0.3]));
+        assert!(session.alignment.guides.is_empty());
+    }
+
+    #[test]
+    fn rows_continue_at_the_same_spacing() {
+        let mut session = session_with_guides(&[[0.0; 3], [0.5, 0.0, 0.0]]);
+
+        let snapped = drag(&mut session, [1.01, 0.0, 0.005]);
+        assert!(close(snapped, [1.0, 0.0, 0.0]), "snapped to {:?}", snapped);
+        assert!(session.alignment.guides.iter().any(|guide| guide.kind == GuideKind::Spacing));
+    }
+}
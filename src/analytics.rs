@@ -0,0 +1,225 @@
+// Usage analytics. High-level events (objects placed, features used, session length)
+// are buffered here for the host to drain and upload. Events are anonymized at the
+// source: no object ids, names, positions or wall-clock times, only a random
+// per-session id and whole seconds since the session started. When analytics is
+// disabled nothing is recorded at all, and the buffer is discarded
+//
+// The store lives outside the AR session so the opt-out and buffered events survive
+// the session being re-initialized
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+
+use crate::clock::SessionClock;
+use crate::events::copy_c_string;
+
+// Oldest events are dropped beyond this
+const MAX_BUFFERED_EVENTS: usize = 512;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Anchors,
+    CameraStreams,
+    ColorGrading,
+    DepthPointCloud,
+    PeopleOcclusion,
+    PlaneExtraction,
+    ReconstructionMesh,
+    Stabilization,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AnalyticsEvent {
+    SessionStarted,
+    SessionEnded { duration: u64 },
+    // Custom object names are collapsed to "custom"
+    ObjectPlaced { kind: &'static str },
+    ObjectRemoved,
+    // Recorded the first time a feature is used in a session
+    FeatureUsed { feature: Feature },
+}
+
+#[derive(Serialize)]
+struct Record {
+    session: String,
+    // Whole seconds since the session started
+    t: u64,
+    #[serde(flatten)]
+    event: AnalyticsEvent,
+}
+
+// What a drain hands to the host
+#[derive(Serialize)]
+struct Batch<'a> {
+    session: &'a str,
+    session_length: u64,
+    events: &'a VecDeque<Record>,
+}
+
+struct Store {
+    session: String,
+    // Time since the session started (see clock.rs for wasm32)
+    clock: SessionClock,
+    features_used: HashSet<Feature>,
+    events: VecDeque<Record>,
+}
+
+impl Store {
+    fn new() -> Self {
+        let id = RandomState::new().build_hasher().finish();
+        Store {
+            session: format!("{:016x}", id),
+            clock: SessionClock::new(),
+            features_used: HashSet::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, event: AnalyticsEvent) {
+        if self.events.len() == MAX_BUFFERED_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(Record {
+            session: self.session.clone(),
+            t: self.clock.now() as u64,
+            event,
+        });
+    }
+}
+
+fn store() -> &'static Mutex<Option<Store>> {
+    static STORE: OnceLock<Mutex<Option<Store>>> = OnceLock::new();
+    STORE.get_or_init(|| Mutex::new(None))
+}
+
+// ENABLED only changes under the store lock, so checking it under the lock too means
+// nothing is recorded after set_analytics_enabled(false) returns
+fn with_store(f: impl FnOnce(&mut Option<Store>)) {
+    if let Ok(mut store) = store().lock() {
+        if ENABLED.load(Ordering::SeqCst) {
+            f(&mut store);
+        }
+    }
+}
+
+// Start a new analytics session, ending the previous one
+pub(crate) fn session_started() {
+    with_store(|store| {
+        if let Some(previous) = store.as_mut() {
+            let duration = previous.clock.now() as u64;
+            previous.push(AnalyticsEvent::SessionEnded { duration });
+        }
+
+        let mut next = Store::new();
+        // Undrained events from the previous session are kept
+        if let Some(previous) = store.take() {
+            next.events = previous.events;
+        }
+        next.push(AnalyticsEvent::SessionStarted);
+        *store = Some(next);
+    });
+}
+
+pub(crate) fn record(event: AnalyticsEvent) {
+    with_store(|store| {
+        if let Some(store) = store.as_mut() {
+            store.push(event);
+        }
+    });
+}
+
+pub(crate) fn feature_used(feature: Feature) {
+    with_store(|store| {
+        if let Some(store) = store.as_mut() {
+            if store.features_used.insert(feature) {
+                store.push(AnalyticsEvent::FeatureUsed { feature });
+            }
+        }
+    });
+}
+
+// Turn analytics on or off. Disabling discards any buffered events and stops all
+// recording until re-enabled; re-enabling starts a fresh anonymous session
+#[no_mangle]
+pub extern "C" fn set_analytics_enabled(enabled: bool) {
+    // A poisoned lock still has to honor an opt-out
+    let mut store = store().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let was_enabled = ENABLED.swap(enabled, Ordering::SeqCst);
+    if !enabled {
+        *store = None;
+    } else if !was_enabled {
+        let mut fresh = Store::new();
+        fresh.push(AnalyticsEvent::SessionStarted);
+        *store = Some(fresh);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn is_analytics_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+// Move all buffered events into `out_json` as one NUL-terminated JSON batch
+// ({"session", "session_length", "events": [...]}). Returns the JSON length, 0 when
+// there is nothing to report (or analytics is disabled). If the batch doesn't fit in
+// `capacity` bytes nothing is drained; retry with at least the returned length + 1
+#[no_mangle]
+pub extern "C" fn drain_analytics_events(out_json: *mut libc::c_char, capacity: u32) -> i32 {
+    if !ENABLED.load(Ordering::SeqCst) {
+        return 0;
+    }
+    let Ok(mut store) = store().lock() else {
+        return 0;
+    };
+    let Some(store) = store.as_mut() else {
+        return 0;
+    };
+    if store.events.is_empty() {
+        return 0;
+    }
+
+    let batch = Batch {
+        session: &store.session,
+        session_length: store.clock.now() as u64,
+        events: &store.events,
+    };
+    let json = serde_json::to_string(&batch).unwrap_or_default();
+    if unsafe { copy_c_string(&json, out_json, capacity) } {
+        store.events.clear();
+    }
+    json.len() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_recorded_after_opt_out() {
+        set_analytics_enabled(true);
+        let recorders: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| {
+                for _ in 0..2000 {
+                    session_started();
+                    record(AnalyticsEvent::ObjectRemoved);
+                }
+            }))
+            .collect();
+        set_analytics_enabled(false);
+        // Recording that started before the opt-out must not bring the store back
+        for recorder in recorders {
+            recorder.join().unwrap();
+        }
+        assert!(store().lock().unwrap().is_none());
+        set_analytics_enabled(true);
+    }
+}
@@ -0,0 +1,394 @@
+// Anchors reported by the host (ARKit ARAnchor or similar) and objects attached to
+// them. An attached object keeps a fixed local offset from its anchor, so anchor
+// corrections move the object with it. Each anchor also remembers the pose content
+// was seated at, so accumulated drift can be reported as an event.
+//
+// Dynamic anchors follow a moving target the host tracks itself (a tracked toy, a
+// turntable marker). Their updates run through a pose filter for a velocity estimate,
+// and between updates the pose is extrapolated every frame, so attached content moves
+// smoothly even when tracking reports at a lower rate than the display. Moving is
+// what they're for, so they never report drift
+
+use std::ffi::CStr;
+
+use tracing::info;
+
+use crate::analytics::{self, Feature};
+use crate::events::SessionEvent;
+use crate::math::{add, length, quat_conjugate, quat_delta_axis_angle, quat_from_axis_angle, quat_mul, quat_normalize, quat_rotate, scale, sub};
+use crate::pose_filter::{write_out, PoseFilter, PoseSample};
+use crate::{with_session, ARSession};
+
+pub(crate) struct ARAnchor {
+    pub id: String,
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    // Pose at creation or at the last drift event
+    seated_position: [f32; 3],
+    seated_rotation: [f32; 4],
+    // Total correction magnitude (meters) since the seated pose
+    cumulative_correction: f32,
+    // Set for dynamic anchors
+    motion: Option<AnchorMotion>,
+}
+
+// Extrapolation past the last tracking update is capped, so content stops rather than
+// flying off when the target is lost
+const MAX_EXTRAPOLATION: f32 = 0.25;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AnchorMotion {
+    filter: PoseFilter,
+    // Seconds of frames advanced since the last update
+    since_update: f32,
+}
+
+// Net correction from the seated pose that counts as drift
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DriftConfig {
+    pub distance: f32,
+    // Radians
+    pub angle: f32,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        DriftConfig {
+            distance: 0.05,
+            angle: 5.0f32.to_radians(),
+        }
+    }
+}
+
+// An object's pose relative to its anchor
+#[derive(Debug, Clone)]
+pub(crate) struct AnchorAttachment {
+    pub anchor_id: String,
+    pub local_position: [f32; 3],
+    pub local_rotation: [f32; 4],
+}
+
+impl ARAnchor {
+    fn new(id: String, position: [f32; 3], rotation: [f32; 4]) -> Self {
+        ARAnchor {
+            id,
+            position,
+            rotation,
+            seated_position: position,
+            seated_rotation: rotation,
+            cumulative_correction: 0.0,
+            motion: None,
+        }
+    }
+
+    // Move the anchor with the world origin; not a correction, so drift is unaffected
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        self.position = add(self.position, offset);
+        self.seated_position = add(self.seated_position, offset);
+        if let Some(motion) = self.motion.as_mut() {
+            motion.filter.translate(offset);
+        }
+    }
+
+    // Filtered pose extrapolated to the current frame
+    fn extrapolate(&mut self, dt: f32) {
+        let Some(motion) = self.motion.as_mut() else {
+            return;
+        };
+        let Some(pose) = motion.filter.smoothed() else {
+            return;
+        };
+        motion.since_update += dt;
+        let t = motion.since_update.min(MAX_EXTRAPOLATION);
+
+        self.position = add(pose.position, scale(pose.velocity, t));
+        let spin = length(pose.angular_velocity);
+        self.rotation = if spin > 1e-6 {
+            let step = quat_from_axis_angle(scale(pose.angular_velocity, 1.0 / spin), spin * t);
+            quat_normalize(quat_mul(step, pose.rotation))
+        } else {
+            pose.rotation
+        };
+    }
+
+    // Apply a correction, returning a drift event if the anchor has moved beyond the
+    // thresholds since it was last seated. Emitting an event re-seats the anchor
+    fn correct(&mut self, position: [f32; 3], rotation: [f32; 4], drift: DriftConfig) -> Option<SessionEvent> {
+        self.cumulative_correction += length(sub(position, self.position));
+        self.position = position;
+        self.rotation = rotation;
+
+        let translation = sub(position, self.seated_position);
+        let distance = length(translation);
+        let angle = length(quat_delta_axis_angle(self.seated_rotation, rotation));
+        if distance <= drift.distance && angle <= drift.angle {
+            return None;
+        }
+
+        let event = SessionEvent::AnchorDrift {
+            anchor_id: self.id.clone(),
+            translation,
+            rotation: quat_normalize(quat_mul(rotation, quat_conjugate(self.seated_rotation))),
+            distance,
+            angle_degrees: angle.to_degrees(),
+            cumulative_correction: self.cumulative_correction,
+        };
+        self.seated_position = position;
+        self.seated_rotation = rotation;
+        self.cumulative_correction = 0.0;
+        Some(event)
+    }
+
+    pub fn to_world(&self, local_position: [f32; 3], local_rotation: [f32; 4]) -> ([f32; 3], [f32; 4]) {
+        (
+            add(self.position, quat_rotate(self.rotation, local_position)),
+            quat_normalize(quat_mul(self.rotation, local_rotation)),
+        )
+    }
+
+    pub fn to_local(&self, position: [f32; 3], rotation: [f32; 4]) -> ([f32; 3], [f32; 4]) {
+        let inverse = quat_conjugate(self.rotation);
+        (
+            quat_rotate(inverse, sub(position, self.position)),
+            quat_normalize(quat_mul(inverse, rotation)),
+        )
+    }
+}
+
+impl ARSession {
+    pub(crate) fn anchor(&self, id: &str) -> Option<&ARAnchor> {
+        self.anchors.iter().find(|anchor| anchor.id == id)
+    }
+
+    // Add an anchor, or update it if the id is already known. A dynamic anchor updated
+    // this way becomes static again
+    pub(crate) fn upsert_anchor(&mut self, id: &str, position: [f32; 3], rotation: [f32; 4]) {
+        let rotation = quat_normalize(rotation);
+        match self.anchors.iter_mut().find(|anchor| anchor.id == id) {
+            Some(anchor) => {
+                anchor.motion = None;
+                if let Some(event) = anchor.correct(position, rotation, self.anchor_drift) {
+                    self.metrics.anchor_drift_events += 1;
+                    self.events.push(event);
+                }
+            }
+            None => {
+                analytics::feature_used(Feature::Anchors);
+                self.anchors.push(ARAnchor::new(id.to_string(), position, rotation));
+            }
+        }
+        self.follow_anchor(id);
+    }
+
+    // Add or update a dynamic anchor from a tracking sample of its target
+    pub(crate) fn update_dynamic_anchor(&mut self, id: &str, sample: PoseSample) {
+        if self.anchor(id).is_none() {
+            analytics::feature_used(Feature::Anchors);
+            self.anchors.push(ARAnchor::new(id.to_string(), sample.position, quat_normalize(sample.rotation)));
+        }
+        let Some(anchor) = self.anchors.iter_mut().find(|anchor| anchor.id == id) else {
+            return;
+        };
+        let motion = anchor.motion.get_or_insert_with(AnchorMotion::default);
+        let pose = motion.filter.update(sample);
+        motion.since_update = 0.0;
+        anchor.position = pose.position;
+        anchor.rotation = pose.rotation;
+        self.follow_anchor(id);
+    }
+
+    // Move dynamic anchors, and their content, along their estimated motion
+    pub(crate) fn extrapolate_anchors(&mut self, dt: f32) {
+        let mut moved = Vec::new();
+        for anchor in self.anchors.iter_mut().filter(|anchor| anchor.motion.is_some()) {
+            anchor.extrapolate(dt);
+            moved.push(anchor.id.clone());
+        }
+        for id in moved {
+            self.follow_anchor(&id);
+        }
+    }
+
+    // Re-derive the pose of every object attached to an anchor
+    fn follow_anchor(&mut self, id: &str) {
+        let Some(anchor) = self.anchors.iter().find(|anchor| anchor.id == id) else {
+            return;
+        };
+
+        for object in &mut self.virtual_objects {
+            let Some(attachment) = object.anchor.as_ref().filter(|a| a.anchor_id == id) else {
+                continue;
+            };
+            let (position, rotation) = anchor.to_world(attachment.local_position, attachment.local_rotation);
+            object.set_target_pose(position, rotation);
+        }
+    }
+
+    // Remove an anchor. Attached objects stay where they are, unattached
+    pub(crate) fn remove_anchor(&mut self, id: &str) -> bool {
+        let Some(index) = self.anchors.iter().position(|anchor| anchor.id == id) else {
+            return false;
+        };
+        self.anchors.remove(index);
+        #[cfg(feature = "physics")]
+        self.joints_anchor_removed(id);
+        for object in &mut self.virtual_objects {
+            if object.anchor.as_ref().is_some_and(|a| a.anchor_id == id) {
+                object.anchor = None;
+            }
+        }
+        true
+    }
+
+    // Attach an object to an anchor, keeping its current world pose
+    pub(crate) fn attach_to_anchor(&mut self, object_index: usize, anchor_id: &str) -> bool {
+        let Some(anchor) = self.anchor(anchor_id) else {
+            return false;
+        };
+        let Some(object) = self.virtual_objects.get(object_index) else {
+            return false;
+        };
+
+        let (local_position, local_rotation) = anchor.to_local(object.position, object.rotation);
+        let attachment = AnchorAttachment {
+            anchor_id: anchor_id.to_string(),
+            local_position,
+            local_rotation,
+        };
+        let object = &mut self.virtual_objects[object_index];
+        object.anchor = Some(attachment);
+        object.camera_attachment = None;
+        true
+    }
+}
+
+unsafe fn anchor_id(id_ptr: *const libc::c_char) -> Option<String> {
+    if id_ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(id_ptr).to_string_lossy().into_owned())
+    }
+}
+
+// Add or update an anchor. Objects attached to it follow the new pose
+#[no_mangle]
+pub extern "C" fn update_anchor(
+    id_ptr: *const libc::c_char,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32
+) -> bool {
+    let Some(id) = (unsafe { anchor_id(id_ptr) }) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let id = session.qualify_id(&id).into_owned();
+        session.upsert_anchor(&id, [pos_x, pos_y, pos_z], [rot_x, rot_y, rot_z, rot_w]);
+    })
+    .is_some()
+}
+
+// Add or update a dynamic anchor with the latest tracked pose of a moving target,
+// timestamped in seconds like camera poses. Attached objects follow its smoothed
+// motion, extrapolated between updates. Returns false for a null id
+#[no_mangle]
+pub extern "C" fn update_dynamic_anchor(
+    id_ptr: *const libc::c_char,
+    timestamp: f64,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32
+) -> bool {
+    let Some(id) = (unsafe { anchor_id(id_ptr) }) else {
+        return false;
+    };
+    with_session(|session| {
+        let timestamp = session.clock.map_host_time(timestamp);
+        let sample = PoseSample { timestamp, position: [pos_x, pos_y, pos_z], rotation: [rot_x, rot_y, rot_z, rot_w] };
+        let id = session.qualify_id(&id).into_owned();
+        session.update_dynamic_anchor(&id, sample);
+    })
+    .is_some()
+}
+
+// Estimated velocity (m/s) and angular velocity (rad/s about a world axis, scaled by
+// the rate) of a dynamic anchor. Outputs may be null. Returns false for an unknown or
+// static anchor
+#[no_mangle]
+pub extern "C" fn get_anchor_velocity(
+    id_ptr: *const libc::c_char,
+    out_velocity: *mut f32,
+    out_angular_velocity: *mut f32,
+) -> bool {
+    let Some(id) = (unsafe { anchor_id(id_ptr) }) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let pose = session.anchor(&session.qualify_id(&id))?.motion.as_ref()?.filter.smoothed()?;
+        unsafe {
+            write_out(out_velocity, pose.velocity);
+            write_out(out_angular_velocity, pose.angular_velocity);
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+// Set how far (meters) or how much (degrees) an anchor may drift from where its
+// content was seated before an anchor_drift event is emitted
+#[no_mangle]
+pub extern "C" fn set_anchor_drift_threshold(distance: f32, angle_degrees: f32) {
+    with_session(|session| {
+        session.anchor_drift = DriftConfig {
+            distance: distance.max(0.0),
+            angle: angle_degrees.max(0.0).to_radians(),
+        };
+    });
+}
+
+// Remove an anchor; attached objects are detached in place
+#[no_mangle]
+pub extern "C" fn remove_anchor(id_ptr: *const libc::c_char) -> bool {
+    let Some(id) = (unsafe { anchor_id(id_ptr) }) else {
+        return false;
+    };
+
+    let removed = with_session(|session| {
+        let id = session.qualify_id(&id).into_owned();
+        session.remove_anchor(&id)
+    })
+    .unwrap_or(false);
+    if removed {
+        info!("Removed anchor {}", id);
+    }
+    removed
+}
+
+// Attach an object to an anchor at its current world pose
+#[no_mangle]
+pub extern "C" fn attach_object_to_anchor(object_id: i32, anchor_id_ptr: *const libc::c_char) -> bool {
+    let (Ok(index), Some(id)) = (usize::try_from(object_id), unsafe { anchor_id(anchor_id_ptr) }) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let id = session.qualify_id(&id).into_owned();
+        session.attach_to_anchor(index, &id)
+    })
+    .unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn detach_object_from_anchor(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        session.virtual_objects.get_mut(index)
+            .and_then(|object| object.anchor.take())
+            .is_some()
+    })
+    .unwrap_or(false)
+}
@@ -0,0 +1,338 @@
+// Object archetypes: named bundles of per-object settings (physics and surface
+// material, collider, stabilization, fade policy, gaze dwell, render layers and LOD
+// distances) applied in one call at placement, instead of a string of set_object_*
+// calls per object. Each setting is what the matching set_object_* call would give.
+//
+// Archetypes are registered as JSON, e.g.
+//
+//   {"physics": {"mass": 0.5, "restitution": 0.6}, "layers": 3, "lod_distances": [2, 6]}
+//
+// Missing sections leave that component off; missing fields within a section take the
+// per-object call's usual defaults. "label", "dynamic_prop" and "static_decor" are
+// built in and can be replaced by registering the same name. Applying an archetype
+// replaces the object's components, it doesn't merge with them. Registrations last for
+// the session. Without the physics feature the physics section is accepted but ignored
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+use serde::Deserialize;
+
+use crate::analytics::{self, Feature};
+#[cfg(feature = "physics")]
+use crate::contacts::ColliderShape;
+use crate::fading::FadePolicy;
+use crate::gaze::GazeTarget;
+#[cfg(feature = "physics")]
+use crate::physics::RigidBody;
+use crate::quotas::AR_QUOTA_EXCEEDED;
+use crate::stabilizer::{Stabilizer, StabilizerConfig};
+#[cfg(feature = "physics")]
+use crate::surfaces::SurfaceMaterial;
+use crate::{with_session, ARObject, ARObjectType, ARSession};
+
+// Objects are drawn in layer 0 unless told otherwise
+pub(crate) const DEFAULT_LAYERS: u32 = 1;
+
+const MAX_LOD_DISTANCES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct PhysicsDefaults {
+    pub mass: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    // Box half extents; the object type's own shape if absent (see set_object_collider)
+    pub collider: Option<[f32; 3]>,
+}
+
+impl Default for PhysicsDefaults {
+    fn default() -> Self {
+        PhysicsDefaults { mass: 1.0, restitution: 0.3, friction: 0.5, collider: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct StabilizationDefaults {
+    pub position_deadband: f32,
+    pub rotation_deadband_degrees: f32,
+    pub time_constant: f32,
+    pub teleport_distance: f32,
+}
+
+impl Default for StabilizationDefaults {
+    fn default() -> Self {
+        StabilizationDefaults { position_deadband: 0.005, rotation_deadband_degrees: 1.0, time_constant: 0.15, teleport_distance: 0.5 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct FadeDefaults {
+    pub fade_start: f32,
+    // 0 or less never hides by distance
+    pub hide_distance: f32,
+    pub hide_inside_geometry: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct Archetype {
+    pub physics: Option<PhysicsDefaults>,
+    pub stabilization: Option<StabilizationDefaults>,
+    pub fade: Option<FadeDefaults>,
+    // Seconds; a gaze target when set
+    pub gaze_dwell: Option<f32>,
+    pub layers: u32,
+    // Camera distances in meters where each lower level of detail starts, ascending
+    pub lod_distances: Vec<f32>,
+}
+
+impl Default for Archetype {
+    fn default() -> Self {
+        Archetype {
+            physics: None,
+            stabilization: None,
+            fade: None,
+            gaze_dwell: None,
+            layers: DEFAULT_LAYERS,
+            lod_distances: Vec::new(),
+        }
+    }
+}
+
+impl Archetype {
+    fn valid(&self) -> bool {
+        let mut values = Vec::new();
+        if let Some(physics) = &self.physics {
+            values.extend([physics.mass, physics.restitution, physics.friction]);
+            values.extend(physics.collider.into_iter().flatten());
+        }
+        if let Some(stabilization) = &self.stabilization {
+            values.extend([
+                stabilization.position_deadband,
+                stabilization.rotation_deadband_degrees,
+                stabilization.time_constant,
+                stabilization.teleport_distance,
+            ]);
+        }
+        if let Some(fade) = &self.fade {
+            values.extend([fade.fade_start, fade.hide_distance]);
+        }
+        values.extend(self.gaze_dwell);
+        values.iter().all(|value| value.is_finite())
+            && self.physics.is_none_or(|physics| physics.mass > 0.0)
+            && valid_lod_distances(&self.lod_distances)
+    }
+
+    fn builtin() -> HashMap<String, Archetype> {
+        let label = Archetype {
+            stabilization: Some(StabilizationDefaults { position_deadband: 0.01, rotation_deadband_degrees: 2.0, ..Default::default() }),
+            fade: Some(FadeDefaults { fade_start: 4.0, hide_distance: 6.0, hide_inside_geometry: true }),
+            gaze_dwell: Some(0.8),
+            ..Default::default()
+        };
+        let dynamic_prop = Archetype {
+            physics: Some(PhysicsDefaults::default()),
+            lod_distances: vec![3.0, 8.0],
+            ..Default::default()
+        };
+        let static_decor = Archetype {
+            stabilization: Some(StabilizationDefaults::default()),
+            fade: Some(FadeDefaults { hide_inside_geometry: true, ..Default::default() }),
+            lod_distances: vec![2.0, 5.0, 10.0],
+            ..Default::default()
+        };
+        HashMap::from([
+            ("label".to_string(), label),
+            ("dynamic_prop".to_string(), dynamic_prop),
+            ("static_decor".to_string(), static_decor),
+        ])
+    }
+}
+
+fn valid_lod_distances(distances: &[f32]) -> bool {
+    distances.len() <= MAX_LOD_DISTANCES
+        && distances.iter().all(|&distance| distance.is_finite() && distance > 0.0)
+        && distances.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ArchetypeRegistry {
+    archetypes: HashMap<String, Archetype>,
+}
+
+impl Default for ArchetypeRegistry {
+    fn default() -> Self {
+        ArchetypeRegistry { archetypes: Archetype::builtin() }
+    }
+}
+
+impl ARObject {
+    // Level of detail to draw at `distance` meters from the camera: 0 is full detail,
+    // and each LOD distance passed drops one level
+    pub(crate) fn lod_level(&self, distance: f32) -> u32 {
+        self.lod_distances.iter().take_while(|&&start| distance >= start).count() as u32
+    }
+
+    fn apply_archetype(&mut self, archetype: &Archetype) {
+        #[cfg(feature = "physics")]
+        {
+            self.body = archetype.physics.map(|physics| {
+                let mut body = RigidBody::for_object(self, physics.mass.max(0.001));
+                body.material = SurfaceMaterial::new(physics.friction, physics.restitution);
+                if let Some(half_extents) = physics.collider {
+                    body.shape = if half_extents.iter().all(|&h| h > 0.0) { ColliderShape::Box(half_extents) } else { ColliderShape::Bounds };
+                }
+                body
+            });
+            if self.body.is_some() {
+                self.camera_attachment = None;
+            }
+        }
+        if archetype.stabilization.is_some() {
+            analytics::feature_used(Feature::Stabilization);
+        }
+        self.stabilizer = archetype.stabilization.map(|stabilization| Stabilizer::new(StabilizerConfig {
+            position_deadband: stabilization.position_deadband.max(0.0),
+            rotation_deadband: stabilization.rotation_deadband_degrees.max(0.0).to_radians(),
+            time_constant: stabilization.time_constant.max(0.0),
+            teleport_distance: stabilization.teleport_distance.max(0.0),
+        }));
+        self.fade = archetype.fade.map(|fade| {
+            let hide_distance = if fade.hide_distance > 0.0 { fade.hide_distance } else { f32::INFINITY };
+            FadePolicy { fade_start: fade.fade_start.clamp(0.0, hide_distance), hide_distance, hide_inside_geometry: fade.hide_inside_geometry }
+        });
+        self.gaze = archetype.gaze_dwell.map(|dwell_time| GazeTarget { dwell_time: dwell_time.max(0.0) });
+        self.layers = archetype.layers;
+        self.lod_distances = archetype.lod_distances.clone();
+    }
+}
+
+impl ARSession {
+    // Apply a registered archetype to an object. False for an unknown name or index
+    pub(crate) fn apply_archetype(&mut self, index: usize, name: &str) -> bool {
+        let Some(archetype) = self.archetypes.archetypes.get(name) else {
+            return false;
+        };
+        let Some(object) = self.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.apply_archetype(archetype);
+        true
+    }
+}
+
+unsafe fn read_name(name_ptr: *const libc::c_char) -> Option<String> {
+    (!name_ptr.is_null()).then(|| CStr::from_ptr(name_ptr).to_string_lossy().into_owned())
+}
+
+// Register an archetype from JSON (see the top of archetypes.rs) under `name`,
+// replacing any archetype of that name, built-in ones included. Returns false for
+// malformed JSON, a non-positive mass, non-finite values, or LOD distances that aren't
+// positive and ascending (at most 8)
+#[no_mangle]
+pub extern "C" fn register_archetype(name_ptr: *const libc::c_char, json_ptr: *const libc::c_char) -> bool {
+    let (Some(name), Some(json)) = (unsafe { read_name(name_ptr) }, unsafe { read_name(json_ptr) }) else {
+        return false;
+    };
+    let Ok(archetype) = serde_json::from_str::<Archetype>(&json) else {
+        return false;
+    };
+    if !archetype.valid() {
+        return false;
+    }
+
+    with_session(|session| {
+        session.archetypes.archetypes.insert(name, archetype);
+    })
+    .is_some()
+}
+
+// Place an object as place_virtual_object does, with the settings of the archetype
+// `name`. Returns the object id, AR_QUOTA_EXCEEDED if the object quota is full, or -1
+// for an unknown archetype (nothing is placed)
+#[no_mangle]
+pub extern "C" fn place_archetype_object(
+    name_ptr: *const libc::c_char,
+    object_type: i32,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32
+) -> i32 {
+    let Some(name) = (unsafe { read_name(name_ptr) }) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        if !session.archetypes.archetypes.contains_key(&name) {
+            return -1;
+        }
+        let Some(index) = session.place_object(ARObjectType::from_code(object_type), [pos_x, pos_y, pos_z], [rot_x, rot_y, rot_z, rot_w]) else {
+            return AR_QUOTA_EXCEEDED;
+        };
+        session.apply_archetype(index, &name);
+        index as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Apply the archetype `name` to an existing object, e.g. text from place_text or
+// scattered objects, replacing its physics, stabilization, fade, gaze, layer and LOD
+// settings. Returns false for an invalid id or an unknown archetype
+#[no_mangle]
+pub extern "C" fn apply_archetype(object_id: i32, name_ptr: *const libc::c_char) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+    let Some(name) = (unsafe { read_name(name_ptr) }) else {
+        return false;
+    };
+
+    with_session(|session| session.apply_archetype(index, &name)).unwrap_or(false)
+}
+
+// Set the render layers an object is drawn in, one bit per host-defined layer (layer
+// 0 by default), reported with each drawable. Returns false for an invalid id
+#[no_mangle]
+pub extern "C" fn set_object_layers(object_id: i32, layers: u32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.layers = layers;
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Set the camera distances in meters where an object drops to each lower level of
+// detail; its drawable reports the level to use. `count` 0 keeps it at full detail.
+// Returns false for an invalid id, or distances that aren't positive and ascending (at
+// most 8)
+#[no_mangle]
+pub extern "C" fn set_object_lod(object_id: i32, distances: *const f32, count: u32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+    if distances.is_null() && count > 0 {
+        return false;
+    }
+    let distances = if count == 0 { Vec::new() } else { unsafe { std::slice::from_raw_parts(distances, count as usize) }.to_vec() };
+    if !valid_lod_distances(&distances) {
+        return false;
+    }
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.lod_distances = distances;
+        true
+    })
+    .unwrap_or(false)
+}
@@ -0,0 +1,154 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::{bail, Context};
+use ARLens::sim::{self, EventLog, Scenario};
+
+#[cfg(not(feature = "offscreen"))]
+const USAGE: &str = "usage: arlens-sim <scenario.json> [--out <report.json>] \
+    [--golden-log <log.json> [--update-golden] [--log-tolerance <epsilon>]]";
+#[cfg(feature = "offscreen")]
+const USAGE: &str = "usage: arlens-sim <scenario.json> [--out <report.json>] \
+    [--golden-log <log.json> [--update-golden] [--log-tolerance <epsilon>]] \
+    [--render <frame.png>] [--golden <golden.png>] [--tolerance <0-255>]";
+
+// Default allowed difference between logged and replayed numbers
+const DEFAULT_LOG_TOLERANCE: f64 = 1e-4;
+
+#[cfg(feature = "offscreen")]
+const RENDER_SIZE: (u32, u32) = (640, 480);
+
+#[derive(Default)]
+struct Args {
+    scenario: PathBuf,
+    out: Option<PathBuf>,
+    render: Option<PathBuf>,
+    golden: Option<PathBuf>,
+    tolerance: u8,
+    golden_log: Option<PathBuf>,
+    update_golden: bool,
+    log_tolerance: f64,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut parsed = Args { log_tolerance: DEFAULT_LOG_TOLERANCE, ..Args::default() };
+    let mut scenario = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" | "-o" => {
+                parsed.out = Some(PathBuf::from(args.next().context("--out needs a path")?));
+            }
+            "--golden-log" => {
+                parsed.golden_log = Some(PathBuf::from(args.next().context("--golden-log needs a path")?));
+            }
+            "--update-golden" => parsed.update_golden = true,
+            "--log-tolerance" => {
+                parsed.log_tolerance = args.next().context("--log-tolerance needs a value")?
+                    .parse().context("--log-tolerance must be a number")?;
+                if parsed.log_tolerance.is_nan() || parsed.log_tolerance < 0.0 {
+                    bail!("--log-tolerance must be non-negative");
+                }
+            }
+            "--render" if cfg!(feature = "offscreen") => {
+                parsed.render = Some(PathBuf::from(args.next().context("--render needs a path")?));
+            }
+            "--golden" if cfg!(feature = "offscreen") => {
+                parsed.golden = Some(PathBuf::from(args.next().context("--golden needs a path")?));
+            }
+            "--tolerance" if cfg!(feature = "offscreen") => {
+                parsed.tolerance = args.next().context("--tolerance needs a value")?
+                    .parse().context("--tolerance must be 0-255")?;
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if scenario.is_none() => scenario = Some(PathBuf::from(arg)),
+            _ => bail!("unexpected argument '{}'", arg),
+        }
+    }
+
+    parsed.scenario = scenario.context("missing scenario path")?;
+    if parsed.update_golden && parsed.golden_log.is_none() {
+        bail!("--update-golden needs --golden-log");
+    }
+    Ok(parsed)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> anyhow::Result<bool> {
+    let args = parse_args()?;
+    let scenario = Scenario::load(&args.scenario)?;
+    let mut report = sim::run(&scenario);
+
+    // Record the run as the new golden log, or replay-check it against the stored one
+    if let Some(path) = &args.golden_log {
+        if args.update_golden {
+            report.event_log().save(path)?;
+            println!("golden log: wrote {}", path.display());
+        } else {
+            report.check_event_log(&EventLog::load(path)?, args.log_tolerance);
+        }
+    }
+
+    println!("{}", report);
+
+    if let Some(out) = &args.out {
+        let json = serde_json::to_string_pretty(&report)?;
+        std::fs::write(out, json)
+            .with_context(|| format!("failed to write report {}", out.display()))?;
+    }
+
+    let images_match = check_render(&args)?;
+
+    Ok(report.passed() && images_match)
+}
+
+// Render the final state offscreen and compare it against a golden image, if requested
+#[cfg(feature = "offscreen")]
+fn check_render(args: &Args) -> anyhow::Result<bool> {
+    use ARLens::offscreen::{self, Image};
+    use ARLens::render::RenderSnapshot;
+
+    if args.render.is_none() && args.golden.is_none() {
+        return Ok(true);
+    }
+
+    let snapshot = RenderSnapshot::capture().context("no session to render")?;
+    let image = offscreen::render(&snapshot, RENDER_SIZE.0, RENDER_SIZE.1);
+
+    if let Some(path) = &args.render {
+        image.save_png(path)?;
+    }
+
+    if let Some(path) = &args.golden {
+        let golden = Image::load_png(path)?;
+        let diff = image.compare(&golden, args.tolerance)?;
+        if !diff.is_match() {
+            println!("FAILED: {} pixels differ from {} (max channel delta {})",
+                diff.differing_pixels, path.display(), diff.max_channel_delta);
+            return Ok(false);
+        }
+        println!("golden: {} matches", path.display());
+    }
+
+    Ok(true)
+}
+
+#[cfg(not(feature = "offscreen"))]
+fn check_render(_args: &Args) -> anyhow::Result<bool> {
+    Ok(true)
+}
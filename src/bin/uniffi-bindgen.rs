@@ -0,0 +1,4 @@
+// Generates Swift/Kotlin bindings from src/arlens.udl
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
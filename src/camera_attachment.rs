@@ -0,0 +1,329 @@
+// Camera-attached content: tool palettes, companion characters and other objects that
+// stay at a fixed offset from the camera. The offset is held in camera space and the
+// object's world pose is re-derived every frame in advance_frame. With a follow time
+// constant the object lags behind instead of being rigidly locked, which reads as
+// floating alongside the user rather than being glued to the screen.
+//
+// An object is attached to either the camera or an anchor, not both; attaching to one
+// detaches from the other. Dynamic bodies can't be attached, as physics and the camera
+// would fight over them.
+//
+// When the user backs into a corner the offset can put content inside a wall. Unless
+// clamping is turned off for it, an attached object is pulled in along the line from
+// the camera until its bounding sphere clears every detected plane it would cross,
+// with attachment_clamped / attachment_unclamped events as that starts and stops.
+// With the reconstruction mesh (see mesh_store.rs) the line is also cast against the
+// mesh, which catches furniture and clutter that plane detection doesn't report
+
+use crate::events::SessionEvent;
+#[cfg(feature = "reconstruction")]
+use crate::math::length;
+use crate::math::{add, dot, quat_conjugate, quat_mul, quat_normalize, quat_rotate, quat_slerp, scale, sub};
+use crate::{with_session, ARSession};
+
+// Extra clearance kept between clamped content and the surface, in meters
+const CLAMP_MARGIN: f32 = 0.02;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CameraAttachment {
+    // In the camera's frame (x right, y up, -z forward)
+    pub local_position: [f32; 3],
+    pub local_rotation: [f32; 4],
+    // Seconds to close ~63% of the gap to the camera-locked pose; 0 is rigid
+    pub follow_time_constant: f32,
+    pub clamp: bool,
+    // Whether the object was pulled in last frame, for clamp events
+    pub clamped: bool,
+}
+
+// What a clamped object was pulled in front of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClampSurface {
+    Plane(usize),
+    #[cfg(feature = "reconstruction")]
+    Mesh,
+}
+
+impl ARSession {
+    // World pose an attachment locks to for the current camera pose
+    fn camera_locked_pose(&self, attachment: &CameraAttachment) -> ([f32; 3], [f32; 4]) {
+        let position = add(self.camera_position, quat_rotate(self.camera_rotation, attachment.local_position));
+        let rotation = quat_normalize(quat_mul(self.camera_rotation, attachment.local_rotation));
+        (position, rotation)
+    }
+
+    // Attach an object at `offset` in camera space, keeping its orientation relative
+    // to the camera
+    pub(crate) fn attach_to_camera(&mut self, index: usize, offset: [f32; 3], follow_time_constant: f32) -> bool {
+        let Some(object) = self.virtual_objects.get(index).filter(|object| !object.is_dynamic()) else {
+            return false;
+        };
+        let attachment = CameraAttachment {
+            local_position: offset,
+            local_rotation: quat_normalize(quat_mul(quat_conjugate(self.camera_rotation), object.rotation)),
+            follow_time_constant,
+            clamp: true,
+            clamped: false,
+        };
+        let (position, rotation) = self.camera_locked_pose(&attachment);
+
+        let object = &mut self.virtual_objects[index];
+        object.position = position;
+        object.rotation = rotation;
+        object.anchor = None;
+        if let Some(stabilizer) = object.stabilizer.as_mut() {
+            stabilizer.reset();
+        }
+        object.camera_attachment = Some(attachment);
+        true
+    }
+
+    // Re-derive a camera-attached object's offset from its current pose, e.g. after
+    // the user dragged it
+    pub(crate) fn rebase_camera_attachment(&mut self, index: usize) {
+        let (camera_position, camera_rotation) = (self.camera_position, self.camera_rotation);
+        let Some(object) = self.virtual_objects.get_mut(index) else {
+            return;
+        };
+        if let Some(attachment) = object.camera_attachment.as_mut() {
+            let inverse = quat_conjugate(camera_rotation);
+            attachment.local_position = quat_rotate(inverse, sub(object.position, camera_position));
+            attachment.local_rotation = quat_normalize(quat_mul(inverse, object.rotation));
+        }
+    }
+
+    // Pull a point in toward the camera until a sphere of `radius` there stays in front
+    // of every plane the camera sees it through, and of the mesh along the line from the
+    // camera. Returns the point and the surface that limited it, if any
+    fn clamp_to_surfaces(&self, point: [f32; 3], radius: f32) -> ([f32; 3], Option<ClampSurface>) {
+        let camera = self.camera_position;
+        let clearance = radius + CLAMP_MARGIN;
+        let mut limit = 1.0;
+        let mut limiting = None;
+        for (index, plane) in self.detected_planes.iter().enumerate() {
+            let camera_distance = dot(sub(camera, plane.center), plane.normal);
+            let point_distance = dot(sub(point, plane.center), plane.normal);
+            // Only surfaces facing the camera, which the content would go into
+            if camera_distance <= 0.0 || point_distance >= clearance {
+                continue;
+            }
+            let t = ((camera_distance - clearance) / (camera_distance - point_distance)).max(0.0);
+            let contact = add(camera, scale(sub(point, camera), t));
+            if t < limit && plane.within_extent(contact) {
+                limit = t;
+                limiting = Some(ClampSurface::Plane(index));
+            }
+        }
+        #[cfg(feature = "reconstruction")]
+        {
+            let reach = length(sub(point, camera));
+            if let Some(hit) = self.mesh.raycast(camera, sub(point, camera), reach + clearance) {
+                let t = ((hit.distance - clearance) / reach).max(0.0);
+                if t < limit {
+                    limit = t;
+                    limiting = Some(ClampSurface::Mesh);
+                }
+            }
+        }
+        (add(camera, scale(sub(point, camera), limit)), limiting)
+    }
+
+    // Move camera-attached objects toward their locked poses, once per frame, then keep
+    // them out of surfaces
+    pub(crate) fn follow_camera(&mut self, dt: f32) {
+        for index in 0..self.virtual_objects.len() {
+            let Some(attachment) = self.virtual_objects[index].camera_attachment else {
+                continue;
+            };
+            let (position, rotation) = self.camera_locked_pose(&attachment);
+
+            let object = &mut self.virtual_objects[index];
+            if attachment.follow_time_constant <= 0.0 {
+                object.position = position;
+                object.rotation = rotation;
+            } else {
+                let t = 1.0 - (-dt / attachment.follow_time_constant).exp();
+                object.position = add(object.position, scale(sub(position, object.position), t));
+                object.rotation = quat_slerp(object.rotation, rotation, t);
+            }
+
+            if !attachment.clamp {
+                continue;
+            }
+            let object = &self.virtual_objects[index];
+            let (position, limiting) = self.clamp_to_surfaces(object.position, object.bounding_radius());
+            let object = &mut self.virtual_objects[index];
+            object.position = position;
+            let Some(attachment) = object.camera_attachment.as_mut() else {
+                continue;
+            };
+            match (attachment.clamped, limiting) {
+                (false, Some(surface)) => {
+                    let plane_id = match surface {
+                        ClampSurface::Plane(plane) => Some(self.detected_planes[plane].id.clone()),
+                        #[cfg(feature = "reconstruction")]
+                        ClampSurface::Mesh => None,
+                    };
+                    self.events.push(SessionEvent::AttachmentClamped { object_id: index, plane_id });
+                }
+                (true, None) => self.events.push(SessionEvent::AttachmentUnclamped { object_id: index }),
+                _ => {}
+            }
+            attachment.clamped = limiting.is_some();
+        }
+    }
+}
+
+// Attach an object to the camera at `offset` meters in camera space (x right, y up, -z
+// forward), e.g. (0.2, -0.1, -0.6) for a palette down and to the right. The object jumps
+// there and keeps its current orientation relative to the camera. With a positive
+// `follow_time_constant` (seconds) it lazily follows the camera instead of being locked
+// to it. Detaches it from any anchor. Returns false for an invalid id or a dynamic object
+#[no_mangle]
+pub extern "C" fn attach_object_to_camera(
+    object_id: i32,
+    offset_x: f32, offset_y: f32, offset_z: f32,
+    follow_time_constant: f32,
+) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+    let follow_time_constant = if follow_time_constant.is_finite() { follow_time_constant.max(0.0) } else { 0.0 };
+
+    with_session(|session| session.attach_to_camera(index, [offset_x, offset_y, offset_z], follow_time_constant))
+        .unwrap_or(false)
+}
+
+// Detach an object from the camera, leaving it where it is in the world. Returns false
+// for an invalid id or an object that wasn't attached
+#[no_mangle]
+pub extern "C" fn detach_object_from_camera(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        session.virtual_objects.get_mut(index)
+            .and_then(|object| object.camera_attachment.take())
+            .is_some()
+    })
+    .unwrap_or(false)
+}
+
+// Turn collision clamping on or off for a camera-attached object (on by default).
+// Returns false for an invalid id or an object that isn't attached
+#[no_mangle]
+pub extern "C" fn set_camera_attachment_clamping(object_id: i32, enabled: bool) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(attachment) = session.virtual_objects.get_mut(index).and_then(|object| object.camera_attachment.as_mut()) else {
+            return false;
+        };
+        attachment.clamp = enabled;
+        if !enabled && attachment.clamped {
+            attachment.clamped = false;
+            session.events.push(SessionEvent::AttachmentUnclamped { object_id: index });
+        }
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Whether a camera-attached object is currently pulled in to avoid a surface
+#[no_mangle]
+pub extern "C" fn is_camera_attachment_clamped(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        session.virtual_objects.get(index)
+            .and_then(|object| object.camera_attachment)
+            .is_some_and(|attachment| attachment.clamped)
+    })
+    .unwrap_or(false)
+}
+
+// Whether an object is attached to the camera
+#[no_mangle]
+pub extern "C" fn is_object_attached_to_camera(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        session.virtual_objects.get(index).is_some_and(|object| object.camera_attachment.is_some())
+    })
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{length, quat_from_axis_angle};
+    use crate::ARObjectType;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn events(session: &mut ARSession) -> Vec<SessionEvent> {
+        std::iter::from_fn(|| session.events.pop()).collect()
+    }
+
+    // A cube attached 1.5 m in front of a camera at the origin looking down -z
+    fn attached_cube(session: &mut ARSession) -> usize {
+        let index = session.place_object(ARObjectType::Cube, [0.0, 0.0, -1.5], IDENTITY).unwrap();
+        assert!(session.attach_to_camera(index, [0.0, 0.0, -1.5], 0.0));
+        index
+    }
+
+    #[test]
+    fn attached_objects_are_pulled_in_front_of_a_wall_plane() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("wall".to_string()), [0.0, 0.0, -1.0], [4.0, 4.0], [0.0, 0.0, 1.0]);
+        let index = attached_cube(&mut session);
+        events(&mut session);
+
+        session.follow_camera(0.016);
+        let object = &session.virtual_objects[index];
+        let expected = -1.0 + object.bounding_radius() + CLAMP_MARGIN;
+        assert!((object.position[2] - expected).abs() < 1e-4, "clamped to {:?}", object.position);
+        assert_eq!(events(&mut session), vec![SessionEvent::AttachmentClamped { object_id: index, plane_id: Some("wall".to_string()) }]);
+
+        // Turning away takes the offset clear of the wall again
+        session.camera_rotation = quat_from_axis_angle([0.0, 1.0, 0.0], std::f32::consts::PI);
+        session.follow_camera(0.016);
+        assert!(length(sub(session.virtual_objects[index].position, [0.0, 0.0, 1.5])) < 1e-5);
+        assert_eq!(events(&mut session), vec![SessionEvent::AttachmentUnclamped { object_id: index }]);
+    }
+
+    #[test]
+    fn unclamped_objects_go_through_surfaces() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("wall".to_string()), [0.0, 0.0, -1.0], [4.0, 4.0], [0.0, 0.0, 1.0]);
+        let index = attached_cube(&mut session);
+        session.virtual_objects[index].camera_attachment.as_mut().unwrap().clamp = false;
+
+        session.follow_camera(0.016);
+        assert_eq!(session.virtual_objects[index].position, [0.0, 0.0, -1.5]);
+    }
+
+    #[cfg(feature = "reconstruction")]
+    #[test]
+    fn attached_objects_are_pulled_in_front_of_the_mesh() {
+        let mut session = ARSession::new();
+        // A mesh wall 1.2 m ahead, with no plane detected for it
+        let (a, b, c, d) = ([-1.0, -1.0, -1.2], [1.0, -1.0, -1.2], [1.0, 1.0, -1.2], [-1.0, 1.0, -1.2]);
+        session.mesh.submit("wall", &[[a, b, c], [a, c, d]], [0.0; 3]);
+        let index = attached_cube(&mut session);
+        events(&mut session);
+
+        session.follow_camera(0.016);
+        let object = &session.virtual_objects[index];
+        let expected = -1.2 + object.bounding_radius() + CLAMP_MARGIN;
+        assert!((object.position[2] - expected).abs() < 1e-4, "clamped to {:?}", object.position);
+        assert_eq!(events(&mut session), vec![SessionEvent::AttachmentClamped { object_id: index, plane_id: None }]);
+    }
+}
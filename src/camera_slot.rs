@@ -0,0 +1,177 @@
+// Lock-free camera pose slot. Render threads want the freshest camera pose at the last
+// moment before drawing, and shouldn't wait on the session lock for it while the host
+// is ingesting a depth frame. Every camera pose update also publishes the pose to this
+// triple buffer, outside the session's data; reading it takes no lock and never waits.
+//
+// The slot is process-wide, so only the session behind the C API (the one created by
+// ios_main) publishes to it. Other sessions, such as the wasm preview or one rebuilt
+// from a snapshot, never do, and reinitializing resets the slot to the origin before
+// the new session takes over.
+//
+// The writer fills the back slot and swaps it with the middle one; the reader swaps the
+// middle slot for its own when a newer pose has been published. Writes are serialized
+// by the session lock. There's one reading side: a read that overlaps another read
+// returns nothing rather than waiting, so the render thread should be the only reader
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::pose_filter::{write_out, PoseSample};
+use crate::ARSession;
+
+// Set in `middle` when it holds a pose the reader hasn't taken yet
+const FRESH: u8 = 0b100;
+
+struct TripleBuffer {
+    slots: [UnsafeCell<PoseSample>; 3],
+    // Index of the middle slot, plus FRESH
+    middle: AtomicU8,
+    // Slot owned by the writer, and by the reader. Atomics only to share them safely;
+    // each side's index is touched by that side alone
+    back: AtomicU8,
+    front: AtomicU8,
+    reading: AtomicBool,
+}
+
+// Each slot is only accessed by the side that currently owns its index
+unsafe impl Sync for TripleBuffer {}
+
+const ORIGIN: PoseSample = PoseSample { timestamp: 0.0, position: [0.0; 3], rotation: [0.0, 0.0, 0.0, 1.0] };
+
+static SLOT: TripleBuffer = TripleBuffer {
+    slots: [UnsafeCell::new(ORIGIN), UnsafeCell::new(ORIGIN), UnsafeCell::new(ORIGIN)],
+    middle: AtomicU8::new(1),
+    back: AtomicU8::new(0),
+    front: AtomicU8::new(2),
+    reading: AtomicBool::new(false),
+};
+
+// Callers must be the only writer: the publishing session under its lock, or
+// initialization while no session publishes
+fn publish(sample: PoseSample) {
+    let back = SLOT.back.load(Ordering::Relaxed);
+    unsafe { *SLOT.slots[back as usize].get() = sample };
+    let previous = SLOT.middle.swap(back | FRESH, Ordering::AcqRel);
+    SLOT.back.store(previous & !FRESH, Ordering::Relaxed);
+}
+
+// Publish the origin pose for a session about to start
+pub(crate) fn reset() {
+    publish(ORIGIN);
+}
+
+impl ARSession {
+    // Publish a camera pose if this is the session behind the C API
+    pub(crate) fn publish_camera(&self, sample: PoseSample) {
+        if self.publishes_camera {
+            publish(sample);
+        }
+    }
+}
+
+// The latest published camera pose, or None while another read is in progress
+pub(crate) fn latest() -> Option<PoseSample> {
+    if SLOT.reading.swap(true, Ordering::Acquire) {
+        return None;
+    }
+    let mut front = SLOT.front.load(Ordering::Relaxed);
+    if SLOT.middle.load(Ordering::Relaxed) & FRESH != 0 {
+        front = SLOT.middle.swap(front, Ordering::AcqRel) & !FRESH;
+        SLOT.front.store(front, Ordering::Relaxed);
+    }
+    let sample = unsafe { *SLOT.slots[front as usize].get() };
+    SLOT.reading.store(false, Ordering::Release);
+    Some(sample)
+}
+
+// The latest camera pose, read without taking the session lock: position (3 floats),
+// rotation (4) and the session timestamp it was reported with. Outputs may be null.
+// Returns false if another thread is reading it at the same moment
+#[no_mangle]
+pub extern "C" fn get_latest_camera_pose(out_position: *mut f32, out_rotation: *mut f32, out_timestamp: *mut f64) -> bool {
+    let Some(sample) = latest() else {
+        return false;
+    };
+    unsafe {
+        write_out(out_position, sample.position);
+        write_out(out_rotation, sample.rotation);
+        if !out_timestamp.is_null() {
+            *out_timestamp = sample.timestamp;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // The slot is process-wide; tests touching it take turns
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn sample(timestamp: f64) -> PoseSample {
+        PoseSample { timestamp, position: [timestamp as f32, 0.0, 0.0], rotation: [0.0, 0.0, 0.0, 1.0] }
+    }
+
+    #[test]
+    fn reader_gets_the_latest_pose_and_keeps_it() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        reset();
+        assert_eq!(latest().unwrap().timestamp, 0.0);
+
+        for timestamp in 1..=5 {
+            publish(sample(timestamp as f64));
+        }
+        assert_eq!(latest().unwrap().timestamp, 5.0);
+        // Nothing newer published: the reader's own slot is returned again
+        assert_eq!(latest().unwrap().timestamp, 5.0);
+        publish(sample(6.0));
+        assert_eq!(latest().unwrap().timestamp, 6.0);
+    }
+
+    #[test]
+    fn overlapping_read_returns_nothing() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        SLOT.reading.store(true, Ordering::Release);
+        assert!(latest().is_none());
+        SLOT.reading.store(false, Ordering::Release);
+        assert!(latest().is_some());
+    }
+
+    #[test]
+    fn only_the_publishing_session_writes_the_slot() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        reset();
+        let mut preview = ARSession::new();
+        preview.set_camera_pose(1.0, [1.0, 2.0, 3.0], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(latest().unwrap().position, [0.0; 3]);
+
+        let mut session = ARSession::new();
+        session.publishes_camera = true;
+        session.set_camera_pose(2.0, [1.0, 2.0, 3.0], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(latest().unwrap().position, [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn late_samples_leave_the_session_untouched() {
+        let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        reset();
+        let mut session = ARSession::new();
+        session.publishes_camera = true;
+        session.set_camera_pose(1.0, [0.0; 3], [0.0, 0.0, 0.0, 1.0]);
+        session.set_camera_pose(2.0, [1.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]);
+        let keyframes: Vec<f64> = session.pose_graph.keyframes.iter().map(|keyframe| keyframe.timestamp).collect();
+
+        for timestamp in [1.5, 2.0] {
+            session.set_camera_pose(timestamp, [5.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]);
+        }
+
+        assert_eq!(session.camera_position, [1.0, 0.0, 0.0]);
+        assert_eq!(session.pose_graph.keyframes.iter().map(|keyframe| keyframe.timestamp).collect::<Vec<_>>(), keyframes);
+        let published = latest().unwrap();
+        assert_eq!((published.timestamp, published.position), (2.0, [1.0, 0.0, 0.0]));
+        assert_eq!(session.metrics.camera_updates, 2);
+    }
+}
@@ -0,0 +1,256 @@
+// Camera streams. ARKit can run face tracking on the front camera while world tracking
+// runs on the rear one, so the session keeps pose, intrinsics and frame bookkeeping per
+// camera. Each feature declares the stream it consumes in `CameraFeature::stream`; the
+// session-level camera pose is the world tracking stream's
+
+use crate::analytics::{self, Feature};
+use crate::math::{quat_conjugate, quat_rotate, sub};
+use crate::pose_filter::{write_out, PoseSample};
+use crate::render::DEFAULT_VERTICAL_FOV;
+use crate::{with_session, ARSession};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraId {
+    Rear = 0,
+    Front = 1,
+}
+
+impl CameraId {
+    pub(crate) const COUNT: usize = 2;
+
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(CameraId::Rear),
+            1 => Some(CameraId::Front),
+            _ => None,
+        }
+    }
+}
+
+// Session features that consume camera data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraFeature {
+    WorldTracking = 0,
+    Depth = 1,
+    PeopleOcclusion = 2,
+    FaceTracking = 3,
+}
+
+impl CameraFeature {
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(CameraFeature::WorldTracking),
+            1 => Some(CameraFeature::Depth),
+            2 => Some(CameraFeature::PeopleOcclusion),
+            3 => Some(CameraFeature::FaceTracking),
+            _ => None,
+        }
+    }
+
+    pub fn stream(self) -> CameraId {
+        match self {
+            CameraFeature::WorldTracking => CameraId::Rear,
+            // LiDAR sits beside the rear camera
+            CameraFeature::Depth => CameraId::Rear,
+            CameraFeature::PeopleOcclusion => CameraId::Rear,
+            CameraFeature::FaceTracking => CameraId::Front,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CameraStream {
+    pub pose: Option<PoseSample>,
+    // fx, fy, cx, cy in pixels, for `resolution`
+    pub intrinsics: Option<[f32; 4]>,
+    pub resolution: [u32; 2],
+    pub frames: u64,
+}
+
+// Image size assumed when the host hasn't reported intrinsics (ARKit's 4:3 capture)
+const DEFAULT_RESOLUTION: [u32; 2] = [1920, 1440];
+
+impl CameraStream {
+    pub fn can_project(&self) -> bool {
+        self.pose.is_some() && self.intrinsics.is_some()
+    }
+
+    // This stream, with intrinsics derived from the default field of view if the host
+    // never reported any
+    pub fn or_default_intrinsics(&self) -> CameraStream {
+        let mut stream = self.clone();
+        if stream.intrinsics.is_none() {
+            let [width, height] = DEFAULT_RESOLUTION;
+            let focal = height as f32 * 0.5 / (DEFAULT_VERTICAL_FOV * 0.5).tan();
+            stream.intrinsics = Some([focal, focal, width as f32 * 0.5, height as f32 * 0.5]);
+            stream.resolution = DEFAULT_RESOLUTION;
+        }
+        stream
+    }
+
+    // Project a world point to pixel coordinates at `resolution`, along with its depth
+    // in front of the camera. None without a pose and intrinsics, or if the point is
+    // behind the camera
+    pub fn project(&self, point: [f32; 3]) -> Option<([f32; 2], f32)> {
+        let pose = self.pose?;
+        let [fx, fy, cx, cy] = self.intrinsics?;
+
+        // Camera looks down -Z with +Y up; image rows grow downward
+        let local = quat_rotate(quat_conjugate(pose.rotation), sub(point, pose.position));
+        let depth = -local[2];
+        if depth <= 1e-4 {
+            return None;
+        }
+        Some(([cx + fx * local[0] / depth, cy - fy * local[1] / depth], depth))
+    }
+}
+
+impl ARSession {
+    pub(crate) fn camera_stream(&self, camera: CameraId) -> &CameraStream {
+        &self.cameras[camera as usize]
+    }
+
+    // Record a pose on a stream. Called for every camera update, including the
+    // session-level ones which belong to the world tracking stream
+    pub(crate) fn record_camera_pose(&mut self, camera: CameraId, sample: PoseSample) {
+        let stream = &mut self.cameras[camera as usize];
+        stream.pose = Some(sample);
+        stream.frames += 1;
+    }
+
+    fn ingest_camera_frame(&mut self, camera: CameraId, sample: PoseSample, intrinsics: [f32; 4], resolution: [u32; 2]) {
+        let stream = &mut self.cameras[camera as usize];
+        stream.intrinsics = Some(intrinsics);
+        stream.resolution = resolution;
+
+        if camera == CameraFeature::WorldTracking.stream() {
+            self.set_camera_pose(sample.timestamp, sample.position, sample.rotation);
+        } else if camera == CameraFeature::FaceTracking.stream() {
+            // Face anchors are posed relative to this stream; it never moves the
+            // session camera
+            self.record_camera_pose(camera, sample);
+        }
+    }
+}
+
+// Report a frame from one camera (0 = rear, 1 = front) with its pose and intrinsics.
+// Frames from the world tracking camera also update the session camera pose; frames
+// from the face tracking camera only update their own stream.
+// Returns false for an unknown camera or without a session
+#[no_mangle]
+pub extern "C" fn update_camera_stream(
+    camera: i32,
+    timestamp: f64,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32,
+    fx: f32, fy: f32, cx: f32, cy: f32,
+    width: u32, height: u32
+) -> bool {
+    let Some(camera) = CameraId::from_code(camera) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let sample = PoseSample {
+            timestamp: session.clock.map_host_time(timestamp),
+            position: [pos_x, pos_y, pos_z],
+            rotation: [rot_x, rot_y, rot_z, rot_w],
+        };
+        session.ingest_camera_frame(camera, sample, [fx, fy, cx, cy], [width, height]);
+        analytics::feature_used(Feature::CameraStreams);
+    })
+    .is_some()
+}
+
+// Latest pose of one camera. Returns false before its first frame. Outputs may be null
+#[no_mangle]
+pub extern "C" fn get_camera_stream_pose(
+    camera: i32,
+    out_timestamp: *mut f64,
+    out_position: *mut f32,
+    out_rotation: *mut f32,
+) -> bool {
+    let Some(camera) = CameraId::from_code(camera) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let pose = session.camera_stream(camera).pose?;
+        unsafe {
+            if !out_timestamp.is_null() {
+                *out_timestamp = pose.timestamp;
+            }
+            write_out(out_position, pose.position);
+            write_out(out_rotation, pose.rotation);
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+// Intrinsics (fx, fy, cx, cy) and resolution (width, height) last reported for one
+// camera. Returns false if none were reported. Outputs may be null
+#[no_mangle]
+pub extern "C" fn get_camera_stream_intrinsics(camera: i32, out_intrinsics: *mut f32, out_resolution: *mut u32) -> bool {
+    let Some(camera) = CameraId::from_code(camera) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let stream = session.camera_stream(camera);
+        let intrinsics = stream.intrinsics?;
+        unsafe {
+            write_out(out_intrinsics, intrinsics);
+            if !out_resolution.is_null() {
+                std::slice::from_raw_parts_mut(out_resolution, 2).copy_from_slice(&stream.resolution);
+            }
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+// Frames received from one camera, or 0 for an unknown camera
+#[no_mangle]
+pub extern "C" fn get_camera_stream_frame_count(camera: i32) -> u64 {
+    let Some(camera) = CameraId::from_code(camera) else {
+        return 0;
+    };
+
+    with_session(|session| session.camera_stream(camera).frames).unwrap_or(0)
+}
+
+// Camera a feature consumes (see AR_FEATURE_* in arlens.h), or -1 if unknown
+#[no_mangle]
+pub extern "C" fn get_feature_camera(feature: i32) -> i32 {
+    CameraFeature::from_code(feature).map_or(-1, |feature| feature.stream() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn sample(timestamp: f64, position: [f32; 3]) -> PoseSample {
+        PoseSample { timestamp, position, rotation: IDENTITY }
+    }
+
+    #[test]
+    fn face_tracking_frames_stay_on_the_front_stream() {
+        let mut session = ARSession::new();
+        let intrinsics = [1000.0, 1000.0, 640.0, 360.0];
+        session.ingest_camera_frame(CameraFeature::WorldTracking.stream(), sample(1.0, [1.0, 0.0, 0.0]), intrinsics, [1280, 720]);
+        session.ingest_camera_frame(CameraFeature::FaceTracking.stream(), sample(1.1, [0.0, 0.0, 5.0]), intrinsics, [640, 480]);
+
+        assert_eq!(CameraFeature::FaceTracking.stream(), CameraId::Front);
+        let face = session.camera_stream(CameraFeature::FaceTracking.stream());
+        assert_eq!(face.pose.map(|pose| pose.position), Some([0.0, 0.0, 5.0]));
+        assert_eq!(face.resolution, [640, 480]);
+        assert_eq!(face.frames, 1);
+        assert_eq!(session.camera_position, [1.0, 0.0, 0.0]);
+        assert_eq!(session.camera_stream(CameraId::Rear).resolution, [1280, 720]);
+    }
+}
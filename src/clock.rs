@@ -0,0 +1,147 @@
+// Session timeline. Every ingested sample (camera poses, planes, depth frames) is
+// stamped in seconds on one monotonic timeline. Until the host provides a time source
+// this is time since the session started; after `ar_set_time_source` it follows the
+// host clock, so host-supplied timestamps such as ARFrame.timestamp line up with ours.
+// A host timestamp arriving before any time source adopts the host clock the same way,
+// so samples stamped by us and by the host never mix two timelines.
+//
+// wasm32 has no monotonic clock (Instant panics there), so in the browser preview the
+// timeline only moves when the JS host sets it, e.g. from performance.now()
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use crate::pose_filter::write_out;
+use crate::with_session;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SessionClock {
+    // Host time in seconds at `synced_at`, or session start when never synced
+    base: f64,
+    #[cfg(not(target_arch = "wasm32"))]
+    synced_at: Instant,
+    // Added to host timestamps to put them on the timeline; None until synced
+    host_offset: Option<f64>,
+}
+
+impl SessionClock {
+    pub fn new() -> Self {
+        SessionClock {
+            base: 0.0,
+            #[cfg(not(target_arch = "wasm32"))]
+            synced_at: Instant::now(),
+            host_offset: None,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn now(&self) -> f64 {
+        self.base + self.synced_at.elapsed().as_secs_f64()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn now(&self) -> f64 {
+        self.base
+    }
+
+    // Re-anchor the timeline to the host clock. The timeline never runs backwards, so
+    // a host time behind the current estimate only stops it advancing until caught up
+    pub fn sync(&mut self, host_seconds: f64) {
+        let current = self.now();
+        self.base = host_seconds.max(current);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.synced_at = Instant::now();
+        }
+        self.host_offset = Some(self.base - host_seconds);
+    }
+
+    // A host-supplied timestamp on the session timeline. The first one seen before any
+    // time source syncs the clock to it
+    pub fn map_host_time(&mut self, host_seconds: f64) -> f64 {
+        if self.host_offset.is_none() {
+            self.sync(host_seconds);
+        }
+        host_seconds + self.host_offset.unwrap_or(0.0)
+    }
+}
+
+// Provide the host's current monotonic time in nanoseconds (e.g. from
+// clock_gettime_nsec_np(CLOCK_UPTIME_RAW), the clock behind ARFrame.timestamp). Can be
+// called every frame to keep the timelines from drifting apart
+#[no_mangle]
+pub extern "C" fn ar_set_time_source(host_time_ns: u64) {
+    with_session(|session| session.clock.sync(host_time_ns as f64 / 1e9));
+}
+
+// Current time on the session timeline in seconds, or -1 without a session
+#[no_mangle]
+pub extern "C" fn get_session_time() -> f64 {
+    with_session(|session| session.clock.now()).unwrap_or(-1.0)
+}
+
+// Camera pose as of `timestamp`, interpolated between the recorded samples. Returns
+// false if the time falls outside the retained history. Outputs may be null
+#[no_mangle]
+pub extern "C" fn get_camera_pose_at(timestamp: f64, out_position: *mut f32, out_rotation: *mut f32) -> bool {
+    with_session(|session| {
+        let (position, rotation) = session.camera_filter.pose_at(timestamp)?;
+        unsafe {
+            write_out(out_position, position);
+            write_out(out_rotation, rotation);
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+// Number of planes that had been detected as of `timestamp`, or -1 without a session
+#[no_mangle]
+pub extern "C" fn get_plane_count_at(timestamp: f64) -> i32 {
+    with_session(|session| {
+        session.detected_planes.iter().filter(|plane| plane.detected_at <= timestamp).count() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Timestamp of the depth frame behind the current point cloud, or -1 if there is none
+#[cfg(feature = "reconstruction")]
+#[no_mangle]
+pub extern "C" fn get_point_cloud_timestamp() -> f64 {
+    with_session(|session| session.point_cloud_timestamp).flatten().unwrap_or(-1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_host_timestamp_adopts_host_clock() {
+        let mut clock = SessionClock::new();
+        let before = clock.now();
+        let stamped = clock.map_host_time(1000.0);
+        assert_eq!(stamped, 1000.0);
+        assert!(stamped >= before);
+        let after = clock.now();
+        assert!((1000.0..1001.0).contains(&after));
+        // Later host timestamps keep the same mapping
+        assert_eq!(clock.map_host_time(1000.5), 1000.5);
+    }
+
+    #[test]
+    fn host_clock_behind_session_is_shifted_forward() {
+        let mut clock = SessionClock::new();
+        clock.sync(10.0);
+        let stamped = clock.map_host_time(4.0);
+        // Synced to 10 while the host said 10, so the mapping is exact
+        assert_eq!(stamped, 4.0);
+
+        let mut clock = SessionClock::new();
+        clock.base = 50.0;
+        let stamped = clock.map_host_time(20.0);
+        // The timeline can't run backwards, so host times are moved onto it
+        assert!(stamped >= 50.0);
+        assert!(clock.now() >= stamped);
+    }
+}
@@ -0,0 +1,217 @@
+// Camera image analysis for matching virtual content to the real image. Each submitted
+// frame is sampled on a sparse grid to estimate its white balance (gray-world, ignoring
+// clipped and near-black pixels) and exposure (log-average luminance against middle
+// gray). The results are smoothed over time and published as multipliers the renderer
+// applies to virtual objects, so they pick up the camera's color cast and brightness
+
+use serde::{Deserialize, Serialize};
+
+use crate::analytics::{self, Feature};
+use crate::ingestion::{self, IngestStream};
+use crate::shared_buffers;
+use crate::with_session;
+
+pub const AR_PIXEL_FORMAT_BGRA8: i32 = 0;
+pub const AR_PIXEL_FORMAT_RGBA8: i32 = 1;
+
+// Sample every Nth pixel in each direction; plenty for global statistics
+const SAMPLE_STRIDE: usize = 8;
+
+// Log-average luminance a "correctly exposed" frame is expected to have
+const MIDDLE_GRAY: f32 = 0.18;
+
+// Seconds for the smoothed parameters to close ~63% of the gap to a new estimate
+const SMOOTHING_TIME_CONSTANT: f64 = 0.5;
+
+// Per-frame grading for virtual content. Multiply an object's linear color by
+// `white_balance` and `exposure_scale`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorGrading {
+    // Per-channel RGB multipliers with unit luminance
+    pub white_balance: [f32; 3],
+    pub exposure_scale: f32,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        ColorGrading { white_balance: [1.0; 3], exposure_scale: 1.0 }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ColorAnalysis {
+    pub grading: ColorGrading,
+    last_frame: Option<f64>,
+}
+
+impl ColorAnalysis {
+    // Blend a frame's estimate into the running grading
+    fn update(&mut self, timestamp: f64, estimate: ColorGrading) {
+        let blend = match self.last_frame {
+            Some(last) if timestamp > last => (1.0 - (-(timestamp - last) / SMOOTHING_TIME_CONSTANT).exp()) as f32,
+            Some(_) => return,
+            None => 1.0,
+        };
+        let lerp = |a: f32, b: f32| a + (b - a) * blend;
+
+        let current = &mut self.grading;
+        for channel in 0..3 {
+            current.white_balance[channel] = lerp(current.white_balance[channel], estimate.white_balance[channel]);
+        }
+        // Exposure blends in log space so brightening and darkening ease alike
+        current.exposure_scale = lerp(current.exposure_scale.log2(), estimate.exposure_scale.log2()).exp2();
+        self.last_frame = Some(timestamp);
+    }
+}
+
+pub(crate) fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
+
+// Estimate grading from an 8-bit sRGB image. `channel_order` gives the byte offsets of
+// R, G and B within each 4-byte pixel. Returns None for a frame with nothing usable,
+// e.g. fully black or blown out
+pub(crate) fn analyze(pixels: &[u8], width: usize, height: usize, bytes_per_row: usize, channel_order: [usize; 3]) -> Option<ColorGrading> {
+    let mut sum = [0.0f32; 3];
+    let mut log_luminance = 0.0f32;
+    let mut count = 0usize;
+
+    for y in (0..height).step_by(SAMPLE_STRIDE) {
+        let row = &pixels[y * bytes_per_row..];
+        for x in (0..width).step_by(SAMPLE_STRIDE) {
+            let pixel = &row[x * 4..x * 4 + 4];
+            let bytes = channel_order.map(|offset| pixel[offset]);
+            // Clipped channels no longer carry color information
+            if bytes.iter().any(|&b| b >= 250) {
+                continue;
+            }
+
+            let rgb = bytes.map(srgb_to_linear);
+            let luma = luminance(rgb);
+            if luma < 0.005 {
+                continue;
+            }
+            for channel in 0..3 {
+                sum[channel] += rgb[channel];
+            }
+            log_luminance += luma.ln();
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return None;
+    }
+
+    let mean = sum.map(|s| s / count as f32);
+    let mean_luma = luminance(mean);
+    let key = (log_luminance / count as f32).exp();
+
+    Some(ColorGrading {
+        white_balance: mean.map(|c| (c / mean_luma).clamp(0.5, 2.0)),
+        exposure_scale: (key / MIDDLE_GRAY).clamp(0.25, 4.0),
+    })
+}
+
+// Analyze a camera frame (AR_PIXEL_FORMAT_BGRA8 or _RGBA8, 4 bytes per pixel) and fold
+// it into the session's color grading, and into the environment map while capture is
+// on. Returns false on bad input, an unusable frame, a frame turned away by the camera
+// image queue, or without a session
+#[no_mangle]
+pub extern "C" fn submit_camera_image(
+    pixels: *const u8,
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    pixel_format: i32,
+) -> bool {
+    let Some((channel_order, width, height, bytes_per_row)) = image_layout(width, height, bytes_per_row, pixel_format) else {
+        return false;
+    };
+    if pixels.is_null() {
+        return false;
+    }
+
+    let image = unsafe { std::slice::from_raw_parts(pixels, image_size(width, height, bytes_per_row)) };
+    ingest_camera_image(image, width, height, bytes_per_row, channel_order)
+}
+
+// Like submit_camera_image, but reading the image in place from a shared buffer (see
+// shared_buffers.rs) written at `generation`. Also returns false for an unknown buffer,
+// one too small for the image, or one rewritten since or being written
+#[no_mangle]
+pub extern "C" fn submit_shared_camera_image(
+    buffer_id: i32,
+    generation: u64,
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    pixel_format: i32,
+) -> bool {
+    let Some((channel_order, width, height, bytes_per_row)) = image_layout(width, height, bytes_per_row, pixel_format) else {
+        return false;
+    };
+    let Ok(read) = shared_buffers::read(buffer_id, generation) else {
+        return false;
+    };
+
+    let Some(image) = read.bytes().get(..image_size(width, height, bytes_per_row)) else {
+        return false;
+    };
+    ingest_camera_image(image, width, height, bytes_per_row, channel_order)
+}
+
+// Channel order and sizes for an image, or None if they're unusable
+fn image_layout(width: u32, height: u32, bytes_per_row: u32, pixel_format: i32) -> Option<([usize; 3], usize, usize, usize)> {
+    let channel_order = match pixel_format {
+        AR_PIXEL_FORMAT_BGRA8 => [2, 1, 0],
+        AR_PIXEL_FORMAT_RGBA8 => [0, 1, 2],
+        _ => return None,
+    };
+    let (width, height, bytes_per_row) = (width as usize, height as usize, bytes_per_row as usize);
+    (width > 0 && height > 0 && bytes_per_row >= width * 4).then_some((channel_order, width, height, bytes_per_row))
+}
+
+// Bytes spanned by an image; the last row needn't be padded
+fn image_size(width: usize, height: usize, bytes_per_row: usize) -> usize {
+    bytes_per_row * (height - 1) + width * 4
+}
+
+// Queue and process a validated camera image; see submit_camera_image
+fn ingest_camera_image(image: &[u8], width: usize, height: usize, bytes_per_row: usize, channel_order: [usize; 3]) -> bool {
+    let Some(timestamp) = with_session(|session| session.clock.now()) else {
+        return false;
+    };
+    let Some(_turn) = ingestion::begin_frame(IngestStream::CameraImage) else {
+        return false;
+    };
+    #[cfg(feature = "environment")]
+    with_session(|session| session.capture_environment(image, width, height, bytes_per_row, channel_order));
+    let Some(estimate) = analyze(image, width, height, bytes_per_row, channel_order) else {
+        return false;
+    };
+
+    analytics::feature_used(Feature::ColorGrading);
+    with_session(|session| session.color_analysis.update(timestamp, estimate)).is_some()
+}
+
+// Current grading for virtual content: RGB white balance multipliers (3 floats) and an
+// exposure multiplier. Outputs may be null. Neutral until a frame has been analyzed
+#[no_mangle]
+pub extern "C" fn get_color_grading(out_white_balance: *mut f32, out_exposure_scale: *mut f32) -> bool {
+    with_session(|session| {
+        let grading = session.color_analysis.grading;
+        unsafe {
+            crate::pose_filter::write_out(out_white_balance, grading.white_balance);
+            if !out_exposure_scale.is_null() {
+                *out_exposure_scale = grading.exposure_scale;
+            }
+        }
+    })
+    .is_some()
+}
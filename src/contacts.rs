@@ -0,0 +1,557 @@
+// Contact resolution for dynamic bodies, against planes and against each other. Bodies
+// collide as spheres, boxes or capsules. Contacts are resolved with impulses at the contact
+// points, so a box struck off-center or pushed past an edge picks up spin, topples and
+// settles onto a face rather than staying frozen upright. Spheres slide without
+// rolling.
+//
+// Contacts are solved iteratively with accumulated impulses, so the corners of a box
+// lying on a face share its weight evenly and it stays put. Box-box contacts test each
+// box's corners against the other box, so edge-on-edge grazes are missed; capsules
+// meet boxes only with their end spheres, for the same reason. Capsules spin like a
+// box around them. Sleeping
+// bodies (see sleep.rs) are immovable until an awake body moving into them wakes them
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::{add, cross, dot, length, quat_conjugate, quat_rotate, scale, sub, tangent_basis};
+use crate::physics::RigidBody;
+use crate::surfaces::SurfaceMaterial;
+use crate::{ARObject, ARPlane, ARSession};
+
+// Below this approach speed in m/s contacts don't bounce, so resting bodies settle
+const RESTING_SPEED: f32 = 0.2;
+
+// Impulse passes over a substep's contacts
+const ITERATIONS: usize = 8;
+
+// Box corners this close above a plane get a contact ahead of touching it, so a box
+// landing on a face meets the plane with all four corners instead of rocking between
+// them
+const CONTACT_SLOP: f32 = 0.005;
+
+// Penetration beyond the slop is pushed out over a few substeps, at this fraction per
+// substep. Correcting through the contact impulses rather than moving the body keeps a
+// resting box from being lifted off its corners and rocking
+const PENETRATION_SLOP: f32 = 0.001;
+const PENETRATION_CORRECTION: f32 = 0.2;
+
+// A body's collision shape before the object's scale
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColliderShape {
+    // The object's bounding sphere
+    Bounds,
+    // Half extents in meters
+    Box([f32; 3]),
+    // Along local y, `half_height` from the center to each cap's center
+    Capsule { radius: f32, half_height: f32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Collider {
+    Sphere(f32),
+    // Half extents in meters
+    Box([f32; 3]),
+    Capsule { radius: f32, half_height: f32 },
+}
+
+impl Collider {
+    pub fn of(object: &ARObject, body: &RigidBody) -> Self {
+        match body.shape {
+            ColliderShape::Bounds => Collider::Sphere(object.bounding_radius()),
+            ColliderShape::Box(half_extents) => Collider::Box(scale(half_extents, object.scale)),
+            ColliderShape::Capsule { radius, half_height } => {
+                Collider::Capsule { radius: radius * object.scale, half_height: half_height * object.scale }
+            }
+        }
+    }
+
+    // Largest sphere inside the shape, for swept collision
+    pub fn inner_radius(self) -> f32 {
+        match self {
+            Collider::Sphere(radius) | Collider::Capsule { radius, .. } => radius,
+            Collider::Box([x, y, z]) => x.min(y).min(z),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Contact {
+    point: [f32; 3],
+    // From the first body toward the second
+    normal: [f32; 3],
+    // Negative for a gap not yet closed
+    depth: f32,
+}
+
+// Working copy of a body's state while contacts are resolved
+#[derive(Debug, Clone, Copy)]
+struct Motion {
+    position: [f32; 3],
+    rotation: [f32; 4],
+    velocity: [f32; 3],
+    angular_velocity: [f32; 3],
+    inverse_mass: f32,
+    // Body-frame inverse inertia; zero for spheres, which don't spin
+    inverse_inertia: [f32; 3],
+    collider: Collider,
+    material: SurfaceMaterial,
+}
+
+impl Motion {
+    fn of(object: &ARObject, body: &RigidBody) -> Self {
+        let collider = Collider::of(object, body);
+        let box_extents = match collider {
+            Collider::Sphere(_) => None,
+            Collider::Box(half_extents) => Some(half_extents),
+            Collider::Capsule { radius, half_height } => Some([radius, half_height + radius, radius]),
+        };
+        let inverse_inertia = box_extents.map_or([0.0; 3], |[x, y, z]| {
+            let k = body.mass / 3.0;
+            [1.0 / (k * (y * y + z * z)), 1.0 / (k * (x * x + z * z)), 1.0 / (k * (x * x + y * y))]
+        });
+        let awake = if body.sleeping { 0.0 } else { 1.0 };
+        Motion {
+            position: object.position,
+            rotation: object.rotation,
+            velocity: body.velocity,
+            angular_velocity: body.angular_velocity,
+            inverse_mass: awake / body.mass,
+            inverse_inertia: inverse_inertia.map(|i| i * awake),
+            collider,
+            material: body.material,
+        }
+    }
+
+    // Immovable stand-in for a plane
+    fn fixed() -> Self {
+        Motion {
+            position: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            velocity: [0.0; 3],
+            angular_velocity: [0.0; 3],
+            inverse_mass: 0.0,
+            inverse_inertia: [0.0; 3],
+            collider: Collider::Sphere(0.0),
+            material: SurfaceMaterial::new(0.0, 0.0),
+        }
+    }
+
+    fn apply_inverse_inertia(&self, v: [f32; 3]) -> [f32; 3] {
+        let local = quat_rotate(quat_conjugate(self.rotation), v);
+        let [x, y, z] = self.inverse_inertia;
+        quat_rotate(self.rotation, [local[0] * x, local[1] * y, local[2] * z])
+    }
+
+    fn point_velocity(&self, point: [f32; 3]) -> [f32; 3] {
+        add(self.velocity, cross(self.angular_velocity, sub(point, self.position)))
+    }
+
+    // Velocity change at `point` along `direction` per unit impulse there
+    fn response(&self, point: [f32; 3], direction: [f32; 3]) -> f32 {
+        let r = sub(point, self.position);
+        self.inverse_mass + dot(direction, cross(self.apply_inverse_inertia(cross(r, direction)), r))
+    }
+
+    fn apply_impulse(&mut self, impulse: [f32; 3], point: [f32; 3]) {
+        let r = sub(point, self.position);
+        self.velocity = add(self.velocity, scale(impulse, self.inverse_mass));
+        self.angular_velocity = add(self.angular_velocity, self.apply_inverse_inertia(cross(r, impulse)));
+    }
+}
+
+fn box_corners(position: [f32; 3], rotation: [f32; 4], half_extents: [f32; 3]) -> [[f32; 3]; 8] {
+    let [x, y, z] = half_extents;
+    std::array::from_fn(|i| {
+        let local = [
+            if i & 1 == 0 { -x } else { x },
+            if i & 2 == 0 { -y } else { y },
+            if i & 4 == 0 { -z } else { z },
+        ];
+        add(position, quat_rotate(rotation, local))
+    })
+}
+
+// For a point inside a box: the outward normal of the nearest face and the depth below it
+fn point_in_box(point: [f32; 3], position: [f32; 3], rotation: [f32; 4], half_extents: [f32; 3]) -> Option<([f32; 3], f32)> {
+    let local = quat_rotate(quat_conjugate(rotation), sub(point, position));
+    let mut nearest: Option<(usize, f32)> = None;
+    for axis in 0..3 {
+        let depth = half_extents[axis] - local[axis].abs();
+        if depth <= 0.0 {
+            return None;
+        }
+        if nearest.is_none_or(|(_, best)| depth < best) {
+            nearest = Some((axis, depth));
+        }
+    }
+    let (axis, depth) = nearest?;
+    let mut normal = [0.0; 3];
+    normal[axis] = if local[axis] < 0.0 { -1.0 } else { 1.0 };
+    Some((quat_rotate(rotation, normal), depth))
+}
+
+// Contact with the normal pointing from the box toward the sphere
+fn sphere_box(center: [f32; 3], radius: f32, position: [f32; 3], rotation: [f32; 4], half_extents: [f32; 3]) -> Option<Contact> {
+    let local = quat_rotate(quat_conjugate(rotation), sub(center, position));
+    let clamped: [f32; 3] = std::array::from_fn(|i| local[i].clamp(-half_extents[i], half_extents[i]));
+    let offset = sub(local, clamped);
+    let distance = length(offset);
+    if distance > 1e-6 {
+        if distance >= radius {
+            return None;
+        }
+        return Some(Contact {
+            point: add(position, quat_rotate(rotation, clamped)),
+            normal: quat_rotate(rotation, scale(offset, 1.0 / distance)),
+            depth: radius - distance,
+        });
+    }
+    // Center inside the box
+    let (normal, depth) = point_in_box(center, position, rotation, half_extents)?;
+    Some(Contact { point: sub(center, scale(normal, radius)), normal, depth: depth + radius })
+}
+
+fn reversed(contact: Contact) -> Contact {
+    Contact { normal: scale(contact.normal, -1.0), ..contact }
+}
+
+// Contact with the normal pointing from the first sphere toward the second
+fn sphere_sphere(a: [f32; 3], ra: f32, b: [f32; 3], rb: f32) -> Option<Contact> {
+    let offset = sub(b, a);
+    let distance = length(offset);
+    if distance >= ra + rb || distance < 1e-6 {
+        return None;
+    }
+    let normal = scale(offset, 1.0 / distance);
+    Some(Contact { point: add(a, scale(normal, ra)), normal, depth: ra + rb - distance })
+}
+
+// End points of a capsule's core segment
+fn capsule_segment(position: [f32; 3], rotation: [f32; 4], half_height: f32) -> [[f32; 3]; 2] {
+    let axis = quat_rotate(rotation, [0.0, half_height, 0.0]);
+    [sub(position, axis), add(position, axis)]
+}
+
+fn closest_on_segment(point: [f32; 3], [start, end]: [[f32; 3]; 2]) -> [f32; 3] {
+    let direction = sub(end, start);
+    let length_squared = dot(direction, direction);
+    if length_squared < 1e-12 {
+        return start;
+    }
+    let t = (dot(sub(point, start), direction) / length_squared).clamp(0.0, 1.0);
+    add(start, scale(direction, t))
+}
+
+// Closest points between two segments
+fn closest_between_segments(a: [[f32; 3]; 2], b: [[f32; 3]; 2]) -> ([f32; 3], [f32; 3]) {
+    let (da, db) = (sub(a[1], a[0]), sub(b[1], b[0]));
+    let r = sub(a[0], b[0]);
+    let (aa, bb, ab) = (dot(da, da), dot(db, db), dot(da, db));
+    let (ar, br) = (dot(da, r), dot(db, r));
+    let denominator = aa * bb - ab * ab;
+    // Parallel (or degenerate) segments: any point of overlap will do
+    let s = if denominator > 1e-9 { ((ab * br - ar * bb) / denominator).clamp(0.0, 1.0) } else { 0.0 };
+    let on_b = closest_on_segment(add(a[0], scale(da, s)), b);
+    let on_a = closest_on_segment(on_b, a);
+    (on_a, on_b)
+}
+
+fn body_contacts(a: &Motion, b: &Motion) -> Vec<Contact> {
+    match (a.collider, b.collider) {
+        (Collider::Sphere(ra), Collider::Sphere(rb)) => {
+            sphere_sphere(a.position, ra, b.position, rb).into_iter().collect()
+        }
+        (Collider::Sphere(radius), Collider::Box(half_extents)) => {
+            sphere_box(a.position, radius, b.position, b.rotation, half_extents).map(reversed).into_iter().collect()
+        }
+        (Collider::Box(half_extents), Collider::Sphere(radius)) => {
+            sphere_box(b.position, radius, a.position, a.rotation, half_extents).into_iter().collect()
+        }
+        (Collider::Box(ha), Collider::Box(hb)) => {
+            // Corners of each box inside the other
+            let a_in_b = box_corners(a.position, a.rotation, ha).into_iter().filter_map(|corner| {
+                let (normal, depth) = point_in_box(corner, b.position, b.rotation, hb)?;
+                Some(Contact { point: corner, normal: scale(normal, -1.0), depth })
+            });
+            let b_in_a = box_corners(b.position, b.rotation, hb).into_iter().filter_map(|corner| {
+                let (normal, depth) = point_in_box(corner, a.position, a.rotation, ha)?;
+                Some(Contact { point: corner, normal, depth })
+            });
+            a_in_b.chain(b_in_a).collect()
+        }
+        (Collider::Capsule { radius: ra, half_height }, Collider::Sphere(rb)) => {
+            let core = closest_on_segment(b.position, capsule_segment(a.position, a.rotation, half_height));
+            sphere_sphere(core, ra, b.position, rb).into_iter().collect()
+        }
+        (Collider::Sphere(ra), Collider::Capsule { radius: rb, half_height }) => {
+            let core = closest_on_segment(a.position, capsule_segment(b.position, b.rotation, half_height));
+            sphere_sphere(a.position, ra, core, rb).into_iter().collect()
+        }
+        (Collider::Capsule { radius: ra, half_height: ha }, Collider::Capsule { radius: rb, half_height: hb }) => {
+            let (on_a, on_b) = closest_between_segments(
+                capsule_segment(a.position, a.rotation, ha),
+                capsule_segment(b.position, b.rotation, hb),
+            );
+            sphere_sphere(on_a, ra, on_b, rb).into_iter().collect()
+        }
+        (Collider::Capsule { radius, half_height }, Collider::Box(half_extents)) => {
+            capsule_segment(a.position, a.rotation, half_height).into_iter()
+                .filter_map(|end| sphere_box(end, radius, b.position, b.rotation, half_extents).map(reversed))
+                .collect()
+        }
+        (Collider::Box(half_extents), Collider::Capsule { radius, half_height }) => {
+            capsule_segment(b.position, b.rotation, half_height).into_iter()
+                .filter_map(|end| sphere_box(end, radius, a.position, a.rotation, half_extents))
+                .collect()
+        }
+    }
+}
+
+// Contacts with the normal pointing out of the plane toward the body, on whichever
+// side its center is
+fn plane_contacts(plane: &ARPlane, body: &Motion) -> Vec<Contact> {
+    let side = if dot(sub(body.position, plane.center), plane.normal) >= 0.0 { 1.0 } else { -1.0 };
+    let normal = scale(plane.normal, side);
+    let height = |point: [f32; 3]| dot(sub(point, plane.center), normal);
+
+    match body.collider {
+        Collider::Sphere(radius) => {
+            let distance = height(body.position);
+            if distance >= radius || !plane.within_extent(body.position) {
+                return Vec::new();
+            }
+            vec![Contact { point: sub(body.position, scale(normal, radius)), normal, depth: radius - distance }]
+        }
+        Collider::Box(half_extents) => box_corners(body.position, body.rotation, half_extents)
+            .into_iter()
+            .filter(|&corner| height(corner) < CONTACT_SLOP && plane.within_extent(corner))
+            .map(|corner| Contact { point: corner, normal, depth: -height(corner) })
+            .collect(),
+        // Each end sphere, ahead of touching like box corners so a capsule lying down
+        // rests on both
+        Collider::Capsule { radius, half_height } => capsule_segment(body.position, body.rotation, half_height)
+            .into_iter()
+            .filter(|&end| height(end) < radius + CONTACT_SLOP && plane.within_extent(end))
+            .map(|end| Contact { point: sub(end, scale(normal, radius)), normal, depth: radius - height(end) })
+            .collect(),
+    }
+}
+
+// A contact being solved, with the impulses accumulated on it so far. Clamping the
+// totals rather than each step keeps a resting box's corners from fighting
+struct ContactPoint {
+    contact: Contact,
+    tangents: ([f32; 3], [f32; 3]),
+    // Separation speed to reach: the bounce, zero for a resting contact, or the
+    // approach that just closes a gap within the substep
+    target_speed: f32,
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+}
+
+impl ContactPoint {
+    fn new(contact: Contact, a: &Motion, b: &Motion, material: SurfaceMaterial, h: f32) -> Self {
+        let relative = sub(b.point_velocity(contact.point), a.point_velocity(contact.point));
+        let normal_speed = dot(relative, contact.normal);
+        let target_speed = if contact.depth < 0.0 {
+            contact.depth / h
+        } else if normal_speed < -RESTING_SPEED {
+            -normal_speed * material.restitution
+        } else {
+            0.0
+        };
+        let push_out = PENETRATION_CORRECTION * (contact.depth - PENETRATION_SLOP).max(0.0) / h;
+        ContactPoint {
+            contact,
+            tangents: tangent_basis(contact.normal),
+            target_speed: target_speed.max(push_out),
+            normal_impulse: 0.0,
+            tangent_impulse: [0.0; 2],
+        }
+    }
+
+    // One solver pass: push the contact apart, then apply friction bounded by the
+    // normal impulse. Impulses act on `b` and oppositely on `a`
+    fn solve(&mut self, a: &mut Motion, b: &mut Motion, material: SurfaceMaterial) {
+        let (point, normal) = (self.contact.point, self.contact.normal);
+        let relative = |a: &Motion, b: &Motion| sub(b.point_velocity(point), a.point_velocity(point));
+        let apply = |a: &mut Motion, b: &mut Motion, impulse: [f32; 3]| {
+            a.apply_impulse(scale(impulse, -1.0), point);
+            b.apply_impulse(impulse, point);
+        };
+
+        let normal_speed = dot(relative(a, b), normal);
+        let step = (self.target_speed - normal_speed) / (a.response(point, normal) + b.response(point, normal));
+        let total = (self.normal_impulse + step).max(0.0);
+        apply(a, b, scale(normal, total - self.normal_impulse));
+        self.normal_impulse = total;
+
+        let (t1, t2) = self.tangents;
+        let velocity = relative(a, b);
+        let mut total = [
+            self.tangent_impulse[0] - dot(velocity, t1) / (a.response(point, t1) + b.response(point, t1)),
+            self.tangent_impulse[1] - dot(velocity, t2) / (a.response(point, t2) + b.response(point, t2)),
+        ];
+        let limit = material.friction * self.normal_impulse;
+        let magnitude = (total[0] * total[0] + total[1] * total[1]).sqrt();
+        if magnitude > limit {
+            total = total.map(|t| t * limit / magnitude);
+        }
+        let delta = [total[0] - self.tangent_impulse[0], total[1] - self.tangent_impulse[1]];
+        apply(a, b, add(scale(t1, delta[0]), scale(t2, delta[1])));
+        self.tangent_impulse = total;
+    }
+}
+
+enum Pair {
+    Plane(usize),
+    Bodies(usize, usize),
+}
+
+// Mutable access to two distinct entries, a < b
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    let (head, tail) = items.split_at_mut(b);
+    (&mut head[a], &mut tail[0])
+}
+
+impl ARSession {
+    pub(crate) fn resolve_contacts(&mut self, h: f32) {
+        let mut motions: Vec<Option<Motion>> = self.virtual_objects.iter()
+            .map(|object| object.body.as_ref().map(|body| Motion::of(object, body)))
+            .collect();
+
+        let sleeping: Vec<bool> = self.virtual_objects.iter()
+            .map(|object| object.body.as_ref().is_some_and(|body| body.sleeping))
+            .collect();
+
+        let ground = Motion::fixed();
+        let mut manifolds = Vec::new();
+        for (index, motion) in motions.iter().enumerate() {
+            let Some(motion) = motion else {
+                continue;
+            };
+            for (plane_index, plane) in self.detected_planes.iter().enumerate().filter(|_| !sleeping[index]) {
+                let material = motion.material.combine(self.plane_material(plane_index));
+                let points: Vec<_> = plane_contacts(plane, motion).into_iter()
+                    .map(|contact| ContactPoint::new(contact, &ground, motion, material, h))
+                    .collect();
+                if !points.is_empty() {
+                    manifolds.push((Pair::Plane(index), points, material));
+                }
+            }
+            for (other_index, other) in motions.iter().enumerate().skip(index + 1) {
+                let Some(other) = other.as_ref().filter(|_| !(sleeping[index] && sleeping[other_index])) else {
+                    continue;
+                };
+                let material = motion.material.combine(other.material);
+                let points: Vec<_> = body_contacts(motion, other).into_iter()
+                    .map(|contact| ContactPoint::new(contact, motion, other, material, h))
+                    .collect();
+                if !points.is_empty() {
+                    manifolds.push((Pair::Bodies(index, other_index), points, material));
+                }
+            }
+        }
+        if manifolds.is_empty() {
+            return;
+        }
+
+        // A sleeping body struck by a moving one joins the solve
+        let linear_threshold = self.physics.sleep.linear_threshold;
+        for (pair, ..) in &manifolds {
+            let Pair::Bodies(a, b) = *pair else {
+                continue;
+            };
+            for (sleeper, other) in [(a, b), (b, a)] {
+                let moving = motions[other].as_ref().is_some_and(|m| length(m.velocity) >= linear_threshold);
+                if sleeping[sleeper] && !sleeping[other] && moving && self.wake_object(sleeper) {
+                    let object = &self.virtual_objects[sleeper];
+                    motions[sleeper] = object.body.as_ref().map(|body| Motion::of(object, body));
+                }
+            }
+        }
+
+        let mut ground = ground;
+        for _ in 0..ITERATIONS {
+            for (pair, points, material) in &mut manifolds {
+                let (a, b) = match *pair {
+                    Pair::Plane(body) => (&mut ground, motions[body].as_mut()),
+                    Pair::Bodies(a, b) => {
+                        let (a, b) = pair_mut(&mut motions, a, b);
+                        let Some(a) = a.as_mut() else {
+                            continue;
+                        };
+                        (a, b.as_mut())
+                    }
+                };
+                let Some(b) = b else {
+                    continue;
+                };
+                for point in points.iter_mut() {
+                    point.solve(a, b, *material);
+                }
+            }
+        }
+
+        for (object, motion) in self.virtual_objects.iter_mut().zip(motions) {
+            let (Some(body), Some(motion)) = (object.body.as_mut().filter(|body| !body.sleeping), motion) else {
+                continue;
+            };
+            object.position = motion.position;
+            body.velocity = motion.velocity;
+            body.angular_velocity = motion.angular_velocity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+    const DT: f32 = 1.0 / 60.0;
+
+    fn place_body(session: &mut ARSession, object_type: ARObjectType, position: [f32; 3], velocity: [f32; 3]) -> usize {
+        let index = session.place_object(object_type, position, IDENTITY).unwrap();
+        let mut body = RigidBody::for_object(&session.virtual_objects[index], 1.0);
+        body.velocity = velocity;
+        session.virtual_objects[index].body = Some(body);
+        index
+    }
+
+    #[test]
+    fn a_box_resting_on_a_face_stays_put() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".into()), [0.0; 3], [2.0, 2.0], [0.0, 1.0, 0.0]);
+        let index = place_body(&mut session, ARObjectType::Cube, [0.0, 0.05, 0.0], [0.0; 3]);
+
+        for _ in 0..120 {
+            session.step_physics(DT);
+        }
+
+        let object = &session.virtual_objects[index];
+        assert!(length(sub(object.position, [0.0, 0.05, 0.0])) < 0.005, "moved to {:?}", object.position);
+        assert!(object.rotation[3].abs() > 0.999, "tipped to {:?}", object.rotation);
+    }
+
+    #[test]
+    fn head_on_spheres_exchange_momentum_without_overlapping() {
+        let mut session = ARSession::new();
+        session.physics.gravity = [0.0; 3];
+        let striker = place_body(&mut session, ARObjectType::Sphere, [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]);
+        let struck = place_body(&mut session, ARObjectType::Sphere, [0.2, 1.0, 0.0], [0.0; 3]);
+
+        for _ in 0..30 {
+            session.step_physics(DT);
+        }
+
+        let velocity = |index: usize| session.virtual_objects[index].body.unwrap().velocity[0];
+        assert!(velocity(struck) > velocity(striker));
+        // Equal masses: the total is the striker's original speed
+        assert!((velocity(striker) + velocity(struck) - 1.0).abs() < 1e-3);
+        let gap = length(sub(session.virtual_objects[struck].position, session.virtual_objects[striker].position));
+        assert!(gap >= 0.1 - PENETRATION_SLOP, "overlapping at {}", gap);
+    }
+}
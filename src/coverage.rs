@@ -0,0 +1,358 @@
+// Scan coverage for detected planes and the reconstruction mesh. Each plane is divided
+// into square cells, and every frame the cells in view of the camera accumulate the time
+// they've been observed. The heatmap lets scanning UIs point users at parts of a surface
+// they haven't looked at yet.
+//
+// Cells are fixed in a frame taken when the plane is first observed, so coverage stays
+// put as ARKit grows the plane and shifts its center. The mesh (see mesh_store.rs) is
+// covered in cubic cells of the same size fixed in the world. A mesh cell is credited
+// when a face in it is in view and nothing else in the mesh is in front of it, so the
+// far side of furniture doesn't count as scanned
+
+use std::collections::HashMap;
+
+#[cfg(feature = "reconstruction")]
+use crate::cameras::CameraStream;
+use crate::math::{add, dot, length, normalize, scale, sub, tangent_basis};
+use crate::{with_session, ARPlane, ARSession};
+
+// Cell edge length in meters
+pub const COVERAGE_CELL_SIZE: f32 = 0.1;
+
+// Cells farther than this from the camera aren't resolved well enough to count
+const MAX_OBSERVE_DISTANCE: f32 = 4.0;
+
+// Cells seen at a grazing angle don't count; cosine between view ray and plane normal
+const MIN_VIEW_COSINE: f32 = 0.25;
+
+#[derive(Debug, Clone)]
+pub(crate) struct PlaneCoverage {
+    origin: [f32; 3],
+    axes: ([f32; 3], [f32; 3]),
+    // Seconds observed per cell
+    cells: HashMap<(i32, i32), f32>,
+}
+
+impl PlaneCoverage {
+    fn new(plane: &ARPlane) -> Self {
+        PlaneCoverage { origin: plane.center, axes: tangent_basis(plane.normal), cells: HashMap::new() }
+    }
+
+    fn cell_at(&self, point: [f32; 3]) -> (i32, i32) {
+        let offset = sub(point, self.origin);
+        (
+            (dot(offset, self.axes.0) / COVERAGE_CELL_SIZE).floor() as i32,
+            (dot(offset, self.axes.1) / COVERAGE_CELL_SIZE).floor() as i32,
+        )
+    }
+
+    fn cell_center(&self, (i, j): (i32, i32)) -> [f32; 3] {
+        let u = scale(self.axes.0, (i as f32 + 0.5) * COVERAGE_CELL_SIZE);
+        let v = scale(self.axes.1, (j as f32 + 0.5) * COVERAGE_CELL_SIZE);
+        add(self.origin, add(u, v))
+    }
+
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        self.origin = add(self.origin, offset);
+    }
+
+    pub fn observed(&self, point: [f32; 3]) -> f32 {
+        self.cells.get(&self.cell_at(point)).copied().unwrap_or(0.0)
+    }
+}
+
+// Seconds observed per cubic cell of the reconstruction mesh
+#[cfg(feature = "reconstruction")]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MeshCoverage {
+    origin: [f32; 3],
+    cells: HashMap<[i32; 3], f32>,
+}
+
+#[cfg(feature = "reconstruction")]
+impl MeshCoverage {
+    fn cell_at(&self, point: [f32; 3]) -> [i32; 3] {
+        sub(point, self.origin).map(|v| (v / COVERAGE_CELL_SIZE).floor() as i32)
+    }
+
+    fn cell_center(&self, cell: [i32; 3]) -> [f32; 3] {
+        add(self.origin, cell.map(|k| (k as f32 + 0.5) * COVERAGE_CELL_SIZE))
+    }
+
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        self.origin = add(self.origin, offset);
+    }
+
+    pub fn observed(&self, point: [f32; 3]) -> f32 {
+        self.cells.get(&self.cell_at(point)).copied().unwrap_or(0.0)
+    }
+}
+
+// Corners of a plane's rectangle
+fn plane_corners(plane: &ARPlane) -> [[f32; 3]; 4] {
+    let (tangent, bitangent) = tangent_basis(plane.normal);
+    let u = scale(tangent, plane.extent[0] * 0.5);
+    let v = scale(bitangent, plane.extent[1] * 0.5);
+    [
+        sub(sub(plane.center, u), v),
+        add(sub(plane.center, u), v),
+        sub(add(plane.center, u), v),
+        add(add(plane.center, u), v),
+    ]
+}
+
+// Heatmap cell centers, aligned with the plane's current rectangle: `columns` along its
+// first extent and `rows` along the second, row-major
+pub(crate) fn heatmap_grid(plane: &ARPlane) -> (usize, usize, Vec<[f32; 3]>) {
+    let columns = (plane.extent[0] / COVERAGE_CELL_SIZE).ceil().max(1.0) as usize;
+    let rows = (plane.extent[1] / COVERAGE_CELL_SIZE).ceil().max(1.0) as usize;
+    let (tangent, bitangent) = tangent_basis(plane.normal);
+    let [u_step, v_step] = [plane.extent[0] / columns as f32, plane.extent[1] / rows as f32];
+
+    let mut centers = Vec::with_capacity(columns * rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let u = (column as f32 + 0.5) * u_step - plane.extent[0] * 0.5;
+            let v = (row as f32 + 0.5) * v_step - plane.extent[1] * 0.5;
+            centers.push(add(plane.center, add(scale(tangent, u), scale(bitangent, v))));
+        }
+    }
+    (columns, rows, centers)
+}
+
+impl ARSession {
+    // Credit `dt` seconds to every plane cell the camera currently sees, and mark planes
+    // with any cell in view as observed
+    pub(crate) fn update_coverage(&mut self, dt: f32) {
+        let Some(camera) = self.view_camera() else {
+            return;
+        };
+        let Some(pose) = camera.pose else {
+            return;
+        };
+        let [width, height] = camera.resolution.map(|v| v as f32);
+        let now = self.clock.now();
+
+        for plane in self.detected_planes.iter_mut() {
+            let coverage = self.coverage.entry(plane.id.clone()).or_insert_with(|| PlaneCoverage::new(plane));
+
+            // Range of cells spanned by the plane's current rectangle
+            let corners = plane_corners(plane).map(|corner| coverage.cell_at(corner));
+            let (min_i, max_i) = (corners.iter().map(|c| c.0).min().unwrap_or(0), corners.iter().map(|c| c.0).max().unwrap_or(0));
+            let (min_j, max_j) = (corners.iter().map(|c| c.1).min().unwrap_or(0), corners.iter().map(|c| c.1).max().unwrap_or(0));
+
+            for i in min_i..=max_i {
+                for j in min_j..=max_j {
+                    let center = coverage.cell_center((i, j));
+                    if !plane.within_extent(center) {
+                        continue;
+                    }
+                    let to_camera = sub(pose.position, center);
+                    let distance = length(to_camera);
+                    if distance > MAX_OBSERVE_DISTANCE || dot(normalize(to_camera), plane.normal).abs() < MIN_VIEW_COSINE {
+                        continue;
+                    }
+                    let Some(([x, y], _)) = camera.project(center) else {
+                        continue;
+                    };
+                    if (0.0..width).contains(&x) && (0.0..height).contains(&y) {
+                        *coverage.cells.entry((i, j)).or_insert(0.0) += dt;
+                        plane.observed_at = now;
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "reconstruction")]
+        self.update_mesh_coverage(&camera, pose.position, dt);
+    }
+
+    #[cfg(feature = "reconstruction")]
+    fn update_mesh_coverage(&mut self, camera: &CameraStream, position: [f32; 3], dt: f32) {
+        let [width, height] = camera.resolution.map(|v| v as f32);
+        let reach = [MAX_OBSERVE_DISTANCE; 3];
+
+        // One face per cell stands in for it: the first found facing the camera in view
+        let mut probes: HashMap<[i32; 3], [f32; 3]> = HashMap::new();
+        for triangle in self.mesh.region(sub(position, reach), add(position, reach)) {
+            let [a, b, c] = triangle.vertices;
+            let centroid = scale(add(add(a, b), c), 1.0 / 3.0);
+            let cell = self.mesh_coverage.cell_at(centroid);
+            if probes.contains_key(&cell) {
+                continue;
+            }
+            let to_camera = sub(position, centroid);
+            if length(to_camera) > MAX_OBSERVE_DISTANCE || dot(normalize(to_camera), triangle.normal()).abs() < MIN_VIEW_COSINE {
+                continue;
+            }
+            let Some(([x, y], _)) = camera.project(centroid) else {
+                continue;
+            };
+            if (0.0..width).contains(&x) && (0.0..height).contains(&y) {
+                probes.insert(cell, centroid);
+            }
+        }
+
+        for (cell, probe) in probes {
+            // Hidden unless the first mesh hit toward the probe is in its cell
+            let to_probe = sub(probe, position);
+            let distance = length(to_probe);
+            let hidden = self.mesh.raycast(position, to_probe, distance)
+                .is_some_and(|hit| hit.distance < distance - COVERAGE_CELL_SIZE * 0.5);
+            if !hidden {
+                *self.mesh_coverage.cells.entry(cell).or_insert(0.0) += dt;
+            }
+        }
+    }
+
+    // Fraction of a plane's heatmap cells observed for at least `min_seconds`
+    pub(crate) fn plane_coverage_fraction(&self, index: usize, min_seconds: f32) -> Option<f32> {
+        let plane = self.detected_planes.get(index)?;
+        let (_, _, centers) = heatmap_grid(plane);
+        let Some(coverage) = self.coverage.get(&plane.id) else {
+            return Some(0.0);
+        };
+        let covered = centers.iter().filter(|&&center| coverage.observed(center) >= min_seconds).count();
+        Some(covered as f32 / centers.len() as f32)
+    }
+}
+
+// Coverage heatmap for the plane at `index`: seconds each cell has been observed, in a
+// grid of COVERAGE_CELL_SIZE-ish cells aligned with the plane's extent (columns along
+// the first extent, rows along the second, row-major). Writes up to `capacity` values
+// and the grid size (outputs may be null). Returns the total number of cells, or -1 for
+// a bad index
+#[no_mangle]
+pub extern "C" fn get_plane_coverage(
+    index: i32,
+    out_values: *mut f32,
+    capacity: u32,
+    out_columns: *mut u32,
+    out_rows: *mut u32,
+) -> i32 {
+    let Ok(index) = usize::try_from(index) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        let plane = session.detected_planes.get(index)?;
+        let (columns, rows, centers) = heatmap_grid(plane);
+        let coverage = session.coverage.get(&plane.id);
+        unsafe {
+            if !out_values.is_null() {
+                let count = centers.len().min(capacity as usize);
+                let out = std::slice::from_raw_parts_mut(out_values, count);
+                for (value, center) in out.iter_mut().zip(&centers) {
+                    *value = coverage.map_or(0.0, |coverage| coverage.observed(*center));
+                }
+            }
+            if !out_columns.is_null() {
+                *out_columns = columns as u32;
+            }
+            if !out_rows.is_null() {
+                *out_rows = rows as u32;
+            }
+        }
+        Some(centers.len() as i32)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// Coverage heatmap on the reconstruction mesh within a box: every COVERAGE_CELL_SIZE
+// cube holding mesh there, as its center and the seconds it's been observed (4 floats
+// per cell, ordered by cell). Writes up to `capacity` cells (may be null). Returns the
+// total number of cells, or -1 without a session
+#[cfg(feature = "reconstruction")]
+#[no_mangle]
+pub extern "C" fn get_mesh_coverage(
+    min_x: f32, min_y: f32, min_z: f32,
+    max_x: f32, max_y: f32, max_z: f32,
+    out_cells: *mut f32,
+    capacity: u32,
+) -> i32 {
+    let (min, max) = ([min_x, min_y, min_z], [max_x, max_y, max_z]);
+    with_session(|session| {
+        let coverage = &session.mesh_coverage;
+        let cells: std::collections::BTreeSet<[i32; 3]> = session.mesh.region(min, max)
+            .iter()
+            .map(|triangle| {
+                let [a, b, c] = triangle.vertices;
+                scale(add(add(a, b), c), 1.0 / 3.0)
+            })
+            .filter(|centroid| (0..3).all(|axis| (min[axis]..=max[axis]).contains(&centroid[axis])))
+            .map(|centroid| coverage.cell_at(centroid))
+            .collect();
+        if !out_cells.is_null() {
+            let count = cells.len().min(capacity as usize);
+            let out = unsafe { std::slice::from_raw_parts_mut(out_cells, count * 4) };
+            for (chunk, &cell) in out.chunks_exact_mut(4).zip(&cells) {
+                let center = coverage.cell_center(cell);
+                chunk.copy_from_slice(&[center[0], center[1], center[2], coverage.observed(center)]);
+            }
+        }
+        cells.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Fraction (0-1) of the plane at `index` observed for at least `min_seconds`, or -1 for
+// a bad index
+#[no_mangle]
+pub extern "C" fn get_plane_coverage_fraction(index: i32, min_seconds: f32) -> f32 {
+    let Ok(index) = usize::try_from(index) else {
+        return -1.0;
+    };
+
+    with_session(|session| session.plane_coverage_fraction(index, min_seconds))
+        .flatten()
+        .unwrap_or(-1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    #[test]
+    fn planes_in_view_accumulate_coverage() {
+        let mut session = ARSession::new();
+        // A wall 2 m ahead of a camera looking down -z, and one behind it
+        session.add_plane(Some("ahead".to_string()), [0.0, 0.0, -2.0], [1.0, 1.0], [0.0, 0.0, 1.0]);
+        session.add_plane(Some("behind".to_string()), [0.0, 0.0, 2.0], [1.0, 1.0], [0.0, 0.0, -1.0]);
+        session.set_camera_pose(0.1, [0.0; 3], IDENTITY);
+
+        session.update_coverage(0.5);
+        session.update_coverage(0.5);
+        assert_eq!(session.plane_coverage_fraction(0, 1.0), Some(1.0));
+        assert_eq!(session.plane_coverage_fraction(1, 0.1), Some(0.0));
+        assert_eq!(session.coverage["ahead"].observed([0.0, 0.0, -2.0]), 1.0);
+    }
+
+    #[cfg(feature = "reconstruction")]
+    #[test]
+    fn mesh_in_view_accumulates_coverage_unless_hidden() {
+        let mut session = ARSession::new();
+        // Two faces per quad, with centroids a third of the way in from opposite corners
+        let quad = |z: f32, half: f32| {
+            let (a, b, c, d) = ([-half, -half, z], [half, -half, z], [half, half, z], [-half, half, z]);
+            [[a, b, c], [a, c, d]]
+        };
+        // A small screen ahead of a wall, in front of the wall's face centroids from here
+        session.mesh.submit("screen", &quad(-1.05, 0.2), [0.0; 3]);
+        session.mesh.submit("wall", &quad(-2.05, 1.0), [0.0; 3]);
+        session.set_camera_pose(0.1, [0.0; 3], IDENTITY);
+
+        session.update_coverage(0.25);
+        let wall_face = [1.0 / 3.0, -1.0 / 3.0, -2.05];
+        assert_eq!(session.mesh_coverage.observed([0.2 / 3.0, -0.2 / 3.0, -1.05]), 0.25);
+        assert_eq!(session.mesh_coverage.observed([-0.2 / 3.0, 0.2 / 3.0, -1.05]), 0.25);
+        assert_eq!(session.mesh_coverage.observed(wall_face), 0.0);
+
+        // With the screen gone the wall is seen
+        session.mesh.remove("screen");
+        session.update_coverage(0.25);
+        assert_eq!(session.mesh_coverage.observed(wall_face), 0.25);
+    }
+}
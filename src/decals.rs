@@ -0,0 +1,490 @@
+// Decals: textures projected onto surfaces, for posters on walls and markings on
+// floors. A decal is a box: a width x height rectangle facing along its projection
+// direction, reaching depth / 2 either side. It lands on the first plane the direction
+// hits within that reach, is projected onto it and clipped to the plane's extent, so it
+// never hangs off an edge. The host gets back a flat polygon with texture coordinates
+// and draws it like any other mesh.
+//
+// The polygon is lifted a millimetre off the plane, plus a tenth of a millimetre per
+// decal in creation order, so neither the plane nor an earlier decal it overlaps can
+// z-fight with it. Geometry is rebuilt on every query, so decals follow plane updates.
+//
+// With the reconstruction mesh (see mesh_store.rs) a decal lands on the mesh instead
+// when the mesh is nearer the projector than any plane it hits, e.g. a marking on a
+// sofa or a rug. Mesh faces inside the decal's box that turn toward the projector are
+// clipped to the box and each lifted along its own normal; the result is a triangle
+// list rather than a single polygon. Where a plane and the mesh coincide the plane wins,
+// as its one polygon is cleaner than the mesh faces along it
+
+use std::ffi::CStr;
+
+use crate::math::{add, cross, dot, length, normalize, quat_from_axis_angle, quat_rotate, scale, sub, tangent_basis};
+#[cfg(feature = "reconstruction")]
+use crate::mesh_store::MeshStore;
+use crate::pose_filter::write_out;
+use crate::render::DecalDrawable;
+use crate::{with_session, ARPlane, ARSession};
+
+// Clipping a quad by a rectangle leaves at most 8 corners
+pub const AR_DECAL_MAX_VERTICES: usize = 8;
+
+const BASE_LIFT: f32 = 0.001;
+const LAYER_LIFT: f32 = 0.0001;
+
+// Planes hit more obliquely than this (80 degrees from head-on) would smear the texture
+// and receive nothing
+const MIN_FACING: f32 = 0.17;
+
+// How much nearer than a plane hit the mesh must be to take the decal, so the mesh's
+// reconstruction noise around a wall or floor doesn't steal decals from its plane
+#[cfg(feature = "reconstruction")]
+const MESH_PLANE_TOLERANCE: f32 = 0.03;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Decal {
+    pub id: i32,
+    // Host-defined texture name, passed through to the renderer
+    pub texture: String,
+    pub position: [f32; 3],
+    // Unit projection direction, and the texture's up perpendicular to it
+    pub direction: [f32; 3],
+    pub up: [f32; 3],
+    pub size: [f32; 2],
+    pub depth: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DecalState {
+    pub decals: Vec<Decal>,
+    next_id: i32,
+}
+
+// A decal's clipped geometry, counter-clockwise seen from the side it was projected
+// from. On a plane it's one convex polygon and the indices are a fan over it; on the
+// mesh there's no plane id and one fan per clipped face
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DecalGeometry {
+    pub plane_id: Option<String>,
+    pub positions: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    pub normal: [f32; 3],
+}
+
+// Triangle fan over a convex polygon of `count` corners starting at `base`
+fn fan(base: u32, count: usize) -> impl Iterator<Item = u32> {
+    (1..count.saturating_sub(1) as u32).flat_map(move |i| [base, base + i, base + i + 1])
+}
+
+// Up vector for a decal: world up on walls, -z on floors and ceilings, then turned by
+// `rotation` radians about the direction
+fn decal_up(direction: [f32; 3], rotation: f32) -> [f32; 3] {
+    let reference = if direction[1].abs() < 0.99 { [0.0, 1.0, 0.0] } else { [0.0, 0.0, -1.0] };
+    let up = normalize(sub(reference, scale(direction, dot(reference, direction))));
+    quat_rotate(quat_from_axis_angle(direction, rotation), up)
+}
+
+// Clip a convex polygon to the half-space where `inside` is non-negative; `inside` must
+// be linear
+fn clip<const N: usize>(polygon: &[[f32; N]], inside: impl Fn([f32; N]) -> f32) -> Vec<[f32; N]> {
+    let mut out = Vec::with_capacity(polygon.len() + 1);
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (da, db) = (inside(a), inside(b));
+        if da >= 0.0 {
+            out.push(a);
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            out.push(std::array::from_fn(|k| a[k] + (b[k] - a[k]) * t));
+        }
+    }
+    out
+}
+
+impl Decal {
+    fn right(&self) -> [f32; 3] {
+        cross(self.direction, self.up)
+    }
+
+    // Distance along the direction to a plane it hits within reach, if it does
+    fn hit(&self, plane: &ARPlane) -> Option<f32> {
+        let facing = dot(self.direction, plane.normal);
+        if facing.abs() < MIN_FACING {
+            return None;
+        }
+        let t = dot(sub(plane.center, self.position), plane.normal) / facing;
+        (t.abs() <= self.depth * 0.5 && plane.within_extent(add(self.position, scale(self.direction, t)))).then_some(t)
+    }
+
+    fn geometry(&self, session: &ARSession, layer: usize) -> Option<DecalGeometry> {
+        let plane = session.detected_planes.iter()
+            .filter_map(|plane| Some((plane, self.hit(plane)?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        #[cfg(feature = "reconstruction")]
+        {
+            let start = sub(self.position, scale(self.direction, self.depth * 0.5));
+            if let Some(hit) = session.mesh.raycast(start, self.direction, self.depth) {
+                let t = hit.distance - self.depth * 0.5;
+                if plane.is_none_or(|(_, plane_t)| t < plane_t - MESH_PLANE_TOLERANCE) {
+                    return self.mesh_geometry(&session.mesh, layer);
+                }
+            }
+        }
+        self.plane_geometry(plane?.0, layer)
+    }
+
+    fn plane_geometry(&self, plane: &ARPlane, layer: usize) -> Option<DecalGeometry> {
+        let facing = dot(self.direction, plane.normal);
+        let normal = if facing < 0.0 { plane.normal } else { scale(plane.normal, -1.0) };
+        let (tangent, bitangent) = tangent_basis(plane.normal);
+
+        // Decal corners projected along the direction, in plane coordinates
+        let (right, up) = (scale(self.right(), self.size[0] * 0.5), scale(self.up, self.size[1] * 0.5));
+        let mut polygon: Vec<[f32; 2]> = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(x, y)| {
+                let corner = add(self.position, add(scale(right, x), scale(up, y)));
+                let on_plane = add(corner, scale(self.direction, dot(sub(plane.center, corner), plane.normal) / facing));
+                let offset = sub(on_plane, plane.center);
+                [dot(offset, tangent), dot(offset, bitangent)]
+            })
+            .to_vec();
+
+        let [half_x, half_y] = plane.extent.map(|e| e * 0.5);
+        polygon = clip(&polygon, |p| half_x - p[0]);
+        polygon = clip(&polygon, |p| p[0] + half_x);
+        polygon = clip(&polygon, |p| half_y - p[1]);
+        polygon = clip(&polygon, |p| p[1] + half_y);
+        if polygon.len() < 3 {
+            return None;
+        }
+
+        // Counter-clockwise in (tangent, bitangent) faces +normal
+        let area: f32 = (0..polygon.len())
+            .map(|i| {
+                let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                a[0] * b[1] - b[0] * a[1]
+            })
+            .sum();
+        if (area > 0.0) != (facing < 0.0) {
+            polygon.reverse();
+        }
+
+        let lift = scale(normal, BASE_LIFT + LAYER_LIFT * layer as f32);
+        let positions: Vec<[f32; 3]> = polygon.iter()
+            .map(|&[x, y]| add(add(plane.center, add(scale(tangent, x), scale(bitangent, y))), lift))
+            .collect();
+        let uvs = positions.iter()
+            .map(|&position| {
+                let offset = sub(position, self.position);
+                [dot(offset, self.right()) / self.size[0] + 0.5, 0.5 - dot(offset, self.up) / self.size[1]]
+            })
+            .collect();
+
+        let indices = fan(0, positions.len()).collect();
+        Some(DecalGeometry { plane_id: Some(plane.id.clone()), positions, uvs, indices, normal })
+    }
+
+    #[cfg(feature = "reconstruction")]
+    fn mesh_geometry(&self, mesh: &MeshStore, layer: usize) -> Option<DecalGeometry> {
+        let (right, up, direction) = (self.right(), self.up, self.direction);
+        let half = [self.size[0] * 0.5, self.size[1] * 0.5, self.depth * 0.5];
+        let reach: [f32; 3] = std::array::from_fn(|axis| {
+            right[axis].abs() * half[0] + up[axis].abs() * half[1] + direction[axis].abs() * half[2]
+        });
+        let lift = BASE_LIFT + LAYER_LIFT * layer as f32;
+
+        let mut geometry = DecalGeometry {
+            plane_id: None,
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+            normal: scale(direction, -1.0),
+        };
+        for triangle in mesh.region(sub(self.position, reach), add(self.position, reach)) {
+            let normal = triangle.normal();
+            if dot(normal, direction) > -MIN_FACING {
+                continue;
+            }
+            // Clip to the decal's box in its own frame: x right, y up, z along the direction
+            let mut polygon: Vec<[f32; 3]> = triangle.vertices
+                .map(|vertex| {
+                    let offset = sub(vertex, self.position);
+                    [dot(offset, right), dot(offset, up), dot(offset, direction)]
+                })
+                .to_vec();
+            for axis in 0..3 {
+                polygon = clip(&polygon, |p| half[axis] - p[axis]);
+                polygon = clip(&polygon, |p| p[axis] + half[axis]);
+            }
+            if polygon.len() < 3 {
+                continue;
+            }
+
+            let base = geometry.positions.len() as u32;
+            for &[x, y, z] in &polygon {
+                let on_mesh = add(self.position, add(add(scale(right, x), scale(up, y)), scale(direction, z)));
+                geometry.positions.push(add(on_mesh, scale(normal, lift)));
+                geometry.uvs.push([x / self.size[0] + 0.5, 0.5 - y / self.size[1]]);
+            }
+            geometry.indices.extend(fan(base, polygon.len()));
+        }
+        (!geometry.indices.is_empty()).then_some(geometry)
+    }
+}
+
+impl ARSession {
+    pub(crate) fn decal_geometry(&self, id: i32) -> Option<Option<DecalGeometry>> {
+        let layer = self.decals.decals.iter().position(|decal| decal.id == id)?;
+        Some(self.decals.decals[layer].geometry(self, layer))
+    }
+
+    pub(crate) fn decal_drawables(&self) -> Vec<DecalDrawable> {
+        self.decals.decals.iter()
+            .enumerate()
+            .filter_map(|(layer, decal)| {
+                let geometry = decal.geometry(self, layer)?;
+                Some(DecalDrawable {
+                    id: decal.id,
+                    texture: decal.texture.clone(),
+                    indices: geometry.indices,
+                    plane_id: geometry.plane_id,
+                    positions: geometry.positions,
+                    uvs: geometry.uvs,
+                    normal: geometry.normal,
+                })
+            })
+            .collect()
+    }
+}
+
+// Decal placement from FFI arguments, or None for a degenerate one
+fn decal_shape(direction: [f32; 3], width: f32, height: f32, depth: f32) -> Option<[f32; 3]> {
+    let sizes_valid = [width, height, depth].iter().all(|value| value.is_finite() && *value > 0.0);
+    let direction_valid = direction.iter().all(|d| d.is_finite()) && length(direction) > 1e-6;
+    (sizes_valid && direction_valid).then(|| normalize(direction))
+}
+
+// Project the texture named `texture` (passed through to the renderer) as a `width` x
+// `height` meter decal from `position` along `direction`, turned `rotation` radians
+// about it, onto the first plane (or mesh) within `depth` / 2 either side. Up is world up on walls
+// and -z on floors before rotation. Returns the decal id, or -1 for bad input
+#[no_mangle]
+pub extern "C" fn add_decal(
+    texture_ptr: *const libc::c_char,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    direction_x: f32, direction_y: f32, direction_z: f32,
+    width: f32, height: f32,
+    rotation: f32,
+    depth: f32
+) -> i32 {
+    if texture_ptr.is_null() {
+        return -1;
+    }
+    let Some(direction) = decal_shape([direction_x, direction_y, direction_z], width, height, depth) else {
+        return -1;
+    };
+    let texture = unsafe { CStr::from_ptr(texture_ptr) }.to_string_lossy().into_owned();
+
+    with_session(|session| {
+        session.decals.next_id += 1;
+        let id = session.decals.next_id;
+        session.decals.decals.push(Decal {
+            id,
+            texture,
+            position: [pos_x, pos_y, pos_z],
+            direction,
+            up: decal_up(direction, rotation),
+            size: [width, height],
+            depth,
+        });
+        id
+    })
+    .unwrap_or(-1)
+}
+
+// Move, resize or re-aim a decal, keeping its texture and layer. Returns false for an
+// unknown id or bad input
+#[no_mangle]
+pub extern "C" fn update_decal(
+    decal_id: i32,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    direction_x: f32, direction_y: f32, direction_z: f32,
+    width: f32, height: f32,
+    rotation: f32,
+    depth: f32
+) -> bool {
+    let Some(direction) = decal_shape([direction_x, direction_y, direction_z], width, height, depth) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(decal) = session.decals.decals.iter_mut().find(|decal| decal.id == decal_id) else {
+            return false;
+        };
+        decal.position = [pos_x, pos_y, pos_z];
+        decal.direction = direction;
+        decal.up = decal_up(direction, rotation);
+        decal.size = [width, height];
+        decal.depth = depth;
+        true
+    })
+    .unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn remove_decal(decal_id: i32) -> bool {
+    with_session(|session| {
+        let Some(position) = session.decals.decals.iter().position(|decal| decal.id == decal_id) else {
+            return false;
+        };
+        session.decals.decals.remove(position);
+        true
+    })
+    .unwrap_or(false)
+}
+
+// A decal's polygon as it currently lands: up to AR_DECAL_MAX_VERTICES corners in world
+// space (3 floats each) with texture coordinates (2 floats each, v down), and the
+// surface normal facing the projector. Draw it as a triangle fan. Outputs may be null.
+// Returns the corner count, 0 if the decal lands on no plane (decals on the mesh are
+// read with get_decal_mesh), or -1 for an unknown id
+#[no_mangle]
+pub extern "C" fn get_decal_geometry(
+    decal_id: i32,
+    out_positions: *mut f32,
+    out_uvs: *mut f32,
+    out_normal: *mut f32,
+) -> i32 {
+    with_session(|session| {
+        let Some(geometry) = session.decal_geometry(decal_id)?.filter(|geometry| geometry.plane_id.is_some()) else {
+            return Some(0);
+        };
+        unsafe {
+            if !out_positions.is_null() {
+                let out = std::slice::from_raw_parts_mut(out_positions, geometry.positions.len() * 3);
+                out.copy_from_slice(geometry.positions.as_flattened());
+            }
+            if !out_uvs.is_null() {
+                let out = std::slice::from_raw_parts_mut(out_uvs, geometry.uvs.len() * 2);
+                out.copy_from_slice(geometry.uvs.as_flattened());
+            }
+            write_out(out_normal, geometry.normal);
+        }
+        Some(geometry.positions.len() as i32)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// A decal's geometry as an indexed triangle list, for decals on the mesh as well as
+// planes. Writes up to `vertex_capacity` vertices (3 floats of position and 2 of uv each)
+// and up to `index_capacity` indices; outputs may be null. Sets `out_index_count` to the
+// total index count and returns the total vertex count, 0 if the decal lands on nothing,
+// or -1 for an unknown id
+#[no_mangle]
+pub extern "C" fn get_decal_mesh(
+    decal_id: i32,
+    out_positions: *mut f32,
+    out_uvs: *mut f32,
+    vertex_capacity: u32,
+    out_indices: *mut u32,
+    index_capacity: u32,
+    out_index_count: *mut u32,
+) -> i32 {
+    with_session(|session| {
+        let geometry = session.decal_geometry(decal_id)?.unwrap_or_else(|| DecalGeometry {
+            plane_id: None,
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+            normal: [0.0; 3],
+        });
+        let vertices = geometry.positions.len().min(vertex_capacity as usize);
+        let indices = geometry.indices.len().min(index_capacity as usize);
+        unsafe {
+            if !out_positions.is_null() {
+                let out = std::slice::from_raw_parts_mut(out_positions, vertices * 3);
+                out.copy_from_slice(geometry.positions[..vertices].as_flattened());
+            }
+            if !out_uvs.is_null() {
+                let out = std::slice::from_raw_parts_mut(out_uvs, vertices * 2);
+                out.copy_from_slice(geometry.uvs[..vertices].as_flattened());
+            }
+            if !out_indices.is_null() {
+                std::slice::from_raw_parts_mut(out_indices, indices).copy_from_slice(&geometry.indices[..indices]);
+            }
+            if !out_index_count.is_null() {
+                *out_index_count = geometry.indices.len() as u32;
+            }
+        }
+        Some(geometry.positions.len() as i32)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decal(position: [f32; 3], direction: [f32; 3], size: [f32; 2]) -> Decal {
+        Decal { id: 1, texture: "poster".to_string(), position, direction, up: decal_up(direction, 0.0), size, depth: 0.4 }
+    }
+
+    #[test]
+    fn decals_are_clipped_to_their_plane() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [0.0; 3], [1.0, 1.0], [0.0, 1.0, 0.0]);
+
+        // Half of a 0.4 m marking hangs past the floor's +x edge
+        let geometry = decal([0.5, 0.1, 0.0], [0.0, -1.0, 0.0], [0.4, 0.4]).geometry(&session, 0).unwrap();
+        assert_eq!(geometry.plane_id.as_deref(), Some("floor"));
+        assert_eq!(geometry.positions.len(), 4);
+        assert_eq!(geometry.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(geometry.normal, [0.0, 1.0, 0.0]);
+        for (position, uv) in geometry.positions.iter().zip(&geometry.uvs) {
+            assert!(position[0] <= 0.5 + 1e-5 && (position[1] - BASE_LIFT).abs() < 1e-6);
+            assert!(uv[0] <= 0.5 + 1e-5);
+        }
+
+        // Out of reach of the floor, it lands nowhere
+        assert!(decal([0.0, 0.5, 0.0], [0.0, -1.0, 0.0], [0.4, 0.4]).geometry(&session, 0).is_none());
+    }
+
+    #[test]
+    fn later_decals_are_lifted_above_earlier_ones() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [0.0; 3], [1.0, 1.0], [0.0, 1.0, 0.0]);
+        let marking = decal([0.0, 0.1, 0.0], [0.0, -1.0, 0.0], [0.2, 0.2]);
+        let first = marking.geometry(&session, 0).unwrap();
+        let second = marking.geometry(&session, 1).unwrap();
+        assert!((second.positions[0][1] - first.positions[0][1] - LAYER_LIFT).abs() < 1e-6);
+    }
+
+    #[cfg(feature = "reconstruction")]
+    #[test]
+    fn decals_land_on_the_mesh_where_it_is_nearer_than_any_plane() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [0.0; 3], [4.0, 4.0], [0.0, 1.0, 0.0]);
+        // A 0.4 m step on the floor, wound to face up, with a face along the floor that
+        // the plane should win over
+        let top = [[-0.2, 0.4, -0.2], [-0.2, 0.4, 0.2], [0.2, 0.4, 0.2], [0.2, 0.4, -0.2]];
+        let floor = [[1.0, 0.0, -0.2], [1.0, 0.0, 0.2], [1.4, 0.0, 0.2], [1.4, 0.0, -0.2]];
+        let faces = [[top[0], top[1], top[2]], [top[0], top[2], top[3]], [floor[0], floor[1], floor[2]], [floor[0], floor[2], floor[3]]];
+        session.mesh.submit("room", &faces, [0.0; 3]);
+
+        // A marking half over the step's edge is clipped to the step's top
+        let geometry = decal([0.2, 0.5, 0.0], [0.0, -1.0, 0.0], [0.2, 0.2]).geometry(&session, 0).unwrap();
+        assert_eq!(geometry.plane_id, None);
+        assert_eq!(geometry.normal, [0.0, 1.0, 0.0]);
+        assert_eq!(geometry.indices.len() % 3, 0);
+        for position in &geometry.positions {
+            assert!(position[0] <= 0.2 + 1e-5 && (position[1] - 0.4 - BASE_LIFT).abs() < 1e-5, "{:?}", position);
+        }
+
+        // Where the mesh lies along the floor, the plane takes the decal
+        let geometry = decal([1.2, 0.1, 0.0], [0.0, -1.0, 0.0], [0.2, 0.2]).geometry(&session, 0).unwrap();
+        assert_eq!(geometry.plane_id.as_deref(), Some("floor"));
+    }
+}
@@ -0,0 +1,96 @@
+// Deterministic mode, for replays and lockstep multiplayer. While it's on, advance_frame
+// no longer simulates the frame time it's given directly: time accumulates, and per-frame
+// state (physics, stabilization, gaze, fading, ...) advances in whole steps of a fixed
+// timestep, so the same inputs produce the same steps however the host's frame times
+// jitter. Procedural effects draw from an RNG seeded by the session seed instead of
+// varying run to run. Peers that enable it with the same seed and timestep and feed the
+// same inputs at the same ticks stay bit-identical.
+//
+// Identical results assume the same build on the same architecture: sin, cos and friends
+// come from the platform's libm, which may round differently elsewhere. Timestamps taken
+// from the session clock (trajectories, plane detection times) still follow real time
+
+use crate::rng::Rng;
+use crate::{with_session, ARSession};
+
+const DEFAULT_TIMESTEP: f32 = 1.0 / 60.0;
+
+// A long stall runs at most this many steps; the rest of the time is dropped rather
+// than simulated in a burst
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Determinism {
+    pub timestep: f32,
+    pub seed: u64,
+    // Frame time not yet simulated, in seconds. f64 so summing jittery frame times
+    // doesn't drift
+    accumulator: f64,
+    // Fixed steps run since the mode was enabled
+    pub tick: u64,
+}
+
+impl Determinism {
+    fn new(seed: u64, timestep: f32) -> Self {
+        Determinism { timestep, seed, accumulator: 0.0, tick: 0 }
+    }
+}
+
+impl ARSession {
+    // Advance by a host frame of `dt` seconds: directly, or in fixed steps when
+    // deterministic
+    pub(crate) fn advance_frame_time(&mut self, dt: f32) {
+        let Some(determinism) = self.determinism.as_mut() else {
+            self.advance(dt);
+            return;
+        };
+
+        determinism.accumulator += dt as f64;
+        let timestep = determinism.timestep;
+        let due = (determinism.accumulator / timestep as f64).floor() as u32;
+        let steps = due.min(MAX_STEPS_PER_FRAME);
+        determinism.accumulator -= steps as f64 * timestep as f64;
+        if due > steps {
+            determinism.accumulator %= timestep as f64;
+        }
+        determinism.tick += steps as u64;
+        for _ in 0..steps {
+            self.advance(timestep);
+        }
+    }
+
+    // RNG for a procedural effect. Deterministic sessions mix `salt` with the session
+    // seed, so one seed fixes every effect; otherwise the salt alone seeds it
+    pub(crate) fn effect_rng(&self, salt: u64) -> Rng {
+        match &self.determinism {
+            Some(determinism) => Rng::new(Rng::new(determinism.seed).next_u64() ^ salt),
+            None => Rng::new(salt),
+        }
+    }
+}
+
+// Turn deterministic mode on with `seed` and a fixed `timestep` in seconds (0 for the
+// default of 1/60), or off. Enabling restarts the tick count and drops any unsimulated
+// time, so peers should enable it at the same point in their input streams. Returns
+// false for a bad timestep or without a session
+#[no_mangle]
+pub extern "C" fn set_deterministic_mode(enabled: bool, seed: u64, timestep: f32) -> bool {
+    let timestep = if timestep == 0.0 { DEFAULT_TIMESTEP } else { timestep };
+    if !timestep.is_finite() || timestep <= 0.0 {
+        return false;
+    }
+
+    with_session(|session| {
+        session.determinism = enabled.then(|| Determinism::new(seed, timestep));
+    })
+    .is_some()
+}
+
+// Fixed steps run since deterministic mode was enabled, for checking peers or a replay
+// are in step. Returns -1 when the mode is off
+#[no_mangle]
+pub extern "C" fn get_simulation_tick() -> i64 {
+    with_session(|session| session.determinism.as_ref().map(|determinism| determinism.tick as i64))
+        .flatten()
+        .unwrap_or(-1)
+}
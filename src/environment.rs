@@ -0,0 +1,207 @@
+// Environment capture for reflections. While enabled, every frame passed to
+// submit_camera_image is splatted into a cube map around the camera: each texel's
+// direction is projected into the rear camera's image and, if it lands inside, the
+// pixel there is blended in. Over a session the user looking around fills in a rough
+// panorama of the actual room, which the host uploads as a cube texture for shiny
+// materials instead of a static probe.
+//
+// Texels are treated as infinitely far away, so parallax from walking around is
+// ignored; for reflections that's rarely noticeable. Faces follow the usual cube map
+// order and orientation (+X, -X, +Y, -Y, +Z, -Z, rows top to bottom). Texels never
+// seen are filled with the average of those that were, with zero alpha so the host
+// can tell them apart
+
+use crate::cameras::CameraFeature;
+use crate::color_grading::srgb_to_linear;
+use crate::math::{add, normalize};
+use crate::{with_session, ARSession};
+
+const DEFAULT_FACE_SIZE: usize = 64;
+const MAX_FACE_SIZE: usize = 512;
+
+// A texel's history counts as at most this many frames, so it keeps adapting when the
+// lighting changes or a better view comes along
+const MAX_TEXEL_WEIGHT: f32 = 8.0;
+
+#[derive(Debug, Clone)]
+pub(crate) struct EnvironmentMap {
+    face_size: usize,
+    // Linear RGB per texel, faces in order
+    color: Vec<[f32; 3]>,
+    weight: Vec<f32>,
+    // Bumped whenever texels change, so hosts can skip redundant uploads
+    version: u64,
+}
+
+// Unit direction through the center of texel (x, y) on a face
+fn texel_direction(face: usize, x: usize, y: usize, size: usize) -> [f32; 3] {
+    let u = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+    let v = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+    let direction = match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    };
+    normalize(direction)
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.clamp(0.0, 1.0);
+    let encoded = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (encoded * 255.0).round() as u8
+}
+
+impl EnvironmentMap {
+    fn new(face_size: usize) -> Self {
+        let texels = 6 * face_size * face_size;
+        EnvironmentMap {
+            face_size,
+            color: vec![[0.0; 3]; texels],
+            weight: vec![0.0; texels],
+            version: 0,
+        }
+    }
+
+    fn coverage(&self) -> f32 {
+        self.weight.iter().filter(|&&weight| weight > 0.0).count() as f32 / self.weight.len() as f32
+    }
+
+    fn mean_color(&self) -> [f32; 3] {
+        let mut sum = [0.0; 3];
+        let mut count = 0;
+        for (color, _) in self.color.iter().zip(&self.weight).filter(|(_, &weight)| weight > 0.0) {
+            sum = [sum[0] + color[0], sum[1] + color[1], sum[2] + color[2]];
+            count += 1;
+        }
+        if count == 0 {
+            return [0.0; 3];
+        }
+        sum.map(|s| s / count as f32)
+    }
+
+    // One face as 8-bit sRGB RGBA
+    fn face_rgba(&self, face: usize) -> Vec<u8> {
+        let texels = self.face_size * self.face_size;
+        let fill = self.mean_color();
+        let mut out = Vec::with_capacity(texels * 4);
+        for texel in face * texels..(face + 1) * texels {
+            let seen = self.weight[texel] > 0.0;
+            let color = if seen { self.color[texel] } else { fill };
+            out.extend(color.map(linear_to_srgb));
+            out.push(if seen { 255 } else { 0 });
+        }
+        out
+    }
+}
+
+impl ARSession {
+    // Blend a camera image into the environment map, if capture is on. The image is
+    // 4 bytes per pixel with R, G and B at `channel_order`
+    pub(crate) fn capture_environment(&mut self, pixels: &[u8], width: usize, height: usize, bytes_per_row: usize, channel_order: [usize; 3]) {
+        let stream = self.camera_stream(CameraFeature::WorldTracking.stream()).or_default_intrinsics();
+        let Some(pose) = stream.pose else {
+            return;
+        };
+        let Some(map) = self.environment.as_mut() else {
+            return;
+        };
+        // Intrinsics are for the stream's resolution, which the image may not match
+        let scale_x = width as f32 / stream.resolution[0] as f32;
+        let scale_y = height as f32 / stream.resolution[1] as f32;
+
+        let size = map.face_size;
+        let mut changed = false;
+        for face in 0..6 {
+            for y in 0..size {
+                for x in 0..size {
+                    let direction = texel_direction(face, x, y, size);
+                    let Some(([px, py], _)) = stream.project(add(pose.position, direction)) else {
+                        continue;
+                    };
+                    let (px, py) = (px * scale_x, py * scale_y);
+                    if px < 0.0 || py < 0.0 || px >= width as f32 || py >= height as f32 {
+                        continue;
+                    }
+
+                    let offset = py as usize * bytes_per_row + px as usize * 4;
+                    let sample = channel_order.map(|channel| srgb_to_linear(pixels[offset + channel]));
+                    let texel = (face * size + y) * size + x;
+                    let weight = (map.weight[texel] + 1.0).min(MAX_TEXEL_WEIGHT);
+                    let color = &mut map.color[texel];
+                    for channel in 0..3 {
+                        color[channel] += (sample[channel] - color[channel]) / weight;
+                    }
+                    map.weight[texel] = weight;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            map.version += 1;
+        }
+    }
+}
+
+// Start capturing an environment cube map with `face_size` texels per edge (0 for the
+// default of 64, at most 512), or stop and discard it. Restarting with a different size
+// starts over. Frames come from submit_camera_image, projected through the rear camera
+// stream's pose and intrinsics
+#[no_mangle]
+pub extern "C" fn set_environment_capture(enabled: bool, face_size: u32) {
+    let face_size = match face_size as usize {
+        0 => DEFAULT_FACE_SIZE,
+        size => size.min(MAX_FACE_SIZE),
+    };
+
+    with_session(|session| {
+        if !enabled {
+            session.environment = None;
+        } else if session.environment.as_ref().is_none_or(|map| map.face_size != face_size) {
+            session.environment = Some(EnvironmentMap::new(face_size));
+        }
+    });
+}
+
+// Copy one face (0-5: +X, -X, +Y, -Y, +Z, -Z) of the environment map into `out_rgba` as
+// 8-bit sRGB RGBA, rows top to bottom; alpha is 0 for texels not yet seen. Returns the
+// face size in texels per edge, or -1 for a bad face or with capture off. Nothing is
+// written unless `capacity` holds face_size * face_size * 4 bytes
+#[no_mangle]
+pub extern "C" fn get_environment_map_face(face: i32, out_rgba: *mut u8, capacity: u32) -> i32 {
+    let Ok(face) = usize::try_from(face) else {
+        return -1;
+    };
+    if face >= 6 {
+        return -1;
+    }
+
+    with_session(|session| {
+        let map = session.environment.as_ref()?;
+        let bytes = map.face_size * map.face_size * 4;
+        if !out_rgba.is_null() && bytes <= capacity as usize {
+            let rgba = map.face_rgba(face);
+            unsafe { std::ptr::copy_nonoverlapping(rgba.as_ptr(), out_rgba, bytes) };
+        }
+        Some(map.face_size as i32)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// Fraction of the environment map's texels seen so far (0-1), and a version that
+// changes whenever texels do (`out_version` may be null). Returns -1 with capture off
+#[no_mangle]
+pub extern "C" fn get_environment_map_status(out_version: *mut u64) -> f32 {
+    with_session(|session| {
+        let map = session.environment.as_ref()?;
+        if !out_version.is_null() {
+            unsafe { *out_version = map.version };
+        }
+        Some(map.coverage())
+    })
+    .flatten()
+    .unwrap_or(-1.0)
+}
@@ -0,0 +1,145 @@
+// Session events for the host app. Events are queued as they happen during FFI calls
+// and drained by polling, typically once per frame; each is delivered as a JSON object
+// tagged with its "type"
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::quotas::Resource;
+use crate::with_session;
+
+// Oldest events are dropped beyond this, so a host that never polls can't grow the
+// queue without bound
+const MAX_QUEUED_EVENTS: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEvent {
+    // An anchor has moved further from where content was seated than the drift
+    // threshold. `translation` and `rotation` are the net correction since the last
+    // drift event (or since the anchor was added)
+    AnchorDrift {
+        anchor_id: String,
+        translation: [f32; 3],
+        rotation: [f32; 4],
+        distance: f32,
+        angle_degrees: f32,
+        // Sum of every correction magnitude, including jitter that cancelled out
+        cumulative_correction: f32,
+    },
+    // The gaze ray moved onto or off an object
+    FocusEnter { object_id: usize },
+    FocusExit { object_id: usize },
+    // An object held gaze focus for its full dwell time
+    DwellComplete { object_id: usize },
+    // A camera-attached object was pulled in to keep it out of a surface, or was
+    // released again (see camera_attachment.rs). plane_id is null when the
+    // reconstruction mesh was in the way
+    AttachmentClamped { object_id: usize, plane_id: Option<String> },
+    AttachmentUnclamped { object_id: usize },
+    // The camera stepped into or out of a visibility zone (see zones.rs)
+    ZoneEntered { zone_id: i32 },
+    ZoneExited { zone_id: i32 },
+    // A dynamic object came to rest, or started moving again (see sleep.rs)
+    ObjectSleep { object_id: usize },
+    ObjectWake { object_id: usize },
+    // The scan met every completion criterion (see scan_quality.rs)
+    ScanSufficient {
+        coverage: f32,
+        feature_density: f32,
+        loop_closures: u32,
+    },
+    // A loop closure corrected an object's or plane's pose (see pose_graph.rs).
+    // `translation` is its change in position, `rotation` the rotation applied to it
+    ObjectPoseAdjusted {
+        object_id: usize,
+        translation: [f32; 3],
+        rotation: [f32; 4],
+    },
+    PlanePoseAdjusted {
+        plane_id: String,
+        translation: [f32; 3],
+        rotation: [f32; 4],
+    },
+    // A plane went unseen and un-updated long enough to be flagged stale, was seen or
+    // updated again, or was removed (see plane_expiry.rs)
+    PlaneStale { plane_id: String },
+    PlaneRefreshed { plane_id: String },
+    PlaneRemoved { plane_id: String },
+    // Usage of a limited resource reached the warning fraction of its limit, or an
+    // addition was refused for going over it (see quotas.rs)
+    QuotaWarning { resource: Resource, used: u64, limit: u64 },
+    QuotaExceeded { resource: Resource, requested: u64, limit: u64 },
+    // The session's coordinate system moved (see world_origin.rs). Every stored position
+    // has been shifted by `translation`; the host should move ARKit's world origin to
+    // -translation in the old frame so new poses match
+    WorldOriginChanged {
+        translation: [f32; 3],
+        floor_plane_id: String,
+    },
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    events: VecDeque<SessionEvent>,
+    dropped: u64,
+}
+
+impl EventQueue {
+    pub fn push(&mut self, event: SessionEvent) {
+        if self.events.len() == MAX_QUEUED_EVENTS {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn front(&self) -> Option<&SessionEvent> {
+        self.events.front()
+    }
+
+    pub fn pop(&mut self) -> Option<SessionEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+// Copy `value` plus a NUL terminator into a caller-provided buffer. Returns false,
+// writing nothing, if the buffer is null or too small
+pub(crate) unsafe fn copy_c_string(value: &str, out: *mut libc::c_char, capacity: u32) -> bool {
+    if out.is_null() || value.len() + 1 > capacity as usize {
+        return false;
+    }
+    std::ptr::copy_nonoverlapping(value.as_ptr(), out as *mut u8, value.len());
+    *out.add(value.len()) = 0;
+    true
+}
+
+// Copy the next event as NUL-terminated JSON into `out_json`. Returns the JSON length
+// in bytes, 0 if there are no events, or -1 without a session. If the event doesn't
+// fit in `capacity` bytes (including the terminator) nothing is written and the event
+// stays queued, so the caller can retry with a buffer of at least the returned length + 1
+#[no_mangle]
+pub extern "C" fn poll_session_event(out_json: *mut libc::c_char, capacity: u32) -> i32 {
+    with_session(|session| {
+        let Some(event) = session.events.front() else {
+            return 0;
+        };
+        let json = serde_json::to_string(event).unwrap_or_default();
+        if unsafe { copy_c_string(&json, out_json, capacity) } {
+            session.events.pop();
+        }
+        json.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Number of events discarded because the queue was full
+#[no_mangle]
+pub extern "C" fn get_dropped_event_count() -> u64 {
+    with_session(|session| session.events.dropped()).unwrap_or(0)
+}
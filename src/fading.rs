@@ -0,0 +1,122 @@
+// Distance fading and auto-hide. Objects can carry a policy that fades them out as the
+// camera moves away (fully visible up to the fade start, hidden beyond the hide
+// distance) and optionally hides them while they're inside real geometry. Policies are
+// evaluated every frame; the resulting opacity goes into the render snapshot, so labels
+// far across the room stop cluttering the view.
+//
+// Without a reconstruction mesh, "inside geometry" means behind a detected plane,
+// within its extent and no deeper than a wall or table top is thick. Anything further
+// back is in the open space beyond (e.g. under the table) and stays visible
+
+use crate::math::{dot, length, sub};
+use crate::{with_session, ARObject, ARSession};
+
+// Planes are treated as solid this far behind their surface
+const SURFACE_THICKNESS: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FadePolicy {
+    // Meters from the camera; infinite when the object never hides by distance
+    pub fade_start: f32,
+    pub hide_distance: f32,
+    pub hide_inside_geometry: bool,
+}
+
+impl FadePolicy {
+    fn distance_opacity(&self, distance: f32) -> f32 {
+        if distance >= self.hide_distance {
+            0.0
+        } else if distance <= self.fade_start {
+            1.0
+        } else {
+            1.0 - (distance - self.fade_start) / (self.hide_distance - self.fade_start)
+        }
+    }
+}
+
+impl ARSession {
+    // Whether the whole of an object's bounding sphere is behind a plane's surface
+    fn inside_geometry(&self, object: &ARObject) -> bool {
+        let radius = object.bounding_radius();
+        self.detected_planes.iter().any(|plane| {
+            let depth = -dot(sub(object.position, plane.center), plane.normal);
+            depth > radius && depth < SURFACE_THICKNESS + radius && plane.within_extent(object.position)
+        })
+    }
+
+    // Re-evaluate every object's policy against the current camera position
+    pub(crate) fn update_fading(&mut self) {
+        for index in 0..self.virtual_objects.len() {
+            let object = &self.virtual_objects[index];
+            let Some(policy) = object.fade else {
+                continue;
+            };
+            let distance = length(sub(object.position, self.camera_position));
+            let opacity = if policy.hide_inside_geometry && self.inside_geometry(object) {
+                0.0
+            } else {
+                policy.distance_opacity(distance)
+            };
+            self.virtual_objects[index].opacity = opacity;
+        }
+    }
+}
+
+// Fade an object out with distance from the camera: fully visible up to `fade_start`
+// meters, fading linearly to hidden at `hide_distance` (equal values hide it abruptly;
+// a hide distance of 0 or less never hides by distance). `hide_inside_geometry` also
+// hides it while it's behind a detected surface. Takes effect from the next frame.
+// Returns false for an invalid id
+#[no_mangle]
+pub extern "C" fn set_object_fade_policy(
+    object_id: i32,
+    fade_start: f32,
+    hide_distance: f32,
+    hide_inside_geometry: bool,
+) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+    let hide_distance = if hide_distance > 0.0 { hide_distance } else { f32::INFINITY };
+    let fade_start = fade_start.clamp(0.0, hide_distance);
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.fade = Some(FadePolicy { fade_start, hide_distance, hide_inside_geometry });
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Remove an object's policy, making it fully visible again
+#[no_mangle]
+pub extern "C" fn clear_object_fade_policy(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.fade = None;
+        object.opacity = 1.0;
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Opacity from the last frame's policy evaluation: 1 fully visible, 0 hidden. Returns
+// -1 for an invalid id
+#[no_mangle]
+pub extern "C" fn get_object_opacity(object_id: i32) -> f32 {
+    let Ok(index) = usize::try_from(object_id) else {
+        return -1.0;
+    };
+
+    with_session(|session| session.virtual_objects.get(index).map(|object| object.opacity))
+        .flatten()
+        .unwrap_or(-1.0)
+}
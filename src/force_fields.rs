@@ -0,0 +1,152 @@
+// Force field volumes for dynamic objects: wind blowing through a region, a radial
+// blast pushing outward from a point, or an attractor pulling toward it. Each field is
+// a sphere; bodies inside it feel the force on every physics substep. Fields are
+// animated by updating them from the host, e.g. ramping an explosion's strength up and
+// back down over a few frames. Changing a field wakes sleeping bodies in its reach
+
+use crate::math::{add, length, normalize, scale, sub};
+use crate::{with_session, ARSession};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForceFieldKind {
+    // Uniform force along `direction`
+    Wind = 0,
+    // Away from the center, fading to zero at the edge
+    Radial = 1,
+    // Toward the center, fading to zero at the edge
+    Attractor = 2,
+}
+
+impl ForceFieldKind {
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(ForceFieldKind::Wind),
+            1 => Some(ForceFieldKind::Radial),
+            2 => Some(ForceFieldKind::Attractor),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ForceField {
+    pub id: i32,
+    pub kind: ForceFieldKind,
+    pub center: [f32; 3],
+    pub radius: f32,
+    // Unit vector; only used by wind
+    pub direction: [f32; 3],
+    // Newtons at full strength
+    pub strength: f32,
+}
+
+impl ForceField {
+    fn force_at(&self, position: [f32; 3]) -> [f32; 3] {
+        let offset = sub(position, self.center);
+        let distance = length(offset);
+        if distance > self.radius {
+            return [0.0; 3];
+        }
+        let falloff = 1.0 - distance / self.radius;
+        match self.kind {
+            ForceFieldKind::Wind => scale(self.direction, self.strength),
+            // No direction at the exact center
+            _ if distance < 1e-6 => [0.0; 3],
+            ForceFieldKind::Radial => scale(offset, self.strength * falloff / distance),
+            ForceFieldKind::Attractor => scale(offset, -self.strength * falloff / distance),
+        }
+    }
+}
+
+// Total force from all fields on a body at `position`
+pub(crate) fn field_force(fields: &[ForceField], position: [f32; 3]) -> [f32; 3] {
+    fields.iter().fold([0.0; 3], |total, field| add(total, field.force_at(position)))
+}
+
+impl ARSession {
+    fn force_field_mut(&mut self, id: i32) -> Option<&mut ForceField> {
+        self.physics.force_fields.iter_mut().find(|field| field.id == id)
+    }
+}
+
+fn field_shape(radius: f32, direction: [f32; 3]) -> Option<[f32; 3]> {
+    if !radius.is_finite() || radius <= 0.0 {
+        return None;
+    }
+    Some(if length(direction) > 1e-6 { normalize(direction) } else { [0.0; 3] })
+}
+
+// Add a force field (AR_FORCE_FIELD_*) as a sphere at `center` with `radius` meters.
+// `direction` is only used by wind; strength is in newtons. Returns the field id, or
+// -1 on bad input
+#[no_mangle]
+pub extern "C" fn add_force_field(
+    kind: i32,
+    center_x: f32, center_y: f32, center_z: f32,
+    radius: f32,
+    direction_x: f32, direction_y: f32, direction_z: f32,
+    strength: f32
+) -> i32 {
+    let (Some(kind), Some(direction)) = (ForceFieldKind::from_code(kind), field_shape(radius, [direction_x, direction_y, direction_z])) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        let id = session.physics.next_force_field_id;
+        session.physics.next_force_field_id += 1;
+        session.physics.force_fields.push(ForceField {
+            id,
+            kind,
+            center: [center_x, center_y, center_z],
+            radius,
+            direction,
+            strength,
+        });
+        session.wake_within([center_x, center_y, center_z], radius);
+        id
+    })
+    .unwrap_or(-1)
+}
+
+// Move, resize or re-aim a field; call per frame to animate it. Returns false for an
+// unknown id or bad input
+#[no_mangle]
+pub extern "C" fn update_force_field(
+    field_id: i32,
+    center_x: f32, center_y: f32, center_z: f32,
+    radius: f32,
+    direction_x: f32, direction_y: f32, direction_z: f32,
+    strength: f32
+) -> bool {
+    let Some(direction) = field_shape(radius, [direction_x, direction_y, direction_z]) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(field) = session.force_field_mut(field_id) else {
+            return false;
+        };
+        let (old_center, old_radius) = (field.center, field.radius);
+        field.center = [center_x, center_y, center_z];
+        field.radius = radius;
+        field.direction = direction;
+        field.strength = strength;
+        session.wake_within(old_center, old_radius);
+        session.wake_within([center_x, center_y, center_z], radius);
+        true
+    })
+    .unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn remove_force_field(field_id: i32) -> bool {
+    with_session(|session| {
+        let Some(position) = session.physics.force_fields.iter().position(|field| field.id == field_id) else {
+            return false;
+        };
+        let field = session.physics.force_fields.remove(position);
+        session.wake_within(field.center, field.radius);
+        true
+    })
+    .unwrap_or(false)
+}
@@ -0,0 +1,182 @@
+// Gaze interaction for hands-free selection. Each frame a ray is cast forward from the
+// (smoothed) camera pose; the nearest gaze-enabled object it hits gains focus, unless
+// a person stands in front of the hit (see occlusion.rs). Focus changes emit
+// focus_enter / focus_exit events, and holding focus on an object for its dwell time
+// emits dwell_complete once
+
+use crate::events::SessionEvent;
+use crate::math::{add, dot, length, quat_rotate, scale, sub};
+use crate::{with_session, ARSession};
+
+// Objects farther than this along the ray are ignored
+const MAX_GAZE_DISTANCE: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GazeTarget {
+    // Seconds of continuous focus before dwell_complete
+    pub dwell_time: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GazeState {
+    focused: Option<usize>,
+    dwell: f32,
+    completed: bool,
+}
+
+impl GazeState {
+    pub fn focused(&self) -> Option<usize> {
+        self.focused
+    }
+
+    // Keep the focused index in step with object removal
+    pub fn object_removed(&mut self, index: usize) {
+        match self.focused {
+            Some(focused) if focused == index => *self = GazeState::default(),
+            Some(focused) if focused > index => self.focused = Some(focused - 1),
+            _ => {}
+        }
+    }
+}
+
+// Distance along a ray to a sphere, if the ray hits it
+fn ray_sphere(origin: [f32; 3], direction: [f32; 3], center: [f32; 3], radius: f32) -> Option<f32> {
+    let to_center = sub(center, origin);
+    let along = dot(to_center, direction);
+    let closest = add(origin, scale(direction, along));
+    let miss = length(sub(center, closest));
+    if miss > radius {
+        return None;
+    }
+    let half_chord = (radius * radius - miss * miss).sqrt();
+    let near = along - half_chord;
+    let far = along + half_chord;
+    if far < 0.0 {
+        return None;
+    }
+    Some(near.max(0.0))
+}
+
+impl ARSession {
+    fn gaze_ray(&self) -> ([f32; 3], [f32; 3]) {
+        let (position, rotation) = match self.camera_filter.smoothed() {
+            Some(pose) => (pose.position, pose.rotation),
+            None => (self.camera_position, self.camera_rotation),
+        };
+        (position, quat_rotate(rotation, [0.0, 0.0, -1.0]))
+    }
+
+    // Nearest gaze-enabled object under the gaze ray
+    fn gaze_hit(&self) -> Option<usize> {
+        let (origin, direction) = self.gaze_ray();
+        self.virtual_objects.iter()
+            .enumerate()
+            .filter(|(_, object)| object.gaze.is_some())
+            .filter_map(|(index, object)| {
+                let distance = ray_sphere(origin, direction, object.position, object.bounding_radius())?;
+                let blocked = self.point_occluded(add(origin, scale(direction, distance))) == Some(true);
+                (distance <= MAX_GAZE_DISTANCE && !blocked).then_some((index, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+
+    // Keep focus in step with object removal. Removing the focused object ends its focus
+    pub(crate) fn gaze_object_removed(&mut self, index: usize) {
+        if self.gaze.focused == Some(index) {
+            self.events.push(SessionEvent::FocusExit { object_id: index });
+        }
+        self.gaze.object_removed(index);
+    }
+
+    pub(crate) fn update_gaze(&mut self, dt: f32) {
+        let hit = self.gaze_hit();
+
+        if hit != self.gaze.focused {
+            if let Some(previous) = self.gaze.focused {
+                self.events.push(SessionEvent::FocusExit { object_id: previous });
+            }
+            if let Some(next) = hit {
+                self.events.push(SessionEvent::FocusEnter { object_id: next });
+            }
+            self.gaze = GazeState { focused: hit, dwell: 0.0, completed: false };
+            return;
+        }
+
+        let Some(focused) = hit else {
+            return;
+        };
+        let Some(target) = self.virtual_objects[focused].gaze else {
+            return;
+        };
+        self.gaze.dwell += dt;
+        if !self.gaze.completed && self.gaze.dwell >= target.dwell_time {
+            self.gaze.completed = true;
+            self.events.push(SessionEvent::DwellComplete { object_id: focused });
+        }
+    }
+}
+
+// Make an object a gaze target with the given dwell time in seconds, or stop it being
+// one. Returns false for an invalid id
+#[no_mangle]
+pub extern "C" fn set_object_gaze(object_id: i32, enabled: bool, dwell_time: f32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.gaze = enabled.then_some(GazeTarget { dwell_time: dwell_time.max(0.0) });
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Object currently under the gaze ray, or -1 if none
+#[no_mangle]
+pub extern "C" fn get_gaze_target() -> i32 {
+    with_session(|session| session.gaze.focused())
+        .flatten()
+        .map_or(-1, |index| index as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn drain(session: &mut ARSession) -> Vec<SessionEvent> {
+        std::iter::from_fn(|| session.events.pop()).collect()
+    }
+
+    #[test]
+    fn removing_the_focused_object_ends_focus() {
+        let mut session = ARSession::new();
+        session.set_camera_pose(0.0, [0.0, 0.0, 0.0], IDENTITY);
+        let before = session.place_object(ARObjectType::Cube, [2.0, 0.0, -1.0], IDENTITY).unwrap();
+        let target = session.place_object(ARObjectType::Sphere, [0.0, 0.0, -1.0], IDENTITY).unwrap();
+        session.virtual_objects[target].gaze = Some(GazeTarget { dwell_time: 1.0 });
+        session.update_gaze(0.1);
+        assert_eq!(session.gaze.focused(), Some(target));
+        drain(&mut session);
+
+        // Removing an earlier object only shifts the focused index
+        assert!(session.remove_object(before));
+        assert_eq!(session.gaze.focused(), Some(target - 1));
+        assert!(drain(&mut session).iter().all(|event| !matches!(event, SessionEvent::FocusExit { .. })));
+
+        assert!(session.remove_object(target - 1));
+        assert_eq!(session.gaze.focused(), None);
+        let events = drain(&mut session);
+        assert!(events.iter().any(|event| matches!(event, SessionEvent::FocusExit { object_id } if *object_id == target - 1)));
+
+        // Nothing left to exit on the next frame
+        session.update_gaze(0.1);
+        assert!(drain(&mut session).is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+// Two-finger gesture resolution. The host forwards its recognizers' raw cumulative
+// state (pinch scale, rotation angle, pan translation) and the core turns it into a
+// world-space transform, so snapping, constraints and undo behave the same everywhere.
+//
+// Pinch scales uniformly, rotation turns the object about world up, and pan slides it
+// across the horizontal plane at its current height. Values are relative to the start
+// of the gesture, as UIKit reports them; each committed gesture is one undo step
+
+use crate::math::{add, dot, length, normalize, quat_from_axis_angle, quat_mul, quat_normalize, quat_rotate, scale, sub};
+use crate::{with_session, ARSession};
+
+// Undo steps kept; the oldest are dropped first
+const MAX_HISTORY: usize = 64;
+
+// Scale snaps back to 1 within this fraction
+const SCALE_SNAP_TOLERANCE: f32 = 0.05;
+
+// Rotation snaps to the nearest increment within this many radians
+const ROTATION_SNAP_TOLERANCE: f32 = 3.0 * std::f32::consts::PI / 180.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Transform {
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GestureConstraints {
+    pub min_scale: f32,
+    pub max_scale: f32,
+    // Radians; 0 disables rotation snapping
+    pub rotation_snap: f32,
+    pub allow_translation: bool,
+}
+
+impl Default for GestureConstraints {
+    fn default() -> Self {
+        GestureConstraints {
+            min_scale: 0.25,
+            max_scale: 4.0,
+            rotation_snap: 15.0f32.to_radians(),
+            allow_translation: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveGesture {
+    object: usize,
+    start: Transform,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GestureState {
+    pub constraints: GestureConstraints,
+    active: Option<ActiveGesture>,
+    // (object index, transform to restore)
+    undo: Vec<(usize, Transform)>,
+    redo: Vec<(usize, Transform)>,
+}
+
+impl GestureState {
+    pub fn active_object(&self) -> Option<usize> {
+        self.active.map(|gesture| gesture.object)
+    }
+
+    // Keep indices in step with object removal; history for the removed object is lost
+    pub fn object_removed(&mut self, index: usize) {
+        if self.active.is_some_and(|gesture| gesture.object == index) {
+            self.active = None;
+        }
+        if let Some(gesture) = self.active.as_mut().filter(|gesture| gesture.object > index) {
+            gesture.object -= 1;
+        }
+        for history in [&mut self.undo, &mut self.redo] {
+            history.retain(|(object, _)| *object != index);
+            for (object, _) in history.iter_mut() {
+                if *object > index {
+                    *object -= 1;
+                }
+            }
+        }
+    }
+
+    // Shift recorded transforms, e.g. when the world origin moves
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        if let Some(gesture) = self.active.as_mut() {
+            gesture.start.position = add(gesture.start.position, offset);
+        }
+        for history in [&mut self.undo, &mut self.redo] {
+            for (_, transform) in history.iter_mut() {
+                transform.position = add(transform.position, offset);
+            }
+        }
+    }
+
+    fn push_undo(&mut self, object: usize, transform: Transform) {
+        push_bounded(&mut self.undo, (object, transform));
+        self.redo.clear();
+    }
+}
+
+fn push_bounded(history: &mut Vec<(usize, Transform)>, entry: (usize, Transform)) {
+    if history.len() >= MAX_HISTORY {
+        history.drain(..=history.len() - MAX_HISTORY);
+    }
+    history.push(entry);
+}
+
+fn snap_scale(value: f32, constraints: &GestureConstraints) -> f32 {
+    let value = if (value - 1.0).abs() <= SCALE_SNAP_TOLERANCE { 1.0 } else { value };
+    value.clamp(constraints.min_scale, constraints.max_scale)
+}
+
+fn snap_angle(angle: f32, constraints: &GestureConstraints) -> f32 {
+    if constraints.rotation_snap <= 0.0 {
+        return angle;
+    }
+    let snapped = (angle / constraints.rotation_snap).round() * constraints.rotation_snap;
+    if (angle - snapped).abs() <= ROTATION_SNAP_TOLERANCE { snapped } else { angle }
+}
+
+// Project onto the horizontal plane
+fn horizontal(v: [f32; 3]) -> [f32; 3] {
+    [v[0], 0.0, v[2]]
+}
+
+impl ARSession {
+    pub(crate) fn object_transform(&self, index: usize) -> Option<Transform> {
+        let object = self.virtual_objects.get(index)?;
+        Some(Transform { position: object.position, rotation: object.rotation, scale: object.scale })
+    }
+
+    // Set an object's transform directly, re-basing its anchor or camera offset and
+    // dropping any stabilization in flight so the object doesn't ease back
+    pub(crate) fn set_object_transform(&mut self, index: usize, transform: Transform) {
+        let Some(object) = self.virtual_objects.get_mut(index) else {
+            return;
+        };
+        object.position = transform.position;
+        object.rotation = transform.rotation;
+        object.scale = transform.scale;
+        if let Some(stabilizer) = object.stabilizer.as_mut() {
+            stabilizer.reset();
+        }
+        if let Some(anchor_id) = object.anchor.as_ref().map(|a| a.anchor_id.clone()) {
+            self.attach_to_anchor(index, &anchor_id);
+        }
+        self.rebase_camera_attachment(index);
+        #[cfg(feature = "physics")]
+        self.wake_object(index);
+    }
+
+    // World-space offset for a pan, in fractions of the view height (y down)
+    fn pan_offset(&self, from: [f32; 3], pan: [f32; 2]) -> [f32; 3] {
+        let Some(camera) = self.view_camera() else {
+            return [0.0; 3];
+        };
+        let Some(pose) = camera.pose else {
+            return [0.0; 3];
+        };
+        let [_, fy, ..] = camera.intrinsics.unwrap_or([1.0; 4]);
+
+        let forward = quat_rotate(pose.rotation, [0.0, 0.0, -1.0]);
+        let depth = dot(sub(from, pose.position), forward).max(0.1);
+        // Meters spanned by the full view height at the object's depth
+        let view_height = depth * camera.resolution[1] as f32 / fy;
+
+        let right = normalize(horizontal(quat_rotate(pose.rotation, [1.0, 0.0, 0.0])));
+        // Looking straight down, "away" is the camera's up vector instead
+        let away = if length(horizontal(forward)) > 0.1 {
+            normalize(horizontal(forward))
+        } else {
+            normalize(horizontal(quat_rotate(pose.rotation, [0.0, 1.0, 0.0])))
+        };
+        add(scale(right, pan[0] * view_height), scale(away, -pan[1] * view_height))
+    }
+
+    fn resolve_gesture(&self, start: Transform, pinch: f32, rotation: f32, pan: [f32; 2]) -> Transform {
+        let constraints = &self.gestures.constraints;
+
+        let scale = snap_scale(start.scale * pinch.max(0.0), constraints);
+        // Clockwise on screen is clockwise seen from above: negative about +Y
+        let yaw = snap_angle(-rotation, constraints);
+        let rotation = quat_normalize(quat_mul(quat_from_axis_angle([0.0, 1.0, 0.0], yaw), start.rotation));
+        let position = if constraints.allow_translation {
+            add(start.position, self.pan_offset(start.position, pan))
+        } else {
+            start.position
+        };
+
+        Transform { position, rotation, scale }
+    }
+}
+
+// Start a gesture on an object. An unfinished gesture on another object is committed
+#[no_mangle]
+pub extern "C" fn begin_object_gesture(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(start) = session.object_transform(index) else {
+            return false;
+        };
+        if let Some(previous) = session.gestures.active.take() {
+            session.gestures.push_undo(previous.object, previous.start);
+        }
+        session.gestures.active = Some(ActiveGesture { object: index, start });
+        session.alignment.guides.clear();
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Apply the gesture's cumulative state: pinch scale (1 = unchanged), rotation in radians
+// (clockwise positive, as UIRotationGestureRecognizer), and pan in fractions of the view
+// height (y down). Panned positions snap to alignment guides when they're on (see
+// alignment.rs). Returns false if no gesture is active
+#[no_mangle]
+pub extern "C" fn update_object_gesture(pinch_scale: f32, rotation: f32, pan_x: f32, pan_y: f32) -> bool {
+    with_session(|session| {
+        let Some(gesture) = session.gestures.active else {
+            return false;
+        };
+        let mut transform = session.resolve_gesture(gesture.start, pinch_scale, rotation, [pan_x, pan_y]);
+        if session.gestures.constraints.allow_translation {
+            transform = session.align_transform(gesture.object, gesture.start.position, transform);
+        }
+        session.set_object_transform(gesture.object, transform);
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Finish the active gesture. Committing records an undo step; cancelling restores the
+// transform from before the gesture
+#[no_mangle]
+pub extern "C" fn end_object_gesture(commit: bool) -> bool {
+    with_session(|session| {
+        let Some(gesture) = session.gestures.active.take() else {
+            return false;
+        };
+        if commit {
+            if session.object_transform(gesture.object) != Some(gesture.start) {
+                session.gestures.push_undo(gesture.object, gesture.start);
+            }
+        } else {
+            session.set_object_transform(gesture.object, gesture.start);
+        }
+        true
+    })
+    .unwrap_or(false)
+}
+
+impl ARSession {
+    // Undo or redo one step, moving the object's current transform to the other
+    // history. Returns the affected object
+    fn step_history(&mut self, undo: bool) -> Option<usize> {
+        let history = &mut self.gestures;
+        let (index, transform) = if undo { history.undo.pop() } else { history.redo.pop() }?;
+        let current = self.object_transform(index)?;
+
+        let history = &mut self.gestures;
+        push_bounded(if undo { &mut history.redo } else { &mut history.undo }, (index, current));
+        self.set_object_transform(index, transform);
+        Some(index)
+    }
+}
+
+fn step_history(undo: bool) -> i32 {
+    with_session(|session| session.step_history(undo))
+        .flatten()
+        .map_or(-1, |index| index as i32)
+}
+
+// Revert the last committed gesture. Returns the affected object id, or -1 if there
+// is nothing to undo
+#[no_mangle]
+pub extern "C" fn undo_object_transform() -> i32 {
+    step_history(true)
+}
+
+// Re-apply the last undone gesture. Returns the affected object id, or -1
+#[no_mangle]
+pub extern "C" fn redo_object_transform() -> i32 {
+    step_history(false)
+}
+
+// Limits applied while resolving gestures. rotation_snap_degrees of 0 disables
+// rotation snapping
+#[no_mangle]
+pub extern "C" fn set_gesture_constraints(
+    min_scale: f32,
+    max_scale: f32,
+    rotation_snap_degrees: f32,
+    allow_translation: bool,
+) {
+    with_session(|session| {
+        let min_scale = min_scale.max(0.01);
+        session.gestures.constraints = GestureConstraints {
+            min_scale,
+            max_scale: max_scale.max(min_scale),
+            rotation_snap: rotation_snap_degrees.max(0.0).to_radians(),
+            allow_translation,
+        };
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    fn transform(x: f32) -> Transform {
+        Transform { position: [x, 0.0, 0.0], rotation: [0.0, 0.0, 0.0, 1.0], scale: 1.0 }
+    }
+
+    #[test]
+    fn history_stays_bounded_through_undo_and_redo() {
+        let mut session = ARSession::new();
+        let index = session.place_object(ARObjectType::Cube, [0.0; 3], [0.0, 0.0, 0.0, 1.0]).unwrap();
+        for step in 0..MAX_HISTORY + 10 {
+            session.gestures.push_undo(index, transform(step as f32));
+        }
+        assert_eq!(session.gestures.undo.len(), MAX_HISTORY);
+        // The oldest steps went first
+        assert_eq!(session.gestures.undo[0].1, transform(10.0));
+
+        // A full redo stack plus a redone step is still one history's worth
+        session.gestures.redo = vec![(index, transform(0.0)); MAX_HISTORY];
+        assert_eq!(session.step_history(true), Some(index));
+        assert_eq!(session.gestures.redo.len(), MAX_HISTORY);
+        for _ in 0..3 {
+            assert_eq!(session.step_history(false), Some(index));
+        }
+        assert!(session.gestures.undo.len() <= MAX_HISTORY);
+        assert!(session.gestures.redo.len() <= MAX_HISTORY);
+    }
+}
@@ -0,0 +1,400 @@
+// Session handoff between devices. One device exports a bundle with its ARWorldMap
+// (archived by the host and carried opaquely), its planes, anchors and placed objects
+// (with their anchor attachments), and the session preferences. Another device imports
+// it to resume the same experience: the host runs ARKit with the bundled world map, and
+// once it relocalizes, re-reported anchors carry attached content back into place.
+//
+// Bundles are versioned. Newer fields are optional, so older bundles still import;
+// bundles from a newer version are rejected. Each device declares its capabilities,
+// and importing on a device without something the exporter had (e.g. LiDAR) still
+// restores all content but reports what's missing, so the app can turn off the
+// features that need it
+
+use serde::{Deserialize, Serialize};
+
+use crate::anchors::{AnchorAttachment, DriftConfig};
+use crate::events::copy_c_string;
+use crate::pose_filter::PoseFilterConfig;
+use crate::snapshot::{ObjectSnapshot, PlaneSnapshot, SessionSnapshot};
+use crate::{with_session, ARSession};
+
+pub const HANDOFF_VERSION: u32 = 1;
+
+pub const AR_CAPABILITY_DEPTH: u32 = 1;
+pub const AR_CAPABILITY_PEOPLE_OCCLUSION: u32 = 2;
+pub const AR_CAPABILITY_FRONT_CAMERA: u32 = 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceCapabilities {
+    // LiDAR scene depth
+    #[serde(default)]
+    pub depth: bool,
+    #[serde(default)]
+    pub people_occlusion: bool,
+    // Face tracking on the front camera alongside world tracking
+    #[serde(default)]
+    pub front_camera: bool,
+}
+
+impl DeviceCapabilities {
+    fn from_mask(mask: u32) -> Self {
+        DeviceCapabilities {
+            depth: mask & AR_CAPABILITY_DEPTH != 0,
+            people_occlusion: mask & AR_CAPABILITY_PEOPLE_OCCLUSION != 0,
+            front_camera: mask & AR_CAPABILITY_FRONT_CAMERA != 0,
+        }
+    }
+
+    fn mask(self) -> u32 {
+        (self.depth as u32 * AR_CAPABILITY_DEPTH)
+            | (self.people_occlusion as u32 * AR_CAPABILITY_PEOPLE_OCCLUSION)
+            | (self.front_camera as u32 * AR_CAPABILITY_FRONT_CAMERA)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorSnapshot {
+    pub id: String,
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttachmentSnapshot {
+    // Index into the bundle's objects
+    pub object: usize,
+    pub anchor_id: String,
+    pub local_position: [f32; 3],
+    pub local_rotation: [f32; 4],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HandoffPreferences {
+    // Alpha, beta and rotation time constant (see pose_filter.rs)
+    pub camera_smoothing: [f32; 3],
+    // Meters and degrees
+    pub anchor_drift: [f32; 2],
+    // Written and read by builds without physics too, so bundles have one format
+    pub gravity: [f32; 3],
+}
+
+impl Default for HandoffPreferences {
+    fn default() -> Self {
+        let smoothing = PoseFilterConfig::default();
+        let drift = DriftConfig::default();
+        HandoffPreferences {
+            camera_smoothing: [smoothing.alpha, smoothing.beta, smoothing.rotation_time_constant],
+            anchor_drift: [drift.distance, drift.angle.to_degrees()],
+            gravity: [0.0, -9.81, 0.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HandoffBundle {
+    pub version: u32,
+    // The exporting device's
+    #[serde(default)]
+    pub capabilities: DeviceCapabilities,
+    // Base64 ARWorldMap archive
+    #[serde(default)]
+    pub world_map: Option<String>,
+    #[serde(default)]
+    pub planes: Vec<PlaneSnapshot>,
+    #[serde(default)]
+    pub anchors: Vec<AnchorSnapshot>,
+    #[serde(default)]
+    pub objects: Vec<ObjectSnapshot>,
+    #[serde(default)]
+    pub attachments: Vec<AttachmentSnapshot>,
+    #[serde(default)]
+    pub preferences: HandoffPreferences,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HandoffState {
+    pub capabilities: DeviceCapabilities,
+    // From the last imported bundle, until the host takes it
+    world_map: Option<Vec<u8>>,
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = BASE64.iter().position(|&b| b == c)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(out)
+}
+
+impl ARSession {
+    pub(crate) fn handoff_bundle(&self, world_map: Option<&[u8]>) -> HandoffBundle {
+        let snapshot = SessionSnapshot::from(self);
+        let attachments = self.virtual_objects.iter()
+            .enumerate()
+            .filter_map(|(object, o)| {
+                let attachment = o.anchor.as_ref()?;
+                Some(AttachmentSnapshot {
+                    object,
+                    anchor_id: attachment.anchor_id.clone(),
+                    local_position: attachment.local_position,
+                    local_rotation: attachment.local_rotation,
+                })
+            })
+            .collect();
+        let smoothing = self.camera_filter.config;
+
+        HandoffBundle {
+            version: HANDOFF_VERSION,
+            capabilities: self.handoff.capabilities,
+            world_map: world_map.map(base64_encode),
+            planes: snapshot.planes,
+            anchors: self.anchors.iter()
+                .map(|anchor| AnchorSnapshot { id: anchor.id.clone(), position: anchor.position, rotation: anchor.rotation })
+                .collect(),
+            objects: snapshot.objects,
+            attachments,
+            preferences: HandoffPreferences {
+                camera_smoothing: [smoothing.alpha, smoothing.beta, smoothing.rotation_time_constant],
+                anchor_drift: [self.anchor_drift.distance, self.anchor_drift.angle.to_degrees()],
+                #[cfg(feature = "physics")]
+                gravity: self.physics.gravity,
+                #[cfg(not(feature = "physics"))]
+                gravity: HandoffPreferences::default().gravity,
+            },
+        }
+    }
+
+    // Replace the session's content with a bundle's. Returns the capabilities the
+    // exporting device had that this one lacks, or None for an unusable bundle
+    pub(crate) fn import_handoff(&mut self, bundle: HandoffBundle) -> Option<u32> {
+        if bundle.version > HANDOFF_VERSION {
+            return None;
+        }
+        let world_map = match &bundle.world_map {
+            Some(encoded) => Some(base64_decode(encoded)?),
+            None => None,
+        };
+
+        // Release per-object state before the objects are replaced
+        for index in (0..self.virtual_objects.len()).rev() {
+            self.gaze_object_removed(index);
+            self.gestures.object_removed(index);
+            #[cfg(feature = "physics")]
+            self.joints_object_removed(index);
+        }
+
+        let restored = ARSession::from(&SessionSnapshot {
+            planes: bundle.planes,
+            objects: bundle.objects,
+            ..Default::default()
+        });
+        self.detected_planes = restored.detected_planes;
+        self.coverage.clear();
+        self.virtual_objects = restored.virtual_objects;
+        self.anchors.clear();
+        for anchor in &bundle.anchors {
+            self.upsert_anchor(&anchor.id, anchor.position, anchor.rotation);
+        }
+        for attachment in bundle.attachments {
+            if self.anchor(&attachment.anchor_id).is_none() {
+                continue;
+            }
+            if let Some(object) = self.virtual_objects.get_mut(attachment.object) {
+                object.anchor = Some(AnchorAttachment {
+                    anchor_id: attachment.anchor_id,
+                    local_position: attachment.local_position,
+                    local_rotation: attachment.local_rotation,
+                });
+            }
+        }
+
+        let [alpha, beta, rotation_time_constant] = bundle.preferences.camera_smoothing;
+        self.camera_filter.config = PoseFilterConfig { alpha, beta, rotation_time_constant, ..self.camera_filter.config };
+        let [distance, angle] = bundle.preferences.anchor_drift;
+        self.anchor_drift = DriftConfig { distance, angle: angle.to_radians() };
+        #[cfg(feature = "physics")]
+        {
+            self.physics.gravity = bundle.preferences.gravity;
+        }
+        self.handoff.world_map = world_map;
+
+        Some(bundle.capabilities.mask() & !self.handoff.capabilities.mask())
+    }
+}
+
+// Declare what this device supports (AR_CAPABILITY_* flags). Exported bundles record
+// it, and imports compare against it
+#[no_mangle]
+pub extern "C" fn set_device_capabilities(mask: u32) {
+    with_session(|session| session.handoff.capabilities = DeviceCapabilities::from_mask(mask));
+}
+
+// Export a handoff bundle as NUL-terminated JSON, including the host's archived
+// ARWorldMap (`world_map` may be null). Returns the JSON length, or -1 without a
+// session. Nothing is written if it doesn't fit in `capacity` bytes; retry with at
+// least the returned length + 1
+#[no_mangle]
+pub extern "C" fn export_handoff_bundle(
+    world_map: *const u8,
+    world_map_len: u32,
+    out_json: *mut libc::c_char,
+    capacity: u32,
+) -> i32 {
+    let world_map = (!world_map.is_null()).then(|| unsafe { std::slice::from_raw_parts(world_map, world_map_len as usize) });
+
+    with_session(|session| {
+        let json = serde_json::to_string(&session.handoff_bundle(world_map)).unwrap_or_default();
+        unsafe { copy_c_string(&json, out_json, capacity) };
+        json.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Replace the session's planes, anchors, objects and preferences with a bundle's.
+// Returns the AR_CAPABILITY_* flags the exporting device had that this one lacks (0 for
+// a full-fidelity handoff), or -1 for a malformed or newer bundle. The bundled world
+// map, if any, is then available from take_handoff_world_map
+#[no_mangle]
+pub extern "C" fn import_handoff_bundle(json_ptr: *const libc::c_char) -> i32 {
+    if json_ptr.is_null() {
+        return -1;
+    }
+    let json = unsafe { std::ffi::CStr::from_ptr(json_ptr) }.to_string_lossy();
+    let Ok(bundle) = serde_json::from_str::<HandoffBundle>(&json) else {
+        return -1;
+    };
+
+    with_session(|session| session.import_handoff(bundle))
+        .flatten()
+        .map_or(-1, |missing| missing as i32)
+}
+
+// Copy the world map from the last imported bundle into `out`, for the host to
+// unarchive into ARWorldTrackingConfiguration.initialWorldMap. Returns its length, 0 if
+// there is none, or -1 without a session. It's handed over once: nothing is written
+// (and it's kept) if it doesn't fit in `capacity` bytes
+#[no_mangle]
+pub extern "C" fn take_handoff_world_map(out: *mut u8, capacity: u32) -> i32 {
+    with_session(|session| {
+        let Some(world_map) = session.handoff.world_map.as_ref() else {
+            return 0;
+        };
+        let len = world_map.len() as i32;
+        if !out.is_null() && world_map.len() <= capacity as usize {
+            unsafe { std::ptr::copy_nonoverlapping(world_map.as_ptr(), out, world_map.len()) };
+            session.handoff.world_map = None;
+        }
+        len
+    })
+    .unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    #[test]
+    fn base64_round_trips_every_padding() {
+        let cases: [(&[u8], &str); 5] = [(b"", ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"), (b"foob", "Zm9vYg==")];
+        for (bytes, encoded) in cases {
+            assert_eq!(base64_encode(bytes), encoded);
+            assert_eq!(base64_decode(encoded).as_deref(), Some(bytes));
+        }
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(base64_decode(&base64_encode(&all)), Some(all));
+
+        // A lone trailing character or one outside the alphabet can't be decoded
+        assert_eq!(base64_decode("Zm9vY"), None);
+        assert_eq!(base64_decode("Zm9v!A=="), None);
+    }
+
+    fn exporting_session() -> ARSession {
+        let mut session = ARSession::new();
+        session.handoff.capabilities = DeviceCapabilities { depth: true, people_occlusion: true, front_camera: false };
+        session.add_plane(Some("floor".to_string()), [0.0; 3], [2.0, 3.0], [0.0, 1.0, 0.0]);
+        session.upsert_anchor("table", [1.0, 0.7, -1.0], IDENTITY);
+        session.place_object(ARObjectType::Cube, [0.0, 0.05, -1.0], IDENTITY).unwrap();
+        let vase = session.place_object(ARObjectType::Sphere, [1.0, 0.8, -1.0], IDENTITY).unwrap();
+        assert!(session.attach_to_anchor(vase, "table"));
+        session.camera_filter.config.alpha = 0.3;
+        session.anchor_drift.distance = 0.2;
+        session
+    }
+
+    #[test]
+    fn bundles_restore_content_and_preferences_on_another_device() {
+        let bundle = exporting_session().handoff_bundle(Some(b"world map"));
+        assert_eq!(bundle.version, HANDOFF_VERSION);
+
+        let mut session = ARSession::new();
+        session.handoff.capabilities = bundle.capabilities;
+        session.place_object(ARObjectType::Cube, [5.0, 0.0, 0.0], IDENTITY).unwrap();
+        assert_eq!(session.import_handoff(bundle), Some(0));
+
+        assert_eq!(session.detected_planes.len(), 1);
+        assert_eq!(session.detected_planes[0].id, "floor");
+        assert_eq!(session.virtual_objects.len(), 2);
+        assert_eq!(session.virtual_objects[0].position, [0.0, 0.05, -1.0]);
+        let attachment = session.virtual_objects[1].anchor.as_ref().unwrap();
+        assert_eq!(attachment.anchor_id, "table");
+        assert!((attachment.local_position[1] - 0.1).abs() < 1e-6);
+        assert_eq!(session.anchor("table").unwrap().position, [1.0, 0.7, -1.0]);
+        assert_eq!(session.camera_filter.config.alpha, 0.3);
+        assert!((session.anchor_drift.distance - 0.2).abs() < 1e-6);
+        assert_eq!(session.handoff.world_map.as_deref(), Some(&b"world map"[..]));
+    }
+
+    #[test]
+    fn bundles_from_a_newer_version_are_rejected() {
+        let mut bundle = exporting_session().handoff_bundle(None);
+        bundle.version = HANDOFF_VERSION + 1;
+
+        let mut session = ARSession::new();
+        session.place_object(ARObjectType::Cube, [5.0, 0.0, 0.0], IDENTITY).unwrap();
+        assert_eq!(session.import_handoff(bundle), None);
+        // The session keeps its own content
+        assert_eq!(session.virtual_objects.len(), 1);
+        assert!(session.detected_planes.is_empty());
+    }
+
+    #[test]
+    fn importing_without_lidar_reports_it_missing() {
+        let bundle = exporting_session().handoff_bundle(None);
+
+        let mut session = ARSession::new();
+        session.handoff.capabilities = DeviceCapabilities { people_occlusion: true, front_camera: true, ..Default::default() };
+        assert_eq!(session.import_handoff(bundle), Some(AR_CAPABILITY_DEPTH));
+        // Everything still comes across
+        assert_eq!(session.virtual_objects.len(), 2);
+        assert!(session.handoff.world_map.is_none());
+    }
+}
@@ -0,0 +1,350 @@
+// Backpressure for frame ingestion. Camera images and depth frames are processed on the
+// thread that submits them, one at a time per stream; when the host submits from several
+// threads faster than frames are processed, the extra submissions queue up behind the
+// one in progress. Each stream's policy bounds that queue:
+//
+// - drop-oldest keeps up to `capacity` frames waiting; a newer frame evicts the oldest
+// - coalesce keeps only the newest waiting frame (the default, since only the latest
+//   camera state matters for tracking)
+// - block waits in line for up to a timeout, then gives up on the frame
+//
+// A frame turned away returns from its submit call without being processed and is
+// counted in the session metrics. Queues are per process, like the session
+
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::metrics::SessionMetrics;
+use crate::with_session;
+
+pub const AR_INGEST_CAMERA_IMAGE: i32 = 0;
+pub const AR_INGEST_DEPTH: i32 = 1;
+
+pub const AR_QUEUE_DROP_OLDEST: i32 = 0;
+pub const AR_QUEUE_COALESCE: i32 = 1;
+pub const AR_QUEUE_BLOCK: i32 = 2;
+
+// Returned by submit_depth_frame for a frame turned away by its queue
+pub const AR_FRAME_DROPPED: i32 = -2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IngestStream {
+    CameraImage,
+    Depth,
+}
+
+impl IngestStream {
+    const COUNT: usize = 2;
+
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            AR_INGEST_CAMERA_IMAGE => Some(IngestStream::CameraImage),
+            AR_INGEST_DEPTH => Some(IngestStream::Depth),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum QueuePolicy {
+    DropOldest { capacity: usize },
+    #[default]
+    Coalesce,
+    Block { timeout: Duration },
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct IngestionConfig {
+    pub policies: [QueuePolicy; IngestStream::COUNT],
+}
+
+// Why a frame wasn't processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rejection {
+    Dropped,
+    Coalesced,
+}
+
+#[derive(Debug)]
+struct QueueState {
+    // A frame is being processed
+    busy: bool,
+    // Tickets of waiting frames, oldest first
+    waiting: VecDeque<u64>,
+    // Tickets turned away while waiting, collected by their threads
+    rejected: Vec<(u64, Rejection)>,
+    next_ticket: u64,
+}
+
+struct IngestQueue {
+    state: Mutex<QueueState>,
+    turn: Condvar,
+}
+
+impl IngestQueue {
+    const fn new() -> Self {
+        IngestQueue {
+            state: Mutex::new(QueueState { busy: false, waiting: VecDeque::new(), rejected: Vec::new(), next_ticket: 0 }),
+            turn: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Wait for this frame's turn under `policy`
+    fn admit(&self, policy: QueuePolicy) -> Result<(), Rejection> {
+        let mut state = self.lock();
+        if !state.busy && state.waiting.is_empty() {
+            state.busy = true;
+            return Ok(());
+        }
+
+        let evicted = match policy {
+            QueuePolicy::DropOldest { capacity: 0 } => return Err(Rejection::Dropped),
+            QueuePolicy::DropOldest { capacity } => {
+                let excess = (state.waiting.len() + 1).saturating_sub(capacity);
+                state.waiting.drain(..excess).map(|ticket| (ticket, Rejection::Dropped)).collect()
+            }
+            QueuePolicy::Coalesce => state.waiting.drain(..).map(|ticket| (ticket, Rejection::Coalesced)).collect(),
+            QueuePolicy::Block { .. } => Vec::new(),
+        };
+        state.rejected.extend(evicted);
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.push_back(ticket);
+        self.turn.notify_all();
+
+        let deadline = match policy {
+            QueuePolicy::Block { timeout } => Some(Instant::now() + timeout),
+            _ => None,
+        };
+        loop {
+            if let Some(index) = state.rejected.iter().position(|&(rejected, _)| rejected == ticket) {
+                return Err(state.rejected.swap_remove(index).1);
+            }
+            if !state.busy && state.waiting.front() == Some(&ticket) {
+                state.waiting.pop_front();
+                state.busy = true;
+                return Ok(());
+            }
+            state = match deadline {
+                None => self.turn.wait(state).unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        state.waiting.retain(|&waiting| waiting != ticket);
+                        return Err(Rejection::Dropped);
+                    }
+                    self.turn.wait_timeout(state, deadline - now).unwrap_or_else(PoisonError::into_inner).0
+                }
+            };
+        }
+    }
+
+    fn release(&self) {
+        self.lock().busy = false;
+        self.turn.notify_all();
+    }
+}
+
+static QUEUES: [IngestQueue; IngestStream::COUNT] = [IngestQueue::new(), IngestQueue::new()];
+
+// A stream's turn to process a frame, handed to the next waiting frame when dropped
+pub(crate) struct IngestTurn(IngestStream);
+
+impl Drop for IngestTurn {
+    fn drop(&mut self) {
+        QUEUES[self.0 as usize].release();
+    }
+}
+
+// Wait for a turn to process a frame on `stream`, under the session's policy for it.
+// None if the frame was turned away (and counted), or without a session
+pub(crate) fn begin_frame(stream: IngestStream) -> Option<IngestTurn> {
+    let policy = with_session(|session| session.ingestion.policies[stream as usize])?;
+    match QUEUES[stream as usize].admit(policy) {
+        Ok(()) => Some(IngestTurn(stream)),
+        Err(rejection) => {
+            with_session(|session| count_rejection(&mut session.metrics, stream, rejection));
+            None
+        }
+    }
+}
+
+fn count_rejection(metrics: &mut SessionMetrics, stream: IngestStream, rejection: Rejection) {
+    let counter = match (stream, rejection) {
+        (IngestStream::CameraImage, Rejection::Dropped) => &mut metrics.camera_images_dropped,
+        (IngestStream::CameraImage, Rejection::Coalesced) => &mut metrics.camera_images_coalesced,
+        (IngestStream::Depth, Rejection::Dropped) => &mut metrics.depth_frames_dropped,
+        (IngestStream::Depth, Rejection::Coalesced) => &mut metrics.depth_frames_coalesced,
+    };
+    *counter += 1;
+}
+
+// Set how a stream (AR_INGEST_*) queues frames submitted while another is being
+// processed (AR_QUEUE_*). `capacity` is the number of waiting frames kept by
+// AR_QUEUE_DROP_OLDEST (0 drops every frame that would wait); `timeout_ms` is how long a
+// frame waits under AR_QUEUE_BLOCK. Returns false for a bad stream or policy
+#[no_mangle]
+pub extern "C" fn set_ingestion_policy(stream: i32, policy: i32, capacity: u32, timeout_ms: u32) -> bool {
+    let Some(stream) = IngestStream::from_code(stream) else {
+        return false;
+    };
+    let policy = match policy {
+        AR_QUEUE_DROP_OLDEST => QueuePolicy::DropOldest { capacity: capacity as usize },
+        AR_QUEUE_COALESCE => QueuePolicy::Coalesce,
+        AR_QUEUE_BLOCK => QueuePolicy::Block { timeout: Duration::from_millis(timeout_ms as u64) },
+        _ => return false,
+    };
+
+    with_session(|session| session.ingestion.policies[stream as usize] = policy).is_some()
+}
+
+// Frames on a stream (AR_INGEST_*) turned away so far: dropped (evicted or timed out)
+// and coalesced into a newer frame. Outputs may be null. Returns false for a bad stream
+#[no_mangle]
+pub extern "C" fn get_ingestion_drop_counts(stream: i32, out_dropped: *mut u64, out_coalesced: *mut u64) -> bool {
+    let Some(stream) = IngestStream::from_code(stream) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let metrics = &session.metrics;
+        let (dropped, coalesced) = match stream {
+            IngestStream::CameraImage => (metrics.camera_images_dropped, metrics.camera_images_coalesced),
+            IngestStream::Depth => (metrics.depth_frames_dropped, metrics.depth_frames_coalesced),
+        };
+        unsafe {
+            if !out_dropped.is_null() {
+                *out_dropped = dropped;
+            }
+            if !out_coalesced.is_null() {
+                *out_coalesced = coalesced;
+            }
+        }
+    })
+    .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    // Start a frame on its own thread, returning once it has joined the line
+    fn submit(queue: &Arc<IngestQueue>, policy: QueuePolicy) -> thread::JoinHandle<Result<(), Rejection>> {
+        let ticket = queue.lock().next_ticket;
+        let handle = {
+            let queue = Arc::clone(queue);
+            thread::spawn(move || queue.admit(policy))
+        };
+        let mut state = queue.lock();
+        while state.next_ticket == ticket && !handle.is_finished() {
+            state = queue.turn.wait_timeout(state, Duration::from_millis(10)).unwrap().0;
+        }
+        handle
+    }
+
+    #[test]
+    fn idle_queue_admits_at_once() {
+        let queue = IngestQueue::new();
+        assert_eq!(queue.admit(QueuePolicy::DropOldest { capacity: 0 }), Ok(()));
+        assert!(queue.lock().busy);
+        // Nothing may wait under a zero capacity
+        assert_eq!(queue.admit(QueuePolicy::DropOldest { capacity: 0 }), Err(Rejection::Dropped));
+        queue.release();
+        assert_eq!(queue.admit(QueuePolicy::Coalesce), Ok(()));
+    }
+
+    #[test]
+    fn drop_oldest_evicts_beyond_capacity() {
+        let queue = Arc::new(IngestQueue::new());
+        let policy = QueuePolicy::DropOldest { capacity: 2 };
+        queue.admit(policy).unwrap();
+
+        let first = submit(&queue, policy);
+        let second = submit(&queue, policy);
+        let third = submit(&queue, policy);
+        assert_eq!(first.join().unwrap(), Err(Rejection::Dropped));
+        assert_eq!(queue.lock().waiting.len(), 2);
+
+        // The survivors go in submission order, each after the previous releases
+        queue.release();
+        assert_eq!(second.join().unwrap(), Ok(()));
+        queue.release();
+        assert_eq!(third.join().unwrap(), Ok(()));
+        queue.release();
+        assert!(queue.lock().waiting.is_empty());
+    }
+
+    #[test]
+    fn coalesce_keeps_only_the_newest_waiter() {
+        let queue = Arc::new(IngestQueue::new());
+        queue.admit(QueuePolicy::Coalesce).unwrap();
+
+        let first = submit(&queue, QueuePolicy::Coalesce);
+        let second = submit(&queue, QueuePolicy::Coalesce);
+        assert_eq!(first.join().unwrap(), Err(Rejection::Coalesced));
+        let third = submit(&queue, QueuePolicy::Coalesce);
+        assert_eq!(second.join().unwrap(), Err(Rejection::Coalesced));
+
+        queue.release();
+        assert_eq!(third.join().unwrap(), Ok(()));
+        assert!(queue.lock().rejected.is_empty());
+    }
+
+    #[test]
+    fn block_gives_up_after_its_timeout() {
+        let queue = Arc::new(IngestQueue::new());
+        queue.admit(QueuePolicy::Coalesce).unwrap();
+
+        let started = Instant::now();
+        let timeout = Duration::from_millis(50);
+        assert_eq!(queue.admit(QueuePolicy::Block { timeout }), Err(Rejection::Dropped));
+        assert!(started.elapsed() >= timeout);
+        // The timed-out frame left the line
+        assert!(queue.lock().waiting.is_empty());
+        assert!(queue.lock().busy);
+    }
+
+    #[test]
+    fn release_hands_the_turn_to_a_waiter_on_another_thread() {
+        let queue = Arc::new(IngestQueue::new());
+        let policy = QueuePolicy::Block { timeout: Duration::from_secs(10) };
+        queue.admit(policy).unwrap();
+
+        let waiter = submit(&queue, policy);
+        assert!(!waiter.is_finished());
+        queue.release();
+        assert_eq!(waiter.join().unwrap(), Ok(()));
+        // The waiter now holds the turn
+        let state = queue.lock();
+        assert!(state.busy);
+        assert!(state.waiting.is_empty());
+    }
+
+    #[test]
+    fn rejections_are_counted_per_stream() {
+        let queue = Arc::new(IngestQueue::new());
+        let mut metrics = SessionMetrics::default();
+        queue.admit(QueuePolicy::Coalesce).unwrap();
+
+        let coalesced = submit(&queue, QueuePolicy::Coalesce);
+        let kept = submit(&queue, QueuePolicy::Coalesce);
+        count_rejection(&mut metrics, IngestStream::CameraImage, coalesced.join().unwrap().unwrap_err());
+        let dropped = queue.admit(QueuePolicy::DropOldest { capacity: 0 }).unwrap_err();
+        count_rejection(&mut metrics, IngestStream::Depth, dropped);
+        queue.release();
+        assert_eq!(kept.join().unwrap(), Ok(()));
+
+        assert_eq!(metrics.camera_images_coalesced, 1);
+        assert_eq!(metrics.camera_images_dropped, 0);
+        assert_eq!(metrics.depth_frames_dropped, 1);
+        assert_eq!(metrics.depth_frames_coalesced, 0);
+    }
+}
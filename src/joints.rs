@@ -0,0 +1,382 @@
+// Joints between a dynamic object and a target: another object or an anchor. The
+// target is treated as immovable by the joint (it can still move on its own, and the
+// jointed object follows), which covers hanging and swinging content such as a sign
+// on a string or a door on its hinge.
+//
+// - Fixed joints hold the object rigidly at its offset from the target
+// - Hinge joints let it swing about an axis through a pivot, at a fixed radius
+// - Spring joints pull it toward a pivot with a damped spring
+
+use std::ffi::CStr;
+
+use crate::math::{add, cross, dot, length, normalize, quat_conjugate, quat_from_axis_angle, quat_mul, quat_normalize, quat_rotate, scale, sub, tangent_basis};
+use crate::physics::RigidBody;
+use crate::{with_session, ARSession};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum JointTarget {
+    Object(usize),
+    Anchor(String),
+}
+
+// Vectors and rotations are stored in the target's frame so the joint follows it
+#[derive(Debug, Clone)]
+pub(crate) enum JointKind {
+    Fixed {
+        local_position: [f32; 3],
+        local_rotation: [f32; 4],
+    },
+    Hinge {
+        pivot: [f32; 3],
+        axis: [f32; 3],
+        // The object's rest pose relative to the pivot
+        rest_direction: [f32; 3],
+        rest_rotation: [f32; 4],
+        axial_offset: f32,
+        radius: f32,
+    },
+    Spring {
+        pivot: [f32; 3],
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Joint {
+    pub id: i32,
+    pub object: usize,
+    pub target: JointTarget,
+    pub kind: JointKind,
+}
+
+// Angle about `axis` from `from` to `to`, both perpendicular to it
+fn signed_angle(from: [f32; 3], to: [f32; 3], axis: [f32; 3]) -> f32 {
+    dot(cross(from, to), axis).atan2(dot(from, to))
+}
+
+impl ARSession {
+    fn joint_target_pose(&self, target: &JointTarget) -> Option<([f32; 3], [f32; 4])> {
+        match target {
+            JointTarget::Object(index) => self.virtual_objects.get(*index).map(|o| (o.position, o.rotation)),
+            JointTarget::Anchor(id) => self.anchor(id).map(|a| (a.position, a.rotation)),
+        }
+    }
+
+    // Spring forces per object, applied before integration
+    pub(crate) fn joint_forces(&self) -> Vec<[f32; 3]> {
+        let mut forces = vec![[0.0; 3]; self.virtual_objects.len()];
+        for joint in &self.physics.joints {
+            let JointKind::Spring { pivot, rest_length, stiffness, damping } = joint.kind else {
+                continue;
+            };
+            let (Some((origin, rotation)), Some(object)) = (self.joint_target_pose(&joint.target), self.virtual_objects.get(joint.object)) else {
+                continue;
+            };
+            let Some(body) = object.body else {
+                continue;
+            };
+
+            let anchor_point = add(origin, quat_rotate(rotation, pivot));
+            let offset = sub(object.position, anchor_point);
+            let distance = length(offset);
+            if distance < 1e-6 {
+                continue;
+            }
+            let direction = scale(offset, 1.0 / distance);
+            let magnitude = -stiffness * (distance - rest_length) - damping * dot(body.velocity, direction);
+            forces[joint.object] = add(forces[joint.object], scale(direction, magnitude));
+        }
+        forces
+    }
+
+    // Project fixed and hinge joints onto their constraints
+    pub(crate) fn solve_joints(&mut self) {
+        for joint_index in 0..self.physics.joints.len() {
+            let joint = &self.physics.joints[joint_index];
+            let Some((origin, target_rotation)) = self.joint_target_pose(&joint.target) else {
+                continue;
+            };
+            let Some(object) = self.virtual_objects.get(joint.object) else {
+                continue;
+            };
+
+            let (position, rotation) = match joint.kind {
+                JointKind::Fixed { local_position, local_rotation } => (
+                    add(origin, quat_rotate(target_rotation, local_position)),
+                    quat_normalize(quat_mul(target_rotation, local_rotation)),
+                ),
+                JointKind::Hinge { pivot, axis, rest_direction, rest_rotation, axial_offset, radius } => {
+                    let pivot = add(origin, quat_rotate(target_rotation, pivot));
+                    let axis = quat_rotate(target_rotation, axis);
+                    let rest_direction = quat_rotate(target_rotation, rest_direction);
+
+                    let offset = sub(object.position, pivot);
+                    let radial = sub(offset, scale(axis, dot(offset, axis)));
+                    let direction = if length(radial) > 1e-6 { normalize(radial) } else { rest_direction };
+                    let angle = signed_angle(rest_direction, direction, axis);
+                    (
+                        add(pivot, add(scale(axis, axial_offset), scale(direction, radius))),
+                        quat_normalize(quat_mul(quat_from_axis_angle(axis, angle), quat_mul(target_rotation, rest_rotation))),
+                    )
+                }
+                JointKind::Spring { .. } => continue,
+            };
+
+            let object = &mut self.virtual_objects[self.physics.joints[joint_index].object];
+            object.position = position;
+            object.rotation = rotation;
+        }
+    }
+
+    // Joints referencing a removed object go with it; later indices shift down
+    pub(crate) fn joints_object_removed(&mut self, index: usize) {
+        let references = |joint: &Joint| joint.object == index || joint.target == JointTarget::Object(index);
+        self.physics.joints.retain(|joint| !references(joint));
+        for joint in &mut self.physics.joints {
+            if joint.object > index {
+                joint.object -= 1;
+            }
+            if let JointTarget::Object(target) = &mut joint.target {
+                if *target > index {
+                    *target -= 1;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn joints_anchor_removed(&mut self, id: &str) {
+        self.physics.joints.retain(|joint| joint.target != JointTarget::Anchor(id.to_string()));
+    }
+
+    // Build a joint from world-space parameters. The object becomes dynamic if it wasn't
+    fn add_joint(&mut self, object: usize, target: JointTarget, make: impl FnOnce(&Self, [f32; 3], [f32; 4]) -> Option<JointKind>) -> Option<i32> {
+        if target == JointTarget::Object(object) || object >= self.virtual_objects.len() {
+            return None;
+        }
+        let target = match target {
+            JointTarget::Anchor(id) => JointTarget::Anchor(self.qualify_id(&id).into_owned()),
+            target => target,
+        };
+        let (origin, rotation) = self.joint_target_pose(&target)?;
+        let kind = make(self, origin, rotation)?;
+
+        let id = self.physics.next_joint_id;
+        self.physics.next_joint_id += 1;
+        self.physics.joints.push(Joint { id, object, target, kind });
+
+        let index = object;
+        let object = &mut self.virtual_objects[index];
+        if object.body.is_none() {
+            object.body = Some(RigidBody::for_object(object, 1.0));
+        }
+        object.camera_attachment = None;
+        self.wake_object(index);
+        Some(id)
+    }
+}
+
+// Into the target's frame
+fn to_local(origin: [f32; 3], rotation: [f32; 4], point: [f32; 3]) -> [f32; 3] {
+    quat_rotate(quat_conjugate(rotation), sub(point, origin))
+}
+
+unsafe fn joint_target(target_object_id: i32, target_anchor_id: *const libc::c_char) -> Option<JointTarget> {
+    if !target_anchor_id.is_null() {
+        let id = CStr::from_ptr(target_anchor_id).to_string_lossy().into_owned();
+        return Some(JointTarget::Anchor(id));
+    }
+    usize::try_from(target_object_id).ok().map(JointTarget::Object)
+}
+
+// Each add_*_joint call joins `object_id` to either an anchor (when `target_anchor_id`
+// is non-null) or the object `target_object_id`. Points and axes are in world space.
+// They return the new joint id, or -1 if an id is invalid
+
+// Hold the object at its current offset from the target
+#[no_mangle]
+pub extern "C" fn add_fixed_joint(object_id: i32, target_object_id: i32, target_anchor_id: *const libc::c_char) -> i32 {
+    let (Ok(index), Some(target)) = (usize::try_from(object_id), unsafe { joint_target(target_object_id, target_anchor_id) }) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        session.add_joint(index, target, |session, origin, rotation| {
+            let object = session.virtual_objects.get(index)?;
+            Some(JointKind::Fixed {
+                local_position: to_local(origin, rotation, object.position),
+                local_rotation: quat_normalize(quat_mul(quat_conjugate(rotation), object.rotation)),
+            })
+        })
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// Let the object swing about `axis` through `pivot`, keeping its current distance
+#[no_mangle]
+pub extern "C" fn add_hinge_joint(
+    object_id: i32,
+    target_object_id: i32,
+    target_anchor_id: *const libc::c_char,
+    pivot_x: f32, pivot_y: f32, pivot_z: f32,
+    axis_x: f32, axis_y: f32, axis_z: f32
+) -> i32 {
+    let (Ok(index), Some(target)) = (usize::try_from(object_id), unsafe { joint_target(target_object_id, target_anchor_id) }) else {
+        return -1;
+    };
+    let (pivot, axis) = ([pivot_x, pivot_y, pivot_z], [axis_x, axis_y, axis_z]);
+    if length(axis) < 1e-6 {
+        return -1;
+    }
+    let axis = normalize(axis);
+
+    with_session(|session| {
+        session.add_joint(index, target, |session, origin, rotation| {
+            let object = session.virtual_objects.get(index)?;
+            let offset = sub(object.position, pivot);
+            let axial_offset = dot(offset, axis);
+            let radial = sub(offset, scale(axis, axial_offset));
+            // An object on the axis has no swing direction; pick any perpendicular
+            let direction = if length(radial) > 1e-6 { normalize(radial) } else { tangent_basis(axis).0 };
+
+            let inverse = quat_conjugate(rotation);
+            Some(JointKind::Hinge {
+                pivot: to_local(origin, rotation, pivot),
+                axis: quat_rotate(inverse, axis),
+                rest_direction: quat_rotate(inverse, direction),
+                rest_rotation: quat_normalize(quat_mul(inverse, object.rotation)),
+                axial_offset,
+                radius: length(radial),
+            })
+        })
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// Pull the object toward `pivot` with a damped spring. A negative rest length uses
+// the current distance. Stiffness is in N/m and damping in N·s/m
+#[no_mangle]
+pub extern "C" fn add_spring_joint(
+    object_id: i32,
+    target_object_id: i32,
+    target_anchor_id: *const libc::c_char,
+    pivot_x: f32, pivot_y: f32, pivot_z: f32,
+    rest_length: f32,
+    stiffness: f32,
+    damping: f32
+) -> i32 {
+    let (Ok(index), Some(target)) = (usize::try_from(object_id), unsafe { joint_target(target_object_id, target_anchor_id) }) else {
+        return -1;
+    };
+    let pivot = [pivot_x, pivot_y, pivot_z];
+
+    with_session(|session| {
+        session.add_joint(index, target, |session, origin, rotation| {
+            let object = session.virtual_objects.get(index)?;
+            Some(JointKind::Spring {
+                pivot: to_local(origin, rotation, pivot),
+                rest_length: if rest_length < 0.0 { length(sub(object.position, pivot)) } else { rest_length },
+                stiffness: stiffness.max(0.0),
+                damping: damping.max(0.0),
+            })
+        })
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "C" fn remove_joint(joint_id: i32) -> bool {
+    with_session(|session| {
+        let before = session.physics.joints.len();
+        session.physics.joints.retain(|joint| joint.id != joint_id);
+        session.physics.joints.len() != before
+    })
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+    const DT: f32 = 1.0 / 60.0;
+
+    // A static target object and a cube to join to it
+    fn pair(target: [f32; 3], object: [f32; 3]) -> ARSession {
+        let mut session = ARSession::new();
+        session.place_object(ARObjectType::Cube, target, IDENTITY).unwrap();
+        session.place_object(ARObjectType::Cube, object, IDENTITY).unwrap();
+        session
+    }
+
+    fn advance(session: &mut ARSession, frames: usize) {
+        for _ in 0..frames {
+            session.step_physics(DT);
+        }
+    }
+
+    #[test]
+    fn objects_cannot_join_themselves() {
+        let mut session = pair([0.0; 3], [1.0, 0.0, 0.0]);
+        assert!(session.add_joint(1, JointTarget::Object(1), |_, _, _| Some(JointKind::Fixed { local_position: [0.0; 3], local_rotation: IDENTITY })).is_none());
+        assert!(session.virtual_objects[1].body.is_none());
+    }
+
+    #[test]
+    fn fixed_joints_follow_their_target() {
+        let mut session = pair([0.0, 1.0, 0.0], [0.3, 1.0, 0.0]);
+        let joint = session.add_joint(1, JointTarget::Object(0), |_, _, _| Some(JointKind::Fixed { local_position: [0.3, 0.0, 0.0], local_rotation: IDENTITY }));
+        assert!(joint.is_some());
+        assert!(session.virtual_objects[1].body.is_some());
+
+        let turn = quat_from_axis_angle([0.0, 1.0, 0.0], std::f32::consts::FRAC_PI_2);
+        session.virtual_objects[0].position = [1.0, 1.0, 1.0];
+        session.virtual_objects[0].rotation = turn;
+        advance(&mut session, 10);
+
+        let object = &session.virtual_objects[1];
+        let expected = add([1.0, 1.0, 1.0], quat_rotate(turn, [0.3, 0.0, 0.0]));
+        assert!(length(sub(object.position, expected)) < 1e-4, "at {:?}", object.position);
+        assert!(dot(quat_rotate(object.rotation, [1.0, 0.0, 0.0]), quat_rotate(turn, [1.0, 0.0, 0.0])) > 0.9999);
+    }
+
+    #[test]
+    fn hinged_objects_swing_at_a_fixed_radius() {
+        let mut session = pair([0.0, 2.0, 0.0], [0.5, 2.0, 0.0]);
+        session.add_joint(1, JointTarget::Object(0), |_, _, _| Some(JointKind::Hinge {
+            pivot: [0.0; 3],
+            axis: [0.0, 0.0, 1.0],
+            rest_direction: [1.0, 0.0, 0.0],
+            rest_rotation: IDENTITY,
+            axial_offset: 0.0,
+            radius: 0.5,
+        }))
+        .unwrap();
+
+        for _ in 0..30 {
+            advance(&mut session, 1);
+            let position = session.virtual_objects[1].position;
+            assert!((length(sub(position, [0.0, 2.0, 0.0])) - 0.5).abs() < 1e-4);
+            assert!(position[2].abs() < 1e-4);
+        }
+        assert!(session.virtual_objects[1].position[1] < 1.8);
+    }
+
+    #[test]
+    fn springs_settle_at_their_rest_length() {
+        let mut session = pair([0.0; 3], [1.0, 0.0, 0.0]);
+        session.physics.gravity = [0.0; 3];
+        session.add_joint(1, JointTarget::Object(0), |_, _, _| Some(JointKind::Spring { pivot: [0.0; 3], rest_length: 0.5, stiffness: 20.0, damping: 4.0 }))
+            .unwrap();
+
+        advance(&mut session, 300);
+
+        let distance = length(session.virtual_objects[1].position);
+        assert!((distance - 0.5).abs() < 0.01, "settled at {}", distance);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::f32::consts::PI;
 use std::sync::{Arc, Mutex};
 use tracing::{debug, info};
@@ -8,6 +9,126 @@
 #[cfg(target_os = "ios")]
 use metal::{Device, CommandQueue};
 
+pub mod alignment;
+pub mod analytics;
+pub mod anchors;
+pub mod archetypes;
+pub mod camera_attachment;
+pub mod camera_slot;
+pub mod cameras;
+pub mod clock;
+pub mod color_grading;
+#[cfg(feature = "physics")]
+pub mod contacts;
+pub mod coverage;
+pub mod decals;
+pub mod determinism;
+#[cfg(feature = "environment")]
+pub mod environment;
+pub mod events;
+pub mod fading;
+#[cfg(feature = "physics")]
+pub mod force_fields;
+pub mod gaze;
+pub mod gestures;
+pub mod handoff;
+pub mod ingestion;
+#[cfg(feature = "physics")]
+pub mod joints;
+mod math;
+#[cfg(feature = "reconstruction")]
+pub mod mesh_store;
+mod metrics;
+pub mod namespaces;
+pub mod occlusion;
+#[cfg(feature = "offscreen")]
+pub mod offscreen;
+pub mod ops;
+#[cfg(feature = "physics")]
+pub mod physics;
+pub mod plane_expiry;
+#[cfg(feature = "reconstruction")]
+pub mod plane_extraction;
+#[cfg(feature = "reconstruction")]
+pub mod pointcloud;
+#[cfg(all(target_os = "ios", feature = "reconstruction"))]
+mod pointcloud_metal;
+pub mod pose_filter;
+pub mod pose_graph;
+pub mod primitives;
+pub mod quotas;
+pub mod render;
+mod rng;
+pub mod scan_quality;
+pub mod scatter;
+pub mod session_report;
+pub mod shared_buffers;
+pub mod sim;
+#[cfg(feature = "physics")]
+pub mod sleep;
+pub mod snapshot;
+pub mod stairs;
+pub mod stabilizer;
+pub mod surfaces;
+#[cfg(feature = "text")]
+pub mod text_mesh;
+pub mod trajectories;
+#[cfg(feature = "uniffi")]
+pub mod uniffi_api;
+pub mod visibility;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+pub mod world_origin;
+pub mod zones;
+
+use alignment::AlignmentState;
+use analytics::AnalyticsEvent;
+use anchors::{ARAnchor, AnchorAttachment, DriftConfig};
+use archetypes::ArchetypeRegistry;
+use camera_attachment::CameraAttachment;
+use cameras::{CameraFeature, CameraId, CameraStream};
+use clock::SessionClock;
+use color_grading::ColorAnalysis;
+#[cfg(feature = "reconstruction")]
+use coverage::MeshCoverage;
+use coverage::PlaneCoverage;
+use decals::DecalState;
+use determinism::Determinism;
+#[cfg(feature = "environment")]
+use environment::EnvironmentMap;
+use events::{EventQueue, SessionEvent};
+use fading::FadePolicy;
+use gaze::{GazeState, GazeTarget};
+use gestures::GestureState;
+use handoff::HandoffState;
+use ingestion::IngestionConfig;
+#[cfg(feature = "reconstruction")]
+use mesh_store::MeshStore;
+use metrics::SessionMetrics;
+use namespaces::IdNamespace;
+use occlusion::PersonMatte;
+#[cfg(feature = "physics")]
+use physics::{PhysicsWorld, RigidBody};
+use plane_expiry::PlaneExpiryConfig;
+#[cfg(feature = "reconstruction")]
+use plane_extraction::PlaneExtractionConfig;
+#[cfg(feature = "reconstruction")]
+use pointcloud::{CloudPoint, PointCloudConfig};
+use pose_filter::{PoseFilter, PoseSample};
+use pose_graph::PoseGraph;
+use primitives::Primitive;
+use quotas::{QuotaState, Resource};
+use scan_quality::ScanState;
+use serde::{Deserialize, Serialize};
+use session_report::ReportState;
+use stabilizer::Stabilizer;
+#[cfg(feature = "text")]
+use text_mesh::TextLabel;
+#[cfg(feature = "physics")]
+use surfaces::SurfaceMaterial;
+use trajectories::{Trajectory, TrajectoryState};
+use zones::{ZoneState, ZoneVisibility};
+
 // Required by iOS for FFI
 #[no_mangle]
 pub extern "C" fn ios_main() {
@@ -21,9 +142,58 @@ pub extern "C" fn ios_main() {
 // Simple struct to hold AR state
 struct ARSession {
     initialized: bool,
+    clock: SessionClock,
     camera_position: [f32; 3],
+    camera_rotation: [f32; 4], // Quaternion
+    camera_filter: PoseFilter,
+    cameras: [CameraStream; CameraId::COUNT],
+    color_analysis: ColorAnalysis,
+    // Set while environment capture is on
+    #[cfg(feature = "environment")]
+    environment: Option<EnvironmentMap>,
+    person_matte: Option<PersonMatte>,
     detected_planes: Vec<ARPlane>,
+    // Scan coverage by plane id
+    coverage: HashMap<String, PlaneCoverage>,
+    #[cfg(feature = "reconstruction")]
+    mesh_coverage: MeshCoverage,
+    scan: ScanState,
     virtual_objects: Vec<ARObject>,
+    archetypes: ArchetypeRegistry,
+    anchors: Vec<ARAnchor>,
+    anchor_drift: DriftConfig,
+    decals: DecalState,
+    // Set while deterministic mode is on
+    determinism: Option<Determinism>,
+    events: EventQueue,
+    gaze: GazeState,
+    gestures: GestureState,
+    alignment: AlignmentState,
+    #[cfg(feature = "physics")]
+    physics: PhysicsWorld,
+    metrics: SessionMetrics,
+    report: ReportState,
+    handoff: HandoffState,
+    ingestion: IngestionConfig,
+    id_namespace: IdNamespace,
+    trajectories: TrajectoryState,
+    zones: ZoneState,
+    pose_graph: PoseGraph,
+    plane_expiry: PlaneExpiryConfig,
+    quotas: QuotaState,
+    // Whether camera poses go to the process-wide camera slot. Only the FFI session
+    // publishes; previews and sessions rebuilt from snapshots keep to themselves
+    publishes_camera: bool,
+    #[cfg(feature = "reconstruction")]
+    point_cloud: Vec<CloudPoint>,
+    #[cfg(feature = "reconstruction")]
+    point_cloud_timestamp: Option<f64>,
+    #[cfg(feature = "reconstruction")]
+    point_cloud_config: PointCloudConfig,
+    #[cfg(feature = "reconstruction")]
+    plane_extraction_config: PlaneExtractionConfig,
+    #[cfg(feature = "reconstruction")]
+    mesh: MeshStore,
 }
 
 // Structure for detected AR planes
@@ -32,6 +202,70 @@ struct ARPlane {
     center: [f32; 3],
     extent: [f32; 2],
     normal: [f32; 3],
+    source: PlaneSource,
+    classification: PlaneClassification,
+    // Overrides the classification's default material
+    #[cfg(feature = "physics")]
+    material: Option<SurfaceMaterial>,
+    // Session time when the plane was first seen, last changed and last in view
+    detected_at: f64,
+    updated_at: f64,
+    observed_at: f64,
+    // Left alone long enough to be flagged (see plane_expiry.rs)
+    stale: bool,
+}
+
+impl ARPlane {
+    // Whether `point` projects inside the plane's rectangle
+    fn within_extent(&self, point: [f32; 3]) -> bool {
+        let offset = math::sub(point, self.center);
+        let (tangent, bitangent) = math::tangent_basis(self.normal);
+        math::dot(offset, tangent).abs() <= self.extent[0] * 0.5
+            && math::dot(offset, bitangent).abs() <= self.extent[1] * 0.5
+    }
+}
+
+// Where a plane came from: reported by ARKit, or fitted from depth data by us
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaneSource {
+    #[default]
+    Native,
+    Derived,
+}
+
+// What a plane is, as ARKit classifies it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaneClassification {
+    #[default]
+    None = 0,
+    Floor = 1,
+    Wall = 2,
+    Ceiling = 3,
+    Table = 4,
+    Seat = 5,
+    Window = 6,
+    Door = 7,
+}
+
+impl PlaneClassification {
+    #[cfg_attr(not(feature = "physics"), allow(dead_code))]
+    const COUNT: usize = 8;
+
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(PlaneClassification::None),
+            1 => Some(PlaneClassification::Floor),
+            2 => Some(PlaneClassification::Wall),
+            3 => Some(PlaneClassification::Ceiling),
+            4 => Some(PlaneClassification::Table),
+            5 => Some(PlaneClassification::Seat),
+            6 => Some(PlaneClassification::Window),
+            7 => Some(PlaneClassification::Door),
+            _ => None,
+        }
+    }
 }
 
 // Structure for virtual objects in AR
@@ -39,25 +273,302 @@ struct ARObject {
     id: String,
     position: [f32; 3],
     rotation: [f32; 4], // Quaternion
+    // Uniform scale relative to the default object size
+    scale: f32,
     object_type: ARObjectType,
+    anchor: Option<AnchorAttachment>,
+    camera_attachment: Option<CameraAttachment>,
+    stabilizer: Option<Stabilizer>,
+    gaze: Option<GazeTarget>,
+    fade: Option<FadePolicy>,
+    // From the fade policy as of the last frame; 1 without one
+    opacity: f32,
+    // From visibility zones as of the last frame
+    zone_visibility: ZoneVisibility,
+    // Dynamic bodies are moved by the physics step; others stay put
+    #[cfg(feature = "physics")]
+    body: Option<RigidBody>,
+    // Set while the host is tracking the object's path
+    trajectory: Option<Trajectory>,
+    // Pose graph keyframe it was placed at, for loop closure corrections
+    keyframe: Option<i32>,
+    // Render layer bits, and where lower levels of detail start (see archetypes.rs)
+    layers: u32,
+    lod_distances: Vec<f32>,
+}
+
+impl ARObject {
+    fn new(id: String, object_type: ARObjectType, position: [f32; 3], rotation: [f32; 4]) -> Self {
+        ARObject {
+            id,
+            position,
+            rotation,
+            scale: 1.0,
+            object_type,
+            anchor: None,
+            camera_attachment: None,
+            stabilizer: None,
+            gaze: None,
+            fade: None,
+            opacity: 1.0,
+            zone_visibility: ZoneVisibility::Visible,
+            #[cfg(feature = "physics")]
+            body: None,
+            trajectory: None,
+            keyframe: None,
+            layers: archetypes::DEFAULT_LAYERS,
+            lod_distances: Vec::new(),
+        }
+    }
+
+    // Whether the physics step moves the object
+    fn is_dynamic(&self) -> bool {
+        #[cfg(feature = "physics")]
+        return self.body.is_some();
+        #[cfg(not(feature = "physics"))]
+        false
+    }
+
+    // Radius of a sphere enclosing the object's mesh
+    fn bounding_radius(&self) -> f32 {
+        let half_size = render::DEFAULT_OBJECT_SIZE * self.scale * 0.5;
+        match &self.object_type {
+            ARObjectType::Sphere => half_size,
+            ARObjectType::Primitive(primitive) => primitive.bounding_radius() * self.scale,
+            #[cfg(feature = "text")]
+            ARObjectType::Text(label) => math::length(label.half_extents) * self.scale,
+            _ => half_size * 3.0f32.sqrt(),
+        }
+    }
 }
 
 // Types of AR objects
 enum ARObjectType {
     Cube,
     Sphere,
+    Primitive(Primitive),
+    #[cfg(feature = "text")]
+    Text(Box<TextLabel>),
     Custom(String),
 }
 
+impl ARObjectType {
+    // Map the integer type codes used across FFI
+    fn from_code(code: i32) -> Self {
+        match code {
+            0 => ARObjectType::Cube,
+            1 => ARObjectType::Sphere,
+            _ => ARObjectType::Custom(format!("custom_{}", code)),
+        }
+    }
+
+    // Kind reported to analytics; custom names could identify content, so they aren't
+    fn analytics_kind(&self) -> &'static str {
+        match self {
+            ARObjectType::Cube => "cube",
+            ARObjectType::Sphere => "sphere",
+            ARObjectType::Primitive(primitive) => primitive.name(),
+            #[cfg(feature = "text")]
+            ARObjectType::Text(_) => "text",
+            ARObjectType::Custom(_) => "custom",
+        }
+    }
+}
+
+impl ARSession {
+    fn new() -> Self {
+        ARSession {
+            initialized: true,
+            clock: SessionClock::new(),
+            camera_position: [0.0, 0.0, 0.0],
+            camera_rotation: [0.0, 0.0, 0.0, 1.0],
+            camera_filter: PoseFilter::default(),
+            cameras: Default::default(),
+            color_analysis: ColorAnalysis::default(),
+            #[cfg(feature = "environment")]
+            environment: None,
+            person_matte: None,
+            detected_planes: Vec::new(),
+            coverage: HashMap::new(),
+            #[cfg(feature = "reconstruction")]
+            mesh_coverage: MeshCoverage::default(),
+            scan: ScanState::default(),
+            virtual_objects: Vec::new(),
+            archetypes: ArchetypeRegistry::default(),
+            anchors: Vec::new(),
+            anchor_drift: DriftConfig::default(),
+            decals: DecalState::default(),
+            determinism: None,
+            events: EventQueue::default(),
+            gaze: GazeState::default(),
+            gestures: GestureState::default(),
+            alignment: AlignmentState::default(),
+            #[cfg(feature = "physics")]
+            physics: PhysicsWorld::default(),
+            metrics: SessionMetrics::default(),
+            report: ReportState::default(),
+            handoff: HandoffState::default(),
+            ingestion: IngestionConfig::default(),
+            id_namespace: IdNamespace::default(),
+            trajectories: TrajectoryState::default(),
+            zones: ZoneState::default(),
+            pose_graph: PoseGraph::default(),
+            plane_expiry: PlaneExpiryConfig::default(),
+            quotas: QuotaState::default(),
+            publishes_camera: false,
+            #[cfg(feature = "reconstruction")]
+            point_cloud: Vec::new(),
+            #[cfg(feature = "reconstruction")]
+            point_cloud_timestamp: None,
+            #[cfg(feature = "reconstruction")]
+            point_cloud_config: PointCloudConfig::default(),
+            #[cfg(feature = "reconstruction")]
+            plane_extraction_config: PlaneExtractionConfig::default(),
+            #[cfg(feature = "reconstruction")]
+            mesh: MeshStore::default(),
+        }
+    }
+
+    // Position-only update, timestamped with the session clock
+    fn set_camera_position(&mut self, position: [f32; 3]) {
+        let timestamp = self.clock.now();
+        self.set_camera_pose(timestamp, position, self.camera_rotation);
+    }
+
+    // Samples no newer than the last one taken arrived late (or twice) and are dropped
+    // whole, so the raw pose, keyframes and published pose only ever move forward in time
+    fn set_camera_pose(&mut self, timestamp: f64, position: [f32; 3], rotation: [f32; 4]) {
+        if self.camera_filter.last_timestamp().is_some_and(|last| timestamp <= last) {
+            return;
+        }
+        self.camera_position = position;
+        self.camera_rotation = rotation;
+        let sample = PoseSample { timestamp, position, rotation };
+        self.camera_filter.update(sample);
+        self.record_camera_pose(CameraFeature::WorldTracking.stream(), sample);
+        self.record_camera_trajectory(sample);
+        self.update_pose_graph(sample);
+        self.publish_camera(sample);
+        self.metrics.camera_updates += 1;
+    }
+
+    // Number for a generated plane id: planes added so far, so ids stay unique after
+    // planes expire
+    fn plane_number(&self) -> u64 {
+        self.metrics.planes_added + self.metrics.derived_planes_added
+    }
+
+    // Add a plane, generating an id if none was given, or update the plane with that id
+    // when ARKit reports it again. The id gets the default source prefix if it has none.
+    // Returns false if the plane is new and the plane quota is full
+    fn add_plane(&mut self, id: Option<String>, center: [f32; 3], extent: [f32; 2], normal: [f32; 3]) -> bool {
+        let id = id.unwrap_or_else(|| format!("plane_{}", self.plane_number()));
+        let id = self.qualify_id(&id).into_owned();
+        let now = self.clock.now();
+        if let Some(plane) = self.detected_planes.iter_mut().find(|plane| plane.id == id) {
+            plane.center = center;
+            plane.extent = extent;
+            plane.normal = normal;
+            plane.updated_at = now;
+            plane.observed_at = now;
+            if plane.stale {
+                plane.stale = false;
+                self.events.push(SessionEvent::PlaneRefreshed { plane_id: id });
+            }
+            return true;
+        }
+
+        if !self.admit(Resource::Planes, self.detected_planes.len() as u64 + 1) {
+            return false;
+        }
+        self.detected_planes.push(ARPlane {
+            id,
+            center,
+            extent,
+            normal,
+            source: PlaneSource::Native,
+            classification: PlaneClassification::None,
+            #[cfg(feature = "physics")]
+            material: None,
+            detected_at: now,
+            updated_at: now,
+            observed_at: now,
+            stale: false,
+        });
+        self.metrics.planes_added += 1;
+        true
+    }
+
+    // Place an object and return its index, or None if the object quota is full
+    fn place_object(&mut self, object_type: ARObjectType, position: [f32; 3], rotation: [f32; 4]) -> Option<usize> {
+        if !self.admit(Resource::Objects, self.virtual_objects.len() as u64 + 1) {
+            return None;
+        }
+        let index = self.virtual_objects.len();
+        analytics::record(AnalyticsEvent::ObjectPlaced { kind: object_type.analytics_kind() });
+        let mut object = ARObject::new(format!("object_{}", index), object_type, position, rotation);
+        object.keyframe = self.pose_graph.latest();
+        self.virtual_objects.push(object);
+        self.metrics.objects_placed += 1;
+        Some(index)
+    }
+
+    // Remove an object by index. This shifts the indices of later objects
+    fn remove_object(&mut self, index: usize) -> bool {
+        if index < self.virtual_objects.len() {
+            self.virtual_objects.remove(index);
+            self.gaze_object_removed(index);
+            self.gestures.object_removed(index);
+            #[cfg(feature = "physics")]
+            self.joints_object_removed(index);
+            self.metrics.objects_removed += 1;
+            analytics::record(AnalyticsEvent::ObjectRemoved);
+            true
+        } else {
+            self.metrics.failed_removals += 1;
+            false
+        }
+    }
+
+    // Advance per-frame simulation state by `dt` seconds
+    fn advance(&mut self, dt: f32) {
+        self.extrapolate_anchors(dt);
+        self.follow_camera(dt);
+        for object in &mut self.virtual_objects {
+            object.stabilize(dt);
+        }
+        #[cfg(feature = "physics")]
+        self.step_physics(dt);
+        self.update_gaze(dt);
+        self.update_fading();
+        self.update_zones();
+        self.update_coverage(dt);
+        self.update_plane_expiry();
+        self.update_scan_quality();
+        self.record_object_trajectories();
+        #[cfg(feature = "reconstruction")]
+        self.evict_mesh_chunks();
+    }
+
+    // Straight-line distance between two placed objects
+    fn object_distance(&self, a: usize, b: usize) -> Option<f32> {
+        let a = self.virtual_objects.get(a)?;
+        let b = self.virtual_objects.get(b)?;
+        Some(math::length(math::sub(a.position, b.position)))
+    }
+}
+
 // Initialize the AR session
 fn initialize_ar_session() {
-    let session = ARSession {
-        initialized: true,
-        camera_position: [0.0, 0.0, 0.0],
-        detected_planes: Vec::new(),
-        virtual_objects: Vec::new(),
-    };
-    
+    let mut session = ARSession::new();
+    session.publishes_camera = true;
+    analytics::session_started();
+
+    // Stop the outgoing session publishing, then reset the slot before the new session
+    // is visible, so readers never see the old session's camera after the swap
+    with_session(|previous| previous.publishes_camera = false);
+    camera_slot::reset();
+
     // Store in global state
     unsafe {
         AR_SESSION = Some(Arc::new(Mutex::new(session)));
@@ -66,56 +577,83 @@ fn initialize_ar_session() {
     info!("AR session initialized from Rust");
 }
 
+// Run a closure against the global session, if one is initialized
+fn with_session<R>(f: impl FnOnce(&mut ARSession) -> R) -> Option<R> {
+    unsafe {
+        let session = AR_SESSION.as_ref()?;
+        let mut session_lock = session.lock().ok()?;
+        Some(f(&mut session_lock))
+    }
+}
+
 // Update the AR camera position
 #[no_mangle]
 pub extern "C" fn update_camera_position(x: f32, y: f32, z: f32) {
     unsafe {
         if let Some(session) = &AR_SESSION {
             if let Ok(mut session_lock) = session.lock() {
-                session_lock.camera_position = [x, y, z];
+                session_lock.set_camera_position([x, y, z]);
             }
         }
     }
 }
 
-// Add a detected plane
+// Update the full camera pose. `timestamp` is the frame time in seconds on the host
+// clock (e.g. ARFrame.timestamp) and drives the pose smoothing filter; see clock.rs
+#[no_mangle]
+pub extern "C" fn update_camera_pose(
+    timestamp: f64,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32
+) {
+    with_session(|session| {
+        let timestamp = session.clock.map_host_time(timestamp);
+        session.set_camera_pose(timestamp, [pos_x, pos_y, pos_z], [rot_x, rot_y, rot_z, rot_w]);
+    });
+}
+
+// Add a detected plane, or update the one with the same id. Returns false without a
+// session or if the plane is new and the plane quota is full
 #[no_mangle]
 pub extern "C" fn add_detected_plane(
     id_ptr: *const libc::c_char,
     center_x: f32, center_y: f32, center_z: f32,
     width: f32, height: f32,
     normal_x: f32, normal_y: f32, normal_z: f32
-) {
+) -> bool {
     unsafe {
         if let Some(session) = &AR_SESSION {
             if let Ok(mut session_lock) = session.lock() {
                 // Convert C string to Rust string
                 let id = if !id_ptr.is_null() {
                     let c_str = std::ffi::CStr::from_ptr(id_ptr);
-                    c_str.to_string_lossy().into_owned()
+                    Some(c_str.to_string_lossy().into_owned())
                 } else {
-                    format!("plane_{}", session_lock.detected_planes.len())
-                };
-                
-                // Create new plane
-                let plane = ARPlane {
-                    id,
-                    center: [center_x, center_y, center_z],
-                    extent: [width, height],
-                    normal: [normal_x, normal_y, normal_z],
+                    None
                 };
                 
                 // Add to session
-                session_lock.detected_planes.push(plane);
+                let added = session_lock.add_plane(
+                    id,
+                    [center_x, center_y, center_z],
+                    [width, height],
+                    [normal_x, normal_y, normal_z],
+                );
                 
-                info!("Added plane: center=[{}, {}, {}], extent=[{}, {}]", 
-                    center_x, center_y, center_z, width, height);
+                if added {
+                    info!("Added plane: center=[{}, {}, {}], extent=[{}, {}]", 
+                        center_x, center_y, center_z, width, height);
+                }
+                return added;
             }
         }
     }
+    
+    false
 }
 
-// Place a virtual object in AR space
+// Place a virtual object in AR space. Returns the object id, AR_QUOTA_EXCEEDED if the
+// object quota is full, or -1 without a session
 #[no_mangle]
 pub extern "C" fn place_virtual_object(
     object_type: i32,
@@ -125,24 +663,15 @@ pub extern "C" fn place_virtual_object(
     unsafe {
         if let Some(session) = &AR_SESSION {
             if let Ok(mut session_lock) = session.lock() {
-                // Determine object type
-                let object_type = match object_type {
-                    0 => ARObjectType::Cube,
-                    1 => ARObjectType::Sphere,
-                    _ => ARObjectType::Custom(format!("custom_{}", object_type)),
-                };
-                
-                // Create new object
-                let object = ARObject {
-                    id: format!("object_{}", session_lock.virtual_objects.len()),
-                    position: [pos_x, pos_y, pos_z],
-                    rotation: [rot_x, rot_y, rot_z, rot_w],
-                    object_type,
-                };
-                
                 // Add to session
-                let object_id = session_lock.virtual_objects.len() as i32;
-                session_lock.virtual_objects.push(object);
+                let Some(object_id) = session_lock.place_object(
+                    ARObjectType::from_code(object_type),
+                    [pos_x, pos_y, pos_z],
+                    [rot_x, rot_y, rot_z, rot_w],
+                ) else {
+                    return quotas::AR_QUOTA_EXCEEDED;
+                };
+                let object_id = object_id as i32;
                 
                 info!("Placed object {} at position [{}, {}, {}]", 
                     object_id, pos_x, pos_y, pos_z);
@@ -162,9 +691,9 @@ pub extern "C" fn remove_virtual_object(object_id: i32) -> bool {
     unsafe {
         if let Some(session) = &AR_SESSION {
             if let Ok(mut session_lock) = session.lock() {
-                if object_id >= 0 && (object_id as usize) < session_lock.virtual_objects.len() {
-                    // Remove the object (this shifts array indices, but Swift will maintain its own mapping)
-                    session_lock.virtual_objects.remove(object_id as usize);
+                // Removal shifts array indices, but Swift will maintain its own mapping
+                let index = usize::try_from(object_id).unwrap_or(usize::MAX);
+                if session_lock.remove_object(index) {
                     println!("Removed object {}", object_id);
                     return true;
                 }
@@ -175,6 +704,92 @@ pub extern "C" fn remove_virtual_object(object_id: i32) -> bool {
     false
 }
 
+// Copy a plane's geometry into the (optional) output arrays and return its source:
+// 0 for ARKit planes, 1 for planes derived from depth data, or -1 for a bad index
+#[no_mangle]
+pub extern "C" fn get_plane_info(
+    index: i32,
+    out_center: *mut f32,
+    out_extent: *mut f32,
+    out_normal: *mut f32,
+) -> i32 {
+    let Ok(index) = usize::try_from(index) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        let plane = session.detected_planes.get(index)?;
+        unsafe {
+            if !out_center.is_null() {
+                std::slice::from_raw_parts_mut(out_center, 3).copy_from_slice(&plane.center);
+            }
+            if !out_extent.is_null() {
+                std::slice::from_raw_parts_mut(out_extent, 2).copy_from_slice(&plane.extent);
+            }
+            if !out_normal.is_null() {
+                std::slice::from_raw_parts_mut(out_normal, 3).copy_from_slice(&plane.normal);
+            }
+        }
+        Some(plane.source as i32)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// Measure the distance in meters between two placed objects, or -1 if either id is invalid
+#[no_mangle]
+pub extern "C" fn get_object_distance(object_a: i32, object_b: i32) -> f32 {
+    let (Ok(a), Ok(b)) = (usize::try_from(object_a), usize::try_from(object_b)) else {
+        return -1.0;
+    };
+
+    with_session(|session| session.object_distance(a, b))
+        .flatten()
+        .unwrap_or(-1.0)
+}
+
+// Advance per-frame state (object stabilization, physics, gaze dwell, scan coverage) by
+// `dt` seconds, in fixed steps in deterministic mode. Call once per rendered frame, after
+// the frame's camera and anchor updates
+#[no_mangle]
+pub extern "C" fn advance_frame(dt: f32) {
+    if !dt.is_finite() || dt <= 0.0 {
+        return;
+    }
+    with_session(|session| {
+        session.advance_frame_time(dt);
+        session.record_frame(dt);
+    });
+}
+
+// Copy an object's position (3 floats), rotation (4) and scale into the outputs, any
+// of which may be null. Returns false for an invalid id
+#[no_mangle]
+pub extern "C" fn get_object_transform(
+    object_id: i32,
+    out_position: *mut f32,
+    out_rotation: *mut f32,
+    out_scale: *mut f32,
+) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let transform = session.object_transform(index)?;
+        unsafe {
+            pose_filter::write_out(out_position, transform.position);
+            pose_filter::write_out(out_rotation, transform.rotation);
+            if !out_scale.is_null() {
+                *out_scale = transform.scale;
+            }
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
 // Get statistics about the AR session (for debugging)
 #[no_mangle]
 pub extern "C" fn get_session_stats(
@@ -205,14 +820,14 @@ pub extern "C" fn setup_metal_context(device_ptr: *mut std::ffi::c_void) -> bool
             return false;
         }
         
-        // Convert the raw pointer to a Metal device
-        let device_obj = device_ptr as *mut objc::runtime::Object;
-        
         println!("Received Metal device from Swift");
         
-        // In a real app, you would store this device for later use
-        
-        true
+        // Build the compute pipelines used for point cloud processing, the only user
+        // of Metal so far
+        #[cfg(feature = "reconstruction")]
+        return pointcloud_metal::install(device_ptr);
+        #[cfg(not(feature = "reconstruction"))]
+        return false;
     }
 }
 
@@ -0,0 +1,136 @@
+// Small vector/quaternion helpers over the plain arrays used by the session model.
+// Quaternions are stored as [x, y, z, w] to match the FFI layout
+
+pub(crate) fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub(crate) fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+pub(crate) fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+// Returns the zero vector for degenerate input rather than NaNs
+pub(crate) fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = length(a);
+    if len > f32::EPSILON {
+        scale(a, 1.0 / len)
+    } else {
+        [0.0; 3]
+    }
+}
+
+pub(crate) fn quat_conjugate(q: [f32; 4]) -> [f32; 4] {
+    [-q[0], -q[1], -q[2], q[3]]
+}
+
+// Rotate a vector by a unit quaternion
+pub(crate) fn quat_rotate(q: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+    let u = [q[0], q[1], q[2]];
+    let t = scale(cross(u, v), 2.0);
+    add(add(v, scale(t, q[3])), cross(u, t))
+}
+
+// Build an orthonormal (tangent, bitangent) pair perpendicular to a unit normal
+pub(crate) fn tangent_basis(normal: [f32; 3]) -> ([f32; 3], [f32; 3]) {
+    let reference = if normal[1].abs() < 0.99 { [0.0, 1.0, 0.0] } else { [0.0, 0.0, 1.0] };
+    let tangent = normalize(cross(reference, normal));
+    let bitangent = cross(normal, tangent);
+    (tangent, bitangent)
+}
+
+pub(crate) fn quat_mul(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [
+        a[3] * b[0] + a[0] * b[3] + a[1] * b[2] - a[2] * b[1],
+        a[3] * b[1] - a[0] * b[2] + a[1] * b[3] + a[2] * b[0],
+        a[3] * b[2] + a[0] * b[1] - a[1] * b[0] + a[2] * b[3],
+        a[3] * b[3] - a[0] * b[0] - a[1] * b[1] - a[2] * b[2],
+    ]
+}
+
+// Rotation of `angle` radians about a unit `axis`
+pub(crate) fn quat_from_axis_angle(axis: [f32; 3], angle: f32) -> [f32; 4] {
+    let (sin, cos) = (angle * 0.5).sin_cos();
+    [axis[0] * sin, axis[1] * sin, axis[2] * sin, cos]
+}
+
+pub(crate) fn quat_dot(a: [f32; 4], b: [f32; 4]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+// Falls back to identity for degenerate input
+pub(crate) fn quat_normalize(q: [f32; 4]) -> [f32; 4] {
+    let len = quat_dot(q, q).sqrt();
+    if len > f32::EPSILON {
+        q.map(|c| c / len)
+    } else {
+        [0.0, 0.0, 0.0, 1.0]
+    }
+}
+
+// Spherical interpolation along the shortest arc
+pub(crate) fn quat_slerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut cos = quat_dot(a, b);
+    let b = if cos < 0.0 {
+        cos = -cos;
+        b.map(|c| -c)
+    } else {
+        b
+    };
+
+    // Nearly parallel: lerp is accurate and avoids dividing by ~0
+    if cos > 0.9995 {
+        return quat_normalize([
+            a[0] + (b[0] - a[0]) * t,
+            a[1] + (b[1] - a[1]) * t,
+            a[2] + (b[2] - a[2]) * t,
+            a[3] + (b[3] - a[3]) * t,
+        ]);
+    }
+
+    let angle = cos.acos();
+    let sin = angle.sin();
+    let wa = ((1.0 - t) * angle).sin() / sin;
+    let wb = (t * angle).sin() / sin;
+    [
+        a[0] * wa + b[0] * wb,
+        a[1] * wa + b[1] * wb,
+        a[2] * wa + b[2] * wb,
+        a[3] * wa + b[3] * wb,
+    ]
+}
+
+// Rotation from `from` to `to` as an axis scaled by the angle in radians
+pub(crate) fn quat_delta_axis_angle(from: [f32; 4], to: [f32; 4]) -> [f32; 3] {
+    let mut delta = quat_mul(to, quat_conjugate(from));
+    if delta[3] < 0.0 {
+        delta = delta.map(|c| -c);
+    }
+    let axis = [delta[0], delta[1], delta[2]];
+    let sin_half = length(axis);
+    if sin_half < 1e-6 {
+        // Small-angle approximation: angle * axis ~= 2 * xyz
+        return scale(axis, 2.0);
+    }
+    let angle = 2.0 * sin_half.atan2(delta[3]);
+    scale(axis, angle / sin_half)
+}
@@ -0,0 +1,637 @@
+// Reconstruction mesh from the host's scene reconstruction (ARKit ARMeshAnchor), kept in
+// a spatial hash of cubic chunks so queries only touch the chunks they pass through.
+// Each triangle is filed under every chunk its bounding box overlaps, tagged with its
+// anchor and its index in that anchor's mesh so a query can tell copies apart.
+// Resubmitting an anchor replaces its triangles; ARKit reports anchors again as their
+// geometry changes, so the store follows the reconstruction as it refines.
+//
+// Chunks farther than the eviction distance from the camera are dropped every frame, and
+// triangles submitted beyond it are not filed. Geometry returns when the host next
+// submits its anchor with the camera nearby. Raycasts walk the chunks along the ray in
+// order, so a hit in a near chunk ends the walk
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+
+use tracing::debug;
+
+use crate::analytics::{self, Feature};
+use crate::math::{add, cross, dot, length, normalize, scale, sub};
+use crate::pointcloud::read_transform;
+use crate::with_session;
+
+// Triangles whose bounding box spans more chunks than this along an axis are rejected
+// as degenerate input; real mesh faces are a few centimeters across
+const MAX_TRIANGLE_CHUNKS: i32 = 16;
+
+// Tunables for the mesh store, stored on the session
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MeshStoreConfig {
+    // Chunk edge length in meters
+    pub chunk_size: f32,
+    // Chunks whose center is farther than this from the camera are evicted; 0 keeps
+    // everything
+    pub evict_distance: f32,
+}
+
+impl Default for MeshStoreConfig {
+    fn default() -> Self {
+        MeshStoreConfig {
+            chunk_size: 0.5,
+            evict_distance: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MeshTriangle {
+    pub anchor: u32,
+    pub index: u32,
+    pub vertices: [[f32; 3]; 3],
+}
+
+impl MeshTriangle {
+    pub fn normal(&self) -> [f32; 3] {
+        let [a, b, c] = self.vertices;
+        normalize(cross(sub(b, a), sub(c, a)))
+    }
+
+    // Distance along a unit ray to the triangle (either face), Möller–Trumbore
+    fn intersect(&self, origin: [f32; 3], direction: [f32; 3]) -> Option<f32> {
+        let [a, b, c] = self.vertices;
+        let (edge1, edge2) = (sub(b, a), sub(c, a));
+        let p = cross(direction, edge2);
+        let det = dot(edge1, p);
+        if det.abs() < 1e-9 {
+            return None;
+        }
+        let to_origin = sub(origin, a);
+        let u = dot(to_origin, p) / det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = cross(to_origin, edge1);
+        let v = dot(direction, q) / det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = dot(edge2, q) / det;
+        (t >= 0.0).then_some(t)
+    }
+}
+
+// Nearest mesh surface along a ray. The normal faces back toward the ray origin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MeshHit {
+    pub distance: f32,
+    pub point: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+type ChunkKey = [i32; 3];
+
+#[derive(Debug, Clone, Default)]
+struct MeshAnchor {
+    handle: u32,
+    chunks: Vec<ChunkKey>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MeshStore {
+    pub config: MeshStoreConfig,
+    chunks: HashMap<ChunkKey, Vec<MeshTriangle>>,
+    anchors: HashMap<String, MeshAnchor>,
+    next_handle: u32,
+    pub evicted_chunks: u64,
+}
+
+impl MeshStore {
+    fn key(&self, point: [f32; 3]) -> ChunkKey {
+        point.map(|v| (v / self.config.chunk_size).floor() as i32)
+    }
+
+    fn chunk_center(&self, key: ChunkKey) -> [f32; 3] {
+        key.map(|k| (k as f32 + 0.5) * self.config.chunk_size)
+    }
+
+    fn in_range(&self, key: ChunkKey, camera: [f32; 3]) -> bool {
+        let limit = self.config.evict_distance;
+        limit <= 0.0 || length(sub(self.chunk_center(key), camera)) <= limit
+    }
+
+    // First and last chunk a triangle's bounding box overlaps, or None for non-finite or
+    // oversized triangles
+    fn chunk_range(&self, vertices: &[[f32; 3]; 3]) -> Option<(ChunkKey, ChunkKey)> {
+        if !vertices.iter().flatten().all(|v| v.is_finite()) {
+            return None;
+        }
+        let lo = self.key([0, 1, 2].map(|axis| vertices.iter().map(|v| v[axis]).fold(f32::INFINITY, f32::min)));
+        let hi = self.key([0, 1, 2].map(|axis| vertices.iter().map(|v| v[axis]).fold(f32::NEG_INFINITY, f32::max)));
+        (0..3).all(|axis| hi[axis] - lo[axis] < MAX_TRIANGLE_CHUNKS).then_some((lo, hi))
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Distinct triangles currently filed
+    pub fn triangle_count(&self) -> usize {
+        self.triangles().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    // Replace the triangles of an anchor. Triangles with non-finite or far-flung
+    // vertices are skipped, as are chunks out of eviction range of `camera`. Returns the
+    // number of triangles filed
+    pub fn submit(&mut self, id: &str, triangles: &[[[f32; 3]; 3]], camera: [f32; 3]) -> usize {
+        let handle = match self.anchors.get(id) {
+            Some(anchor) => anchor.handle,
+            None => {
+                self.next_handle += 1;
+                self.next_handle
+            }
+        };
+        self.clear_anchor(id);
+
+        let mut chunks = HashSet::new();
+        let mut filed = 0;
+        for (index, vertices) in triangles.iter().enumerate() {
+            let Some((lo, hi)) = self.chunk_range(vertices) else {
+                continue;
+            };
+            let triangle = MeshTriangle { anchor: handle, index: index as u32, vertices: *vertices };
+            let mut placed = false;
+            for x in lo[0]..=hi[0] {
+                for y in lo[1]..=hi[1] {
+                    for z in lo[2]..=hi[2] {
+                        let key = [x, y, z];
+                        if !self.in_range(key, camera) {
+                            continue;
+                        }
+                        self.chunks.entry(key).or_default().push(triangle);
+                        chunks.insert(key);
+                        placed = true;
+                    }
+                }
+            }
+            if placed {
+                filed += 1;
+            }
+        }
+
+        self.anchors.insert(id.to_string(), MeshAnchor { handle, chunks: chunks.into_iter().collect() });
+        filed
+    }
+
+    // Drop an anchor's triangles. Returns false for an unknown anchor
+    pub fn remove(&mut self, id: &str) -> bool {
+        let known = self.clear_anchor(id);
+        self.anchors.remove(id);
+        known
+    }
+
+    fn clear_anchor(&mut self, id: &str) -> bool {
+        let Some(anchor) = self.anchors.get(id) else {
+            return false;
+        };
+        for key in &anchor.chunks {
+            if let Some(chunk) = self.chunks.get_mut(key) {
+                chunk.retain(|t| t.anchor != anchor.handle);
+                if chunk.is_empty() {
+                    self.chunks.remove(key);
+                }
+            }
+        }
+        true
+    }
+
+    // Drop chunks out of range of the camera. Returns how many went
+    pub fn evict(&mut self, camera: [f32; 3]) -> usize {
+        if self.config.evict_distance <= 0.0 {
+            return 0;
+        }
+        let far: Vec<ChunkKey> = self.chunks.keys().copied().filter(|&key| !self.in_range(key, camera)).collect();
+        for key in &far {
+            self.chunks.remove(key);
+        }
+        self.evicted_chunks += far.len() as u64;
+        far.len()
+    }
+
+    // Nearest triangle hit along `direction` (normalized here) within `max_distance`
+    pub fn raycast(&self, origin: [f32; 3], direction: [f32; 3], max_distance: f32) -> Option<MeshHit> {
+        if self.is_empty() || !(max_distance.is_finite() && max_distance > 0.0) {
+            return None;
+        }
+        let direction = normalize(direction);
+        if length(direction) < 0.5 || !origin.iter().all(|v| v.is_finite()) {
+            return None;
+        }
+
+        // Step through chunks in the order the ray enters them: `next` is the distance
+        // at which the ray crosses the next chunk boundary on each axis, `delta` the
+        // distance between boundaries
+        let size = self.config.chunk_size;
+        let mut key = self.key(origin);
+        let mut next = [f32::INFINITY; 3];
+        let mut delta = [f32::INFINITY; 3];
+        let mut step = [0i32; 3];
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                next[axis] = ((key[axis] + 1) as f32 * size - origin[axis]) / direction[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                next[axis] = (key[axis] as f32 * size - origin[axis]) / direction[axis];
+            }
+            if step[axis] != 0 {
+                delta[axis] = size / direction[axis].abs();
+            }
+        }
+
+        let mut best: Option<f32> = None;
+        let mut hit_triangle = None;
+        // A straight walk to max_distance crosses at most this many chunks
+        let max_steps = 3 * ((max_distance / size).ceil() as usize + 1);
+        for _ in 0..max_steps {
+            if let Some(chunk) = self.chunks.get(&key) {
+                for triangle in chunk {
+                    if let Some(t) = triangle.intersect(origin, direction) {
+                        if t <= max_distance && best.is_none_or(|b| t < b) {
+                            best = Some(t);
+                            hit_triangle = Some(*triangle);
+                        }
+                    }
+                }
+            }
+            // Anything in later chunks is farther than the chunk's exit
+            let exit = next[0].min(next[1]).min(next[2]);
+            if best.is_some_and(|t| t <= exit) || exit > max_distance {
+                break;
+            }
+            let axis = if next[0] <= next[1] && next[0] <= next[2] {
+                0
+            } else if next[1] <= next[2] {
+                1
+            } else {
+                2
+            };
+            key[axis] += step[axis];
+            next[axis] += delta[axis];
+        }
+
+        let (distance, triangle) = (best?, hit_triangle?);
+        let mut normal = triangle.normal();
+        if dot(normal, direction) > 0.0 {
+            normal = scale(normal, -1.0);
+        }
+        Some(MeshHit { distance, point: add(origin, scale(direction, distance)), normal })
+    }
+
+    // Distinct triangles filed in chunks overlapping the box from `min` to `max`
+    pub fn region(&self, min: [f32; 3], max: [f32; 3]) -> Vec<MeshTriangle> {
+        let (lo, hi) = (self.key(min), self.key(max));
+        if (0..3).any(|axis| hi[axis] < lo[axis]) {
+            return Vec::new();
+        }
+        let overlaps = |key: &ChunkKey| (0..3).all(|axis| (lo[axis]..=hi[axis]).contains(&key[axis]));
+        let span: i64 = (0..3).map(|axis| (hi[axis] - lo[axis]) as i64 + 1).product();
+
+        let mut seen = HashSet::new();
+        let mut triangles = Vec::new();
+        let mut take = |chunk: &Vec<MeshTriangle>| {
+            for triangle in chunk {
+                if seen.insert((triangle.anchor, triangle.index)) {
+                    triangles.push(*triangle);
+                }
+            }
+        };
+        // Walk whichever is smaller: the box's chunk range or the filed chunks
+        if span <= self.chunks.len() as i64 {
+            for x in lo[0]..=hi[0] {
+                for y in lo[1]..=hi[1] {
+                    for z in lo[2]..=hi[2] {
+                        if let Some(chunk) = self.chunks.get(&[x, y, z]) {
+                            take(chunk);
+                        }
+                    }
+                }
+            }
+        } else {
+            for (_, chunk) in self.chunks.iter().filter(|(key, _)| overlaps(key)) {
+                take(chunk);
+            }
+        }
+        triangles
+    }
+
+    // All distinct triangles, in no particular order
+    pub fn triangles(&self) -> Vec<MeshTriangle> {
+        let mut seen = HashSet::new();
+        self.chunks.values().flatten().filter(|t| seen.insert((t.anchor, t.index))).copied().collect()
+    }
+
+    // Change the chunk size, refiling everything currently stored
+    pub fn set_chunk_size(&mut self, chunk_size: f32) {
+        self.rebuild(chunk_size, [0.0; 3]);
+    }
+
+    // Move all geometry by `offset`; used when the world origin is re-based
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        self.rebuild(self.config.chunk_size, offset);
+    }
+
+    fn rebuild(&mut self, chunk_size: f32, offset: [f32; 3]) {
+        let triangles = self.triangles();
+        self.config.chunk_size = chunk_size;
+        self.chunks.clear();
+        let mut anchor_chunks: HashMap<u32, HashSet<ChunkKey>> = HashMap::new();
+        for mut triangle in triangles {
+            triangle.vertices = triangle.vertices.map(|v| add(v, offset));
+            let Some((lo, hi)) = self.chunk_range(&triangle.vertices) else {
+                continue;
+            };
+            for x in lo[0]..=hi[0] {
+                for y in lo[1]..=hi[1] {
+                    for z in lo[2]..=hi[2] {
+                        self.chunks.entry([x, y, z]).or_default().push(triangle);
+                        anchor_chunks.entry(triangle.anchor).or_default().insert([x, y, z]);
+                    }
+                }
+            }
+        }
+        for anchor in self.anchors.values_mut() {
+            anchor.chunks = anchor_chunks.remove(&anchor.handle).map(|keys| keys.into_iter().collect()).unwrap_or_default();
+        }
+    }
+}
+
+impl crate::ARSession {
+    // Evict far mesh chunks; runs every frame
+    pub(crate) fn evict_mesh_chunks(&mut self) {
+        let evicted = self.mesh.evict(self.camera_position);
+        if evicted > 0 {
+            debug!("Evicted {} mesh chunks", evicted);
+        }
+    }
+}
+
+fn transform_point(m: &[f32; 16], p: [f32; 3]) -> [f32; 3] {
+    [
+        m[0] * p[0] + m[4] * p[1] + m[8] * p[2] + m[12],
+        m[1] * p[0] + m[5] * p[1] + m[9] * p[2] + m[13],
+        m[2] * p[0] + m[6] * p[1] + m[10] * p[2] + m[14],
+    ]
+}
+
+// Add or replace the geometry of a mesh anchor. `vertices` holds vertex_count xyz
+// triples in the anchor's space, `indices` index_count vertex indices (three per
+// triangle), and `transform` is the column-major 4x4 anchor-to-world matrix. Returns the
+// number of triangles stored, or -1 on bad input or without a session
+#[no_mangle]
+pub extern "C" fn submit_mesh_anchor(
+    id_ptr: *const libc::c_char,
+    transform: *const f32,
+    vertices: *const f32,
+    vertex_count: u32,
+    indices: *const u32,
+    index_count: u32,
+) -> i32 {
+    if id_ptr.is_null() || transform.is_null() || vertices.is_null() || indices.is_null() || !index_count.is_multiple_of(3) {
+        return -1;
+    }
+    let (id, transform, vertices, indices) = unsafe {
+        (
+            CStr::from_ptr(id_ptr).to_string_lossy().into_owned(),
+            read_transform(transform),
+            std::slice::from_raw_parts(vertices, vertex_count as usize * 3),
+            std::slice::from_raw_parts(indices, index_count as usize),
+        )
+    };
+    let world: Vec<[f32; 3]> = vertices.chunks_exact(3).map(|v| transform_point(&transform, [v[0], v[1], v[2]])).collect();
+    let mut triangles = Vec::with_capacity(indices.len() / 3);
+    for face in indices.chunks_exact(3) {
+        let Some(corners) = face.iter().map(|&i| world.get(i as usize).copied()).collect::<Option<Vec<_>>>() else {
+            return -1;
+        };
+        triangles.push([corners[0], corners[1], corners[2]]);
+    }
+
+    analytics::feature_used(Feature::ReconstructionMesh);
+    with_session(|session| {
+        let id = session.qualify_id(&id).into_owned();
+        session.mesh.submit(&id, &triangles, session.camera_position) as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Drop a mesh anchor's geometry. Returns false for an unknown anchor
+#[no_mangle]
+pub extern "C" fn remove_mesh_anchor(id_ptr: *const libc::c_char) -> bool {
+    if id_ptr.is_null() {
+        return false;
+    }
+    let id = unsafe { CStr::from_ptr(id_ptr).to_string_lossy().into_owned() };
+    with_session(|session| {
+        let id = session.qualify_id(&id).into_owned();
+        session.mesh.remove(&id)
+    })
+    .unwrap_or(false)
+}
+
+// Set the mesh chunk edge length and the distance from the camera past which chunks
+// are evicted (0 keeps everything). Changing the chunk size refiles the stored mesh
+#[no_mangle]
+pub extern "C" fn set_mesh_store_params(chunk_size: f32, evict_distance: f32) -> bool {
+    if !(chunk_size.is_finite() && chunk_size > 0.0 && evict_distance.is_finite() && evict_distance >= 0.0) {
+        return false;
+    }
+    with_session(|session| {
+        session.mesh.config.evict_distance = evict_distance;
+        if chunk_size != session.mesh.config.chunk_size {
+            session.mesh.set_chunk_size(chunk_size);
+        }
+    })
+    .is_some()
+}
+
+// Cast a ray against the mesh. On a hit within max_distance, writes the hit point and
+// surface normal (3 floats each) and the distance, and returns true. Any output may be
+// null
+#[no_mangle]
+pub extern "C" fn raycast_mesh(
+    origin_x: f32, origin_y: f32, origin_z: f32,
+    dir_x: f32, dir_y: f32, dir_z: f32,
+    max_distance: f32,
+    out_point: *mut f32,
+    out_normal: *mut f32,
+    out_distance: *mut f32,
+) -> bool {
+    let hit = with_session(|session| session.mesh.raycast([origin_x, origin_y, origin_z], [dir_x, dir_y, dir_z], max_distance)).flatten();
+    let Some(hit) = hit else {
+        return false;
+    };
+    unsafe {
+        if !out_point.is_null() {
+            std::slice::from_raw_parts_mut(out_point, 3).copy_from_slice(&hit.point);
+        }
+        if !out_normal.is_null() {
+            std::slice::from_raw_parts_mut(out_normal, 3).copy_from_slice(&hit.normal);
+        }
+        if !out_distance.is_null() {
+            *out_distance = hit.distance;
+        }
+    }
+    true
+}
+
+// Copy up to `capacity` triangles from the chunks overlapping the box into
+// `out_vertices` (9 floats per triangle: three world-space corners; may be null).
+// Returns the total number of triangles in the region, or -1 without a session
+#[no_mangle]
+pub extern "C" fn get_mesh_region(
+    min_x: f32, min_y: f32, min_z: f32,
+    max_x: f32, max_y: f32, max_z: f32,
+    out_vertices: *mut f32,
+    capacity: u32,
+) -> i32 {
+    with_session(|session| {
+        let triangles = session.mesh.region([min_x, min_y, min_z], [max_x, max_y, max_z]);
+        let count = triangles.len().min(capacity as usize);
+        if !out_vertices.is_null() {
+            let out = unsafe { std::slice::from_raw_parts_mut(out_vertices, count * 9) };
+            for (chunk, triangle) in out.chunks_exact_mut(9).zip(&triangles) {
+                chunk.copy_from_slice(triangle.vertices.as_flattened());
+            }
+        }
+        triangles.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Number of chunks and distinct triangles stored, and chunks evicted so far. Outputs
+// may be null. Returns false without a session
+#[no_mangle]
+pub extern "C" fn get_mesh_store_stats(out_chunks: *mut u32, out_triangles: *mut u32, out_evicted: *mut u64) -> bool {
+    with_session(|session| unsafe {
+        if !out_chunks.is_null() {
+            *out_chunks = session.mesh.chunk_count() as u32;
+        }
+        if !out_triangles.is_null() {
+            *out_triangles = session.mesh.triangle_count() as u32;
+        }
+        if !out_evicted.is_null() {
+            *out_evicted = session.mesh.evicted_chunks;
+        }
+    })
+    .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two triangles making a square of side `size` in the plane y = `y`, centered on x/z
+    fn floor_square(x: f32, y: f32, z: f32, size: f32) -> Vec<[[f32; 3]; 3]> {
+        let h = size / 2.0;
+        let (a, b, c, d) = ([x - h, y, z - h], [x + h, y, z - h], [x + h, y, z + h], [x - h, y, z + h]);
+        vec![[a, b, c], [a, c, d]]
+    }
+
+    #[test]
+    fn triangles_are_filed_in_every_chunk_they_touch() {
+        let mut store = MeshStore::default();
+        // A 1 m square centered on the origin spans three 0.5 m chunks along x and z
+        assert_eq!(store.submit("floor", &floor_square(0.0, 0.0, 0.0, 1.0), [0.0; 3]), 2);
+        assert_eq!(store.triangle_count(), 2);
+        assert_eq!(store.chunk_count(), 9);
+        // Each triangle comes back once however many chunks it's in
+        assert_eq!(store.region([-1.0; 3], [1.0; 3]).len(), 2);
+    }
+
+    #[test]
+    fn resubmitting_an_anchor_replaces_its_triangles() {
+        let mut store = MeshStore::default();
+        store.submit("a", &floor_square(0.0, 0.0, 0.0, 1.0), [0.0; 3]);
+        store.submit("b", &floor_square(3.0, 0.0, 0.0, 0.4), [0.0; 3]);
+        store.submit("a", &floor_square(0.0, 1.0, 0.0, 0.4), [0.0; 3]);
+
+        assert_eq!(store.triangle_count(), 4);
+        assert!(store.region([-1.0, -0.1, -1.0], [1.0, 0.1, 1.0]).is_empty());
+        assert!(store.remove("a"));
+        assert!(!store.remove("a"));
+        assert_eq!(store.triangle_count(), 2);
+    }
+
+    #[test]
+    fn raycasts_find_the_nearest_surface_across_chunks() {
+        let mut store = MeshStore::default();
+        store.submit("floor", &floor_square(0.0, 0.0, 0.0, 4.0), [0.0; 3]);
+        store.submit("shelf", &floor_square(1.0, 1.2, 1.0, 0.6), [0.0; 3]);
+
+        let hit = store.raycast([1.0, 3.0, 1.0], [0.0, -1.0, 0.0], 10.0).unwrap();
+        assert!((hit.distance - 1.8).abs() < 1e-4);
+        assert_eq!(hit.normal, [0.0, 1.0, 0.0]);
+
+        // Beside the shelf the ray carries on to the floor
+        let hit = store.raycast([-1.0, 3.0, -1.0], [0.0, -1.0, 0.0], 10.0).unwrap();
+        assert!((hit.point[1]).abs() < 1e-4);
+
+        // A diagonal ray crossing chunk boundaries on every axis still lands on the floor
+        let hit = store.raycast([-1.3, 2.0, -1.1], [1.0, -1.0, 0.7], 10.0).unwrap();
+        assert!(hit.point[1].abs() < 1e-4);
+        assert!((hit.point[0] - 0.7).abs() < 1e-3);
+
+        // Too short, or pointing away
+        assert!(store.raycast([-1.0, 3.0, -1.0], [0.0, -1.0, 0.0], 2.5).is_none());
+        assert!(store.raycast([0.0, 3.0, 0.0], [0.0, 1.0, 0.0], 10.0).is_none());
+    }
+
+    #[test]
+    fn far_chunks_are_evicted_and_not_refilled() {
+        let mut store = MeshStore { config: MeshStoreConfig { chunk_size: 0.5, evict_distance: 3.0 }, ..MeshStore::default() };
+        store.submit("near", &floor_square(0.0, 0.0, 0.0, 0.4), [0.0; 3]);
+        store.submit("far", &floor_square(6.0, 0.0, 0.0, 0.4), [6.0, 0.0, 0.0]);
+        assert_eq!(store.triangle_count(), 4);
+
+        // Back at the origin the far anchor's chunks go
+        assert!(store.evict([0.0; 3]) > 0);
+        assert_eq!(store.triangle_count(), 2);
+        assert!(store.raycast([6.0, 1.0, 0.0], [0.0, -1.0, 0.0], 2.0).is_none());
+
+        // Resubmitted from here it stays out of the store
+        assert_eq!(store.submit("far", &floor_square(6.0, 0.0, 0.0, 0.4), [0.0; 3]), 0);
+        assert_eq!(store.evict([0.0; 3]), 0);
+        assert!(store.evicted_chunks > 0);
+    }
+
+    #[test]
+    fn rechunking_and_translating_keep_the_geometry() {
+        let mut store = MeshStore::default();
+        store.submit("floor", &floor_square(0.0, 0.0, 0.0, 2.0), [0.0; 3]);
+        store.set_chunk_size(0.25);
+        assert_eq!(store.triangle_count(), 2);
+        assert_eq!(store.chunk_count(), 81);
+
+        store.translate([0.0, -1.0, 0.0]);
+        let hit = store.raycast([0.5, 1.0, 0.5], [0.0, -1.0, 0.0], 5.0).unwrap();
+        assert!((hit.point[1] + 1.0).abs() < 1e-4);
+        // Removal still finds the moved triangles
+        assert!(store.remove("floor"));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn degenerate_triangles_are_skipped() {
+        let mut store = MeshStore::default();
+        let triangles = [
+            [[0.0, 0.0, 0.0], [f32::NAN, 0.0, 0.0], [0.0, 0.0, 1.0]],
+            [[0.0, 0.0, 0.0], [100.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        ];
+        assert_eq!(store.submit("bad", &triangles, [0.0; 3]), 0);
+        assert!(store.is_empty());
+    }
+}
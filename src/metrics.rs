@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+// Running counters for the AR session, reported by the sim runner and debug tools.
+// Missing counters default to zero so older snapshots still load
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionMetrics {
+    pub camera_updates: u64,
+    pub planes_added: u64,
+    pub objects_placed: u64,
+    pub objects_removed: u64,
+    pub failed_removals: u64,
+    pub depth_frames: u64,
+    pub gpu_depth_frames: u64,
+    pub derived_planes_added: u64,
+    // Planes removed by expiry (see plane_expiry.rs)
+    pub planes_expired: u64,
+    pub anchor_drift_events: u64,
+    // Frames turned away by the ingestion queues (see ingestion.rs)
+    pub camera_images_dropped: u64,
+    pub camera_images_coalesced: u64,
+    pub depth_frames_dropped: u64,
+    pub depth_frames_coalesced: u64,
+    // Additions refused for going over a quota (see quotas.rs)
+    pub quota_refusals: u64,
+}
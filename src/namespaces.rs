@@ -0,0 +1,123 @@
+// Id namespaces for sessions fed by several sources. Planes and anchors can come from
+// ARKit, marker detection or remote peers, each with its own id scheme, and ids from
+// different sources can collide. A namespaced id is a source prefix and a local id
+// joined by ':' ("marker:7", "peer-ab12:plane_0"); the part before the first ':' is
+// the entity's source, and ids without one belong to the unnamed source.
+//
+// Hosts can pass namespaced ids directly. Ids passed without a prefix get the
+// configured default source (typically "arkit"), so a host only has to qualify ids
+// from its other sources. Set the default before reporting anything: entities already
+// stored under bare ids keep them, and bare lookups would then miss them
+
+use std::borrow::Cow;
+use std::ffi::CStr;
+
+use crate::events::copy_c_string;
+use crate::{with_session, ARSession};
+
+pub(crate) const NAMESPACE_SEPARATOR: char = ':';
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IdNamespace {
+    // Prefix for ids reported without one
+    pub(crate) default_source: Option<String>,
+}
+
+// Source prefix of an id, or "" for an unqualified one
+pub(crate) fn source_of(id: &str) -> &str {
+    id.split_once(NAMESPACE_SEPARATOR).map_or("", |(source, _)| source)
+}
+
+impl ARSession {
+    // An id as stored: unchanged if it already has a source, otherwise under the
+    // default source
+    pub(crate) fn qualify_id<'a>(&self, id: &'a str) -> Cow<'a, str> {
+        match &self.id_namespace.default_source {
+            Some(source) if !id.contains(NAMESPACE_SEPARATOR) => {
+                Cow::Owned(format!("{}{}{}", source, NAMESPACE_SEPARATOR, id))
+            }
+            _ => Cow::Borrowed(id),
+        }
+    }
+
+    pub(crate) fn planes_from_source(&self, source: &str) -> Vec<usize> {
+        (0..self.detected_planes.len())
+            .filter(|&index| source_of(&self.detected_planes[index].id) == source)
+            .collect()
+    }
+
+    pub(crate) fn anchors_from_source(&self, source: &str) -> Vec<&str> {
+        self.anchors.iter()
+            .map(|anchor| anchor.id.as_str())
+            .filter(|id| source_of(id) == source)
+            .collect()
+    }
+}
+
+// Null reads as the unnamed source
+unsafe fn source_arg(source_ptr: *const libc::c_char) -> String {
+    if source_ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(source_ptr).to_string_lossy().into_owned()
+    }
+}
+
+// Set the source prefix given to plane and anchor ids reported without one. Null or ""
+// stops prefixing. Returns false for a prefix containing ':'
+#[no_mangle]
+pub extern "C" fn set_default_id_source(source_ptr: *const libc::c_char) -> bool {
+    let source = unsafe { source_arg(source_ptr) };
+    if source.contains(NAMESPACE_SEPARATOR) {
+        return false;
+    }
+
+    with_session(|session| {
+        session.id_namespace.default_source = (!source.is_empty()).then_some(source);
+    })
+    .is_some()
+}
+
+// Indices (as used by get_plane_info) of the planes from a source; null or "" selects
+// planes with unprefixed ids. Writes up to `capacity` indices (`out_indices` may be
+// null) and returns the total count, or -1 without a session
+#[no_mangle]
+pub extern "C" fn get_planes_from_source(
+    source_ptr: *const libc::c_char,
+    out_indices: *mut u32,
+    capacity: u32,
+) -> i32 {
+    let source = unsafe { source_arg(source_ptr) };
+
+    with_session(|session| {
+        let planes = session.planes_from_source(&source);
+        if !out_indices.is_null() {
+            let count = planes.len().min(capacity as usize);
+            let out = unsafe { std::slice::from_raw_parts_mut(out_indices, count) };
+            for (slot, &index) in out.iter_mut().zip(&planes) {
+                *slot = index as u32;
+            }
+        }
+        planes.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Ids of the anchors from a source, as a NUL-terminated JSON array of strings; null or
+// "" selects anchors with unprefixed ids. Returns the JSON length, or -1 without a
+// session. Nothing is written if it doesn't fit in `capacity` bytes
+#[no_mangle]
+pub extern "C" fn get_anchors_from_source(
+    source_ptr: *const libc::c_char,
+    out_json: *mut libc::c_char,
+    capacity: u32,
+) -> i32 {
+    let source = unsafe { source_arg(source_ptr) };
+
+    with_session(|session| {
+        let json = serde_json::to_string(&session.anchors_from_source(&source)).unwrap_or_default();
+        unsafe { copy_c_string(&json, out_json, capacity) };
+        json.len() as i32
+    })
+    .unwrap_or(-1)
+}
@@ -0,0 +1,230 @@
+// People occlusion. The host forwards ARKit's person segmentation matte (and,
+// optionally, the matching estimated depth) each frame; virtual content that projects
+// onto a person pixel is treated as hidden when the person is closer to the camera.
+// Mattes are in the captured image's orientation, like the camera intrinsics.
+//
+// Drawables report how much of each object people cover. An object covered entirely
+// counts as not visible, and people block the gaze ray
+
+use crate::analytics::{self, Feature};
+use crate::cameras::{CameraFeature, CameraStream};
+use crate::with_session;
+
+// A matte this old no longer describes where people are
+const MAX_MATTE_AGE: f64 = 0.25;
+
+// Segmentation values at or above this are people (ARKit writes 255)
+const PERSON_THRESHOLD: u8 = 128;
+
+// Samples per side when estimating how much of an object is covered
+const OBJECT_SAMPLE_GRID: usize = 5;
+
+pub(crate) struct PersonMatte {
+    timestamp: f64,
+    width: usize,
+    height: usize,
+    segmentation: Vec<u8>,
+    // Meters from the camera per matte pixel, when provided
+    depth: Option<Vec<f32>>,
+}
+
+impl PersonMatte {
+    // Whether a point at `pixel` (camera stream coordinates) and `depth` is behind a person
+    fn occludes(&self, camera: &CameraStream, pixel: [f32; 2], depth: f32) -> bool {
+        if camera.resolution[0] == 0 || camera.resolution[1] == 0 {
+            return false;
+        }
+        let x = pixel[0] * self.width as f32 / camera.resolution[0] as f32;
+        let y = pixel[1] * self.height as f32 / camera.resolution[1] as f32;
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return false;
+        }
+
+        let index = y as usize * self.width + x as usize;
+        if self.segmentation[index] < PERSON_THRESHOLD {
+            return false;
+        }
+        // Without depth, people are assumed to be in front of all content
+        self.depth.as_ref().is_none_or(|person_depth| person_depth[index] < depth)
+    }
+}
+
+impl crate::ARSession {
+    fn current_matte(&self) -> Option<&PersonMatte> {
+        self.person_matte.as_ref()
+            .filter(|matte| self.clock.now() - matte.timestamp <= MAX_MATTE_AGE)
+    }
+
+    // Whether a world point is hidden behind a person, or None when that can't be
+    // determined (no recent matte, or the camera has no intrinsics)
+    pub(crate) fn point_occluded(&self, point: [f32; 3]) -> Option<bool> {
+        let matte = self.current_matte()?;
+        let camera = self.camera_stream(CameraFeature::PeopleOcclusion.stream());
+        if !camera.can_project() {
+            return None;
+        }
+        Some(match camera.project(point) {
+            Some((pixel, depth)) => matte.occludes(camera, pixel, depth),
+            None => false,
+        })
+    }
+
+    // Fraction of an object's on-screen footprint covered by people, sampled over a
+    // grid across its bounding sphere's projection
+    pub(crate) fn object_occlusion(&self, index: usize) -> Option<f32> {
+        let object = self.virtual_objects.get(index)?;
+        let matte = self.current_matte()?;
+        let camera = self.camera_stream(CameraFeature::PeopleOcclusion.stream());
+        if !camera.can_project() {
+            return None;
+        }
+        // Behind the camera: nothing on screen to cover
+        let Some((center, depth)) = camera.project(object.position) else {
+            return Some(0.0);
+        };
+        let [fx, ..] = camera.intrinsics?;
+        let radius = fx * object.bounding_radius() / depth;
+
+        let mut covered = 0;
+        let mut total = 0;
+        for row in 0..OBJECT_SAMPLE_GRID {
+            for column in 0..OBJECT_SAMPLE_GRID {
+                let step = |i: usize| (i as f32 + 0.5) / OBJECT_SAMPLE_GRID as f32 * 2.0 - 1.0;
+                let (dx, dy) = (step(column), step(row));
+                if dx * dx + dy * dy > 1.0 {
+                    continue;
+                }
+                total += 1;
+                if matte.occludes(camera, [center[0] + dx * radius, center[1] + dy * radius], depth) {
+                    covered += 1;
+                }
+            }
+        }
+        Some(covered as f32 / total as f32)
+    }
+
+    // Whether people cover all of an object's footprint
+    pub(crate) fn hidden_by_people(&self, index: usize) -> bool {
+        self.object_occlusion(index).is_some_and(|fraction| fraction >= 1.0)
+    }
+}
+
+// Submit ARKit's person segmentation (one byte per pixel, width * height) and,
+// optionally, its estimated depth (meters, same resolution; may be null). Replaces the
+// previous matte. Returns false on bad input or without a session
+#[no_mangle]
+pub extern "C" fn submit_person_segmentation(
+    segmentation: *const u8,
+    depth: *const f32,
+    width: u32,
+    height: u32,
+) -> bool {
+    if segmentation.is_null() || width == 0 || height == 0 {
+        return false;
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let (segmentation, depth) = unsafe {
+        (
+            std::slice::from_raw_parts(segmentation, width * height).to_vec(),
+            (!depth.is_null()).then(|| std::slice::from_raw_parts(depth, width * height).to_vec()),
+        )
+    };
+
+    analytics::feature_used(Feature::PeopleOcclusion);
+    with_session(|session| {
+        session.person_matte = Some(PersonMatte {
+            timestamp: session.clock.now(),
+            width,
+            height,
+            segmentation,
+            depth,
+        });
+    })
+    .is_some()
+}
+
+// Drop the current matte, e.g. when people occlusion is turned off
+#[no_mangle]
+pub extern "C" fn clear_person_segmentation() {
+    with_session(|session| session.person_matte = None);
+}
+
+// 1 if the world point is hidden behind a person, 0 if not, or -1 if unknown (no
+// recent matte or camera intrinsics)
+#[no_mangle]
+pub extern "C" fn is_point_occluded(x: f32, y: f32, z: f32) -> i32 {
+    with_session(|session| session.point_occluded([x, y, z]))
+        .flatten()
+        .map_or(-1, i32::from)
+}
+
+// Fraction (0-1) of an object's footprint hidden behind people, or -1 if unknown
+#[no_mangle]
+pub extern "C" fn get_object_occlusion(object_id: i32) -> f32 {
+    let Ok(index) = usize::try_from(object_id) else {
+        return -1.0;
+    };
+
+    with_session(|session| session.object_occlusion(index))
+        .flatten()
+        .unwrap_or(-1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gaze::GazeTarget;
+    use crate::render::RenderSnapshot;
+    use crate::{ARObjectType, ARSession};
+
+    const RESOLUTION: [u32; 2] = [64, 48];
+
+    // Camera at the origin looking down -Z, with an object 2m ahead
+    fn session_with_object() -> (ARSession, usize) {
+        let mut session = ARSession::new();
+        session.set_camera_pose(session.clock.now(), [0.0; 3], [0.0, 0.0, 0.0, 1.0]);
+        let camera = &mut session.cameras[CameraFeature::PeopleOcclusion.stream() as usize];
+        camera.intrinsics = Some([50.0, 50.0, 32.0, 24.0]);
+        camera.resolution = RESOLUTION;
+        let index = session.place_object(ARObjectType::Cube, [0.0, 0.0, -2.0], [0.0, 0.0, 0.0, 1.0]).unwrap();
+        session.virtual_objects[index].gaze = Some(GazeTarget { dwell_time: 1.0 });
+        (session, index)
+    }
+
+    // A person filling the whole image at `person_depth` meters
+    fn submit_person(session: &mut ARSession, person_depth: f32) {
+        let pixels = (RESOLUTION[0] * RESOLUTION[1]) as usize;
+        session.person_matte = Some(PersonMatte {
+            timestamp: session.clock.now(),
+            width: RESOLUTION[0] as usize,
+            height: RESOLUTION[1] as usize,
+            segmentation: vec![255; pixels],
+            depth: Some(vec![person_depth; pixels]),
+        });
+    }
+
+    #[test]
+    fn person_in_front_hides_object_from_drawables_visibility_and_gaze() {
+        let (mut session, index) = session_with_object();
+        submit_person(&mut session, 1.0);
+
+        assert_eq!(RenderSnapshot::from(&session).objects[index].person_occlusion, 1.0);
+        assert_eq!(session.object_visible(index), Some(false));
+        assert_eq!(session.object_coverage(index), Some(0.0));
+        session.update_gaze(0.1);
+        assert_eq!(session.gaze.focused(), None);
+    }
+
+    #[test]
+    fn person_behind_object_hides_nothing() {
+        let (mut session, index) = session_with_object();
+        submit_person(&mut session, 3.0);
+
+        assert_eq!(RenderSnapshot::from(&session).objects[index].person_occlusion, 0.0);
+        assert_eq!(session.object_visible(index), Some(true));
+        assert!(session.object_coverage(index).unwrap() > 0.0);
+        session.update_gaze(0.1);
+        assert_eq!(session.gaze.focused(), Some(index));
+    }
+}
@@ -0,0 +1,304 @@
+// Offscreen rendering of a RenderSnapshot to PNG for golden-image tests.
+//
+// This is a small CPU rasterizer rather than a Metal/wgpu pipeline: output is
+// bit-identical across machines and GPU drivers, which is what golden images need,
+// and it runs in CI without a device or window server
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use crate::math::{add, cross, dot, normalize, quat_conjugate, quat_rotate, scale, sub, tangent_basis};
+use crate::color_grading::ColorGrading;
+use crate::primitives::Primitive;
+use crate::render::{RenderSnapshot, Shape};
+
+const NEAR_PLANE: f32 = 0.01;
+const BACKGROUND: [u8; 3] = [30, 30, 30];
+const DECAL_COLOR: [u8; 3] = [230, 190, 60];
+const LIGHT_DIRECTION: [f32; 3] = [0.3, 1.0, 0.5];
+const SPHERE_SEGMENTS: usize = 16;
+const SPHERE_RINGS: usize = 8;
+const MAX_SPHERE_LOD: u32 = 2;
+
+// Tightly packed 8-bit RGBA image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+// Result of comparing a render against a golden image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageDiff {
+    pub differing_pixels: usize,
+    pub max_channel_delta: u8,
+}
+
+impl ImageDiff {
+    pub fn is_match(&self) -> bool {
+        self.differing_pixels == 0
+    }
+}
+
+impl Image {
+    fn filled(width: u32, height: u32, rgb: [u8; 3]) -> Self {
+        let pixels = [rgb[0], rgb[1], rgb[2], 255].repeat((width * height) as usize);
+        Image { width, height, pixels }
+    }
+
+    pub fn save_png(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    pub fn load_png(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let mut reader = png::Decoder::new(file).read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            bail!("{} is not an 8-bit RGBA image", path.display());
+        }
+        buffer.truncate(info.buffer_size());
+        Ok(Image { width: info.width, height: info.height, pixels: buffer })
+    }
+
+    // Count pixels where any channel differs by more than `tolerance`
+    pub fn compare(&self, golden: &Image, tolerance: u8) -> anyhow::Result<ImageDiff> {
+        if self.width != golden.width || self.height != golden.height {
+            bail!("image size {}x{} does not match golden {}x{}",
+                self.width, self.height, golden.width, golden.height);
+        }
+
+        let mut diff = ImageDiff { differing_pixels: 0, max_channel_delta: 0 };
+        for (a, b) in self.pixels.chunks_exact(4).zip(golden.pixels.chunks_exact(4)) {
+            let delta = a.iter().zip(b).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0);
+            diff.max_channel_delta = diff.max_channel_delta.max(delta);
+            if delta > tolerance {
+                diff.differing_pixels += 1;
+            }
+        }
+        Ok(diff)
+    }
+}
+
+struct Triangle {
+    vertices: [[f32; 3]; 3],
+    color: [u8; 3],
+}
+
+// Render a snapshot from its camera with flat Lambert shading and a depth buffer
+pub fn render(snapshot: &RenderSnapshot, width: u32, height: u32) -> Image {
+    let mut image = Image::filled(width, height, BACKGROUND);
+    let mut depth = vec![0.0f32; (width * height) as usize];
+
+    let mut triangles = Vec::new();
+    for plane in &snapshot.planes {
+        plane_triangles(plane.center, plane.extent, plane.normal, &mut triangles);
+    }
+    // Decals are drawn in a flat color, without their textures
+    for decal in &snapshot.decals {
+        for triangle in decal.indices.chunks_exact(3) {
+            let vertices = [0, 1, 2].map(|i| decal.positions[triangle[i] as usize]);
+            triangles.push(Triangle { vertices, color: DECAL_COLOR });
+        }
+    }
+    // Hidden objects are skipped; partly faded ones are drawn opaque, and objects seen
+    // through a portal aren't clipped to its doorway
+    for object in snapshot.objects.iter().filter(|object| object.opacity > 0.0) {
+        let color = graded(shape_color(&object.shape), &snapshot.color_grading);
+        // Built-in meshes are unit-sized; primitive meshes are already in meters. Text
+        // is drawn as its bounding box. Spheres coarsen with the object's level of detail
+        let (mesh, mesh_scale) = match &object.shape {
+            Shape::Sphere => (sphere_mesh(object.lod), [object.size * 0.5; 3]),
+            Shape::Primitive(primitive) => (primitive_mesh(primitive), [1.0; 3]),
+            Shape::Text { half_extents, .. } => (cube_mesh(), *half_extents),
+            _ => (cube_mesh(), [object.size * 0.5; 3]),
+        };
+        for [a, b, c] in mesh {
+            let to_world = |v: [f32; 3]| {
+                add(object.position, quat_rotate(object.rotation, std::array::from_fn(|i| v[i] * mesh_scale[i])))
+            };
+            triangles.push(Triangle { vertices: [to_world(a), to_world(b), to_world(c)], color });
+        }
+    }
+
+    let camera = &snapshot.camera;
+    let inverse_rotation = quat_conjugate(camera.rotation);
+    let focal = 1.0 / (camera.vertical_fov * 0.5).tan();
+    let aspect = width as f32 / height as f32;
+    let light = normalize(LIGHT_DIRECTION);
+
+    for triangle in &triangles {
+        let [a, b, c] = triangle.vertices;
+        let normal = normalize(cross(sub(b, a), sub(c, a)));
+        let intensity = 0.35 + 0.65 * dot(normal, light).abs();
+        let shaded = triangle.color.map(|channel| (channel as f32 * intensity).round().min(255.0) as u8);
+
+        let view = triangle.vertices.map(|v| quat_rotate(inverse_rotation, sub(v, camera.position)));
+        let clipped = clip_near(&view);
+        if clipped.len() < 3 {
+            continue;
+        }
+
+        let projected: Vec<[f32; 3]> = clipped.iter()
+            .map(|v| {
+                let inv_depth = 1.0 / -v[2];
+                let x = (focal / aspect * v[0] * inv_depth + 1.0) * 0.5 * width as f32;
+                let y = (1.0 - focal * v[1] * inv_depth) * 0.5 * height as f32;
+                [x, y, inv_depth]
+            })
+            .collect();
+
+        for i in 1..projected.len() - 1 {
+            rasterize([projected[0], projected[i], projected[i + 1]], shaded, &mut image, &mut depth);
+        }
+    }
+
+    image
+}
+
+// Sutherland-Hodgman clip of a view-space triangle against the near plane
+fn clip_near(vertices: &[[f32; 3]; 3]) -> Vec<[f32; 3]> {
+    let inside = |v: &[f32; 3]| v[2] <= -NEAR_PLANE;
+    let mut output = Vec::with_capacity(4);
+    for i in 0..3 {
+        let current = vertices[i];
+        let next = vertices[(i + 1) % 3];
+        if inside(&current) {
+            output.push(current);
+        }
+        if inside(&current) != inside(&next) {
+            let t = (-NEAR_PLANE - current[2]) / (next[2] - current[2]);
+            output.push(add(current, scale(sub(next, current), t)));
+        }
+    }
+    output
+}
+
+// Fill a screen-space triangle; z holds 1/depth, which interpolates linearly
+fn rasterize(v: [[f32; 3]; 3], color: [u8; 3], image: &mut Image, depth: &mut [f32]) {
+    let edge = |a: [f32; 3], b: [f32; 3], x: f32, y: f32| (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0]);
+    let area = edge(v[0], v[1], v[2][0], v[2][1]);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+
+    let min_x = v.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+    let max_x = v.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max).ceil().min(image.width as f32) as u32;
+    let min_y = v.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+    let max_y = v.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max).ceil().min(image.height as f32) as u32;
+
+    for py in min_y..max_y {
+        for px in min_x..max_x {
+            let (x, y) = (px as f32 + 0.5, py as f32 + 0.5);
+            let w0 = edge(v[1], v[2], x, y) / area;
+            let w1 = edge(v[2], v[0], x, y) / area;
+            let w2 = edge(v[0], v[1], x, y) / area;
+            if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                continue;
+            }
+
+            let inv_depth = w0 * v[0][2] + w1 * v[1][2] + w2 * v[2][2];
+            let index = (py * image.width + px) as usize;
+            if inv_depth <= depth[index] {
+                continue;
+            }
+            depth[index] = inv_depth;
+            image.pixels[index * 4..index * 4 + 3].copy_from_slice(&color);
+        }
+    }
+}
+
+fn shape_color(shape: &Shape) -> [u8; 3] {
+    match shape {
+        Shape::Cube => [220, 80, 60],
+        Shape::Sphere => [70, 190, 90],
+        Shape::Primitive(_) => [180, 110, 210],
+        Shape::Text { .. } => [235, 235, 235],
+        Shape::Custom(_) => [200, 200, 70],
+    }
+}
+
+// Apply color grading in (approximately) linear space
+fn graded(color: [u8; 3], grading: &ColorGrading) -> [u8; 3] {
+    let mut out = color;
+    for channel in 0..3 {
+        let linear = (color[channel] as f32 / 255.0).powf(2.2) * grading.white_balance[channel] * grading.exposure_scale;
+        out[channel] = (linear.min(1.0).powf(1.0 / 2.2) * 255.0).round() as u8;
+    }
+    out
+}
+
+fn plane_triangles(center: [f32; 3], extent: [f32; 2], normal: [f32; 3], out: &mut Vec<Triangle>) {
+    let (tangent, bitangent) = tangent_basis(normalize(normal));
+    let u = scale(tangent, extent[0] * 0.5);
+    let v = scale(bitangent, extent[1] * 0.5);
+    let corners = [
+        sub(sub(center, u), v),
+        sub(add(center, u), v),
+        add(add(center, u), v),
+        add(sub(center, u), v),
+    ];
+    let color = [90, 140, 200];
+    out.push(Triangle { vertices: [corners[0], corners[1], corners[2]], color });
+    out.push(Triangle { vertices: [corners[0], corners[2], corners[3]], color });
+}
+
+// Unit cube spanning [-1, 1] on each axis
+fn cube_mesh() -> Vec<[[f32; 3]; 3]> {
+    let corner = |i: usize| [
+        if i & 1 == 0 { -1.0 } else { 1.0 },
+        if i & 2 == 0 { -1.0 } else { 1.0 },
+        if i & 4 == 0 { -1.0 } else { 1.0 },
+    ];
+    let faces = [[0, 1, 3, 2], [4, 6, 7, 5], [0, 4, 5, 1], [2, 3, 7, 6], [0, 2, 6, 4], [1, 5, 7, 3]];
+    faces.iter()
+        .flat_map(|f| [[corner(f[0]), corner(f[1]), corner(f[2])], [corner(f[0]), corner(f[2]), corner(f[3])]])
+        .collect()
+}
+
+fn primitive_mesh(primitive: &Primitive) -> Vec<[[f32; 3]; 3]> {
+    let mesh = primitive.mesh();
+    mesh.indices.chunks_exact(3)
+        .map(|triangle| [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize]))
+        .collect()
+}
+
+// UV sphere of radius 1, with half the rings and segments per level of detail down to
+// an octahedron at level 2
+fn sphere_mesh(lod: u32) -> Vec<[[f32; 3]; 3]> {
+    use std::f32::consts::PI;
+
+    let level = lod.min(MAX_SPHERE_LOD);
+    let (rings, segments) = (SPHERE_RINGS >> level, SPHERE_SEGMENTS >> level);
+    let point = |ring: usize, segment: usize| {
+        let theta = PI * ring as f32 / rings as f32;
+        let phi = 2.0 * PI * segment as f32 / segments as f32;
+        [theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()]
+    };
+
+    let mut triangles = Vec::with_capacity(rings * segments * 2);
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let a = point(ring, segment);
+            let b = point(ring + 1, segment);
+            let c = point(ring + 1, segment + 1);
+            let d = point(ring, segment + 1);
+            triangles.push([a, b, c]);
+            triangles.push([a, c, d]);
+        }
+    }
+    triangles
+}
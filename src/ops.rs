@@ -0,0 +1,269 @@
+// Long-running operations with completion callbacks and cancellation tokens.
+//
+// Every async entry point takes a completion callback plus an opaque context pointer
+// and returns a non-zero operation token. The callback is invoked exactly once, from
+// a background thread, with one of the AR_STATUS_* codes below; this maps directly onto
+// Swift's `withCheckedThrowingContinuation`. Passing the token to `cancel_operation`
+// asks the operation to stop at its next checkpoint, which then completes with
+// AR_STATUS_CANCELLED
+
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr, CString};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::handoff::base64_encode;
+use crate::snapshot::SessionSnapshot;
+#[cfg(feature = "text")]
+use crate::text_mesh;
+use crate::with_session;
+
+pub const AR_STATUS_OK: i32 = 0;
+pub const AR_STATUS_CANCELLED: i32 = 1;
+pub const AR_STATUS_FAILED: i32 = 2;
+
+// `payload` is the operation's result on success (may be null), or an error message
+// on failure. It is only valid for the duration of the callback
+pub type ARCompletionCallback = extern "C" fn(context: *mut c_void, status: i32, payload: *const libc::c_char);
+
+pub(crate) enum Outcome {
+    Completed(Option<String>),
+    Cancelled,
+    Failed(String),
+}
+
+// Shared flag checked by a running operation
+#[derive(Clone, Default)]
+pub(crate) struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// The host's context pointer is only handed back to the host's callback
+struct Context(*mut c_void);
+unsafe impl Send for Context {}
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+fn operations() -> &'static Mutex<HashMap<u64, CancellationToken>> {
+    static OPERATIONS: OnceLock<Mutex<HashMap<u64, CancellationToken>>> = OnceLock::new();
+    OPERATIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Run `work` on a background thread and report its outcome through `callback`
+pub(crate) fn spawn_operation(
+    callback: ARCompletionCallback,
+    context: *mut c_void,
+    work: impl FnOnce(&CancellationToken) -> Outcome + Send + 'static,
+) -> u64 {
+    let token_id = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    let token = CancellationToken::default();
+    if let Ok(mut ops) = operations().lock() {
+        ops.insert(token_id, token.clone());
+    }
+
+    let context = Context(context);
+    std::thread::spawn(move || {
+        // Capture the whole wrapper, not just its (non-Send) pointer field
+        let context = context;
+        let outcome = if token.is_cancelled() { Outcome::Cancelled } else { work(&token) };
+
+        // Unregister before completing so a late cancel reports false
+        if let Ok(mut ops) = operations().lock() {
+            ops.remove(&token_id);
+        }
+
+        let (status, payload) = match outcome {
+            Outcome::Completed(payload) => (AR_STATUS_OK, payload),
+            Outcome::Cancelled => (AR_STATUS_CANCELLED, None),
+            Outcome::Failed(message) => (AR_STATUS_FAILED, Some(message)),
+        };
+        let payload = payload.map(|p| CString::new(p.replace('\0', "")).unwrap_or_default());
+        callback(context.0, status, payload.as_ref().map_or(std::ptr::null(), |p| p.as_ptr()));
+    });
+
+    token_id
+}
+
+// Request cancellation of a running operation. Returns false if the token is unknown
+// or the operation has already completed
+#[no_mangle]
+pub extern "C" fn cancel_operation(token: u64) -> bool {
+    match operations().lock() {
+        Ok(ops) => match ops.get(&token) {
+            Some(cancellation) => {
+                cancellation.cancel();
+                true
+            }
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+// Export the current session as JSON. The state is captured when this is called;
+// serialization and file I/O happen in the background. With a null path the JSON is
+// returned as the callback payload instead of being written. Returns 0 (and does not
+// invoke the callback) if there is no session
+#[no_mangle]
+pub extern "C" fn export_snapshot_async(
+    path_ptr: *const libc::c_char,
+    callback: ARCompletionCallback,
+    context: *mut c_void,
+) -> u64 {
+    let Some(snapshot) = SessionSnapshot::capture() else {
+        return 0;
+    };
+
+    let path = path_from(path_ptr);
+
+    spawn_operation(callback, context, move |token| match serde_json::to_string(&snapshot) {
+        Ok(json) => deliver_json(json, path, token),
+        Err(err) => Outcome::Failed(err.to_string()),
+    })
+}
+
+// Export a handoff bundle as JSON, as export_handoff_bundle does, including the host's
+// archived ARWorldMap (`world_map` may be null; it's copied). The session is captured
+// when this is called; encoding the world map, serialization and file I/O happen in the
+// background. With a null path the JSON is returned as the callback payload instead of
+// being written. Returns 0 (and does not invoke the callback) if there is no session
+#[no_mangle]
+pub extern "C" fn export_handoff_bundle_async(
+    world_map: *const u8,
+    world_map_len: u32,
+    path_ptr: *const libc::c_char,
+    callback: ARCompletionCallback,
+    context: *mut c_void,
+) -> u64 {
+    let Some(mut bundle) = with_session(|session| session.handoff_bundle(None)) else {
+        return 0;
+    };
+    let world_map = (!world_map.is_null()).then(|| unsafe { std::slice::from_raw_parts(world_map, world_map_len as usize) }.to_vec());
+    let path = path_from(path_ptr);
+
+    spawn_operation(callback, context, move |token| {
+        bundle.world_map = world_map.as_deref().map(base64_encode);
+        if token.is_cancelled() {
+            return Outcome::Cancelled;
+        }
+        match serde_json::to_string(&bundle) {
+            Ok(json) => deliver_json(json, path, token),
+            Err(err) => Outcome::Failed(err.to_string()),
+        }
+    })
+}
+
+// Register font data (TrueType or OpenType) under `name`, as register_font does, with
+// parsing in the background. The data is copied. Fails if it can't be parsed or would
+// take registered font data over the asset quota. Returns 0 (and does not invoke the
+// callback) for a null name or data
+#[cfg(feature = "text")]
+#[no_mangle]
+pub extern "C" fn register_font_async(
+    name_ptr: *const libc::c_char,
+    data: *const u8,
+    length: u32,
+    callback: ARCompletionCallback,
+    context: *mut c_void,
+) -> u64 {
+    if name_ptr.is_null() || data.is_null() {
+        return 0;
+    }
+    let name = unsafe { CStr::from_ptr(name_ptr) }.to_string_lossy().into_owned();
+    let bytes: Arc<[u8]> = unsafe { std::slice::from_raw_parts(data, length as usize) }.into();
+
+    spawn_operation(callback, context, move |token| {
+        if !text_mesh::is_font(&bytes) {
+            return Outcome::Failed(format!("font data for {} could not be parsed", name));
+        }
+        if token.is_cancelled() {
+            return Outcome::Cancelled;
+        }
+        if !text_mesh::insert_font(name.clone(), bytes) {
+            return Outcome::Failed(format!("font {} would exceed the asset quota", name));
+        }
+        Outcome::Completed(None)
+    })
+}
+
+fn path_from(path_ptr: *const libc::c_char) -> Option<PathBuf> {
+    (!path_ptr.is_null()).then(|| PathBuf::from(unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().into_owned()))
+}
+
+// Finish an export: return `json` as the payload without a path, or write it there
+fn deliver_json(json: String, path: Option<PathBuf>, token: &CancellationToken) -> Outcome {
+    let Some(path) = path else {
+        return if token.is_cancelled() { Outcome::Cancelled } else { Outcome::Completed(Some(json)) };
+    };
+
+    // Write to a temporary file so a cancelled or failed export never leaves a
+    // partial file at the destination
+    let mut temp_path = path.clone().into_os_string();
+    temp_path.push(".partial");
+    let temp_path = PathBuf::from(temp_path);
+    if let Err(err) = std::fs::write(&temp_path, json) {
+        return Outcome::Failed(format!("failed to write {}: {}", temp_path.display(), err));
+    }
+    if token.is_cancelled() {
+        let _ = std::fs::remove_file(&temp_path);
+        return Outcome::Cancelled;
+    }
+    match std::fs::rename(&temp_path, &path) {
+        Ok(()) => Outcome::Completed(None),
+        Err(err) => Outcome::Failed(format!("failed to write {}: {}", path.display(), err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Sender};
+
+    use super::*;
+
+    extern "C" fn send_status(context: *mut c_void, status: i32, payload: *const libc::c_char) {
+        let sender = unsafe { Box::from_raw(context as *mut Sender<(i32, Option<String>)>) };
+        let payload = (!payload.is_null()).then(|| unsafe { CStr::from_ptr(payload) }.to_string_lossy().into_owned());
+        sender.send((status, payload)).unwrap();
+    }
+
+    #[test]
+    fn json_is_returned_without_a_path_and_written_with_one() {
+        let token = CancellationToken::default();
+        assert!(matches!(deliver_json("{}".to_string(), None, &token), Outcome::Completed(Some(json)) if json == "{}"));
+
+        let path = std::env::temp_dir().join(format!("arlens_ops_{}.json", std::process::id()));
+        assert!(matches!(deliver_json("{}".to_string(), Some(path.clone()), &token), Outcome::Completed(None)));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        std::fs::remove_file(&path).unwrap();
+
+        // Cancelled mid-write: nothing is left behind
+        token.cancel();
+        assert!(matches!(deliver_json("{}".to_string(), Some(path.clone()), &token), Outcome::Cancelled));
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "text")]
+    #[test]
+    fn unparseable_font_fails_through_the_callback() {
+        let (sender, receiver) = channel::<(i32, Option<String>)>();
+        let context = Box::into_raw(Box::new(sender)) as *mut c_void;
+        let data: [u8; 0] = [];
+        let token = register_font_async(c"broken".as_ptr(), data.as_ptr(), data.len() as u32, send_status, context);
+        assert_ne!(token, 0);
+
+        let (status, message) = receiver.recv().unwrap();
+        assert_eq!(status, AR_STATUS_FAILED);
+        assert!(message.unwrap().contains("broken"));
+        // Completed operations can no longer be cancelled
+        assert!(!cancel_operation(token));
+    }
+}
@@ -0,0 +1,426 @@
+// Lightweight physics for placed content. Objects opt in as dynamic bodies and are
+// then moved by gravity, force fields, springs and joints, and collide with detected
+// planes and each other as spheres or boxes (see contacts.rs).
+//
+// Free bodies are swept against planes each substep (continuous collision), so fast
+// throws bounce off instead of tunneling through, with restitution and friction from
+// the object's and the plane's materials. Jointed bodies are position-based: joints
+// project their predicted positions back into place and velocities are derived from
+// the corrected motion, which keeps joints stable at frame-rate time steps
+
+use crate::force_fields::{field_force, ForceField};
+use crate::joints::{Joint, JointKind};
+use crate::contacts::{Collider, ColliderShape};
+use crate::math::{add, dot, length, quat_from_axis_angle, quat_mul, quat_normalize, scale, sub};
+use crate::render::DEFAULT_OBJECT_SIZE;
+use crate::sleep::SleepConfig;
+use crate::surfaces::{default_materials, SurfaceMaterial};
+use crate::{with_session, ARObject, ARObjectType, ARPlane, ARSession, PlaneClassification};
+
+// Fixed substep; frames are split into as many as needed, up to MAX_SUBSTEPS
+const SUBSTEP: f32 = 1.0 / 120.0;
+const MAX_SUBSTEPS: usize = 8;
+
+// Sweeps per substep; each can end in one bounce
+const MAX_BOUNCES: usize = 3;
+
+// Seconds between points of a predicted trajectory
+const TRAJECTORY_STEP: f32 = 1.0 / 30.0;
+
+// Fraction of spin lost per second, so toppled objects come to rest
+const ANGULAR_DAMPING: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RigidBody {
+    pub velocity: [f32; 3],
+    // Radians per second about a world-space axis
+    pub angular_velocity: [f32; 3],
+    pub mass: f32,
+    pub material: SurfaceMaterial,
+    pub shape: ColliderShape,
+    // See sleep.rs
+    pub sleeping: bool,
+    pub still_frames: u32,
+}
+
+impl RigidBody {
+    // Spheres collide as spheres, primitives with their own shape, text as its bounding
+    // box and everything else as a box of the default object size
+    pub fn for_object(object: &ARObject, mass: f32) -> Self {
+        let shape = match &object.object_type {
+            ARObjectType::Sphere => ColliderShape::Bounds,
+            ARObjectType::Primitive(primitive) => primitive.collider_shape(),
+            #[cfg(feature = "text")]
+            ARObjectType::Text(label) => ColliderShape::Box(label.half_extents),
+            _ => ColliderShape::Box([DEFAULT_OBJECT_SIZE * 0.5; 3]),
+        };
+        RigidBody {
+            velocity: [0.0; 3],
+            angular_velocity: [0.0; 3],
+            mass,
+            material: SurfaceMaterial::new(0.5, 0.3),
+            shape,
+            sleeping: false,
+            still_frames: 0,
+        }
+    }
+}
+
+// Bounce a velocity off a surface with the given (unit) normal. Restitution is the
+// fraction of normal speed kept; friction is a Coulomb coefficient
+fn respond(velocity: [f32; 3], normal: [f32; 3], material: SurfaceMaterial) -> [f32; 3] {
+    let normal_speed = dot(velocity, normal);
+    if normal_speed >= 0.0 {
+        return velocity;
+    }
+    let tangential = sub(velocity, scale(normal, normal_speed));
+    let tangential_speed = length(tangential);
+    // Friction impulse is bounded by the normal impulse
+    let friction = (material.friction * (1.0 + material.restitution) * -normal_speed).min(tangential_speed);
+    let tangential = if tangential_speed > 1e-6 {
+        sub(tangential, scale(tangential, friction / tangential_speed))
+    } else {
+        tangential
+    };
+    add(tangential, scale(normal, -normal_speed * material.restitution))
+}
+
+// Earliest plane a sphere moving by `motion` would touch, approaching from either
+// side: the fraction of the motion travelled, the plane's index and the normal facing
+// the sphere
+fn first_hit(planes: &[ARPlane], position: [f32; 3], motion: [f32; 3], radius: f32) -> Option<(f32, usize, [f32; 3])> {
+    let end = add(position, motion);
+    let mut hit: Option<(f32, usize, [f32; 3])> = None;
+    for (index, plane) in planes.iter().enumerate() {
+        let start_distance = dot(sub(position, plane.center), plane.normal);
+        let side = if start_distance >= 0.0 { 1.0 } else { -1.0 };
+        let (d0, d1) = (start_distance * side, dot(sub(end, plane.center), plane.normal) * side);
+        // Already touching is left to the overlap pass
+        if d0 < radius || d1 >= radius {
+            continue;
+        }
+        let t = (d0 - radius) / (d0 - d1);
+        if !plane.within_extent(add(position, scale(motion, t))) {
+            continue;
+        }
+        if hit.is_none_or(|(best, ..)| t < best) {
+            hit = Some((t, index, scale(plane.normal, side)));
+        }
+    }
+    hit
+}
+
+// Move a sphere by `velocity * h`, stopping at the first plane it would cross and
+// continuing with the bounced velocity for the rest of the step
+fn sweep(planes: &[ARPlane], materials: &[SurfaceMaterial], mut position: [f32; 3], body: &mut RigidBody, radius: f32, h: f32) -> [f32; 3] {
+    let mut remaining = h;
+    for _ in 0..MAX_BOUNCES {
+        let motion = scale(body.velocity, remaining);
+        let Some((t, plane, normal)) = first_hit(planes, position, motion, radius) else {
+            return add(position, motion);
+        };
+        position = add(position, scale(motion, t));
+        body.velocity = respond(body.velocity, normal, body.material.combine(materials[plane]));
+        remaining *= 1.0 - t;
+        if remaining <= 0.0 {
+            break;
+        }
+    }
+    position
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PhysicsWorld {
+    pub gravity: [f32; 3],
+    pub joints: Vec<Joint>,
+    pub next_joint_id: i32,
+    pub force_fields: Vec<ForceField>,
+    pub next_force_field_id: i32,
+    // Plane materials by PlaneClassification
+    pub classification_materials: [SurfaceMaterial; PlaneClassification::COUNT],
+    pub sleep: SleepConfig,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        PhysicsWorld {
+            gravity: [0.0, -9.81, 0.0],
+            joints: Vec::new(),
+            next_joint_id: 1,
+            force_fields: Vec::new(),
+            next_force_field_id: 1,
+            classification_materials: default_materials(),
+            sleep: SleepConfig::default(),
+        }
+    }
+}
+
+impl ARSession {
+    pub(crate) fn step_physics(&mut self, dt: f32) {
+        let substeps = ((dt / SUBSTEP).ceil() as usize).clamp(1, MAX_SUBSTEPS);
+        let h = dt / substeps as f32;
+        let before: Vec<_> = self.virtual_objects.iter().map(|object| (object.position, object.rotation)).collect();
+        for _ in 0..substeps {
+            self.physics_substep(h);
+        }
+        self.update_sleep(dt, &before);
+    }
+
+    fn physics_substep(&mut self, h: f32) {
+        let gravity = self.physics.gravity;
+        let forces = self.joint_forces();
+
+        // Objects held by position constraints get velocities from their corrected motion
+        let mut constrained = vec![false; self.virtual_objects.len()];
+        for joint in &self.physics.joints {
+            if !matches!(joint.kind, JointKind::Spring { .. }) {
+                constrained[joint.object] = true;
+            }
+        }
+
+        let materials: Vec<_> = (0..self.detected_planes.len()).map(|index| self.plane_material(index)).collect();
+        let mut previous = Vec::with_capacity(self.virtual_objects.len());
+        for (index, object) in self.virtual_objects.iter_mut().enumerate() {
+            previous.push(object.position);
+            let Some(radius) = object.body.as_ref().map(|body| Collider::of(object, body).inner_radius()) else {
+                continue;
+            };
+            let Some(body) = object.body.as_mut().filter(|body| !body.sleeping) else {
+                continue;
+            };
+            let force = add(forces[index], field_force(&self.physics.force_fields, object.position));
+            let acceleration = add(gravity, scale(force, 1.0 / body.mass));
+            body.velocity = add(body.velocity, scale(acceleration, h));
+            if constrained[index] {
+                object.position = add(object.position, scale(body.velocity, h));
+                continue;
+            }
+            object.position = sweep(&self.detected_planes, &materials, object.position, body, radius, h);
+
+            let spin = length(body.angular_velocity);
+            if spin > 1e-6 {
+                let step = quat_from_axis_angle(scale(body.angular_velocity, 1.0 / spin), spin * h);
+                object.rotation = quat_normalize(quat_mul(step, object.rotation));
+                body.angular_velocity = scale(body.angular_velocity, (1.0 - ANGULAR_DAMPING * h).max(0.0));
+            }
+        }
+
+        self.solve_joints();
+        self.resolve_contacts(h);
+        for (index, (object, previous)) in self.virtual_objects.iter_mut().zip(previous).enumerate() {
+            if let Some(body) = object.body.as_mut().filter(|_| constrained[index]) {
+                body.velocity = scale(sub(object.position, previous), 1.0 / h);
+            }
+        }
+    }
+
+    // Ballistic arc from `origin` under gravity, one point per TRAJECTORY_STEP, ending
+    // at the first plane it hits. Returns the points and whether it hit
+    pub(crate) fn predict_trajectory(&self, origin: [f32; 3], mut velocity: [f32; 3], steps: usize) -> (Vec<[f32; 3]>, bool) {
+        let mut points = vec![origin];
+        let mut position = origin;
+        while points.len() < steps {
+            velocity = add(velocity, scale(self.physics.gravity, TRAJECTORY_STEP));
+            let motion = scale(velocity, TRAJECTORY_STEP);
+            if let Some((t, ..)) = first_hit(&self.detected_planes, position, motion, 0.0) {
+                points.push(add(position, scale(motion, t)));
+                return (points, true);
+            }
+            position = add(position, motion);
+            points.push(position);
+        }
+        (points, false)
+    }
+}
+
+// Make an object a dynamic body with the given mass in kilograms, or static again.
+// Returns false for an invalid id
+#[no_mangle]
+pub extern "C" fn set_object_physics(object_id: i32, enabled: bool, mass: f32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.body = enabled.then(|| RigidBody::for_object(object, mass.max(0.001)));
+        if enabled {
+            object.camera_attachment = None;
+        }
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Gravity in m/s^2, (0, -9.81, 0) by default
+#[no_mangle]
+pub extern "C" fn set_gravity(x: f32, y: f32, z: f32) {
+    with_session(|session| {
+        session.physics.gravity = [x, y, z];
+        session.wake_all();
+    });
+}
+
+// Per-object surface response: restitution (0 = no bounce, 1 = perfectly elastic) and
+// friction coefficient, averaged with the plane's on contact. Returns false for an
+// invalid id or a non-dynamic object
+#[no_mangle]
+pub extern "C" fn set_object_material(object_id: i32, restitution: f32, friction: f32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(body) = session.virtual_objects.get_mut(index).and_then(|o| o.body.as_mut()) else {
+            return false;
+        };
+        body.material = SurfaceMaterial::new(friction, restitution);
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Set a dynamic object's velocity in m/s, e.g. to throw it. Returns false for an
+// invalid id or a non-dynamic object
+#[no_mangle]
+pub extern "C" fn set_object_velocity(object_id: i32, x: f32, y: f32, z: f32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(body) = session.virtual_objects.get_mut(index).and_then(|o| o.body.as_mut()) else {
+            return false;
+        };
+        body.velocity = [x, y, z];
+        session.wake_object(index);
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Throw an object: it becomes dynamic (mass 1 kg if it wasn't already), leaves its
+// anchor, and starts moving at the given velocity in m/s. Returns false for an
+// invalid id
+#[no_mangle]
+pub extern "C" fn launch_object(object_id: i32, x: f32, y: f32, z: f32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        object.anchor = None;
+        object.camera_attachment = None;
+        if let Some(stabilizer) = object.stabilizer.as_mut() {
+            stabilizer.reset();
+        }
+        if object.body.is_none() {
+            object.body = Some(RigidBody::for_object(object, 1.0));
+        }
+        if let Some(body) = object.body.as_mut() {
+            body.velocity = [x, y, z];
+        }
+        session.wake_object(index);
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Preview where a point launched from `origin` at `velocity` goes under the current
+// gravity. Writes up to `capacity` points (3 floats each, 1/30 s apart, starting at the
+// origin) to `out_points`, the last being the impact point when the arc hits a plane
+// within that span; `out_hit` (may be null) reports whether it did. Returns the number
+// of points written
+#[no_mangle]
+pub extern "C" fn predict_trajectory(
+    origin_x: f32, origin_y: f32, origin_z: f32,
+    velocity_x: f32, velocity_y: f32, velocity_z: f32,
+    out_points: *mut f32,
+    out_hit: *mut bool,
+    capacity: u32
+) -> i32 {
+    if out_points.is_null() || capacity == 0 {
+        return -1;
+    }
+
+    with_session(|session| {
+        let origin = [origin_x, origin_y, origin_z];
+        let (points, hit) = session.predict_trajectory(origin, [velocity_x, velocity_y, velocity_z], capacity as usize);
+        unsafe {
+            let out = std::slice::from_raw_parts_mut(out_points, points.len() * 3);
+            for (chunk, point) in out.chunks_exact_mut(3).zip(&points) {
+                chunk.copy_from_slice(point);
+            }
+            if !out_hit.is_null() {
+                *out_hit = hit;
+            }
+        }
+        points.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Collide a dynamic object as a box with the given half extents in meters (before the
+// object's scale), e.g. a tall narrow box for a bowling pin. Zero extents collide as
+// the bounding sphere instead. Returns false for an invalid id or a non-dynamic object
+#[no_mangle]
+pub extern "C" fn set_object_collider(object_id: i32, half_x: f32, half_y: f32, half_z: f32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+    let half_extents = [half_x, half_y, half_z];
+
+    with_session(|session| {
+        let Some(body) = session.virtual_objects.get_mut(index).and_then(|o| o.body.as_mut()) else {
+            return false;
+        };
+        body.shape = if half_extents.iter().all(|&h| h > 0.0) { ColliderShape::Box(half_extents) } else { ColliderShape::Bounds };
+        session.wake_object(index);
+        true
+    })
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    // A small sphere thrown straight down at a floor from a meter up, at a speed that
+    // covers several meters per substep
+    fn thrown_at_floor(x: f32) -> ARSession {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".into()), [0.0; 3], [1.0, 1.0], [0.0, 1.0, 0.0]);
+        let index = session.place_object(ARObjectType::Sphere, [x, 1.0, 0.0], IDENTITY).unwrap();
+        let mut body = RigidBody::for_object(&session.virtual_objects[index], 1.0);
+        body.velocity = [0.0, -300.0, 0.0];
+        session.virtual_objects[index].body = Some(body);
+        session
+    }
+
+    #[test]
+    fn fast_bodies_bounce_off_planes_instead_of_tunneling() {
+        let mut session = thrown_at_floor(0.0);
+        session.step_physics(1.0 / 60.0);
+
+        let object = &session.virtual_objects[0];
+        assert!(object.position[1] > 0.0, "tunneled to {:?}", object.position);
+        assert!(object.body.unwrap().velocity[1] > 0.0);
+    }
+
+    #[test]
+    fn bodies_pass_beside_a_plane_outside_its_extent() {
+        let mut session = thrown_at_floor(2.0);
+        session.step_physics(1.0 / 60.0);
+
+        let object = &session.virtual_objects[0];
+        assert!(object.position[1] < -1.0);
+        assert!(object.body.unwrap().velocity[1] < 0.0);
+    }
+}
@@ -0,0 +1,129 @@
+// Plane expiry. ARKit and depth extraction keep adding planes but never take any away,
+// so a long session walking through a building piles up hundreds of planes the user
+// left behind, and every query (physics, decals, coverage, placement) pays for them.
+// With expiry on, a plane not updated or seen for `stale_after` seconds is flagged
+// stale, and one left alone for `remove_after` seconds is removed, with plane_stale /
+// plane_removed events. A stale plane that's seen or updated again is refreshed, with a
+// plane_refreshed event.
+//
+// A plane counts as seen while any of its coverage cells is in view (see coverage.rs).
+// Both timeouts are off by default; planes are indexed by position, so removal shifts
+// the indices of later planes, as object removal does
+
+use crate::events::SessionEvent;
+use crate::{with_session, ARSession};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct PlaneExpiryConfig {
+    // Seconds without an update or observation; 0 disables
+    pub stale_after: f64,
+    pub remove_after: f64,
+}
+
+impl ARSession {
+    // Flag, refresh and remove planes by how long they've been left alone. Once per frame
+    pub(crate) fn update_plane_expiry(&mut self) {
+        let config = self.plane_expiry;
+        if config.stale_after <= 0.0 && config.remove_after <= 0.0 {
+            return;
+        }
+        let now = self.clock.now();
+
+        let mut index = 0;
+        while index < self.detected_planes.len() {
+            let plane = &mut self.detected_planes[index];
+            let idle = now - plane.updated_at.max(plane.observed_at);
+            if config.remove_after > 0.0 && idle >= config.remove_after {
+                let plane = self.detected_planes.remove(index);
+                self.coverage.remove(&plane.id);
+                self.metrics.planes_expired += 1;
+                self.events.push(SessionEvent::PlaneRemoved { plane_id: plane.id });
+                continue;
+            }
+
+            let stale = config.stale_after > 0.0 && idle >= config.stale_after;
+            match (plane.stale, stale) {
+                (false, true) => self.events.push(SessionEvent::PlaneStale { plane_id: plane.id.clone() }),
+                (true, false) => self.events.push(SessionEvent::PlaneRefreshed { plane_id: plane.id.clone() }),
+                _ => {}
+            }
+            plane.stale = stale;
+            index += 1;
+        }
+    }
+}
+
+// Flag planes stale after `stale_seconds` without an update or observation, and remove
+// them after `remove_seconds`; 0 disables either. Returns false for a negative or
+// non-finite duration
+#[no_mangle]
+pub extern "C" fn set_plane_expiry(stale_seconds: f32, remove_seconds: f32) -> bool {
+    if [stale_seconds, remove_seconds].iter().any(|seconds| !seconds.is_finite() || *seconds < 0.0) {
+        return false;
+    }
+
+    with_session(|session| {
+        session.plane_expiry = PlaneExpiryConfig { stale_after: stale_seconds as f64, remove_after: remove_seconds as f64 };
+    })
+    .is_some()
+}
+
+// Whether the plane at `index` is stale as of the last frame. False for a bad index
+#[no_mangle]
+pub extern "C" fn is_plane_stale(index: i32) -> bool {
+    let Ok(index) = usize::try_from(index) else {
+        return false;
+    };
+
+    with_session(|session| session.detected_planes.get(index).is_some_and(|plane| plane.stale)).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advance_clock(session: &mut ARSession, seconds: f64) {
+        let now = session.clock.now();
+        session.clock.sync(now + seconds);
+    }
+
+    fn drain(session: &mut ARSession) -> Vec<SessionEvent> {
+        std::iter::from_fn(|| session.events.pop()).collect()
+    }
+
+    #[test]
+    fn rereported_plane_updates_in_place() {
+        let mut session = ARSession::new();
+        assert!(session.add_plane(Some("floor".into()), [0.0; 3], [1.0, 1.0], [0.0, 1.0, 0.0]));
+        assert!(session.add_plane(Some("floor".into()), [0.5, 0.0, 0.0], [2.0, 1.0], [0.0, 1.0, 0.0]));
+
+        assert_eq!(session.detected_planes.len(), 1);
+        assert_eq!(session.detected_planes[0].center, [0.5, 0.0, 0.0]);
+        assert_eq!(session.detected_planes[0].extent, [2.0, 1.0]);
+        assert_eq!(session.metrics.planes_added, 1);
+    }
+
+    #[test]
+    fn native_plane_goes_stale_refreshes_and_expires() {
+        let mut session = ARSession::new();
+        session.plane_expiry = PlaneExpiryConfig { stale_after: 5.0, remove_after: 10.0 };
+        session.add_plane(Some("floor".into()), [0.0; 3], [1.0, 1.0], [0.0, 1.0, 0.0]);
+
+        advance_clock(&mut session, 6.0);
+        session.update_plane_expiry();
+        assert!(session.detected_planes[0].stale);
+        assert_eq!(drain(&mut session), [SessionEvent::PlaneStale { plane_id: "floor".into() }]);
+
+        // ARKit reporting it again counts as an update
+        session.add_plane(Some("floor".into()), [0.0; 3], [1.0, 1.0], [0.0, 1.0, 0.0]);
+        session.update_plane_expiry();
+        assert!(!session.detected_planes[0].stale);
+        assert_eq!(drain(&mut session), [SessionEvent::PlaneRefreshed { plane_id: "floor".into() }]);
+
+        advance_clock(&mut session, 11.0);
+        session.update_plane_expiry();
+        assert!(session.detected_planes.is_empty());
+        assert_eq!(session.metrics.planes_expired, 1);
+        assert_eq!(drain(&mut session), [SessionEvent::PlaneRemoved { plane_id: "floor".into() }]);
+    }
+}
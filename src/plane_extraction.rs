@@ -0,0 +1,254 @@
+// RANSAC plane extraction from the processed point cloud. Finds planes ARKit tends
+// to miss (shelves, stair steps, small tabletops) and merges them into the session's
+// plane set as derived planes. Hypothesis scoring, the expensive part, runs as a
+// Metal compute kernel on iOS when available and on the CPU otherwise
+
+use tracing::debug;
+
+use crate::analytics::{self, Feature};
+use crate::math::{add, dot, normalize, scale, sub, tangent_basis};
+use crate::pointcloud::CloudPoint;
+use crate::quotas::Resource;
+use crate::rng::Rng;
+use crate::{with_session, ARPlane, ARSession, PlaneClassification, PlaneSource};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PlaneExtractionConfig {
+    // Max point-to-plane distance in meters for an inlier
+    pub distance_threshold: f32,
+    // Min |cos| between a point's normal and the plane normal for an inlier
+    pub normal_threshold: f32,
+    pub min_inliers: usize,
+    pub hypotheses: usize,
+    pub max_planes: usize,
+    // How close (meters) a candidate must be to an existing plane to be the same plane
+    pub merge_distance: f32,
+}
+
+impl Default for PlaneExtractionConfig {
+    fn default() -> Self {
+        PlaneExtractionConfig {
+            distance_threshold: 0.02,
+            normal_threshold: 0.9,
+            min_inliers: 50,
+            hypotheses: 128,
+            max_planes: 8,
+            merge_distance: 0.05,
+        }
+    }
+}
+
+// A fitted plane before it is merged into the session
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PlaneCandidate {
+    pub center: [f32; 3],
+    pub extent: [f32; 2],
+    pub normal: [f32; 3],
+    pub inliers: usize,
+}
+
+fn is_inlier(hypothesis: &[f32; 4], point: &CloudPoint, config: &PlaneExtractionConfig) -> bool {
+    let normal = [hypothesis[0], hypothesis[1], hypothesis[2]];
+    (dot(normal, point.position) + hypothesis[3]).abs() < config.distance_threshold
+        && dot(normal, point.normal).abs() >= config.normal_threshold
+}
+
+// Inlier count per (nx, ny, nz, d) hypothesis
+pub(crate) fn count_inliers_cpu(points: &[CloudPoint], hypotheses: &[[f32; 4]], config: &PlaneExtractionConfig) -> Vec<u32> {
+    hypotheses.iter()
+        .map(|h| points.iter().filter(|p| is_inlier(h, p, config)).count() as u32)
+        .collect()
+}
+
+fn count_inliers(points: &[CloudPoint], hypotheses: &[[f32; 4]], config: &PlaneExtractionConfig) -> Vec<u32> {
+    #[cfg(target_os = "ios")]
+    if let Some(counts) = crate::pointcloud_metal::count_inliers(points, hypotheses, config) {
+        return counts;
+    }
+    count_inliers_cpu(points, hypotheses, config)
+}
+
+// Sequential RANSAC: repeatedly find the best-supported plane among the remaining
+// points, fit it to its inliers and remove them
+pub(crate) fn extract_planes(points: &[CloudPoint], config: &PlaneExtractionConfig, rng: &mut Rng) -> Vec<PlaneCandidate> {
+    let mut remaining: Vec<CloudPoint> = points.iter()
+        .filter(|p| p.normal != [0.0; 3])
+        .copied()
+        .collect();
+    let mut candidates = Vec::new();
+
+    while candidates.len() < config.max_planes && remaining.len() >= config.min_inliers.max(1) {
+        // Each point with a normal defines a plane hypothesis on its own
+        let hypotheses: Vec<[f32; 4]> = (0..config.hypotheses)
+            .map(|_| {
+                let p = remaining[rng.below(remaining.len())];
+                [p.normal[0], p.normal[1], p.normal[2], -dot(p.normal, p.position)]
+            })
+            .collect();
+
+        let counts = count_inliers(&remaining, &hypotheses, config);
+        let Some((best, &count)) = counts.iter().enumerate().max_by_key(|(_, count)| **count) else {
+            break;
+        };
+        if (count as usize) < config.min_inliers {
+            break;
+        }
+
+        let (inliers, outliers): (Vec<CloudPoint>, Vec<CloudPoint>) = remaining.iter()
+            .partition(|p| is_inlier(&hypotheses[best], p, config));
+        let reference = [hypotheses[best][0], hypotheses[best][1], hypotheses[best][2]];
+        candidates.push(fit_plane(&inliers, reference));
+        remaining = outliers;
+    }
+
+    candidates
+}
+
+// Refine a hypothesis from its inliers: centroid plus averaged (consistently oriented) normals,
+// with the extent taken from the inliers' bounds in the plane's tangent basis
+fn fit_plane(inliers: &[CloudPoint], reference_normal: [f32; 3]) -> PlaneCandidate {
+    let count = inliers.len() as f32;
+    let mut centroid = [0.0; 3];
+    let mut normal = [0.0; 3];
+    for p in inliers {
+        centroid = add(centroid, p.position);
+        let sign = if dot(p.normal, reference_normal) < 0.0 { -1.0 } else { 1.0 };
+        normal = add(normal, scale(p.normal, sign));
+    }
+    let centroid = scale(centroid, 1.0 / count);
+    let normal = normalize(normal);
+
+    let (tangent, bitangent) = tangent_basis(normal);
+    let (mut min_u, mut max_u, mut min_v, mut max_v) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+    for p in inliers {
+        let offset = sub(p.position, centroid);
+        let (u, v) = (dot(offset, tangent), dot(offset, bitangent));
+        min_u = min_u.min(u);
+        max_u = max_u.max(u);
+        min_v = min_v.min(v);
+        max_v = max_v.max(v);
+    }
+
+    let center = add(centroid, add(scale(tangent, (min_u + max_u) * 0.5), scale(bitangent, (min_v + max_v) * 0.5)));
+    // Project the center back onto the plane through the centroid
+    let center = sub(center, scale(normal, dot(sub(center, centroid), normal)));
+
+    PlaneCandidate {
+        center,
+        extent: [max_u - min_u, max_v - min_v],
+        normal,
+        inliers: inliers.len(),
+    }
+}
+
+// Whether a candidate lies on (and within the bounds of) an existing plane
+fn plane_matches(plane: &ARPlane, candidate: &PlaneCandidate, config: &PlaneExtractionConfig) -> bool {
+    let normal = normalize(plane.normal);
+    if dot(normal, candidate.normal).abs() < config.normal_threshold {
+        return false;
+    }
+
+    let offset = sub(candidate.center, plane.center);
+    if dot(offset, normal).abs() > config.merge_distance {
+        return false;
+    }
+
+    let (tangent, bitangent) = tangent_basis(normal);
+    dot(offset, tangent).abs() <= plane.extent[0] * 0.5 + config.merge_distance
+        && dot(offset, bitangent).abs() <= plane.extent[1] * 0.5 + config.merge_distance
+}
+
+impl ARSession {
+    // Merge candidates into the plane set. Candidates covered by a native plane are
+    // dropped; ones matching a derived plane update it. Returns how many derived
+    // planes were added or updated
+    fn merge_derived_planes(&mut self, candidates: &[PlaneCandidate]) -> usize {
+        let config = self.plane_extraction_config;
+        let now = self.clock.now();
+        let mut merged = 0;
+
+        for candidate in candidates {
+            let matches = |plane: &ARPlane| plane_matches(plane, candidate, &config);
+
+            if self.detected_planes.iter().any(|p| p.source == PlaneSource::Native && matches(p)) {
+                continue;
+            }
+
+            if let Some(plane) = self.detected_planes.iter_mut().find(|p| p.source == PlaneSource::Derived && matches(p)) {
+                plane.center = candidate.center;
+                plane.extent = candidate.extent;
+                plane.normal = candidate.normal;
+                plane.updated_at = now;
+            } else {
+                if !self.admit(Resource::Planes, self.detected_planes.len() as u64 + 1) {
+                    continue;
+                }
+                let id = self.qualify_id(&format!("derived_{}", self.plane_number())).into_owned();
+                self.detected_planes.push(ARPlane {
+                    id,
+                    center: candidate.center,
+                    extent: candidate.extent,
+                    normal: candidate.normal,
+                    source: PlaneSource::Derived,
+                    classification: PlaneClassification::None,
+                    #[cfg(feature = "physics")]
+                    material: None,
+                    detected_at: now,
+                    updated_at: now,
+                    observed_at: now,
+                    stale: false,
+                });
+                self.metrics.derived_planes_added += 1;
+            }
+            merged += 1;
+        }
+
+        merged
+    }
+}
+
+// Run plane extraction on the current point cloud. Returns the number of derived
+// planes added or updated, or -1 if there is no session
+#[no_mangle]
+pub extern "C" fn extract_planes_from_point_cloud() -> i32 {
+    // Seeded from the frame counter so repeated runs on the same data agree
+    let Some((points, config, mut rng)) = with_session(|session| {
+        (session.point_cloud.clone(), session.plane_extraction_config, session.effect_rng(session.metrics.depth_frames))
+    }) else {
+        return -1;
+    };
+
+    analytics::feature_used(Feature::PlaneExtraction);
+
+    let candidates = extract_planes(&points, &config, &mut rng);
+    debug!("RANSAC found {} plane candidates in {} points", candidates.len(), points.len());
+
+    with_session(|session| session.merge_derived_planes(&candidates) as i32).unwrap_or(-1)
+}
+
+// Set the inlier distance (meters) and minimum inlier count for plane extraction
+#[no_mangle]
+pub extern "C" fn set_plane_extraction_params(distance_threshold: f32, min_inliers: u32) {
+    with_session(|session| {
+        if distance_threshold.is_finite() && distance_threshold > 0.0 {
+            session.plane_extraction_config.distance_threshold = distance_threshold;
+        }
+        session.plane_extraction_config.min_inliers = min_inliers.max(3) as usize;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_plane_ids_take_the_default_source() {
+        let mut session = ARSession::new();
+        session.id_namespace.default_source = Some("arkit".into());
+        let candidate = PlaneCandidate { center: [0.0; 3], extent: [1.0, 1.0], normal: [0.0, 1.0, 0.0], inliers: 100 };
+
+        assert_eq!(session.merge_derived_planes(&[candidate]), 1);
+        assert_eq!(session.detected_planes[0].id, "arkit:derived_0");
+        assert_eq!(session.planes_from_source("arkit"), vec![0]);
+    }
+}
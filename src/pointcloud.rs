@@ -0,0 +1,306 @@
+// Depth frame to world-space point cloud processing: unprojection, normal estimation
+// and voxel-grid filtering. On iOS all three stages run as Metal compute kernels (see
+// pointcloud_metal.rs) when a device has been provided; this CPU implementation is the
+// fallback and produces the same results
+
+use std::collections::HashMap;
+
+use tracing::debug;
+
+use crate::analytics::{self, Feature};
+use crate::ingestion::{self, IngestStream, AR_FRAME_DROPPED};
+use crate::math::{cross, dot, normalize, sub};
+use crate::quotas::{Resource, AR_QUOTA_EXCEEDED};
+use crate::shared_buffers::{self, ReadError, AR_BUFFER_STALE};
+use crate::with_session;
+
+// Tunables for point cloud processing, stored on the session
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PointCloudConfig {
+    pub voxel_size: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Default for PointCloudConfig {
+    fn default() -> Self {
+        PointCloudConfig {
+            voxel_size: 0.02,
+            min_depth: 0.1,
+            max_depth: 5.0,
+        }
+    }
+}
+
+// A depth map (meters, row-major) with the intrinsics for its resolution and the
+// column-major camera-to-world transform at capture time
+pub(crate) struct DepthFrame<'a> {
+    pub depth: &'a [f32],
+    pub width: usize,
+    pub height: usize,
+    pub intrinsics: [f32; 4], // fx, fy, cx, cy
+    pub camera_transform: [f32; 16],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CloudPoint {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+// Per-pixel output of the unprojection and normal stages. The fourth component is
+// 1.0 for valid entries and 0.0 otherwise, matching the GPU buffer layout
+pub(crate) struct OrganizedCloud {
+    pub positions: Vec<[f32; 4]>,
+    pub normals: Vec<[f32; 4]>,
+}
+
+impl DepthFrame<'_> {
+    pub fn camera_position(&self) -> [f32; 3] {
+        [self.camera_transform[12], self.camera_transform[13], self.camera_transform[14]]
+    }
+
+    fn to_world(&self, p: [f32; 3]) -> [f32; 3] {
+        let m = &self.camera_transform;
+        [
+            m[0] * p[0] + m[4] * p[1] + m[8] * p[2] + m[12],
+            m[1] * p[0] + m[5] * p[1] + m[9] * p[2] + m[13],
+            m[2] * p[0] + m[6] * p[1] + m[10] * p[2] + m[14],
+        ]
+    }
+}
+
+// Full CPU pipeline
+pub(crate) fn process_cpu(frame: &DepthFrame, config: &PointCloudConfig) -> Vec<CloudPoint> {
+    let organized = unproject_cpu(frame, config);
+    voxel_filter(&organized, config.voxel_size)
+}
+
+// Unproject every pixel and estimate normals from its right and lower neighbours.
+// Camera space is ARKit's: +X right, +Y up, looking down -Z; image rows grow downward
+pub(crate) fn unproject_cpu(frame: &DepthFrame, config: &PointCloudConfig) -> OrganizedCloud {
+    let [fx, fy, cx, cy] = frame.intrinsics;
+    let count = frame.width * frame.height;
+
+    let mut positions = vec![[0.0f32; 4]; count];
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let index = y * frame.width + x;
+            let d = frame.depth[index];
+            if !(d >= config.min_depth && d <= config.max_depth) {
+                continue;
+            }
+            let camera_point = [(x as f32 - cx) * d / fx, -(y as f32 - cy) * d / fy, -d];
+            let [wx, wy, wz] = frame.to_world(camera_point);
+            positions[index] = [wx, wy, wz, 1.0];
+        }
+    }
+
+    let camera = frame.camera_position();
+    let mut normals = vec![[0.0f32; 4]; count];
+    for y in 0..frame.height.saturating_sub(1) {
+        for x in 0..frame.width.saturating_sub(1) {
+            let index = y * frame.width + x;
+            let (p, right, down) = (positions[index], positions[index + 1], positions[index + frame.width]);
+            if p[3] == 0.0 || right[3] == 0.0 || down[3] == 0.0 {
+                continue;
+            }
+            let p3 = [p[0], p[1], p[2]];
+            let mut n = normalize(cross(sub([right[0], right[1], right[2]], p3), sub([down[0], down[1], down[2]], p3)));
+            if n == [0.0; 3] {
+                continue;
+            }
+            // Orient toward the camera
+            if dot(n, sub(camera, p3)) < 0.0 {
+                n = [-n[0], -n[1], -n[2]];
+            }
+            normals[index] = [n[0], n[1], n[2], 1.0];
+        }
+    }
+
+    OrganizedCloud { positions, normals }
+}
+
+// Average valid points (and their normals, where known) per voxel. Output order
+// follows the first point seen in each voxel, so results are deterministic
+pub(crate) fn voxel_filter(cloud: &OrganizedCloud, voxel_size: f32) -> Vec<CloudPoint> {
+    struct Cell {
+        position: [f32; 3],
+        normal: [f32; 3],
+        count: u32,
+    }
+
+    let voxel_size = voxel_size.max(1e-4);
+    let mut cells: Vec<Cell> = Vec::new();
+    let mut lookup: HashMap<[i32; 3], usize> = HashMap::new();
+
+    for (p, n) in cloud.positions.iter().zip(&cloud.normals) {
+        if p[3] == 0.0 {
+            continue;
+        }
+        let key = [
+            (p[0] / voxel_size).floor() as i32,
+            (p[1] / voxel_size).floor() as i32,
+            (p[2] / voxel_size).floor() as i32,
+        ];
+        let cell_index = *lookup.entry(key).or_insert_with(|| {
+            cells.push(Cell { position: [0.0; 3], normal: [0.0; 3], count: 0 });
+            cells.len() - 1
+        });
+        let cell = &mut cells[cell_index];
+        for axis in 0..3 {
+            cell.position[axis] += p[axis];
+            cell.normal[axis] += n[axis] * n[3];
+        }
+        cell.count += 1;
+    }
+
+    cells.into_iter()
+        .map(|cell| CloudPoint {
+            position: cell.position.map(|v| v / cell.count as f32),
+            normal: normalize(cell.normal),
+        })
+        .collect()
+}
+
+// Process a depth frame and replace the session's point cloud with the result.
+// `depth` holds width * height meters (row-major), the intrinsics must be for the
+// depth map's resolution, and `camera_transform` is ARKit's column-major 4x4
+// camera-to-world matrix. Returns the number of points kept, -1 on bad input,
+// AR_FRAME_DROPPED if the depth queue turned the frame away, or AR_QUOTA_EXCEEDED if
+// the cloud has more points than the point quota (the previous cloud is kept)
+#[no_mangle]
+pub extern "C" fn submit_depth_frame(
+    depth: *const f32,
+    width: u32, height: u32,
+    fx: f32, fy: f32, cx: f32, cy: f32,
+    camera_transform: *const f32,
+) -> i32 {
+    if depth.is_null() || camera_transform.is_null() || width == 0 || height == 0 {
+        return -1;
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let (depth, transform) = unsafe { (std::slice::from_raw_parts(depth, width * height), read_transform(camera_transform)) };
+    ingest_depth_frame(depth, width, height, [fx, fy, cx, cy], transform)
+}
+
+// Like submit_depth_frame, but reading the depth map in place from a shared buffer (see
+// shared_buffers.rs) written at `generation`. Returns AR_BUFFER_STALE if the buffer has
+// been rewritten since or is being written, and -1 for an unknown buffer or one too
+// small or misaligned for the depth map
+#[no_mangle]
+pub extern "C" fn submit_shared_depth_frame(
+    buffer_id: i32,
+    generation: u64,
+    width: u32, height: u32,
+    fx: f32, fy: f32, cx: f32, cy: f32,
+    camera_transform: *const f32,
+) -> i32 {
+    if camera_transform.is_null() || width == 0 || height == 0 {
+        return -1;
+    }
+    let read = match shared_buffers::read(buffer_id, generation) {
+        Ok(read) => read,
+        Err(ReadError::Stale) => return AR_BUFFER_STALE,
+        Err(ReadError::UnknownBuffer) => return -1,
+    };
+
+    let (width, height) = (width as usize, height as usize);
+    let Some(depth) = read.floats(width * height) else {
+        return -1;
+    };
+    let transform = unsafe { read_transform(camera_transform) };
+    ingest_depth_frame(depth, width, height, [fx, fy, cx, cy], transform)
+}
+
+pub(crate) unsafe fn read_transform(camera_transform: *const f32) -> [f32; 16] {
+    let mut transform = [0.0f32; 16];
+    transform.copy_from_slice(std::slice::from_raw_parts(camera_transform, 16));
+    transform
+}
+
+// Queue and process a validated depth frame; see submit_depth_frame
+fn ingest_depth_frame(depth: &[f32], width: usize, height: usize, intrinsics: [f32; 4], camera_transform: [f32; 16]) -> i32 {
+    // Stamp the frame on arrival, before the processing delay
+    let Some((config, timestamp)) = with_session(|session| (session.point_cloud_config, session.clock.now())) else {
+        return -1;
+    };
+    let Some(_turn) = ingestion::begin_frame(IngestStream::Depth) else {
+        return AR_FRAME_DROPPED;
+    };
+
+    let frame = DepthFrame { depth, width, height, intrinsics, camera_transform };
+
+    // Process outside the session lock; this is the expensive part
+    #[cfg(target_os = "ios")]
+    let (points, on_gpu) = match crate::pointcloud_metal::process(&frame, &config) {
+        Some(points) => (points, true),
+        None => (process_cpu(&frame, &config), false),
+    };
+    #[cfg(not(target_os = "ios"))]
+    let (points, on_gpu) = (process_cpu(&frame, &config), false);
+
+    let count = points.len();
+    analytics::feature_used(Feature::DepthPointCloud);
+    let admitted = with_session(|session| {
+        if !session.admit(Resource::Points, count as u64) {
+            return false;
+        }
+        session.point_cloud = points;
+        session.point_cloud_timestamp = Some(timestamp);
+        session.metrics.depth_frames += 1;
+        if on_gpu {
+            session.metrics.gpu_depth_frames += 1;
+        }
+        true
+    });
+    if admitted == Some(false) {
+        return AR_QUOTA_EXCEEDED;
+    }
+    debug!("Processed {}x{} depth frame into {} points (gpu: {})", width, height, count, on_gpu);
+
+    count as i32
+}
+
+// Set the voxel edge length in meters used to downsample point clouds
+#[no_mangle]
+pub extern "C" fn set_point_cloud_voxel_size(voxel_size: f32) {
+    if voxel_size.is_finite() && voxel_size > 0.0 {
+        with_session(|session| session.point_cloud_config.voxel_size = voxel_size);
+    }
+}
+
+// Copy up to `capacity` points into the output arrays (3 floats per point each;
+// either may be null). Returns the total number of points available
+#[no_mangle]
+pub extern "C" fn get_point_cloud(out_positions: *mut f32, out_normals: *mut f32, capacity: u32) -> i32 {
+    with_session(|session| {
+        let count = session.point_cloud.len().min(capacity as usize);
+        unsafe {
+            if !out_positions.is_null() {
+                let out = std::slice::from_raw_parts_mut(out_positions, count * 3);
+                for (chunk, point) in out.chunks_exact_mut(3).zip(&session.point_cloud) {
+                    chunk.copy_from_slice(&point.position);
+                }
+            }
+            if !out_normals.is_null() {
+                let out = std::slice::from_raw_parts_mut(out_normals, count * 3);
+                for (chunk, point) in out.chunks_exact_mut(3).zip(&session.point_cloud) {
+                    chunk.copy_from_slice(&point.normal);
+                }
+            }
+        }
+        session.point_cloud.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+// Whether depth frames are being processed on the GPU
+#[no_mangle]
+pub extern "C" fn point_cloud_uses_gpu() -> bool {
+    #[cfg(target_os = "ios")]
+    return crate::pointcloud_metal::is_available();
+    #[cfg(not(target_os = "ios"))]
+    return false;
+}
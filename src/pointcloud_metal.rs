@@ -0,0 +1,461 @@
+// Metal compute path for point cloud processing. Unprojection and normal estimation
+// run per pixel on the GPU, then each valid pixel is hashed into a voxel table and
+// summed into its cell with atomics, all in one command buffer. Kernels mirror
+// unproject_cpu and voxel_filter, so both paths give the same points in the same
+// order; only float summation order differs, which may change the last bits.
+//
+// The voxel kernel needs float atomics (Metal 3). It's compiled separately, and on
+// GPUs without them the voxel filter runs on the CPU over the readback instead
+
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::sync::{Mutex, OnceLock};
+
+use metal::foreign_types::ForeignTypeRef;
+use metal::{CommandQueue, CompileOptions, ComputePipelineState, Device, DeviceRef, MTLDevice, MTLResourceOptions, MTLSize};
+use tracing::{info, warn};
+
+use crate::plane_extraction::PlaneExtractionConfig;
+use crate::math::normalize;
+use crate::pointcloud::{voxel_filter, CloudPoint, DepthFrame, OrganizedCloud, PointCloudConfig};
+
+const SHADER_SOURCE: &str = r#"
+#include <metal_stdlib>
+using namespace metal;
+
+struct DepthParams {
+    float4x4 camera_transform;
+    float4 intrinsics; // fx, fy, cx, cy
+    uint width;
+    uint height;
+    float min_depth;
+    float max_depth;
+};
+
+kernel void unproject_depth(device const float *depth [[buffer(0)]],
+                            device float4 *positions [[buffer(1)]],
+                            constant DepthParams &params [[buffer(2)]],
+                            uint2 gid [[thread_position_in_grid]]) {
+    if (gid.x >= params.width || gid.y >= params.height) {
+        return;
+    }
+    uint index = gid.y * params.width + gid.x;
+    float d = depth[index];
+    if (!(d >= params.min_depth && d <= params.max_depth)) {
+        positions[index] = float4(0.0);
+        return;
+    }
+    float x = (float(gid.x) - params.intrinsics.z) * d / params.intrinsics.x;
+    float y = -(float(gid.y) - params.intrinsics.w) * d / params.intrinsics.y;
+    float4 world = params.camera_transform * float4(x, y, -d, 1.0);
+    positions[index] = float4(world.xyz, 1.0);
+}
+
+kernel void estimate_normals(device const float4 *positions [[buffer(0)]],
+                             device float4 *normals [[buffer(1)]],
+                             constant DepthParams &params [[buffer(2)]],
+                             uint2 gid [[thread_position_in_grid]]) {
+    if (gid.x >= params.width || gid.y >= params.height) {
+        return;
+    }
+    uint index = gid.y * params.width + gid.x;
+    normals[index] = float4(0.0);
+    if (gid.x + 1 >= params.width || gid.y + 1 >= params.height) {
+        return;
+    }
+    float4 p = positions[index];
+    float4 right = positions[index + 1];
+    float4 down = positions[index + params.width];
+    if (p.w == 0.0 || right.w == 0.0 || down.w == 0.0) {
+        return;
+    }
+    float3 n = cross(right.xyz - p.xyz, down.xyz - p.xyz);
+    float len = length(n);
+    if (len <= FLT_EPSILON) {
+        return;
+    }
+    n /= len;
+    float3 camera = params.camera_transform[3].xyz;
+    if (dot(n, camera - p.xyz) < 0.0) {
+        n = -n;
+    }
+    normals[index] = float4(n, 1.0);
+}
+
+struct InlierParams {
+    uint point_count;
+    uint hypothesis_count;
+    float distance_threshold;
+    float normal_threshold;
+};
+
+// One thread per plane hypothesis (nx, ny, nz, d)
+kernel void count_inliers(device const float4 *positions [[buffer(0)]],
+                          device const float4 *normals [[buffer(1)]],
+                          device const float4 *hypotheses [[buffer(2)]],
+                          device uint *counts [[buffer(3)]],
+                          constant InlierParams &params [[buffer(4)]],
+                          uint gid [[thread_position_in_grid]]) {
+    if (gid >= params.hypothesis_count) {
+        return;
+    }
+    float4 h = hypotheses[gid];
+    uint count = 0;
+    for (uint i = 0; i < params.point_count; i++) {
+        float distance = fabs(dot(h.xyz, positions[i].xyz) + h.w);
+        if (distance < params.distance_threshold && fabs(dot(h.xyz, normals[i].xyz)) >= params.normal_threshold) {
+            count++;
+        }
+    }
+    counts[gid] = count;
+}
+"#;
+
+const VOXEL_SHADER_SOURCE: &str = r#"
+#include <metal_stdlib>
+using namespace metal;
+
+constant uint EMPTY_SLOT = 0xffffffff;
+
+struct VoxelParams {
+    uint point_count;
+    uint capacity; // power of two
+    float voxel_size;
+};
+
+struct VoxelSums {
+    atomic_float position[3];
+    atomic_float normal[3];
+};
+
+int3 voxel_key(float4 p, float voxel_size) {
+    return int3(floor(p.xyz / voxel_size));
+}
+
+uint voxel_hash(int3 key) {
+    uint3 k = as_type<uint3>(key);
+    return (k.x * 73856093u) ^ (k.y * 19349663u) ^ (k.z * 83492791u);
+}
+
+// One thread per pixel: find or claim the slot of the point's voxel by linear probing
+// (a slot's owner is the first pixel to claim it, which fixes its key), then add the
+// point into it. `firsts` keeps the lowest pixel index per voxel for output order
+kernel void voxel_accumulate(device const float4 *positions [[buffer(0)]],
+                             device const float4 *normals [[buffer(1)]],
+                             device atomic_uint *owners [[buffer(2)]],
+                             device atomic_uint *firsts [[buffer(3)]],
+                             device atomic_uint *counts [[buffer(4)]],
+                             device VoxelSums *sums [[buffer(5)]],
+                             constant VoxelParams &params [[buffer(6)]],
+                             uint gid [[thread_position_in_grid]]) {
+    if (gid >= params.point_count) {
+        return;
+    }
+    float4 p = positions[gid];
+    if (p.w == 0.0) {
+        return;
+    }
+    int3 key = voxel_key(p, params.voxel_size);
+    uint slot = voxel_hash(key) & (params.capacity - 1);
+    for (uint probe = 0; probe < params.capacity; probe++) {
+        uint owner = EMPTY_SLOT;
+        // The weak exchange can fail spuriously, leaving `owner` empty
+        while (owner == EMPTY_SLOT &&
+               !atomic_compare_exchange_weak_explicit(&owners[slot], &owner, gid,
+                                                      memory_order_relaxed, memory_order_relaxed)) {
+        }
+        if (owner == EMPTY_SLOT || all(voxel_key(positions[owner], params.voxel_size) == key)) {
+            float4 n = normals[gid];
+            atomic_fetch_min_explicit(&firsts[slot], gid, memory_order_relaxed);
+            atomic_fetch_add_explicit(&counts[slot], 1u, memory_order_relaxed);
+            for (uint axis = 0; axis < 3; axis++) {
+                atomic_fetch_add_explicit(&sums[slot].position[axis], p[axis], memory_order_relaxed);
+                atomic_fetch_add_explicit(&sums[slot].normal[axis], n[axis] * n.w, memory_order_relaxed);
+            }
+            return;
+        }
+        slot = (slot + 1) & (params.capacity - 1);
+    }
+}
+"#;
+
+// Marks a free slot in the voxel table, and a voxel with no first pixel yet
+const EMPTY_SLOT: u32 = u32::MAX;
+
+// Must match DepthParams in the shader (96 bytes, 16-byte aligned)
+#[repr(C, align(16))]
+struct DepthParams {
+    camera_transform: [f32; 16],
+    intrinsics: [f32; 4],
+    width: u32,
+    height: u32,
+    min_depth: f32,
+    max_depth: f32,
+}
+
+// Must match VoxelParams in the shader
+#[repr(C)]
+struct VoxelParams {
+    point_count: u32,
+    capacity: u32,
+    voxel_size: f32,
+}
+
+// Must match InlierParams in the shader
+#[repr(C)]
+struct InlierParams {
+    point_count: u32,
+    hypothesis_count: u32,
+    distance_threshold: f32,
+    normal_threshold: f32,
+}
+
+pub(crate) struct MetalPointCloud {
+    device: Device,
+    queue: CommandQueue,
+    unproject: ComputePipelineState,
+    normals: ComputePipelineState,
+    inliers: ComputePipelineState,
+    // None on GPUs without float atomics
+    voxels: Option<ComputePipelineState>,
+}
+
+// Voxel hash table buffers for one frame
+struct VoxelTable {
+    capacity: usize,
+    owners: metal::Buffer,
+    firsts: metal::Buffer,
+    counts: metal::Buffer,
+    // Position and normal sums, 6 floats per slot
+    sums: metal::Buffer,
+}
+
+fn processor() -> &'static Mutex<Option<MetalPointCloud>> {
+    static PROCESSOR: OnceLock<Mutex<Option<MetalPointCloud>>> = OnceLock::new();
+    PROCESSOR.get_or_init(|| Mutex::new(None))
+}
+
+// Build the compute pipelines for a device handed over from Swift. On failure the
+// CPU path stays in use
+pub(crate) fn install(device_ptr: *mut c_void) -> bool {
+    // The device is owned by Swift; take our own reference
+    let device = unsafe { DeviceRef::from_ptr(device_ptr as *mut MTLDevice) }.to_owned();
+
+    match MetalPointCloud::new(device) {
+        Ok(gpu) => {
+            if let Ok(mut slot) = processor().lock() {
+                *slot = Some(gpu);
+            }
+            info!("Metal point cloud pipelines ready");
+            true
+        }
+        Err(err) => {
+            warn!("Falling back to CPU point cloud processing: {}", err);
+            false
+        }
+    }
+}
+
+pub(crate) fn is_available() -> bool {
+    processor().lock().map(|slot| slot.is_some()).unwrap_or(false)
+}
+
+// Run the GPU stages, or None if no device is installed or the GPU work failed
+pub(crate) fn process(frame: &DepthFrame, config: &PointCloudConfig) -> Option<Vec<CloudPoint>> {
+    let slot = processor().lock().ok()?;
+    slot.as_ref()?.process(frame, config)
+}
+
+// Score RANSAC plane hypotheses on the GPU, or None if unavailable
+pub(crate) fn count_inliers(
+    points: &[CloudPoint],
+    hypotheses: &[[f32; 4]],
+    config: &PlaneExtractionConfig,
+) -> Option<Vec<u32>> {
+    let slot = processor().lock().ok()?;
+    slot.as_ref()?.count_inliers(points, hypotheses, config)
+}
+
+impl MetalPointCloud {
+    fn new(device: Device) -> Result<Self, String> {
+        let library = device.new_library_with_source(SHADER_SOURCE, &CompileOptions::new())?;
+        let unproject = library.get_function("unproject_depth", None)?;
+        let normals = library.get_function("estimate_normals", None)?;
+        let inliers = library.get_function("count_inliers", None)?;
+        let voxels = Self::voxel_pipeline(&device)
+            .map_err(|err| warn!("Voxel filtering stays on the CPU: {}", err))
+            .ok();
+
+        Ok(MetalPointCloud {
+            queue: device.new_command_queue(),
+            unproject: device.new_compute_pipeline_state_with_function(&unproject)?,
+            normals: device.new_compute_pipeline_state_with_function(&normals)?,
+            inliers: device.new_compute_pipeline_state_with_function(&inliers)?,
+            voxels,
+            device,
+        })
+    }
+
+    fn voxel_pipeline(device: &Device) -> Result<ComputePipelineState, String> {
+        let library = device.new_library_with_source(VOXEL_SHADER_SOURCE, &CompileOptions::new())?;
+        let function = library.get_function("voxel_accumulate", None)?;
+        device.new_compute_pipeline_state_with_function(&function)
+    }
+
+    fn voxel_table(&self, point_count: usize) -> VoxelTable {
+        // At most half full, so probe chains stay short
+        let capacity = (point_count * 2).next_power_of_two();
+        let options = MTLResourceOptions::StorageModeShared;
+        let filled = |value: u32| {
+            let data = vec![value; capacity];
+            self.device.new_buffer_with_data(data.as_ptr() as *const c_void, (capacity * size_of::<u32>()) as u64, options)
+        };
+        let sums = vec![0.0f32; capacity * 6];
+        VoxelTable {
+            capacity,
+            owners: filled(EMPTY_SLOT),
+            firsts: filled(EMPTY_SLOT),
+            counts: filled(0),
+            sums: self.device.new_buffer_with_data(sums.as_ptr() as *const c_void, (sums.len() * size_of::<f32>()) as u64, options),
+        }
+    }
+
+    fn process(&self, frame: &DepthFrame, config: &PointCloudConfig) -> Option<Vec<CloudPoint>> {
+        let count = frame.width * frame.height;
+        let vec4_bytes = (count * size_of::<[f32; 4]>()) as u64;
+        let options = MTLResourceOptions::StorageModeShared;
+
+        let depth = self.device.new_buffer_with_data(
+            frame.depth.as_ptr() as *const c_void,
+            (count * size_of::<f32>()) as u64,
+            options,
+        );
+        let positions = self.device.new_buffer(vec4_bytes, options);
+        let normals = self.device.new_buffer(vec4_bytes, options);
+        let params = DepthParams {
+            camera_transform: frame.camera_transform,
+            intrinsics: frame.intrinsics,
+            width: frame.width as u32,
+            height: frame.height as u32,
+            min_depth: config.min_depth,
+            max_depth: config.max_depth,
+        };
+
+        let grid = MTLSize::new(frame.width as u64, frame.height as u64, 1);
+        let group = MTLSize::new(16, 16, 1);
+
+        let command_buffer = self.queue.new_command_buffer();
+        let encoder = command_buffer.new_compute_command_encoder();
+        for (pipeline, input, output) in [(&self.unproject, &depth, &positions), (&self.normals, &positions, &normals)] {
+            encoder.set_compute_pipeline_state(pipeline);
+            encoder.set_buffer(0, Some(input), 0);
+            encoder.set_buffer(1, Some(output), 0);
+            encoder.set_bytes(2, size_of::<DepthParams>() as u64, &params as *const DepthParams as *const c_void);
+            encoder.dispatch_threads(grid, group);
+        }
+        // Dispatches in one encoder run in order, so this sees the finished normals
+        let table = self.voxels.as_ref().map(|pipeline| {
+            let table = self.voxel_table(count);
+            let voxel_params = VoxelParams { point_count: count as u32, capacity: table.capacity as u32, voxel_size: config.voxel_size.max(1e-4) };
+            encoder.set_compute_pipeline_state(pipeline);
+            for (index, buffer) in [&positions, &normals, &table.owners, &table.firsts, &table.counts, &table.sums].into_iter().enumerate() {
+                encoder.set_buffer(index as u64, Some(buffer), 0);
+            }
+            encoder.set_bytes(6, size_of::<VoxelParams>() as u64, &voxel_params as *const VoxelParams as *const c_void);
+            encoder.dispatch_threads(MTLSize::new(count as u64, 1, 1), MTLSize::new(64, 1, 1));
+            table
+        });
+        encoder.end_encoding();
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+
+        if command_buffer.status() != metal::MTLCommandBufferStatus::Completed {
+            warn!("Point cloud command buffer failed with status {:?}", command_buffer.status());
+            return None;
+        }
+
+        if let Some(table) = table {
+            return Some(table.read());
+        }
+        let read = |buffer: &metal::Buffer| unsafe {
+            std::slice::from_raw_parts(buffer.contents() as *const [f32; 4], count).to_vec()
+        };
+        let organized = OrganizedCloud { positions: read(&positions), normals: read(&normals) };
+        Some(voxel_filter(&organized, config.voxel_size))
+    }
+
+    fn count_inliers(
+        &self,
+        points: &[CloudPoint],
+        hypotheses: &[[f32; 4]],
+        config: &PlaneExtractionConfig,
+    ) -> Option<Vec<u32>> {
+        if points.is_empty() || hypotheses.is_empty() {
+            return Some(vec![0; hypotheses.len()]);
+        }
+
+        let options = MTLResourceOptions::StorageModeShared;
+        let pack = |data: Vec<[f32; 4]>| self.device.new_buffer_with_data(
+            data.as_ptr() as *const c_void,
+            (data.len() * size_of::<[f32; 4]>()) as u64,
+            options,
+        );
+        let positions = pack(points.iter().map(|p| [p.position[0], p.position[1], p.position[2], 1.0]).collect());
+        let normals = pack(points.iter().map(|p| [p.normal[0], p.normal[1], p.normal[2], 0.0]).collect());
+        let hypothesis_buffer = pack(hypotheses.to_vec());
+        let counts = self.device.new_buffer((hypotheses.len() * size_of::<u32>()) as u64, options);
+        let params = InlierParams {
+            point_count: points.len() as u32,
+            hypothesis_count: hypotheses.len() as u32,
+            distance_threshold: config.distance_threshold,
+            normal_threshold: config.normal_threshold,
+        };
+
+        let command_buffer = self.queue.new_command_buffer();
+        let encoder = command_buffer.new_compute_command_encoder();
+        encoder.set_compute_pipeline_state(&self.inliers);
+        encoder.set_buffer(0, Some(&positions), 0);
+        encoder.set_buffer(1, Some(&normals), 0);
+        encoder.set_buffer(2, Some(&hypothesis_buffer), 0);
+        encoder.set_buffer(3, Some(&counts), 0);
+        encoder.set_bytes(4, size_of::<InlierParams>() as u64, &params as *const InlierParams as *const c_void);
+        encoder.dispatch_threads(MTLSize::new(hypotheses.len() as u64, 1, 1), MTLSize::new(64, 1, 1));
+        encoder.end_encoding();
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+
+        if command_buffer.status() != metal::MTLCommandBufferStatus::Completed {
+            warn!("Plane scoring command buffer failed with status {:?}", command_buffer.status());
+            return None;
+        }
+
+        let counts = unsafe { std::slice::from_raw_parts(counts.contents() as *const u32, hypotheses.len()) };
+        Some(counts.to_vec())
+    }
+}
+
+impl VoxelTable {
+    // Averaged points of the occupied slots, ordered by each voxel's first pixel as
+    // voxel_filter orders them
+    fn read(&self) -> Vec<CloudPoint> {
+        let read_u32 = |buffer: &metal::Buffer| unsafe {
+            std::slice::from_raw_parts(buffer.contents() as *const u32, self.capacity).to_vec()
+        };
+        let (owners, firsts, counts) = (read_u32(&self.owners), read_u32(&self.firsts), read_u32(&self.counts));
+        let sums = unsafe { std::slice::from_raw_parts(self.sums.contents() as *const [f32; 6], self.capacity) };
+
+        let mut cells: Vec<(u32, CloudPoint)> = (0..self.capacity)
+            .filter(|&slot| owners[slot] != EMPTY_SLOT && counts[slot] > 0)
+            .map(|slot| {
+                let [px, py, pz, nx, ny, nz] = sums[slot];
+                let point = CloudPoint {
+                    position: [px, py, pz].map(|v| v / counts[slot] as f32),
+                    normal: normalize([nx, ny, nz]),
+                };
+                (firsts[slot], point)
+            })
+            .collect();
+        cells.sort_unstable_by_key(|&(first, _)| first);
+        cells.into_iter().map(|(_, point)| point).collect()
+    }
+}
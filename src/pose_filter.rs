@@ -0,0 +1,284 @@
+// Camera pose smoothing. Position runs through an alpha-beta filter (the steady-state
+// form of a constant-velocity Kalman filter), which yields a velocity estimate for
+// free; rotation uses a complementary filter that slerps toward each measurement.
+// A jump larger than the teleport threshold (e.g. after relocalization) resets the
+// filter rather than being smoothed across
+
+use std::collections::VecDeque;
+
+use crate::math::{add, length, quat_delta_axis_angle, quat_normalize, quat_slerp, scale, sub};
+use crate::with_session;
+
+const HISTORY_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PoseSample {
+    pub timestamp: f64,
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PoseFilterConfig {
+    // Position correction gain in (0, 1]; lower is smoother but lags more
+    pub alpha: f32,
+    // Velocity correction gain; typically well below alpha
+    pub beta: f32,
+    // Rotation time constant in seconds; 0 disables rotation smoothing
+    pub rotation_time_constant: f32,
+    // Position jump in meters treated as a discontinuity
+    pub teleport_distance: f32,
+}
+
+impl Default for PoseFilterConfig {
+    fn default() -> Self {
+        PoseFilterConfig {
+            alpha: 0.5,
+            beta: 0.1,
+            rotation_time_constant: 0.05,
+            teleport_distance: 0.5,
+        }
+    }
+}
+
+// Filter output, alongside the raw pose
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SmoothedPose {
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    // Meters per second
+    pub velocity: [f32; 3],
+    // Radians per second, as a world-space rotation axis scaled by the rate
+    pub angular_velocity: [f32; 3],
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PoseFilter {
+    pub config: PoseFilterConfig,
+    history: VecDeque<PoseSample>,
+    state: Option<(f64, SmoothedPose)>,
+}
+
+impl PoseFilter {
+    pub fn history(&self) -> impl Iterator<Item = &PoseSample> {
+        self.history.iter()
+    }
+
+    pub fn smoothed(&self) -> Option<SmoothedPose> {
+        self.state.map(|(_, pose)| pose)
+    }
+
+    // Timestamp of the newest sample taken, if any
+    pub fn last_timestamp(&self) -> Option<f64> {
+        self.state.map(|(timestamp, _)| timestamp)
+    }
+
+    // Raw pose at `timestamp`, interpolated between the neighbouring samples
+    pub fn pose_at(&self, timestamp: f64) -> Option<([f32; 3], [f32; 4])> {
+        let after = self.history.iter().position(|s| s.timestamp >= timestamp)?;
+        let next = self.history[after];
+        if next.timestamp == timestamp {
+            return Some((next.position, next.rotation));
+        }
+
+        let prev = self.history[after.checked_sub(1)?];
+        let t = ((timestamp - prev.timestamp) / (next.timestamp - prev.timestamp)) as f32;
+        Some((
+            add(prev.position, scale(sub(next.position, prev.position), t)),
+            quat_normalize(quat_slerp(prev.rotation, next.rotation, t)),
+        ))
+    }
+
+    // Shift every stored position by `offset`, e.g. when the world origin moves
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        for sample in self.history.iter_mut() {
+            sample.position = add(sample.position, offset);
+        }
+        if let Some((_, pose)) = self.state.as_mut() {
+            pose.position = add(pose.position, offset);
+        }
+    }
+
+    pub fn update(&mut self, sample: PoseSample) -> SmoothedPose {
+        // Out-of-order or duplicate timestamps carry no timing information, and are kept
+        // out of the history so it stays sorted for pose_at
+        if let Some((last_time, previous)) = self.state {
+            if sample.timestamp <= last_time {
+                return previous;
+            }
+        }
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(sample);
+
+        let rotation = quat_normalize(sample.rotation);
+        let fresh = SmoothedPose {
+            position: sample.position,
+            rotation,
+            velocity: [0.0; 3],
+            angular_velocity: [0.0; 3],
+        };
+
+        let pose = match self.state {
+            Some((last_time, previous)) => {
+                let dt = (sample.timestamp - last_time) as f32;
+                if length(sub(sample.position, previous.position)) > self.config.teleport_distance {
+                    fresh
+                } else {
+                    self.filter(previous, sample.position, rotation, dt)
+                }
+            }
+            None => fresh,
+        };
+
+        self.state = Some((sample.timestamp, pose));
+        pose
+    }
+
+    fn filter(&self, previous: SmoothedPose, position: [f32; 3], rotation: [f32; 4], dt: f32) -> SmoothedPose {
+        let PoseFilterConfig { alpha, beta, rotation_time_constant, .. } = self.config;
+
+        let predicted = add(previous.position, scale(previous.velocity, dt));
+        let residual = sub(position, predicted);
+        let position = add(predicted, scale(residual, alpha));
+        let velocity = add(previous.velocity, scale(residual, beta / dt));
+
+        let blend = if rotation_time_constant > 0.0 { dt / (rotation_time_constant + dt) } else { 1.0 };
+        let smoothed_rotation = quat_normalize(quat_slerp(previous.rotation, rotation, blend));
+        let angular_velocity = scale(quat_delta_axis_angle(previous.rotation, smoothed_rotation), 1.0 / dt);
+
+        SmoothedPose {
+            position,
+            rotation: smoothed_rotation,
+            velocity,
+            angular_velocity,
+        }
+    }
+}
+
+pub(crate) unsafe fn write_out<const N: usize>(out: *mut f32, values: [f32; N]) {
+    if !out.is_null() {
+        std::slice::from_raw_parts_mut(out, N).copy_from_slice(&values);
+    }
+}
+
+// Raw camera pose as last reported by the host. Outputs may be null
+#[no_mangle]
+pub extern "C" fn get_camera_pose(out_position: *mut f32, out_rotation: *mut f32) -> bool {
+    with_session(|session| unsafe {
+        write_out(out_position, session.camera_position);
+        write_out(out_rotation, session.camera_rotation);
+    })
+    .is_some()
+}
+
+// Smoothed camera pose plus velocity (m/s) and angular velocity (rad/s, axis * rate).
+// Outputs may be null. Returns false before the first camera update
+#[no_mangle]
+pub extern "C" fn get_smoothed_camera_pose(
+    out_position: *mut f32,
+    out_rotation: *mut f32,
+    out_velocity: *mut f32,
+    out_angular_velocity: *mut f32,
+) -> bool {
+    with_session(|session| {
+        let pose = session.camera_filter.smoothed()?;
+        unsafe {
+            write_out(out_position, pose.position);
+            write_out(out_rotation, pose.rotation);
+            write_out(out_velocity, pose.velocity);
+            write_out(out_angular_velocity, pose.angular_velocity);
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+// Tune the camera filter. alpha/beta are the position and velocity gains (0-1],
+// rotation_time_constant is in seconds (0 disables rotation smoothing)
+#[no_mangle]
+pub extern "C" fn set_camera_smoothing(alpha: f32, beta: f32, rotation_time_constant: f32) {
+    with_session(|session| {
+        let config = &mut session.camera_filter.config;
+        config.alpha = alpha.clamp(0.01, 1.0);
+        config.beta = beta.clamp(0.0, 1.0);
+        config.rotation_time_constant = rotation_time_constant.max(0.0);
+    });
+}
+
+// Copy up to `capacity` of the most recent raw camera samples, oldest first. Each
+// output may be null; positions take 3 floats and rotations 4 per sample. Returns
+// the number of samples written
+#[no_mangle]
+pub extern "C" fn get_camera_pose_history(
+    out_timestamps: *mut f64,
+    out_positions: *mut f32,
+    out_rotations: *mut f32,
+    capacity: u32,
+) -> i32 {
+    with_session(|session| {
+        let skip = session.camera_filter.history().count().saturating_sub(capacity as usize);
+        let mut written = 0;
+        for (i, sample) in session.camera_filter.history().skip(skip).enumerate() {
+            unsafe {
+                if !out_timestamps.is_null() {
+                    *out_timestamps.add(i) = sample.timestamp;
+                }
+                write_out(if out_positions.is_null() { out_positions } else { out_positions.add(i * 3) }, sample.position);
+                write_out(if out_rotations.is_null() { out_rotations } else { out_rotations.add(i * 4) }, sample.rotation);
+            }
+            written += 1;
+        }
+        written
+    })
+    .unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: f64, x: f32) -> PoseSample {
+        PoseSample { timestamp, position: [x, 0.0, 0.0], rotation: [0.0, 0.0, 0.0, 1.0] }
+    }
+
+    #[test]
+    fn pose_at_interpolates_between_neighbours() {
+        let mut filter = PoseFilter::default();
+        filter.update(sample(1.0, 0.0));
+        filter.update(sample(2.0, 0.2));
+        let (position, rotation) = filter.pose_at(1.25).unwrap();
+        assert!((position[0] - 0.05).abs() < 1e-6);
+        assert_eq!(rotation, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(filter.pose_at(2.0).unwrap().0, [0.2, 0.0, 0.0]);
+        assert!(filter.pose_at(0.5).is_none());
+        assert!(filter.pose_at(2.5).is_none());
+    }
+
+    #[test]
+    fn late_and_duplicate_samples_stay_out_of_history() {
+        let mut filter = PoseFilter::default();
+        filter.update(sample(1.0, 0.0));
+        filter.update(sample(2.0, 0.2));
+        filter.update(sample(1.5, 5.0));
+        filter.update(sample(2.0, 7.0));
+        filter.update(sample(3.0, 0.4));
+
+        let timestamps: Vec<f64> = filter.history().map(|s| s.timestamp).collect();
+        assert_eq!(timestamps, [1.0, 2.0, 3.0]);
+        let (position, _) = filter.pose_at(2.5).unwrap();
+        assert!((position[0] - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn teleport_resets_filter() {
+        let mut filter = PoseFilter::default();
+        filter.update(sample(0.0, 0.0));
+        filter.update(sample(0.1, 0.01));
+        let pose = filter.update(sample(0.2, 3.0));
+        assert_eq!(pose.position, [3.0, 0.0, 0.0]);
+        assert_eq!(pose.velocity, [0.0; 3]);
+    }
+}
@@ -0,0 +1,389 @@
+// Pose graph for loop-closure correction. The camera path is kept as keyframes, one
+// whenever the camera has moved or turned far enough since the last, each the next
+// node in a chain. When the host recognizes a place it has been before (a relocalized
+// frame, a re-seen marker) it adds a loop closure: the pose of one keyframe as measured
+// from another. Odometry drift makes the chain disagree with that measurement, so the
+// graph is relaxed: the newer keyframe stays put, since the camera and everything
+// tracked right now are in that frame, and the older one is pulled to where the
+// measurement says it is. The keyframes in between take a share of the correction that
+// grows with their distance back along the chain, and everything before the loop moves
+// with its start. Closures are relaxed in turn for a few rounds, so nested loops
+// settle together.
+//
+// Objects are bound to the newest keyframe when placed, and planes to the newest one
+// as of their last update; both move with their keyframe and are reported in
+// object_pose_adjusted / plane_pose_adjusted events. Anchored and camera-attached
+// objects follow their anchor or the camera instead
+
+use crate::events::SessionEvent;
+use crate::math::{add, length, quat_conjugate, quat_delta_axis_angle, quat_from_axis_angle, quat_mul, quat_normalize, quat_rotate, scale, sub};
+use crate::pose_filter::{write_out, PoseSample};
+use crate::{with_session, ARSession};
+
+// A new keyframe is taken after moving this many meters or turning this many radians
+// (20 degrees)
+const KEYFRAME_DISTANCE: f32 = 0.5;
+const KEYFRAME_ANGLE: f32 = 0.35;
+
+// The oldest keyframes, and closures touching them, are dropped beyond this
+const MAX_KEYFRAMES: usize = 4096;
+
+const RELAX_ITERATIONS: usize = 10;
+
+// Entities moved less than this (meters, or radians) aren't reported
+const MIN_REPORTED_ADJUSTMENT: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Keyframe {
+    pub id: i32,
+    pub timestamp: f64,
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+}
+
+// Keyframe `to`'s pose in keyframe `from`'s frame, as the host measured it
+#[derive(Debug, Clone, Copy)]
+struct LoopClosure {
+    from: i32,
+    to: i32,
+    position: [f32; 3],
+    rotation: [f32; 4],
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PoseGraph {
+    pub keyframes: Vec<Keyframe>,
+    closures: Vec<LoopClosure>,
+    next_id: i32,
+}
+
+// A rigid move: rotate by `rotation` about `pivot`, then translate by `translation`
+#[derive(Debug, Clone, Copy)]
+struct Correction {
+    pivot: [f32; 3],
+    translation: [f32; 3],
+    rotation: [f32; 4],
+}
+
+impl Correction {
+    fn apply(&self, position: [f32; 3], rotation: [f32; 4]) -> ([f32; 3], [f32; 4]) {
+        let moved = add(add(self.pivot, quat_rotate(self.rotation, sub(position, self.pivot))), self.translation);
+        (moved, quat_normalize(quat_mul(self.rotation, rotation)))
+    }
+
+    // The same move scaled by `fraction` along the shortest arc
+    fn partial(&self, fraction: f32) -> Correction {
+        let axis_angle = quat_delta_axis_angle([0.0, 0.0, 0.0, 1.0], self.rotation);
+        let angle = length(axis_angle);
+        let rotation = if angle > 1e-6 {
+            quat_from_axis_angle(scale(axis_angle, 1.0 / angle), angle * fraction)
+        } else {
+            [0.0, 0.0, 0.0, 1.0]
+        };
+        Correction { pivot: self.pivot, translation: scale(self.translation, fraction), rotation }
+    }
+}
+
+impl PoseGraph {
+    fn index(&self, id: i32) -> Option<usize> {
+        self.keyframes.iter().position(|keyframe| keyframe.id == id)
+    }
+
+    pub fn latest(&self) -> Option<i32> {
+        self.keyframes.last().map(|keyframe| keyframe.id)
+    }
+
+    // Newest keyframe taken at or before `timestamp`
+    fn at(&self, timestamp: f64) -> Option<&Keyframe> {
+        self.keyframes.iter().rev().find(|keyframe| keyframe.timestamp <= timestamp)
+    }
+
+    fn add_keyframe(&mut self, sample: PoseSample) {
+        if let Some(last) = self.keyframes.last() {
+            let moved = length(sub(sample.position, last.position));
+            let turned = length(quat_delta_axis_angle(last.rotation, sample.rotation));
+            if moved < KEYFRAME_DISTANCE && turned < KEYFRAME_ANGLE {
+                return;
+            }
+        }
+        self.next_id += 1;
+        self.keyframes.push(Keyframe {
+            id: self.next_id,
+            timestamp: sample.timestamp,
+            position: sample.position,
+            rotation: sample.rotation,
+        });
+        if self.keyframes.len() > MAX_KEYFRAMES {
+            let dropped = self.keyframes.remove(0).id;
+            self.closures.retain(|closure| closure.from != dropped && closure.to != dropped);
+        }
+    }
+
+    // Pull the older end of one closure into agreement, spreading the correction back
+    // along the chain between its ends
+    fn relax(&mut self, closure: LoopClosure) {
+        let (Some(from), Some(to)) = (self.index(closure.from), self.index(closure.to)) else {
+            return;
+        };
+        // The constraint read from the newer keyframe's side
+        let (older, newer, position, rotation) = if from < to {
+            let inverse = quat_conjugate(closure.rotation);
+            (from, to, quat_rotate(inverse, scale(closure.position, -1.0)), inverse)
+        } else {
+            (to, from, closure.position, closure.rotation)
+        };
+        let anchor = self.keyframes[newer];
+        let target_position = add(anchor.position, quat_rotate(anchor.rotation, position));
+        let target_rotation = quat_normalize(quat_mul(anchor.rotation, rotation));
+        let current = self.keyframes[older];
+        let correction = Correction {
+            pivot: current.position,
+            translation: sub(target_position, current.position),
+            rotation: quat_normalize(quat_mul(target_rotation, quat_conjugate(current.rotation))),
+        };
+
+        for index in 0..newer {
+            let fraction = if index <= older { 1.0 } else { (newer - index) as f32 / (newer - older) as f32 };
+            let keyframe = &mut self.keyframes[index];
+            (keyframe.position, keyframe.rotation) = correction.partial(fraction).apply(keyframe.position, keyframe.rotation);
+        }
+    }
+
+    fn optimize(&mut self) {
+        for _ in 0..RELAX_ITERATIONS {
+            for closure in self.closures.clone() {
+                self.relax(closure);
+            }
+        }
+    }
+
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        for keyframe in self.keyframes.iter_mut() {
+            keyframe.position = add(keyframe.position, offset);
+        }
+    }
+}
+
+// How keyframe `before` moved to become `after`
+fn keyframe_move(before: &Keyframe, after: &Keyframe) -> Correction {
+    Correction {
+        pivot: before.position,
+        translation: sub(after.position, before.position),
+        rotation: quat_normalize(quat_mul(after.rotation, quat_conjugate(before.rotation))),
+    }
+}
+
+impl ARSession {
+    pub(crate) fn update_pose_graph(&mut self, sample: PoseSample) {
+        self.pose_graph.add_keyframe(sample);
+    }
+
+    // Re-optimize after a new closure and carry bound entities along
+    fn apply_loop_closures(&mut self) {
+        let before = self.pose_graph.clone();
+        self.pose_graph.optimize();
+        let moves: Vec<(i32, Correction)> = before.keyframes.iter()
+            .zip(&self.pose_graph.keyframes)
+            .map(|(before, after)| (before.id, keyframe_move(before, after)))
+            .collect();
+        let move_for = |id: i32| moves.iter().find(|(keyframe, _)| *keyframe == id).map(|(_, correction)| *correction);
+        let significant = |translation: [f32; 3], rotation: [f32; 4]| {
+            length(translation) >= MIN_REPORTED_ADJUSTMENT
+                || length(quat_delta_axis_angle([0.0, 0.0, 0.0, 1.0], rotation)) >= MIN_REPORTED_ADJUSTMENT
+        };
+
+        for (index, object) in self.virtual_objects.iter_mut().enumerate() {
+            if object.anchor.is_some() || object.camera_attachment.is_some() {
+                continue;
+            }
+            let Some(correction) = object.keyframe.and_then(move_for) else {
+                continue;
+            };
+            let (position, rotation) = correction.apply(object.position, object.rotation);
+            let translation = sub(position, object.position);
+            let delta = quat_normalize(quat_mul(rotation, quat_conjugate(object.rotation)));
+            object.position = position;
+            object.rotation = rotation;
+            if significant(translation, delta) {
+                self.events.push(SessionEvent::ObjectPoseAdjusted { object_id: index, translation, rotation: delta });
+            }
+        }
+
+        for plane in self.detected_planes.iter_mut() {
+            let Some(correction) = before.at(plane.updated_at).and_then(|keyframe| move_for(keyframe.id)) else {
+                continue;
+            };
+            let (center, rotation) = correction.apply(plane.center, [0.0, 0.0, 0.0, 1.0]);
+            let translation = sub(center, plane.center);
+            plane.center = center;
+            plane.normal = quat_rotate(rotation, plane.normal);
+            if significant(translation, rotation) {
+                self.events.push(SessionEvent::PlanePoseAdjusted { plane_id: plane.id.clone(), translation, rotation });
+            }
+        }
+    }
+}
+
+// Id of the newest keyframe, or -1 if there are none yet. Its pose goes into the
+// outputs, which may be null
+#[no_mangle]
+pub extern "C" fn get_latest_keyframe(out_position: *mut f32, out_rotation: *mut f32) -> i32 {
+    with_session(|session| {
+        let keyframe = session.pose_graph.keyframes.last()?;
+        unsafe {
+            write_out(out_position, keyframe.position);
+            write_out(out_rotation, keyframe.rotation);
+        }
+        Some(keyframe.id)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// Id of the newest keyframe taken at or before `timestamp` on the session timeline,
+// or -1 if there is none
+#[no_mangle]
+pub extern "C" fn get_keyframe_at(timestamp: f64) -> i32 {
+    with_session(|session| session.pose_graph.at(timestamp).map(|keyframe| keyframe.id))
+        .flatten()
+        .unwrap_or(-1)
+}
+
+// A keyframe's current (possibly corrected) pose. Outputs may be null. Returns false
+// for an unknown id
+#[no_mangle]
+pub extern "C" fn get_keyframe_pose(keyframe_id: i32, out_position: *mut f32, out_rotation: *mut f32) -> bool {
+    with_session(|session| {
+        let index = session.pose_graph.index(keyframe_id)?;
+        let keyframe = session.pose_graph.keyframes[index];
+        unsafe {
+            write_out(out_position, keyframe.position);
+            write_out(out_rotation, keyframe.rotation);
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+// Add a loop closure: keyframe `to_keyframe`'s pose measured in `from_keyframe`'s frame
+// (position, then rotation as a quaternion). The graph is re-optimized right away;
+// corrected objects and planes are reported in events. Also counts toward the scan's
+// loop closures. Returns false for unknown or equal keyframes or a bad rotation
+#[no_mangle]
+pub extern "C" fn add_loop_closure(
+    from_keyframe: i32,
+    to_keyframe: i32,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32
+) -> bool {
+    let rotation = [rot_x, rot_y, rot_z, rot_w];
+    let finite = [pos_x, pos_y, pos_z].iter().chain(&rotation).all(|value| value.is_finite());
+    if from_keyframe == to_keyframe || !finite || rotation == [0.0; 4] {
+        return false;
+    }
+
+    with_session(|session| {
+        if session.pose_graph.index(from_keyframe).is_none() || session.pose_graph.index(to_keyframe).is_none() {
+            return false;
+        }
+        session.pose_graph.closures.push(LoopClosure {
+            from: from_keyframe,
+            to: to_keyframe,
+            position: [pos_x, pos_y, pos_z],
+            rotation: quat_normalize(rotation),
+        });
+        session.apply_loop_closures();
+        session.scan.reported_loop_closures += 1;
+        true
+    })
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        length(sub(a, b)) < 1e-5
+    }
+
+    // Keyframes 1 to 5, a meter apart along x
+    fn walked_session() -> ARSession {
+        let mut session = ARSession::new();
+        for step in 0..5 {
+            session.set_camera_pose(step as f64, [step as f32, 0.0, 0.0], IDENTITY);
+        }
+        assert_eq!(session.pose_graph.keyframes.len(), 5);
+        session
+    }
+
+    fn close_loop(session: &mut ARSession, from: i32, to: i32, position: [f32; 3]) {
+        session.pose_graph.closures.push(LoopClosure { from, to, position, rotation: IDENTITY });
+        session.apply_loop_closures();
+    }
+
+    #[test]
+    fn a_closure_pulls_the_older_keyframe_and_spreads_back_along_the_chain() {
+        let mut session = walked_session();
+        // Seen from the newest keyframe, the first is 0.4 m further along z than the
+        // chain says
+        close_loop(&mut session, 5, 1, [-4.0, 0.0, 0.4]);
+
+        let positions: Vec<[f32; 3]> = session.pose_graph.keyframes.iter().map(|keyframe| keyframe.position).collect();
+        let expected = [[0.0, 0.0, 0.4], [1.0, 0.0, 0.3], [2.0, 0.0, 0.2], [3.0, 0.0, 0.1], [4.0, 0.0, 0.0]];
+        for (position, expected) in positions.iter().zip(expected) {
+            assert!(close(*position, expected), "{:?}", positions);
+        }
+        assert!(session.pose_graph.keyframes.iter().all(|keyframe| keyframe.rotation == IDENTITY));
+    }
+
+    #[test]
+    fn bound_objects_move_with_their_keyframe() {
+        let mut session = ARSession::new();
+        for step in 0..3 {
+            session.set_camera_pose(step as f64, [step as f32, 0.0, 0.0], IDENTITY);
+        }
+        // Bound to keyframe 3, halfway along the loop
+        let index = session.place_object(ARObjectType::Cube, [2.0, 0.0, -1.0], IDENTITY).unwrap();
+        for step in 3..5 {
+            session.set_camera_pose(step as f64, [step as f32, 0.0, 0.0], IDENTITY);
+        }
+
+        close_loop(&mut session, 5, 1, [-4.0, 0.0, 0.4]);
+        assert!(close(session.virtual_objects[index].position, [2.0, 0.0, -0.8]));
+        let events: Vec<SessionEvent> = std::iter::from_fn(|| session.events.pop()).collect();
+        let [SessionEvent::ObjectPoseAdjusted { object_id, translation, rotation }] = events[..] else {
+            panic!("expected one adjustment, got {:?}", events);
+        };
+        assert_eq!(object_id, index);
+        assert!(close(translation, [0.0, 0.0, 0.2]));
+        assert_eq!(rotation, IDENTITY);
+    }
+
+    #[test]
+    fn closures_on_dropped_keyframes_go_with_them() {
+        let mut graph = PoseGraph::default();
+        let add = |graph: &mut PoseGraph, step: usize| {
+            graph.add_keyframe(PoseSample { timestamp: step as f64, position: [step as f32, 0.0, 0.0], rotation: IDENTITY });
+        };
+        for step in 0..3 {
+            add(&mut graph, step);
+        }
+        let closure = |from, to| LoopClosure { from, to, position: [0.0; 3], rotation: IDENTITY };
+        graph.closures.push(closure(3, 1));
+        graph.closures.push(closure(3, 2));
+
+        for step in 3..=MAX_KEYFRAMES {
+            add(&mut graph, step);
+        }
+        // Keyframe 1 was the oldest and went first
+        assert_eq!(graph.keyframes.len(), MAX_KEYFRAMES);
+        assert_eq!(graph.keyframes[0].id, 2);
+        assert_eq!(graph.closures.len(), 1);
+        assert_eq!((graph.closures[0].from, graph.closures[0].to), (3, 2));
+    }
+}
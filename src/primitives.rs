@@ -0,0 +1,335 @@
+// Parametric primitives beyond the built-in cube and sphere: cylinders, planes, tori,
+// cones and capsules with explicit dimensions and tessellation. Each generates its own
+// mesh, which hosts can fetch to build GPU buffers, and a collision shape for physics.
+// Capsules collide as true capsules; the other shapes use their bounding boxes, which
+// is close for cylinders and planes and rough for cones and tori.
+//
+// Dimensions are in meters at unit scale, with the shape's axis along local +y and its
+// bounding box centered on the object's origin. Meshes are indexed triangle lists,
+// counter-clockwise seen from outside, with per-vertex normals
+
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "physics")]
+use crate::contacts::ColliderShape;
+use crate::math::{length, normalize};
+use crate::quotas::AR_QUOTA_EXCEEDED;
+use crate::{with_session, ARObjectType};
+
+pub const AR_PRIMITIVE_CYLINDER: i32 = 0;
+pub const AR_PRIMITIVE_PLANE: i32 = 1;
+pub const AR_PRIMITIVE_TORUS: i32 = 2;
+pub const AR_PRIMITIVE_CONE: i32 = 3;
+pub const AR_PRIMITIVE_CAPSULE: i32 = 4;
+
+const DEFAULT_SEGMENTS: u32 = 24;
+const MIN_SEGMENTS: u32 = 3;
+const MAX_SEGMENTS: u32 = 128;
+
+// Planes have no thickness to collide with, so they get this much
+#[cfg(feature = "physics")]
+const PLANE_COLLIDER_HALF_THICKNESS: f32 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Primitive {
+    Cylinder { radius: f32, height: f32, segments: u32 },
+    // Flat rectangle in the xz plane, facing +y
+    Plane { width: f32, depth: f32 },
+    // Ring in the xz plane. `segments` go around the ring, half as many around the tube
+    Torus { major_radius: f32, minor_radius: f32, segments: u32 },
+    // Base at the bottom, apex at the top
+    Cone { radius: f32, height: f32, segments: u32 },
+    // `height` is overall, including the hemispherical caps
+    Capsule { radius: f32, height: f32, segments: u32 },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub(crate) fn vertex(&mut self, position: [f32; 3], normal: [f32; 3]) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        (self.positions.len() - 1) as u32
+    }
+
+    pub(crate) fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend([a, b, c]);
+    }
+
+    // Two triangles over a quad given counter-clockwise
+    pub(crate) fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    // Flat disc at height `y` facing up or down
+    fn cap(&mut self, radius: f32, y: f32, segments: u32, up: bool) {
+        let normal = [0.0, if up { 1.0 } else { -1.0 }, 0.0];
+        let center = self.vertex([0.0, y, 0.0], normal);
+        let first = self.positions.len() as u32;
+        for segment in 0..segments {
+            let (x, z) = around(segment, segments);
+            self.vertex([radius * x, y, radius * z], normal);
+        }
+        for segment in 0..segments {
+            let (a, b) = (first + segment, first + (segment + 1) % segments);
+            if up {
+                self.triangle(center, b, a);
+            } else {
+                self.triangle(center, a, b);
+            }
+        }
+    }
+
+    // Grid of `rows` + 1 rings of `columns` + 1 vertices (the seam is duplicated), from
+    // a function giving each vertex's position and normal. Rings run bottom to top and
+    // vertices counter-clockwise seen from above
+    fn grid(&mut self, rows: u32, columns: u32, vertex: impl Fn(u32, u32) -> ([f32; 3], [f32; 3])) {
+        let first = self.positions.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (position, normal) = vertex(row, column);
+                self.vertex(position, normal);
+            }
+        }
+        let index = |row: u32, column: u32| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                self.quad(index(row, column), index(row + 1, column), index(row + 1, column + 1), index(row, column + 1));
+            }
+        }
+    }
+}
+
+// Unit circle position (x, z) of a segment boundary, going clockwise seen from above
+// (+x toward +z) so that grids built from it wind counter-clockwise from outside
+fn around(segment: u32, segments: u32) -> (f32, f32) {
+    let angle = 2.0 * PI * segment as f32 / segments as f32;
+    (angle.cos(), angle.sin())
+}
+
+fn clamp_segments(segments: u32) -> u32 {
+    if segments == 0 { DEFAULT_SEGMENTS } else { segments.clamp(MIN_SEGMENTS, MAX_SEGMENTS) }
+}
+
+impl Primitive {
+    // A primitive from FFI arguments: `a` and `b` are radius and height for cylinders,
+    // cones and capsules, width and depth for planes, and the major and minor radius for
+    // tori. None for an unknown kind or non-positive dimensions
+    pub(crate) fn from_code(kind: i32, a: f32, b: f32, segments: u32) -> Option<Self> {
+        if !(a > 0.0 && b > 0.0 && a.is_finite() && b.is_finite()) {
+            return None;
+        }
+        let segments = clamp_segments(segments);
+        Some(match kind {
+            AR_PRIMITIVE_CYLINDER => Primitive::Cylinder { radius: a, height: b, segments },
+            AR_PRIMITIVE_PLANE => Primitive::Plane { width: a, depth: b },
+            AR_PRIMITIVE_TORUS => Primitive::Torus { major_radius: a, minor_radius: b.min(a), segments },
+            AR_PRIMITIVE_CONE => Primitive::Cone { radius: a, height: b, segments },
+            AR_PRIMITIVE_CAPSULE => Primitive::Capsule { radius: a, height: b.max(2.0 * a), segments },
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Primitive::Cylinder { .. } => "cylinder",
+            Primitive::Plane { .. } => "plane",
+            Primitive::Torus { .. } => "torus",
+            Primitive::Cone { .. } => "cone",
+            Primitive::Capsule { .. } => "capsule",
+        }
+    }
+
+    // The same primitive with every dimension multiplied by `factor`
+    pub fn scaled(self, factor: f32) -> Self {
+        match self {
+            Primitive::Cylinder { radius, height, segments } => Primitive::Cylinder { radius: radius * factor, height: height * factor, segments },
+            Primitive::Plane { width, depth } => Primitive::Plane { width: width * factor, depth: depth * factor },
+            Primitive::Torus { major_radius, minor_radius, segments } => {
+                Primitive::Torus { major_radius: major_radius * factor, minor_radius: minor_radius * factor, segments }
+            }
+            Primitive::Cone { radius, height, segments } => Primitive::Cone { radius: radius * factor, height: height * factor, segments },
+            Primitive::Capsule { radius, height, segments } => Primitive::Capsule { radius: radius * factor, height: height * factor, segments },
+        }
+    }
+
+    pub fn half_extents(&self) -> [f32; 3] {
+        match *self {
+            Primitive::Cylinder { radius, height, .. }
+            | Primitive::Cone { radius, height, .. }
+            | Primitive::Capsule { radius, height, .. } => [radius, height * 0.5, radius],
+            Primitive::Plane { width, depth } => [width * 0.5, 0.0, depth * 0.5],
+            Primitive::Torus { major_radius, minor_radius, .. } => {
+                let outer = major_radius + minor_radius;
+                [outer, minor_radius, outer]
+            }
+        }
+    }
+
+    pub fn bounding_radius(&self) -> f32 {
+        match *self {
+            Primitive::Capsule { height, .. } => height * 0.5,
+            Primitive::Torus { major_radius, minor_radius, .. } => major_radius + minor_radius,
+            _ => length(self.half_extents()),
+        }
+    }
+
+    #[cfg(feature = "physics")]
+    pub(crate) fn collider_shape(&self) -> ColliderShape {
+        match *self {
+            Primitive::Capsule { radius, height, .. } => ColliderShape::Capsule { radius, half_height: height * 0.5 - radius },
+            Primitive::Plane { .. } => {
+                let [x, _, z] = self.half_extents();
+                ColliderShape::Box([x, PLANE_COLLIDER_HALF_THICKNESS, z])
+            }
+            _ => ColliderShape::Box(self.half_extents()),
+        }
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        let mut mesh = Mesh::default();
+        match *self {
+            Primitive::Cylinder { radius, height, segments } => {
+                let half = height * 0.5;
+                mesh.grid(1, segments, |row, column| {
+                    let (x, z) = around(column, segments);
+                    ([radius * x, if row == 0 { -half } else { half }, radius * z], [x, 0.0, z])
+                });
+                mesh.cap(radius, half, segments, true);
+                mesh.cap(radius, -half, segments, false);
+            }
+            Primitive::Plane { width, depth } => {
+                let (x, z) = (width * 0.5, depth * 0.5);
+                let up = [0.0, 1.0, 0.0];
+                let corners = [[-x, 0.0, -z], [-x, 0.0, z], [x, 0.0, z], [x, 0.0, -z]].map(|corner| mesh.vertex(corner, up));
+                mesh.quad(corners[0], corners[1], corners[2], corners[3]);
+            }
+            Primitive::Torus { major_radius, minor_radius, segments } => {
+                let sides = (segments / 2).max(MIN_SEGMENTS);
+                // Rows go around the tube starting at its bottom, through the outside
+                mesh.grid(sides, segments, |row, column| {
+                    let (x, z) = around(column, segments);
+                    let tube = 2.0 * PI * row as f32 / sides as f32 - PI * 0.5;
+                    let (out, up) = (tube.cos(), tube.sin());
+                    let ring = major_radius + minor_radius * out;
+                    ([ring * x, minor_radius * up, ring * z], [out * x, up, out * z])
+                });
+            }
+            Primitive::Cone { radius, height, segments } => {
+                let half = height * 0.5;
+                // Side normals lean up by the slope; the apex is duplicated per column
+                mesh.grid(1, segments, |row, column| {
+                    let (x, z) = around(column, segments);
+                    let normal = normalize([x * height, radius, z * height]);
+                    let r = if row == 0 { radius } else { 0.0 };
+                    ([r * x, if row == 0 { -half } else { half }, r * z], normal)
+                });
+                mesh.cap(radius, -half, segments, false);
+            }
+            Primitive::Capsule { radius, height, segments } => {
+                let rings = (segments / 4).max(2);
+                let offset = height * 0.5 - radius;
+                // Latitude rows from the bottom pole to the top one, with the equator
+                // repeated so the cylindrical part spans between the two copies
+                mesh.grid(2 * rings + 1, segments, |row, column| {
+                    let (x, z) = around(column, segments);
+                    let (latitude, center) = if row <= rings {
+                        (-PI * 0.5 + PI * 0.5 * row as f32 / rings as f32, -offset)
+                    } else {
+                        (PI * 0.5 * (row - rings - 1) as f32 / rings as f32, offset)
+                    };
+                    let (out, up) = (latitude.cos(), latitude.sin());
+                    let normal = [out * x, up, out * z];
+                    ([radius * normal[0], center + radius * up, radius * normal[2]], normal)
+                });
+            }
+        }
+        mesh
+    }
+}
+
+// Place a primitive (AR_PRIMITIVE_*) with dimensions `a` and `b` in meters: radius and
+// height for cylinders, cones and capsules (whose height includes the caps), width and
+// depth for planes, major and minor radius for tori. `segments` sets the tessellation
+// around the axis (0 for the default). Returns the object id, AR_QUOTA_EXCEEDED if the
+// object quota is full, or -1 for an unknown kind or non-positive dimensions
+#[no_mangle]
+pub extern "C" fn place_primitive(
+    kind: i32,
+    a: f32,
+    b: f32,
+    segments: u32,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32
+) -> i32 {
+    let Some(primitive) = Primitive::from_code(kind, a, b, segments) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        session.place_object(ARObjectType::Primitive(primitive), [pos_x, pos_y, pos_z], [rot_x, rot_y, rot_z, rot_w])
+            .map_or(AR_QUOTA_EXCEEDED, |index| index as i32)
+    })
+    .unwrap_or(-1)
+}
+
+// Copy a primitive or text object's mesh (at its current scale, in object space):
+// positions and normals (3 floats per vertex) and triangle indices. Each array is
+// written only if its output is non-null and holds the whole mesh; `out_index_count`
+// (may be null) receives the number of indices. Returns the vertex count, or -1 for an
+// invalid id or an object without a generated mesh
+#[no_mangle]
+pub extern "C" fn get_object_mesh(
+    object_id: i32,
+    out_positions: *mut f32,
+    out_normals: *mut f32,
+    vertex_capacity: u32,
+    out_indices: *mut u32,
+    index_capacity: u32,
+    out_index_count: *mut u32,
+) -> i32 {
+    let Ok(index) = usize::try_from(object_id) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        let object = session.virtual_objects.get(index)?;
+        let mesh = match &object.object_type {
+            ARObjectType::Primitive(primitive) => primitive.scaled(object.scale).mesh(),
+            #[cfg(feature = "text")]
+            ARObjectType::Text(label) => Mesh {
+                positions: label.mesh.positions.iter().map(|&position| crate::math::scale(position, object.scale)).collect(),
+                ..label.mesh.clone()
+            },
+            _ => return None,
+        };
+        let vertices = mesh.positions.len();
+        unsafe {
+            if vertices <= vertex_capacity as usize {
+                for (out, values) in [(out_positions, &mesh.positions), (out_normals, &mesh.normals)] {
+                    if !out.is_null() {
+                        std::ptr::copy_nonoverlapping(values.as_ptr() as *const f32, out, vertices * 3);
+                    }
+                }
+            }
+            if !out_indices.is_null() && mesh.indices.len() <= index_capacity as usize {
+                std::ptr::copy_nonoverlapping(mesh.indices.as_ptr(), out_indices, mesh.indices.len());
+            }
+            if !out_index_count.is_null() {
+                *out_index_count = mesh.indices.len() as u32;
+            }
+        }
+        Some(vertices as i32)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
@@ -0,0 +1,247 @@
+// Soft limits on what the session holds: objects, planes (native and derived),
+// point-cloud points and registered asset bytes (font data). Without limits these grow
+// for as long as the host feeds them; with one set, additions that would go over it are
+// refused with an explicit status instead:
+//
+// - placing an object returns AR_QUOTA_EXCEEDED (place_* and scatter_objects stop early)
+// - add_detected_plane returns false; derived planes are not added
+// - a depth frame whose point cloud has more points than the limit returns
+//   AR_QUOTA_EXCEEDED and the previous cloud is kept
+// - register_font returns false
+//
+// A quota_warning event is queued when usage reaches the warning fraction of a limit,
+// and again after it has dropped back below it. A quota_exceeded event is queued on the
+// first refusal after an accepted addition, so a host retrying every frame doesn't
+// flood the queue. Restoring a snapshot or importing a handoff bundle isn't limited.
+// Limits are 0 (none) by default
+
+use serde::Serialize;
+
+use crate::events::SessionEvent;
+use crate::{with_session, ARSession};
+
+pub const AR_QUOTA_EXCEEDED: i32 = -4;
+
+pub const AR_RESOURCE_OBJECTS: i32 = 0;
+pub const AR_RESOURCE_PLANES: i32 = 1;
+pub const AR_RESOURCE_POINTS: i32 = 2;
+pub const AR_RESOURCE_ASSET_BYTES: i32 = 3;
+
+const RESOURCE_COUNT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resource {
+    Objects,
+    Planes,
+    Points,
+    AssetBytes,
+}
+
+impl Resource {
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            AR_RESOURCE_OBJECTS => Some(Resource::Objects),
+            AR_RESOURCE_PLANES => Some(Resource::Planes),
+            AR_RESOURCE_POINTS => Some(Resource::Points),
+            AR_RESOURCE_ASSET_BYTES => Some(Resource::AssetBytes),
+            _ => None,
+        }
+    }
+
+    fn slot(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct QuotaState {
+    // 0 for no limit
+    limits: [u64; RESOURCE_COUNT],
+    // Fraction of a limit at which usage is warned about
+    warn_fraction: f32,
+    warned: [bool; RESOURCE_COUNT],
+    // Set from a refusal until the next accepted addition
+    refusing: [bool; RESOURCE_COUNT],
+}
+
+impl Default for QuotaState {
+    fn default() -> Self {
+        QuotaState {
+            limits: [0; RESOURCE_COUNT],
+            warn_fraction: 0.8,
+            warned: [false; RESOURCE_COUNT],
+            refusing: [false; RESOURCE_COUNT],
+        }
+    }
+}
+
+impl ARSession {
+    // Whether usage of `resource` may grow to `used`. Queues the warning and exceeded
+    // events and counts refusals
+    pub(crate) fn admit(&mut self, resource: Resource, used: u64) -> bool {
+        let slot = resource.slot();
+        let quotas = &mut self.quotas;
+        let limit = quotas.limits[slot];
+        if limit == 0 {
+            return true;
+        }
+
+        if used > limit {
+            if !quotas.refusing[slot] {
+                quotas.refusing[slot] = true;
+                self.events.push(SessionEvent::QuotaExceeded { resource, requested: used, limit });
+            }
+            self.metrics.quota_refusals += 1;
+            return false;
+        }
+        quotas.refusing[slot] = false;
+        // Compared at the fraction's own precision, so 4 of 5 reaches 0.8
+        let near = (used as f64 / limit as f64) as f32 >= quotas.warn_fraction;
+        if near && !quotas.warned[slot] {
+            self.events.push(SessionEvent::QuotaWarning { resource, used, limit });
+        }
+        quotas.warned[slot] = near;
+        true
+    }
+
+    fn quota_usage(&self, resource: Resource) -> u64 {
+        match resource {
+            Resource::Objects => self.virtual_objects.len() as u64,
+            Resource::Planes => self.detected_planes.len() as u64,
+            #[cfg(feature = "reconstruction")]
+            Resource::Points => self.point_cloud.len() as u64,
+            #[cfg(feature = "text")]
+            Resource::AssetBytes => crate::text_mesh::registered_font_bytes() as u64,
+            #[allow(unreachable_patterns)]
+            _ => 0,
+        }
+    }
+}
+
+// Limit a resource (AR_RESOURCE_*) to `limit` objects, planes, points or bytes; 0
+// removes the limit. Usage already over a new limit is kept, but nothing more is
+// added. Returns false for an unknown resource
+#[no_mangle]
+pub extern "C" fn set_quota(resource: i32, limit: u64) -> bool {
+    let Some(resource) = Resource::from_code(resource) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let slot = resource.slot();
+        session.quotas.limits[slot] = limit;
+        session.quotas.warned[slot] = false;
+        session.quotas.refusing[slot] = false;
+    })
+    .is_some()
+}
+
+// Fraction of each limit (above 0, at most 1) at which a quota_warning event is
+// queued; 0.8 by default. Returns false outside that range
+#[no_mangle]
+pub extern "C" fn set_quota_warning_fraction(fraction: f32) -> bool {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return false;
+    }
+
+    with_session(|session| session.quotas.warn_fraction = fraction).is_some()
+}
+
+// Current usage of a resource (AR_RESOURCE_*) and its limit (0 for none). Outputs may
+// be null. Returns false for an unknown resource or without a session
+#[no_mangle]
+pub extern "C" fn get_quota_usage(resource: i32, out_used: *mut u64, out_limit: *mut u64) -> bool {
+    let Some(resource) = Resource::from_code(resource) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let used = session.quota_usage(resource);
+        let limit = session.quotas.limits[resource.slot()];
+        unsafe {
+            if !out_used.is_null() {
+                *out_used = used;
+            }
+            if !out_limit.is_null() {
+                *out_limit = limit;
+            }
+        }
+    })
+    .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    fn drain(session: &mut ARSession) -> Vec<SessionEvent> {
+        std::iter::from_fn(|| session.events.pop()).collect()
+    }
+
+    fn place(session: &mut ARSession) -> Option<usize> {
+        session.place_object(ARObjectType::Cube, [0.0; 3], [0.0, 0.0, 0.0, 1.0])
+    }
+
+    #[test]
+    fn warns_once_then_refuses_once_until_an_addition_is_accepted() {
+        let mut session = ARSession::new();
+        session.quotas.limits[Resource::Objects.slot()] = 5;
+
+        for _ in 0..3 {
+            assert!(place(&mut session).is_some());
+        }
+        assert!(drain(&mut session).is_empty());
+
+        // 4 of 5 reaches the 0.8 warning fraction; the 5th stays warned without repeating
+        assert!(place(&mut session).is_some());
+        assert!(place(&mut session).is_some());
+        assert_eq!(
+            drain(&mut session),
+            vec![SessionEvent::QuotaWarning { resource: Resource::Objects, used: 4, limit: 5 }]
+        );
+
+        // Retrying every frame reports the refusal once, but counts every one
+        for _ in 0..3 {
+            assert!(place(&mut session).is_none());
+        }
+        assert_eq!(
+            drain(&mut session),
+            vec![SessionEvent::QuotaExceeded { resource: Resource::Objects, requested: 6, limit: 5 }]
+        );
+        assert_eq!(session.metrics.quota_refusals, 3);
+        assert_eq!(session.virtual_objects.len(), 5);
+    }
+
+    #[test]
+    fn dropping_below_the_warning_fraction_rearms_it() {
+        let mut session = ARSession::new();
+        session.quotas.limits[Resource::Planes.slot()] = 10;
+
+        assert!(session.admit(Resource::Planes, 8));
+        assert_eq!(drain(&mut session).len(), 1);
+        assert!(session.admit(Resource::Planes, 10));
+        assert!(!session.admit(Resource::Planes, 11));
+        assert_eq!(drain(&mut session).len(), 1);
+
+        // Usage fell back (planes expired); the next approach warns again
+        assert!(session.admit(Resource::Planes, 3));
+        assert!(session.admit(Resource::Planes, 8));
+        assert_eq!(
+            drain(&mut session),
+            vec![SessionEvent::QuotaWarning { resource: Resource::Planes, used: 8, limit: 10 }]
+        );
+        // A new refusal after the accepted additions is reported again
+        assert!(!session.admit(Resource::Planes, 11));
+        assert_eq!(drain(&mut session).len(), 1);
+    }
+
+    #[test]
+    fn no_limit_admits_everything_silently() {
+        let mut session = ARSession::new();
+        assert!(session.admit(Resource::Points, u64::MAX));
+        assert!(drain(&mut session).is_empty());
+        assert_eq!(session.metrics.quota_refusals, 0);
+    }
+}
@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color_grading::ColorGrading;
+use crate::math::{length, sub};
+use crate::primitives::Primitive;
+use crate::zones::{ZoneKind, ZoneVisibility};
+use crate::{ARObjectType, ARSession, PlaneSource};
+
+// Edge length (cube) or diameter (sphere) in meters for placed objects
+pub const DEFAULT_OBJECT_SIZE: f32 = 0.1;
+
+// Vertical field of view used when no camera intrinsics are available
+pub const DEFAULT_VERTICAL_FOV: f32 = 60.0 * std::f32::consts::PI / 180.0;
+
+// Everything a renderer needs to draw one frame, decoupled from the session lock
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderSnapshot {
+    pub camera: CameraView,
+    pub planes: Vec<PlaneDrawable>,
+    pub objects: Vec<ObjectDrawable>,
+    #[serde(default)]
+    pub decals: Vec<DecalDrawable>,
+    // Doorways of portal zones, for stencilling objects seen through them
+    #[serde(default)]
+    pub portals: Vec<PortalDrawable>,
+    // Applied to objects (not planes) to match the camera image
+    #[serde(default)]
+    pub color_grading: ColorGrading,
+}
+
+// Camera looking down its local -Z axis with +Y up, as in ARKit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraView {
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    pub vertical_fov: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaneDrawable {
+    pub id: String,
+    pub center: [f32; 3],
+    pub extent: [f32; 2],
+    pub normal: [f32; 3],
+    pub source: PlaneSource,
+    // Flagged by plane expiry; hosts may fade it out
+    #[serde(default)]
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDrawable {
+    pub index: usize,
+    pub shape: Shape,
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    pub size: f32,
+    // 0 (hidden) to 1, from the object's fade policy; 0 when a visibility zone hides it
+    #[serde(default = "full_opacity")]
+    pub opacity: f32,
+    // Set when the object is seen through this portal and should be clipped to its
+    // doorway
+    #[serde(default)]
+    pub portal: Option<i32>,
+    // Render layer bits and level of detail to draw at (0 is full detail), see
+    // archetypes.rs
+    #[serde(default = "default_layers")]
+    pub layers: u32,
+    #[serde(default)]
+    pub lod: u32,
+    // Fraction (0-1) of the object hidden behind people, 0 without a recent matte (see
+    // occlusion.rs). Hosts compositing the matte themselves can skip fully hidden objects
+    #[serde(default)]
+    pub person_occlusion: f32,
+}
+
+fn full_opacity() -> f32 {
+    1.0
+}
+
+fn default_layers() -> u32 {
+    crate::archetypes::DEFAULT_LAYERS
+}
+
+// See zones.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortalDrawable {
+    pub id: i32,
+    // Counter-clockwise seen from the front
+    pub doorway: [[f32; 3]; 4],
+}
+
+// A decal's geometry on its plane or the reconstruction mesh (no plane id), already
+// lifted clear of the surface; see decals.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecalDrawable {
+    pub id: i32,
+    pub texture: String,
+    pub plane_id: Option<String>,
+    pub positions: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    pub normal: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shape {
+    Cube,
+    Sphere,
+    // Dimensions already include the object's scale
+    Primitive(Primitive),
+    // A text label's bounding box, including the object's scale; the mesh itself
+    // comes from get_object_mesh
+    Text { text: String, half_extents: [f32; 3] },
+    Custom(String),
+}
+
+impl RenderSnapshot {
+    // Capture the global session, or None if it hasn't been initialized
+    pub fn capture() -> Option<Self> {
+        crate::with_session(|session| Self::from(&*session))
+    }
+
+    // Move the camera to the latest reported pose without taking the session lock, so
+    // a snapshot captured earlier in the frame draws from the freshest viewpoint.
+    // Returns false if the pose couldn't be read (see camera_slot.rs)
+    pub fn refresh_camera(&mut self) -> bool {
+        let Some(sample) = crate::camera_slot::latest() else {
+            return false;
+        };
+        self.camera.position = sample.position;
+        self.camera.rotation = sample.rotation;
+        true
+    }
+}
+
+impl From<&ARSession> for RenderSnapshot {
+    fn from(session: &ARSession) -> Self {
+        let camera = CameraView {
+            position: session.camera_position,
+            rotation: session.camera_rotation,
+            vertical_fov: DEFAULT_VERTICAL_FOV,
+        };
+
+        let planes = session.detected_planes.iter()
+            .map(|plane| PlaneDrawable {
+                id: plane.id.clone(),
+                center: plane.center,
+                extent: plane.extent,
+                normal: plane.normal,
+                source: plane.source,
+                stale: plane.stale,
+            })
+            .collect();
+
+        let objects = session.virtual_objects.iter()
+            .enumerate()
+            .map(|(index, object)| ObjectDrawable {
+                index,
+                shape: match &object.object_type {
+                    ARObjectType::Cube => Shape::Cube,
+                    ARObjectType::Sphere => Shape::Sphere,
+                    ARObjectType::Primitive(primitive) => Shape::Primitive(primitive.scaled(object.scale)),
+                    #[cfg(feature = "text")]
+                    ARObjectType::Text(label) => Shape::Text {
+                        text: label.spec.text.clone(),
+                        half_extents: label.half_extents.map(|h| h * object.scale),
+                    },
+                    ARObjectType::Custom(name) => Shape::Custom(name.clone()),
+                },
+                position: object.position,
+                rotation: object.rotation,
+                size: DEFAULT_OBJECT_SIZE * object.scale,
+                opacity: if object.zone_visibility == ZoneVisibility::Hidden { 0.0 } else { object.opacity },
+                portal: match object.zone_visibility {
+                    ZoneVisibility::ThroughPortal(id) => Some(id),
+                    _ => None,
+                },
+                layers: object.layers,
+                lod: object.lod_level(length(sub(object.position, session.camera_position))),
+                person_occlusion: session.object_occlusion(index).unwrap_or(0.0),
+            })
+            .collect();
+
+        RenderSnapshot {
+            camera,
+            planes,
+            objects,
+            decals: session.decal_drawables(),
+            portals: session.zones.zones.iter()
+                .filter(|zone| zone.kind == ZoneKind::Portal)
+                .map(|zone| PortalDrawable { id: zone.id, doorway: zone.doorway() })
+                .collect(),
+            color_grading: session.color_analysis.grading,
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Scan quality scoring. The session is scored on three signals, each against a
+// configurable completion criterion:
+//
+// - Coverage: fraction of detected plane area observed for long enough
+// - Feature density: point cloud points per square meter of plane area
+// - Loop closures: times tracking recognized a revisited area. ARKit doesn't report
+//   these directly; anchor drift corrections are counted as they're its visible
+//   effect, along with any the host reports itself
+//
+// Once every criterion is met a scan_sufficient event is emitted, so scanning flows can
+// finish on the scan's actual state rather than a timer
+
+use crate::events::SessionEvent;
+use crate::pose_filter::write_out;
+use crate::{with_session, ARSession};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScanCriteria {
+    // 0-1 of total plane area
+    pub min_coverage: f32,
+    // Seconds a plane cell must be observed to count as covered
+    pub min_observe_seconds: f32,
+    // Points per square meter of plane
+    pub min_feature_density: f32,
+    pub min_loop_closures: u32,
+}
+
+impl Default for ScanCriteria {
+    fn default() -> Self {
+        ScanCriteria {
+            min_coverage: 0.6,
+            min_observe_seconds: 0.5,
+            // Without reconstruction there are no feature points to count
+            min_feature_density: if cfg!(feature = "reconstruction") { 100.0 } else { 0.0 },
+            min_loop_closures: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ScanQuality {
+    pub coverage: f32,
+    pub feature_density: f32,
+    pub loop_closures: u32,
+    // 0-1; each signal contributes equally, capped at its criterion
+    pub score: f32,
+    pub sufficient: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanState {
+    pub criteria: ScanCriteria,
+    pub reported_loop_closures: u32,
+    // Whether scan_sufficient has been emitted for the current criteria
+    announced: bool,
+}
+
+// Progress toward a minimum, 1 once reached (or when there's nothing to reach)
+fn progress(value: f32, minimum: f32) -> f32 {
+    if minimum <= 0.0 { 1.0 } else { (value / minimum).min(1.0) }
+}
+
+impl ARSession {
+    pub(crate) fn scan_quality(&self) -> ScanQuality {
+        let criteria = self.scan.criteria;
+
+        let area: f32 = self.detected_planes.iter().map(|plane| plane.extent[0] * plane.extent[1]).sum();
+        let covered: f32 = self.detected_planes.iter()
+            .enumerate()
+            .map(|(index, plane)| {
+                let fraction = self.plane_coverage_fraction(index, criteria.min_observe_seconds).unwrap_or(0.0);
+                fraction * plane.extent[0] * plane.extent[1]
+            })
+            .sum();
+        let coverage = if area > 0.0 { covered / area } else { 0.0 };
+        #[cfg(feature = "reconstruction")]
+        let points = self.point_cloud.len();
+        #[cfg(not(feature = "reconstruction"))]
+        let points = 0;
+        let feature_density = if area > 0.0 { points as f32 / area } else { 0.0 };
+        let loop_closures = self.metrics.anchor_drift_events as u32 + self.scan.reported_loop_closures;
+
+        let score = (progress(coverage, criteria.min_coverage)
+            + progress(feature_density, criteria.min_feature_density)
+            + progress(loop_closures as f32, criteria.min_loop_closures as f32))
+            / 3.0;
+        let sufficient = !self.detected_planes.is_empty()
+            && coverage >= criteria.min_coverage
+            && feature_density >= criteria.min_feature_density
+            && loop_closures >= criteria.min_loop_closures;
+
+        ScanQuality { coverage, feature_density, loop_closures, score, sufficient }
+    }
+
+    // Emit scan_sufficient the first time the criteria are met
+    pub(crate) fn update_scan_quality(&mut self) {
+        if self.scan.announced {
+            return;
+        }
+        let quality = self.scan_quality();
+        if quality.sufficient {
+            self.scan.announced = true;
+            self.events.push(SessionEvent::ScanSufficient {
+                coverage: quality.coverage,
+                feature_density: quality.feature_density,
+                loop_closures: quality.loop_closures,
+            });
+        }
+    }
+}
+
+// Completion criteria for scan_sufficient. Changing them re-arms the event
+#[no_mangle]
+pub extern "C" fn set_scan_criteria(
+    min_coverage: f32,
+    min_observe_seconds: f32,
+    min_feature_density: f32,
+    min_loop_closures: u32,
+) {
+    with_session(|session| {
+        session.scan.criteria = ScanCriteria {
+            min_coverage: min_coverage.clamp(0.0, 1.0),
+            min_observe_seconds: min_observe_seconds.max(0.0),
+            min_feature_density: min_feature_density.max(0.0),
+            min_loop_closures,
+        };
+        session.scan.announced = false;
+    });
+}
+
+// Count a loop closure the host detected itself, e.g. from ARKit relocalization
+#[no_mangle]
+pub extern "C" fn report_loop_closure() {
+    with_session(|session| session.scan.reported_loop_closures += 1);
+}
+
+// Current scan score (0-1), or -1 without a session. `out_signals` (may be null)
+// receives coverage, feature density and loop closure count as 3 floats
+#[no_mangle]
+pub extern "C" fn get_scan_quality(out_signals: *mut f32) -> f32 {
+    with_session(|session| {
+        let quality = session.scan_quality();
+        unsafe {
+            write_out(out_signals, [quality.coverage, quality.feature_density, quality.loop_closures as f32]);
+        }
+        quality.score
+    })
+    .unwrap_or(-1.0)
+}
+
+#[no_mangle]
+pub extern "C" fn is_scan_sufficient() -> bool {
+    with_session(|session| session.scan_quality().sufficient).unwrap_or(false)
+}
@@ -0,0 +1,295 @@
+// Procedural scattering: place many objects at once (grass tufts, confetti,
+// collectibles) at random spots on the detected planes inside a box region. Spots are
+// drawn by dart throwing: a plane is picked with probability proportional to how much
+// of it lies in the region, then a point on that part of it, and the point is kept if
+// it satisfies the rules:
+//
+// - the plane's classification is allowed and its slope (angle of its normal from
+//   straight up) is within the limit
+// - the point is at least the edge margin inside the plane's rectangle
+// - it's at least the minimum spacing from every object, scattered or already placed
+//
+// Each object stands on its surface, origin on the plane and up along the normal, with
+// a random turn about the normal and a random scale in the given range. Draws come from
+// the session's effect RNG, so deterministic sessions scatter identically. When space
+// runs out before `count` spots are found, or the object quota fills up (see quotas.rs),
+// fewer objects are placed. Stale planes (see plane_expiry.rs) are skipped
+
+use crate::math::{add, cross, dot, length, quat_from_axis_angle, quat_mul, scale, sub, tangent_basis};
+use crate::{with_session, ARObjectType, ARPlane, ARSession};
+
+// Draws allowed per requested object before giving up on the rest
+const ATTEMPTS_PER_OBJECT: usize = 30;
+
+// Most objects one call scatters, so a huge count can't spin through draws
+const MAX_SCATTER_COUNT: usize = 10_000;
+
+// Mixed into the effect RNG seed so scattering doesn't share draws with other effects
+const SCATTER_SALT: u64 = 0x5CA7_7E12;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScatterRules {
+    pub min_spacing: f32,
+    pub edge_margin: f32,
+    // Cosine of the steepest allowed slope
+    pub min_up: f32,
+    // Bit per allowed PlaneClassification; 0 allows all
+    pub classifications: u32,
+    pub scale: [f32; 2],
+}
+
+impl ScatterRules {
+    fn allows(&self, plane: &ARPlane) -> bool {
+        let class_allowed = self.classifications == 0 || self.classifications & (1 << plane.classification as u32) != 0;
+        class_allowed && !plane.stale && plane.normal[1] >= self.min_up
+    }
+}
+
+// Axis-aligned box the scattered objects must fall in
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScatterRegion {
+    pub center: [f32; 3],
+    pub half_extents: [f32; 3],
+}
+
+impl ScatterRegion {
+    fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3).all(|axis| (point[axis] - self.center[axis]).abs() <= self.half_extents[axis])
+    }
+
+    fn corners(&self) -> [[f32; 3]; 8] {
+        let [x, y, z] = self.half_extents;
+        std::array::from_fn(|i| {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            add(self.center, [sign(1) * x, sign(2) * y, sign(4) * z])
+        })
+    }
+}
+
+// The part of a plane that can receive objects, as a rectangle in its tangent basis
+struct Surface {
+    plane: usize,
+    tangent: [f32; 3],
+    bitangent: [f32; 3],
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+impl Surface {
+    // The plane's rectangle, shrunk by the margin and clipped to the region's extent
+    // along the plane. None if nothing is left
+    fn new(index: usize, plane: &ARPlane, region: &ScatterRegion, margin: f32) -> Option<Self> {
+        let (tangent, bitangent) = tangent_basis(plane.normal);
+        let [half_u, half_v] = plane.extent.map(|extent| extent * 0.5 - margin);
+        let (mut min, mut max) = ([-half_u, -half_v], [half_u, half_v]);
+
+        let offsets = region.corners().map(|corner| sub(corner, plane.center));
+        for (axis, direction) in [tangent, bitangent].into_iter().enumerate() {
+            let along = offsets.map(|offset| dot(offset, direction));
+            min[axis] = min[axis].max(along.iter().copied().fold(f32::INFINITY, f32::min));
+            max[axis] = max[axis].min(along.iter().copied().fold(f32::NEG_INFINITY, f32::max));
+        }
+        (min[0] < max[0] && min[1] < max[1]).then_some(Surface { plane: index, tangent, bitangent, min, max })
+    }
+
+    fn area(&self) -> f32 {
+        (self.max[0] - self.min[0]) * (self.max[1] - self.min[1])
+    }
+}
+
+// Rotation taking +y to `normal`
+fn stand_on(normal: [f32; 3]) -> [f32; 4] {
+    let axis = cross([0.0, 1.0, 0.0], normal);
+    let sine = length(axis);
+    if sine < 1e-6 {
+        return if normal[1] >= 0.0 { [0.0, 0.0, 0.0, 1.0] } else { [1.0, 0.0, 0.0, 0.0] };
+    }
+    quat_from_axis_angle(scale(axis, 1.0 / sine), sine.atan2(normal[1]))
+}
+
+impl ARSession {
+    // Scatter up to `count` (at most MAX_SCATTER_COUNT) objects and return their indices
+    pub(crate) fn scatter_objects(&mut self, object_type: i32, region: ScatterRegion, count: usize, rules: ScatterRules) -> Vec<usize> {
+        let count = count.min(MAX_SCATTER_COUNT);
+        let surfaces: Vec<Surface> = self.detected_planes.iter()
+            .enumerate()
+            .filter(|(_, plane)| rules.allows(plane))
+            .filter_map(|(index, plane)| Surface::new(index, plane, &region, rules.edge_margin))
+            .collect();
+        let total_area: f32 = surfaces.iter().map(Surface::area).sum();
+        if total_area <= 0.0 {
+            return Vec::new();
+        }
+
+        let mut rng = self.effect_rng(SCATTER_SALT ^ self.metrics.objects_placed);
+        let mut taken: Vec<[f32; 3]> = self.virtual_objects.iter().map(|object| object.position).collect();
+        let mut placed = Vec::new();
+        for _ in 0..count.saturating_mul(ATTEMPTS_PER_OBJECT) {
+            if placed.len() == count {
+                break;
+            }
+            let mut pick = rng.unit() * total_area;
+            let surface = surfaces.iter()
+                .find(|surface| {
+                    pick -= surface.area();
+                    pick < 0.0
+                })
+                .unwrap_or(&surfaces[surfaces.len() - 1]);
+            let plane = &self.detected_planes[surface.plane];
+            let u = surface.min[0] + rng.unit() * (surface.max[0] - surface.min[0]);
+            let v = surface.min[1] + rng.unit() * (surface.max[1] - surface.min[1]);
+            let point = add(plane.center, add(scale(surface.tangent, u), scale(surface.bitangent, v)));
+            let (yaw, size) = (rng.unit() * std::f32::consts::TAU, rules.scale[0] + rng.unit() * (rules.scale[1] - rules.scale[0]));
+
+            let spaced = taken.iter().all(|&other| length(sub(point, other)) >= rules.min_spacing);
+            if !region.contains(point) || !plane.within_extent(point) || !spaced {
+                continue;
+            }
+            let rotation = quat_mul(stand_on(plane.normal), quat_from_axis_angle([0.0, 1.0, 0.0], yaw));
+            let Some(index) = self.place_object(ARObjectType::from_code(object_type), point, rotation) else {
+                break;
+            };
+            self.virtual_objects[index].scale = size;
+            taken.push(point);
+            placed.push(index);
+        }
+        placed
+    }
+}
+
+// Scatter up to `count` objects of `object_type` (as for place_virtual_object) on the
+// planes inside the box at `center` spanning `half_x` x `half_y` x `half_z` meters
+// either side. Objects stay `min_spacing` meters from every object and `edge_margin`
+// inside their plane, on planes at most `max_slope_degrees` from level (90 allows walls,
+// 180 ceilings too) whose classification bit (1 << AR_PLANE_CLASS_*) is in
+// `classification_mask` (0 allows all), scaled between `min_scale` and `max_scale`.
+// Writes up to `capacity` object ids to `out_ids` (may be null) and returns how many
+// objects were placed, which is fewer than `count` if space or the object quota ran
+// out (and never more than 10000), or -1 for bad input
+#[no_mangle]
+pub extern "C" fn scatter_objects(
+    object_type: i32,
+    center_x: f32, center_y: f32, center_z: f32,
+    half_x: f32, half_y: f32, half_z: f32,
+    count: u32,
+    min_spacing: f32,
+    edge_margin: f32,
+    max_slope_degrees: f32,
+    classification_mask: u32,
+    min_scale: f32, max_scale: f32,
+    out_ids: *mut i32,
+    capacity: u32
+) -> i32 {
+    let center = [center_x, center_y, center_z];
+    let half_extents = [half_x, half_y, half_z];
+    let limits = [min_spacing, edge_margin, max_slope_degrees, min_scale, max_scale];
+    let valid = center.iter().chain(&half_extents).chain(&limits).all(|value| value.is_finite())
+        && half_extents.iter().all(|&h| h >= 0.0)
+        && min_spacing >= 0.0
+        && edge_margin >= 0.0
+        && (0.0..=180.0).contains(&max_slope_degrees)
+        && 0.0 < min_scale && min_scale <= max_scale;
+    if !valid {
+        return -1;
+    }
+    let region = ScatterRegion { center, half_extents };
+    let rules = ScatterRules {
+        min_spacing,
+        edge_margin,
+        min_up: max_slope_degrees.to_radians().cos(),
+        classifications: classification_mask,
+        scale: [min_scale, max_scale],
+    };
+
+    with_session(|session| {
+        let placed = session.scatter_objects(object_type, region, count as usize, rules);
+        if !out_ids.is_null() {
+            let written = placed.len().min(capacity as usize);
+            let out = unsafe { std::slice::from_raw_parts_mut(out_ids, written) };
+            for (slot, &index) in out.iter_mut().zip(&placed) {
+                *slot = index as i32;
+            }
+        }
+        placed.len() as i32
+    })
+    .unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PlaneClassification;
+
+    const UP: [f32; 3] = [0.0, 1.0, 0.0];
+
+    fn open_rules() -> ScatterRules {
+        ScatterRules { min_spacing: 0.0, edge_margin: 0.0, min_up: 0.0, classifications: 0, scale: [1.0, 1.0] }
+    }
+
+    fn around(center: [f32; 3]) -> ScatterRegion {
+        ScatterRegion { center, half_extents: [5.0, 0.5, 5.0] }
+    }
+
+    #[test]
+    fn objects_keep_their_spacing() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [0.0; 3], [2.0, 2.0], UP);
+        let existing = session.place_object(ARObjectType::Cube, [0.0; 3], [0.0, 0.0, 0.0, 1.0]).unwrap();
+
+        let rules = ScatterRules { min_spacing: 0.4, ..open_rules() };
+        let placed = session.scatter_objects(0, around([0.0; 3]), 20, rules);
+        assert!(!placed.is_empty());
+        let positions: Vec<[f32; 3]> = placed.iter().chain([&existing]).map(|&index| session.virtual_objects[index].position).collect();
+        for (i, &a) in positions.iter().enumerate() {
+            for &b in &positions[i + 1..] {
+                assert!(length(sub(a, b)) >= 0.4);
+            }
+        }
+    }
+
+    #[test]
+    fn objects_stay_inside_the_edge_margin() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [1.0, 0.0, 1.0], [2.0, 1.0], UP);
+
+        let rules = ScatterRules { edge_margin: 0.3, ..open_rules() };
+        let placed = session.scatter_objects(0, around([0.0; 3]), 50, rules);
+        assert_eq!(placed.len(), 50);
+        for index in placed {
+            let [x, y, z] = session.virtual_objects[index].position;
+            assert_eq!(y, 0.0);
+            assert!((0.3..=1.7).contains(&x) && (0.8..=1.2).contains(&z), "{:?}", [x, y, z]);
+        }
+
+        // A margin wider than the plane leaves nothing to scatter on
+        let rules = ScatterRules { edge_margin: 0.6, ..open_rules() };
+        assert!(session.scatter_objects(0, around([0.0; 3]), 5, rules).is_empty());
+    }
+
+    #[test]
+    fn classification_mask_picks_the_planes() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [0.0; 3], [1.0, 1.0], UP);
+        session.add_plane(Some("table".to_string()), [3.0, 0.8, 0.0], [1.0, 1.0], UP);
+        session.detected_planes[0].classification = PlaneClassification::Floor;
+        session.detected_planes[1].classification = PlaneClassification::Table;
+
+        let rules = ScatterRules { classifications: 1 << PlaneClassification::Table as u32, ..open_rules() };
+        let placed = session.scatter_objects(0, around([1.5, 0.5, 0.0]), 10, rules);
+        assert_eq!(placed.len(), 10);
+        assert!(placed.iter().all(|&index| session.virtual_objects[index].position[1] == 0.8));
+
+        let rules = ScatterRules { classifications: 1 << PlaneClassification::Wall as u32, ..open_rules() };
+        assert!(session.scatter_objects(0, around([1.5, 0.5, 0.0]), 10, rules).is_empty());
+    }
+
+    #[test]
+    fn huge_count_is_capped() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [0.0; 3], [1.0, 1.0], UP);
+        // Spacing leaves room for a handful; the attempts stop well short of count * 30
+        let rules = ScatterRules { min_spacing: 0.5, ..open_rules() };
+        let placed = session.scatter_objects(0, around([0.0; 3]), usize::MAX, rules);
+        assert!(!placed.is_empty() && placed.len() < 10);
+    }
+}
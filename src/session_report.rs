@@ -0,0 +1,311 @@
+// End-of-session report for QA runs: how long the session ran and how smoothly, how
+// long tracking spent in each state, how the plane count grew, the peak memory held by
+// the session's own collections, and the frame-drop counters. Exported as JSON, either
+// returned through FFI or written to a file, and attached to every headless sim run.
+//
+// Tracking state comes from the host (ARCamera.trackingState and its reason), reported
+// as it changes; time before the first report isn't counted. Plane counts and memory
+// are sampled from advance_frame once per sample interval, which doubles whenever the
+// series fills up, so the series stays bounded over long sessions. Memory is estimated
+// from collection sizes (point cloud, planes, objects, keyframes, trajectory samples),
+// not measured from the process
+
+use std::ffi::CStr;
+use std::mem::size_of;
+
+use serde::Serialize;
+
+use crate::events::copy_c_string;
+use crate::metrics::SessionMetrics;
+use crate::pose_filter::PoseSample;
+use crate::pose_graph::Keyframe;
+use crate::{with_session, ARObject, ARPlane, ARSession};
+
+pub const AR_TRACKING_NOT_AVAILABLE: i32 = 0;
+pub const AR_TRACKING_LIMITED: i32 = 1;
+pub const AR_TRACKING_NORMAL: i32 = 2;
+
+pub const AR_TRACKING_REASON_NONE: i32 = 0;
+pub const AR_TRACKING_REASON_INITIALIZING: i32 = 1;
+pub const AR_TRACKING_REASON_EXCESSIVE_MOTION: i32 = 2;
+pub const AR_TRACKING_REASON_INSUFFICIENT_FEATURES: i32 = 3;
+pub const AR_TRACKING_REASON_RELOCALIZING: i32 = 4;
+
+const INITIAL_SAMPLE_INTERVAL: f64 = 1.0;
+const MAX_SAMPLES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrackingState {
+    NotAvailable,
+    Limited(i32),
+    Normal,
+}
+
+impl TrackingState {
+    fn from_codes(state: i32, reason: i32) -> Option<Self> {
+        match (state, reason) {
+            (AR_TRACKING_NOT_AVAILABLE, _) => Some(TrackingState::NotAvailable),
+            (AR_TRACKING_LIMITED, AR_TRACKING_REASON_NONE..=AR_TRACKING_REASON_RELOCALIZING) => Some(TrackingState::Limited(reason)),
+            (AR_TRACKING_NORMAL, _) => Some(TrackingState::Normal),
+            _ => None,
+        }
+    }
+}
+
+// Seconds spent in each tracking state. Limited time is also broken down by reason
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct TrackingHistogram {
+    pub normal: f64,
+    pub limited: f64,
+    pub not_available: f64,
+    pub initializing: f64,
+    pub excessive_motion: f64,
+    pub insufficient_features: f64,
+    pub relocalizing: f64,
+    // State changes reported
+    pub transitions: u32,
+}
+
+impl TrackingHistogram {
+    fn add(&mut self, state: TrackingState, seconds: f64) {
+        match state {
+            TrackingState::NotAvailable => self.not_available += seconds,
+            TrackingState::Normal => self.normal += seconds,
+            TrackingState::Limited(reason) => {
+                self.limited += seconds;
+                match reason {
+                    AR_TRACKING_REASON_INITIALIZING => self.initializing += seconds,
+                    AR_TRACKING_REASON_EXCESSIVE_MOTION => self.excessive_motion += seconds,
+                    AR_TRACKING_REASON_INSUFFICIENT_FEATURES => self.insufficient_features += seconds,
+                    AR_TRACKING_REASON_RELOCALIZING => self.relocalizing += seconds,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+// Estimated bytes held by the session's larger collections
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MemoryUsage {
+    pub point_cloud: usize,
+    pub planes: usize,
+    pub objects: usize,
+    pub keyframes: usize,
+    pub trajectories: usize,
+    pub total: usize,
+}
+
+impl MemoryUsage {
+    fn peak(self, other: MemoryUsage) -> MemoryUsage {
+        MemoryUsage {
+            point_cloud: self.point_cloud.max(other.point_cloud),
+            planes: self.planes.max(other.planes),
+            objects: self.objects.max(other.objects),
+            keyframes: self.keyframes.max(other.keyframes),
+            trajectories: self.trajectories.max(other.trajectories),
+            total: self.total.max(other.total),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PlaneCountSample {
+    // Session time
+    pub t: f64,
+    pub planes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReportState {
+    // Session time of the first frame
+    started_at: Option<f64>,
+    frames: u64,
+    frame_time_total: f64,
+    frame_time_max: f32,
+    // Current tracking state and when it was reported
+    tracking: Option<(TrackingState, f64)>,
+    histogram: TrackingHistogram,
+    plane_counts: Vec<PlaneCountSample>,
+    sample_interval: f64,
+    memory_peak: MemoryUsage,
+}
+
+impl Default for ReportState {
+    fn default() -> Self {
+        ReportState {
+            started_at: None,
+            frames: 0,
+            frame_time_total: 0.0,
+            frame_time_max: 0.0,
+            tracking: None,
+            histogram: TrackingHistogram::default(),
+            plane_counts: Vec::new(),
+            sample_interval: INITIAL_SAMPLE_INTERVAL,
+            memory_peak: MemoryUsage::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionReport {
+    // Session time of the first frame and of the report
+    pub started_at: f64,
+    pub ended_at: f64,
+    pub duration: f64,
+    pub frames: u64,
+    pub mean_frame_time: f64,
+    pub max_frame_time: f32,
+    pub tracking: TrackingHistogram,
+    pub plane_counts: Vec<PlaneCountSample>,
+    pub memory: MemoryUsage,
+    pub memory_peak: MemoryUsage,
+    // Camera images and depth frames turned away by the ingestion queues
+    pub dropped_frames: u64,
+    pub metrics: SessionMetrics,
+}
+
+impl SessionReport {
+    pub fn capture() -> Option<Self> {
+        with_session(|session| Self::from(&*session))
+    }
+}
+
+impl From<&ARSession> for SessionReport {
+    fn from(session: &ARSession) -> Self {
+        let state = &session.report;
+        let now = session.clock.now();
+        let mut tracking = state.histogram;
+        if let Some((current, since)) = state.tracking {
+            tracking.add(current, now - since);
+        }
+        let memory = session.memory_usage();
+        let metrics = &session.metrics;
+        let started_at = state.started_at.unwrap_or(now);
+
+        SessionReport {
+            started_at,
+            ended_at: now,
+            duration: now - started_at,
+            frames: state.frames,
+            mean_frame_time: if state.frames > 0 { state.frame_time_total / state.frames as f64 } else { 0.0 },
+            max_frame_time: state.frame_time_max,
+            tracking,
+            plane_counts: state.plane_counts.clone(),
+            memory,
+            memory_peak: state.memory_peak.peak(memory),
+            dropped_frames: metrics.camera_images_dropped
+                + metrics.camera_images_coalesced
+                + metrics.depth_frames_dropped
+                + metrics.depth_frames_coalesced,
+            metrics: metrics.clone(),
+        }
+    }
+}
+
+impl ARSession {
+    fn memory_usage(&self) -> MemoryUsage {
+        #[cfg(feature = "reconstruction")]
+        let point_cloud = self.point_cloud.capacity() * size_of::<crate::pointcloud::CloudPoint>();
+        #[cfg(not(feature = "reconstruction"))]
+        let point_cloud = 0;
+        let planes = self.detected_planes.capacity() * size_of::<ARPlane>();
+        let objects = self.virtual_objects.capacity() * size_of::<ARObject>();
+        let keyframes = self.pose_graph.keyframes.capacity() * size_of::<Keyframe>();
+        let samples = self.trajectories.camera.sample_count()
+            + self.virtual_objects.iter().filter_map(|object| object.trajectory.as_ref()).map(|t| t.sample_count()).sum::<usize>();
+        let trajectories = samples * size_of::<PoseSample>();
+
+        MemoryUsage {
+            point_cloud,
+            planes,
+            objects,
+            keyframes,
+            trajectories,
+            total: point_cloud + planes + objects + keyframes + trajectories,
+        }
+    }
+
+    // Count a host frame of `dt` seconds and take a sample if one is due
+    pub(crate) fn record_frame(&mut self, dt: f32) {
+        let now = self.clock.now();
+        let memory = self.memory_usage();
+        let planes = self.detected_planes.len();
+        let state = &mut self.report;
+        let started_at = *state.started_at.get_or_insert(now);
+        state.frames += 1;
+        state.frame_time_total += dt as f64;
+        state.frame_time_max = state.frame_time_max.max(dt);
+
+        let due = state.plane_counts.last().map_or(started_at, |last| last.t + state.sample_interval);
+        if now < due {
+            return;
+        }
+        state.plane_counts.push(PlaneCountSample { t: now, planes });
+        state.memory_peak = state.memory_peak.peak(memory);
+        if state.plane_counts.len() == MAX_SAMPLES {
+            // Keep every other sample and halve the rate
+            let mut index = 0;
+            state.plane_counts.retain(|_| {
+                index += 1;
+                index % 2 == 1
+            });
+            state.sample_interval *= 2.0;
+        }
+    }
+}
+
+// Report ARKit's tracking state (AR_TRACKING_*) and, while limited, its reason
+// (AR_TRACKING_REASON_*), whenever it changes. Returns false for an unknown state or
+// reason
+#[no_mangle]
+pub extern "C" fn set_tracking_state(state: i32, reason: i32) -> bool {
+    let Some(state) = TrackingState::from_codes(state, reason) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let now = session.clock.now();
+        let report = &mut session.report;
+        match report.tracking {
+            Some((current, _)) if current == state => {}
+            Some((current, since)) => {
+                report.histogram.add(current, now - since);
+                report.histogram.transitions += 1;
+                report.tracking = Some((state, now));
+            }
+            None => report.tracking = Some((state, now)),
+        }
+    })
+    .is_some()
+}
+
+// Copy the session report as NUL-terminated JSON into `out_json`. Returns the JSON
+// length, or -1 without a session; nothing is written if it doesn't fit in `capacity`
+// bytes, so the caller can retry with a buffer of at least the length + 1
+#[no_mangle]
+pub extern "C" fn get_session_report(out_json: *mut libc::c_char, capacity: u32) -> i32 {
+    let Some(report) = SessionReport::capture() else {
+        return -1;
+    };
+    let json = serde_json::to_string(&report).unwrap_or_default();
+    unsafe { copy_c_string(&json, out_json, capacity) };
+    json.len() as i32
+}
+
+// Write the session report as pretty-printed JSON to the file at `path`, replacing it.
+// Returns false without a session or if the file can't be written
+#[no_mangle]
+pub extern "C" fn write_session_report(path: *const libc::c_char) -> bool {
+    if path.is_null() {
+        return false;
+    }
+    let path = unsafe { CStr::from_ptr(path) }.to_string_lossy().into_owned();
+    let Some(report) = SessionReport::capture() else {
+        return false;
+    };
+    let Ok(json) = serde_json::to_string_pretty(&report) else {
+        return false;
+    };
+    std::fs::write(path, json).is_ok()
+}
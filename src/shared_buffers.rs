@@ -0,0 +1,287 @@
+// Shared buffers for large per-frame payloads. Instead of handing over a fresh pointer
+// to every depth map or camera image, the host registers the memory behind its buffer
+// pool once (a plain region, or an IOSurface on iOS) and then submits frames by buffer
+// id; Rust reads them in place without copying.
+//
+// Generation counters make reuse safe. Before writing a frame into a buffer the host
+// calls begin_shared_buffer_write, which bumps the buffer's generation, and
+// end_shared_buffer_write when done; it submits the frame with that generation. A
+// submission whose generation is no longer current (the host has since rewritten the
+// buffer) or that arrives mid-write is refused as stale rather than reading torn data.
+// While Rust is reading a buffer, begin_shared_buffer_write refuses it, so the host
+// should move on to another buffer in its pool
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+
+// Returned by shared-buffer submissions whose generation is no longer current
+pub const AR_BUFFER_STALE: i32 = -3;
+
+#[cfg(target_os = "ios")]
+mod iosurface {
+    use std::ffi::c_void;
+
+    pub type IOSurfaceRef = *mut c_void;
+
+    pub const LOCK_READ_ONLY: u32 = 1;
+
+    #[link(name = "IOSurface", kind = "framework")]
+    extern "C" {
+        pub fn IOSurfaceLock(surface: IOSurfaceRef, options: u32, seed: *mut u32) -> i32;
+        pub fn IOSurfaceUnlock(surface: IOSurfaceRef, options: u32, seed: *mut u32) -> i32;
+        pub fn IOSurfaceGetBaseAddress(surface: IOSurfaceRef) -> *mut c_void;
+        pub fn IOSurfaceGetAllocSize(surface: IOSurfaceRef) -> usize;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFRetain(object: *const c_void) -> *const c_void;
+        pub fn CFRelease(object: *const c_void);
+    }
+}
+
+enum Memory {
+    Region { data: *const u8, length: usize },
+    // Retained while registered, and locked read-only around each read
+    #[cfg(target_os = "ios")]
+    Surface(iosurface::IOSurfaceRef),
+}
+
+#[derive(Debug, Default)]
+struct BufferState {
+    generation: u64,
+    writing: bool,
+    readers: u32,
+}
+
+struct SharedBuffer {
+    memory: Memory,
+    state: Mutex<BufferState>,
+}
+
+// The host guarantees registered memory stays valid and is only written between
+// begin/end_shared_buffer_write, which the buffer state serializes against reads
+unsafe impl Send for SharedBuffer {}
+unsafe impl Sync for SharedBuffer {}
+
+impl SharedBuffer {
+    fn lock(&self) -> MutexGuard<'_, BufferState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        #[cfg(target_os = "ios")]
+        if let Memory::Surface(surface) = self.memory {
+            unsafe { iosurface::CFRelease(surface) };
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    buffers: HashMap<i32, Arc<SharedBuffer>>,
+    next_id: i32,
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default).lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn buffer(id: i32) -> Option<Arc<SharedBuffer>> {
+    registry().buffers.get(&id).cloned()
+}
+
+fn register(memory: Memory) -> i32 {
+    let mut registry = registry();
+    registry.next_id += 1;
+    let id = registry.next_id;
+    registry.buffers.insert(id, Arc::new(SharedBuffer { memory, state: Mutex::default() }));
+    id
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReadError {
+    UnknownBuffer,
+    Stale,
+}
+
+// A buffer's contents at one generation, held against rewrites until dropped
+pub(crate) struct BufferRead {
+    buffer: Arc<SharedBuffer>,
+    data: *const u8,
+    length: usize,
+}
+
+impl BufferRead {
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.data, self.length) }
+    }
+
+    // The first `count` floats, or None if the buffer is too small or misaligned
+    #[cfg_attr(not(feature = "reconstruction"), allow(dead_code))]
+    pub fn floats(&self, count: usize) -> Option<&[f32]> {
+        let fits = count.checked_mul(4).is_some_and(|bytes| bytes <= self.length);
+        let aligned = (self.data as usize).is_multiple_of(std::mem::align_of::<f32>());
+        (fits && aligned).then(|| unsafe { std::slice::from_raw_parts(self.data as *const f32, count) })
+    }
+}
+
+impl Drop for BufferRead {
+    fn drop(&mut self) {
+        #[cfg(target_os = "ios")]
+        if let Memory::Surface(surface) = self.buffer.memory {
+            unsafe { iosurface::IOSurfaceUnlock(surface, iosurface::LOCK_READ_ONLY, std::ptr::null_mut()) };
+        }
+        self.buffer.lock().readers -= 1;
+    }
+}
+
+// Start reading buffer `id` as of `generation`. The reader is counted under the registry
+// lock, so the buffer can't be unregistered between lookup and read. Generation 0 is
+// never readable: it's a buffer not yet written, and begin_shared_buffer_write's failure
+pub(crate) fn read(id: i32, generation: u64) -> Result<BufferRead, ReadError> {
+    let buffer = {
+        let registry = registry();
+        let buffer = registry.buffers.get(&id).ok_or(ReadError::UnknownBuffer)?;
+        let mut state = buffer.lock();
+        if generation == 0 || state.writing || state.generation != generation {
+            return Err(ReadError::Stale);
+        }
+        state.readers += 1;
+        drop(state);
+        buffer.clone()
+    };
+
+    let (data, length) = match buffer.memory {
+        Memory::Region { data, length } => (data, length),
+        #[cfg(target_os = "ios")]
+        Memory::Surface(surface) => unsafe {
+            iosurface::IOSurfaceLock(surface, iosurface::LOCK_READ_ONLY, std::ptr::null_mut());
+            (iosurface::IOSurfaceGetBaseAddress(surface) as *const u8, iosurface::IOSurfaceGetAllocSize(surface))
+        },
+    };
+    Ok(BufferRead { buffer, data, length })
+}
+
+// Register `length` bytes at `data` as a shared buffer. The memory must stay valid until
+// unregistered. Returns the buffer id, or -1 for bad input
+#[no_mangle]
+pub extern "C" fn register_shared_buffer(data: *const c_void, length: usize) -> i32 {
+    if data.is_null() || length == 0 {
+        return -1;
+    }
+    register(Memory::Region { data: data as *const u8, length })
+}
+
+// Register an IOSurface (e.g. CVPixelBufferGetIOSurface of a pooled buffer) as a shared
+// buffer. It's retained until unregistered. Returns the buffer id, or -1 for null
+#[cfg(target_os = "ios")]
+#[no_mangle]
+pub extern "C" fn register_shared_iosurface(surface: *mut c_void) -> i32 {
+    if surface.is_null() {
+        return -1;
+    }
+    unsafe { iosurface::CFRetain(surface) };
+    register(Memory::Surface(surface))
+}
+
+// Forget a shared buffer; its memory may be freed once this returns true. Returns false
+// for an unknown id or while a frame is being read from it
+#[no_mangle]
+pub extern "C" fn unregister_shared_buffer(buffer_id: i32) -> bool {
+    let mut registry = registry();
+    let Some(buffer) = registry.buffers.get(&buffer_id) else {
+        return false;
+    };
+    if buffer.lock().readers > 0 {
+        return false;
+    }
+    registry.buffers.remove(&buffer_id);
+    true
+}
+
+// Claim a buffer for writing a new frame. Returns the generation to submit the frame
+// with, or 0 for an unknown id or a buffer still being read (use another one)
+#[no_mangle]
+pub extern "C" fn begin_shared_buffer_write(buffer_id: i32) -> u64 {
+    let Some(buffer) = buffer(buffer_id) else {
+        return 0;
+    };
+    let mut state = buffer.lock();
+    if state.readers > 0 {
+        return 0;
+    }
+    state.writing = true;
+    state.generation += 1;
+    state.generation
+}
+
+// Finish writing a frame; it can be submitted from now on. Returns false for an
+// unknown id
+#[no_mangle]
+pub extern "C" fn end_shared_buffer_write(buffer_id: i32) -> bool {
+    let Some(buffer) = buffer(buffer_id) else {
+        return false;
+    };
+    buffer.lock().writing = false;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register_bytes(bytes: &[u8]) -> i32 {
+        register_shared_buffer(bytes.as_ptr() as *const c_void, bytes.len())
+    }
+
+    #[test]
+    fn unwritten_buffer_is_not_readable() {
+        let bytes = [1u8; 16];
+        let id = register_bytes(&bytes);
+        assert!(matches!(read(id, 0), Err(ReadError::Stale)));
+        assert!(matches!(read(id + 1000, 1), Err(ReadError::UnknownBuffer)));
+        assert!(unregister_shared_buffer(id));
+    }
+
+    #[test]
+    fn read_is_refused_mid_write_and_for_an_old_generation() {
+        let bytes = [7u8; 16];
+        let id = register_bytes(&bytes);
+
+        let first = begin_shared_buffer_write(id);
+        assert_eq!(first, 1);
+        assert!(matches!(read(id, first), Err(ReadError::Stale)));
+        assert!(end_shared_buffer_write(id));
+        assert_eq!(read(id, first).unwrap().bytes(), &bytes);
+
+        let second = begin_shared_buffer_write(id);
+        assert!(end_shared_buffer_write(id));
+        assert!(matches!(read(id, first), Err(ReadError::Stale)));
+        assert!(read(id, second).is_ok());
+        assert!(unregister_shared_buffer(id));
+    }
+
+    #[test]
+    fn live_read_holds_off_writes_and_unregistering() {
+        let bytes = [3u8; 16];
+        let id = register_bytes(&bytes);
+        let generation = begin_shared_buffer_write(id);
+        end_shared_buffer_write(id);
+
+        let reading = read(id, generation).unwrap();
+        assert_eq!(begin_shared_buffer_write(id), 0);
+        assert!(!unregister_shared_buffer(id));
+        // The refused write didn't move the generation on
+        assert!(read(id, generation).is_ok());
+
+        drop(reading);
+        assert!(unregister_shared_buffer(id));
+        assert_eq!(begin_shared_buffer_write(id), 0);
+        assert!(!unregister_shared_buffer(id));
+    }
+}
@@ -0,0 +1,408 @@
+use std::ffi::CString;
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::session_report::SessionReport;
+use crate::snapshot::SessionSnapshot;
+
+// Scripted session used by the `arlens-sim` binary. Each list is keyed by time in
+// seconds; entries are merged into a single timeline before being played back
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub camera_path: Vec<CameraKeyframe>,
+    #[serde(default)]
+    pub planes: Vec<PlaneEvent>,
+    #[serde(default)]
+    pub actions: Vec<TimedAction>,
+    #[serde(default)]
+    pub expect: Option<Expectations>,
+    // Frames per second to advance per-frame state (physics, gaze, fading, ...) at
+    // between entries. Without it nothing is simulated between FFI calls
+    #[serde(default)]
+    pub frame_rate: Option<f32>,
+    // Run in deterministic mode with this seed, stepping once per frame at the frame
+    // rate, so repeated runs give identical reports
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CameraKeyframe {
+    pub t: f32,
+    pub position: [f32; 3],
+    #[serde(default = "identity_rotation")]
+    pub rotation: [f32; 4],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaneEvent {
+    pub t: f32,
+    #[serde(default)]
+    pub id: Option<String>,
+    pub center: [f32; 3],
+    pub extent: [f32; 2],
+    #[serde(default = "default_normal")]
+    pub normal: [f32; 3],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimedAction {
+    pub t: f32,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+// User actions, mirroring the FFI calls a host app would make
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Place {
+        object_type: i32,
+        position: [f32; 3],
+        #[serde(default = "identity_rotation")]
+        rotation: [f32; 4],
+    },
+    Remove {
+        object_id: i32,
+    },
+    // Make an object a gaze target (see gaze.rs)
+    Gaze {
+        object_id: i32,
+        dwell_time: f32,
+    },
+    // Set the camera distances where an object drops a level of detail (see
+    // archetypes.rs)
+    Lod {
+        object_id: i32,
+        distances: Vec<f32>,
+    },
+}
+
+// Optional assertions on the final state; any mismatch fails the run
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Expectations {
+    pub planes: Option<usize>,
+    pub objects: Option<usize>,
+}
+
+fn default_normal() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn identity_rotation() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+enum Step<'a> {
+    Camera(&'a CameraKeyframe),
+    Plane(&'a PlaneEvent),
+    Action(&'a Action),
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read scenario {}", path.display()))?;
+        Self::from_json(&contents)
+            .with_context(|| format!("invalid scenario {}", path.display()))
+    }
+
+    pub fn from_json(contents: &str) -> anyhow::Result<Self> {
+        let scenario: Scenario = serde_json::from_str(contents)?;
+
+        let times = scenario.camera_path.iter().map(|k| k.t)
+            .chain(scenario.planes.iter().map(|p| p.t))
+            .chain(scenario.actions.iter().map(|a| a.t));
+        for t in times {
+            if !t.is_finite() || t < 0.0 {
+                bail!("scenario times must be finite and non-negative, got {}", t);
+            }
+        }
+        if let Some(rate) = scenario.frame_rate {
+            if !rate.is_finite() || rate <= 0.0 {
+                bail!("frame_rate must be positive, got {}", rate);
+            }
+        }
+
+        Ok(scenario)
+    }
+
+    // Merge all entries into playback order. The sort is stable, so at equal times
+    // camera updates run before planes, and planes before user actions
+    fn timeline(&self) -> Vec<(f32, Step<'_>)> {
+        let mut steps: Vec<(f32, Step<'_>)> = Vec::new();
+        steps.extend(self.camera_path.iter().map(|k| (k.t, Step::Camera(k))));
+        steps.extend(self.planes.iter().map(|p| (p.t, Step::Plane(p))));
+        steps.extend(self.actions.iter().map(|a| (a.t, Step::Action(&a.action))));
+        steps.sort_by(|a, b| a.0.total_cmp(&b.0));
+        steps
+    }
+}
+
+// A session event as it was emitted during a run, stamped with scenario time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub t: f32,
+    pub event: serde_json::Value,
+}
+
+// Outcome of a headless run
+#[derive(Debug, Clone, Serialize)]
+pub struct SimReport {
+    pub scenario: String,
+    pub steps_run: usize,
+    pub duration: f32,
+    pub events: Vec<RecordedEvent>,
+    pub final_state: SessionSnapshot,
+    pub session_report: Option<SessionReport>,
+    pub failures: Vec<String>,
+}
+
+impl SimReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn event_log(&self) -> EventLog {
+        EventLog {
+            scenario: self.scenario.clone(),
+            events: self.events.clone(),
+            final_state: self.final_state.clone(),
+        }
+    }
+
+    // Compare this run against a golden log, adding a failure for each divergence
+    pub fn check_event_log(&mut self, golden: &EventLog, tolerance: f64) {
+        let divergences = self.event_log().diff(golden, tolerance);
+        self.failures.extend(divergences.into_iter().map(|d| format!("event log: {}", d)));
+    }
+}
+
+// Golden record of a run: every emitted event and the final state. A replay passes
+// if it matches field for field, with numbers equal to within a tolerance so small
+// floating-point differences across platforms and compilers don't fail it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventLog {
+    #[serde(default)]
+    pub scenario: String,
+    pub events: Vec<RecordedEvent>,
+    pub final_state: SessionSnapshot,
+}
+
+// Divergences past this many aren't listed; the first few are what matter
+const MAX_REPORTED_DIVERGENCES: usize = 20;
+
+impl EventLog {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read event log {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("invalid event log {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("failed to write event log {}", path.display()))
+    }
+
+    // Differences from `expected`, each naming the path to the diverging value
+    pub fn diff(&self, expected: &EventLog, tolerance: f64) -> Vec<String> {
+        let mut divergences = Vec::new();
+        if self.events.len() != expected.events.len() {
+            divergences.push(format!("expected {} events, found {}", expected.events.len(), self.events.len()));
+        }
+        for (index, (actual, expected)) in self.events.iter().zip(&expected.events).enumerate() {
+            if (actual.t - expected.t).abs() as f64 > tolerance {
+                divergences.push(format!("events[{}].t: expected {}, found {}", index, expected.t, actual.t));
+            }
+            diff_values(&format!("events[{}]", index), &actual.event, &expected.event, tolerance, &mut divergences);
+        }
+
+        let actual_state = serde_json::to_value(&self.final_state).unwrap_or_default();
+        let expected_state = serde_json::to_value(&expected.final_state).unwrap_or_default();
+        diff_values("final_state", &actual_state, &expected_state, tolerance, &mut divergences);
+
+        if divergences.len() > MAX_REPORTED_DIVERGENCES {
+            let more = divergences.len() - MAX_REPORTED_DIVERGENCES;
+            divergences.truncate(MAX_REPORTED_DIVERGENCES);
+            divergences.push(format!("... and {} more", more));
+        }
+        divergences
+    }
+}
+
+fn diff_values(path: &str, actual: &serde_json::Value, expected: &serde_json::Value, tolerance: f64, out: &mut Vec<String>) {
+    use serde_json::Value;
+
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap_or(f64::NAN), b.as_f64().unwrap_or(f64::NAN));
+            // NaN never compares within tolerance, so a NaN on either side diverges
+            let within = (a - b).abs() <= tolerance;
+            if !within {
+                out.push(format!("{}: expected {}, found {}", path, b, a));
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            if a.len() != b.len() {
+                out.push(format!("{}: expected {} entries, found {}", path, b.len(), a.len()));
+            }
+            for (index, (a, b)) in a.iter().zip(b).enumerate() {
+                diff_values(&format!("{}[{}]", path, index), a, b, tolerance, out);
+            }
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, b_value) in b {
+                match a.get(key) {
+                    Some(a_value) => diff_values(&format!("{}.{}", path, key), a_value, b_value, tolerance, out),
+                    None => out.push(format!("{}.{}: missing", path, key)),
+                }
+            }
+            for key in a.keys().filter(|key| !b.contains_key(*key)) {
+                out.push(format!("{}.{}: unexpected", path, key));
+            }
+        }
+        _ if actual != expected => out.push(format!("{}: expected {}, found {}", path, expected, actual)),
+        _ => {}
+    }
+}
+
+impl fmt::Display for SimReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = &self.final_state;
+        let metrics = &state.metrics;
+        writeln!(f, "scenario: {}", self.scenario)?;
+        writeln!(f, "steps: {} over {:.3}s", self.steps_run, self.duration)?;
+        writeln!(f, "camera: [{}, {}, {}]",
+            state.camera_position[0], state.camera_position[1], state.camera_position[2])?;
+        writeln!(f, "planes: {}", state.planes.len())?;
+        writeln!(f, "objects: {}", state.objects.len())?;
+        writeln!(f, "events: {}", self.events.len())?;
+        writeln!(f, "metrics: camera_updates={} planes_added={} objects_placed={} objects_removed={} failed_removals={}",
+            metrics.camera_updates, metrics.planes_added, metrics.objects_placed,
+            metrics.objects_removed, metrics.failed_removals)?;
+        for failure in &self.failures {
+            writeln!(f, "FAILED: {}", failure)?;
+        }
+        write!(f, "result: {}", if self.passed() { "ok" } else { "failed" })
+    }
+}
+
+// Run a scenario against a fresh global session. Any existing session is replaced
+pub fn run(scenario: &Scenario) -> SimReport {
+    crate::initialize_ar_session();
+
+    let timeline = scenario.timeline();
+    let duration = timeline.last().map(|(t, _)| *t).unwrap_or(0.0);
+    let frame_dt = scenario.frame_rate.map(|rate| 1.0 / rate);
+    if let Some(seed) = scenario.seed {
+        crate::determinism::set_deterministic_mode(true, seed, frame_dt.unwrap_or(0.0));
+    }
+    let mut next_frame = 0;
+    let mut events = Vec::new();
+
+    for (t, step) in &timeline {
+        if let Some(dt) = frame_dt {
+            advance_frames(&mut next_frame, *t, dt, &mut events);
+        }
+
+        // Drive the session timeline from scenario time so sample timestamps match it
+        crate::clock::ar_set_time_source((*t as f64 * 1e9) as u64);
+
+        match step {
+            Step::Camera(keyframe) => {
+                let ([x, y, z], [qx, qy, qz, qw]) = (keyframe.position, keyframe.rotation);
+                crate::update_camera_pose(keyframe.t as f64, x, y, z, qx, qy, qz, qw);
+            }
+            Step::Plane(plane) => {
+                // Interior NULs can't cross the FFI boundary; fall back to a generated id
+                let id = plane.id.as_deref().and_then(|id| CString::new(id).ok());
+                let id_ptr = id.as_ref().map_or(std::ptr::null(), |id| id.as_ptr());
+                crate::add_detected_plane(
+                    id_ptr,
+                    plane.center[0], plane.center[1], plane.center[2],
+                    plane.extent[0], plane.extent[1],
+                    plane.normal[0], plane.normal[1], plane.normal[2],
+                );
+            }
+            Step::Action(Action::Place { object_type, position, rotation }) => {
+                crate::place_virtual_object(
+                    *object_type,
+                    position[0], position[1], position[2],
+                    rotation[0], rotation[1], rotation[2], rotation[3],
+                );
+            }
+            Step::Action(Action::Remove { object_id }) => {
+                crate::remove_virtual_object(*object_id);
+            }
+            Step::Action(Action::Gaze { object_id, dwell_time }) => {
+                crate::gaze::set_object_gaze(*object_id, true, *dwell_time);
+            }
+            Step::Action(Action::Lod { object_id, distances }) => {
+                crate::archetypes::set_object_lod(*object_id, distances.as_ptr(), distances.len() as u32);
+            }
+        }
+        drain_events(*t, &mut events);
+    }
+    if let Some(dt) = frame_dt {
+        advance_frames(&mut next_frame, duration, dt, &mut events);
+    }
+
+    let final_state = SessionSnapshot::capture().unwrap_or_default();
+    let session_report = SessionReport::capture();
+
+    let mut failures = Vec::new();
+    if let Some(expect) = &scenario.expect {
+        if let Some(planes) = expect.planes {
+            if planes != final_state.planes.len() {
+                failures.push(format!("expected {} planes, found {}", planes, final_state.planes.len()));
+            }
+        }
+        if let Some(objects) = expect.objects {
+            if objects != final_state.objects.len() {
+                failures.push(format!("expected {} objects, found {}", objects, final_state.objects.len()));
+            }
+        }
+    }
+
+    SimReport {
+        scenario: scenario.name.clone(),
+        steps_run: timeline.len(),
+        duration,
+        events,
+        final_state,
+        session_report,
+        failures,
+    }
+}
+
+// Run every frame due up to time `until`. Frame times are computed from the frame
+// count rather than accumulated, so they don't drift over long scenarios
+fn advance_frames(next_frame: &mut u32, until: f32, dt: f32, events: &mut Vec<RecordedEvent>) {
+    loop {
+        let t = *next_frame as f32 * dt;
+        if t > until {
+            break;
+        }
+        crate::clock::ar_set_time_source((t as f64 * 1e9) as u64);
+        crate::advance_frame(dt);
+        drain_events(t, events);
+        *next_frame += 1;
+    }
+}
+
+fn drain_events(t: f32, events: &mut Vec<RecordedEvent>) {
+    crate::with_session(|session| {
+        while let Some(event) = session.events.pop() {
+            let event = serde_json::to_value(&event).unwrap_or_default();
+            events.push(RecordedEvent { t, event });
+        }
+    });
+}
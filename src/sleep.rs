@@ -0,0 +1,143 @@
+// Sleep states for dynamic bodies. A body that stays below the velocity thresholds for
+// enough consecutive frames falls asleep: the physics step stops integrating it and
+// generating its plane contacts, and other bodies collide with it as if it were fixed.
+// It wakes when something moves it: being struck by an awake body, a new velocity, a
+// direct transform change, or a change in gravity or a force field around it. Changes
+// emit object_sleep / object_wake so the host can pause effects on settled items.
+//
+// Jointed bodies never sleep, as their targets can move them at any time. Planes
+// updating under a sleeping body don't wake it
+
+use crate::events::SessionEvent;
+use crate::math::{length, quat_delta_axis_angle, sub};
+use crate::{with_session, ARSession};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SleepConfig {
+    // m/s
+    pub linear_threshold: f32,
+    // rad/s
+    pub angular_threshold: f32,
+    // Consecutive still frames before sleeping; 0 disables sleep
+    pub frames: u32,
+}
+
+impl Default for SleepConfig {
+    fn default() -> Self {
+        SleepConfig {
+            linear_threshold: 0.05,
+            angular_threshold: 0.1,
+            frames: 30,
+        }
+    }
+}
+
+impl ARSession {
+    // Count still frames and put bodies to sleep, once per frame after the physics step.
+    // Speeds are measured over the whole frame from the poses in `before`, since contact
+    // jitter within a substep can keep a resting box's instantaneous speed high
+    pub(crate) fn update_sleep(&mut self, dt: f32, before: &[([f32; 3], [f32; 4])]) {
+        let config = self.physics.sleep;
+        for (index, &(position, rotation)) in before.iter().enumerate() {
+            let jointed = self.physics.joints.iter().any(|joint| joint.object == index);
+            let object = &mut self.virtual_objects[index];
+            let Some(body) = object.body.as_mut().filter(|body| !body.sleeping) else {
+                continue;
+            };
+            let speed = length(sub(object.position, position)) / dt;
+            let spin = length(quat_delta_axis_angle(rotation, object.rotation)) / dt;
+            let still = speed < config.linear_threshold && spin < config.angular_threshold;
+            if config.frames == 0 || jointed || !still {
+                body.still_frames = 0;
+                continue;
+            }
+
+            body.still_frames += 1;
+            if body.still_frames >= config.frames {
+                body.sleeping = true;
+                body.velocity = [0.0; 3];
+                body.angular_velocity = [0.0; 3];
+                self.events.push(SessionEvent::ObjectSleep { object_id: index });
+            }
+        }
+    }
+
+    // Wake a sleeping body. Returns whether it was asleep
+    pub(crate) fn wake_object(&mut self, index: usize) -> bool {
+        let Some(body) = self.virtual_objects.get_mut(index).and_then(|o| o.body.as_mut()) else {
+            return false;
+        };
+        body.still_frames = 0;
+        if !body.sleeping {
+            return false;
+        }
+        body.sleeping = false;
+        self.events.push(SessionEvent::ObjectWake { object_id: index });
+        true
+    }
+
+    pub(crate) fn wake_all(&mut self) {
+        for index in 0..self.virtual_objects.len() {
+            self.wake_object(index);
+        }
+    }
+
+    // Wake bodies within `radius` of `center`, allowing for their size
+    pub(crate) fn wake_within(&mut self, center: [f32; 3], radius: f32) {
+        for index in 0..self.virtual_objects.len() {
+            let object = &self.virtual_objects[index];
+            if length(sub(object.position, center)) <= radius + object.bounding_radius() {
+                self.wake_object(index);
+            }
+        }
+    }
+}
+
+// Velocity thresholds below which a body counts as still, and how many consecutive
+// still frames put it to sleep (0 disables sleeping and wakes every body)
+#[no_mangle]
+pub extern "C" fn set_sleep_thresholds(linear: f32, angular: f32, frames: u32) {
+    with_session(|session| {
+        session.physics.sleep = SleepConfig {
+            linear_threshold: linear.max(0.0),
+            angular_threshold: angular.max(0.0),
+            frames,
+        };
+        if frames == 0 {
+            session.wake_all();
+        }
+    });
+}
+
+// Whether a dynamic object is asleep. False for an invalid id or a non-dynamic object
+#[no_mangle]
+pub extern "C" fn is_object_sleeping(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        session.virtual_objects.get(index)
+            .and_then(|object| object.body.as_ref())
+            .is_some_and(|body| body.sleeping)
+    })
+    .unwrap_or(false)
+}
+
+// Wake a dynamic object, e.g. before an effect nudges it. Returns false for an invalid
+// id or a non-dynamic object
+#[no_mangle]
+pub extern "C" fn wake_object(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        if session.virtual_objects.get(index).is_none_or(|object| object.body.is_none()) {
+            return false;
+        }
+        session.wake_object(index);
+        true
+    })
+    .unwrap_or(false)
+}
@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "physics")]
+use crate::contacts::ColliderShape;
+use crate::metrics::SessionMetrics;
+#[cfg(feature = "physics")]
+use crate::physics::RigidBody;
+use crate::primitives::Primitive;
+use crate::stabilizer::Stabilizer;
+#[cfg(feature = "physics")]
+use crate::surfaces::SurfaceMaterial;
+#[cfg(feature = "text")]
+use crate::text_mesh::{TextLabel, TextSpec};
+use crate::{ARObject, ARObjectType, ARPlane, ARSession, PlaneClassification, PlaneSource};
+
+// Serializable copy of the session state, used for exports and scenario reports
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub camera_position: [f32; 3],
+    #[serde(default = "identity_rotation")]
+    pub camera_rotation: [f32; 4],
+    pub planes: Vec<PlaneSnapshot>,
+    pub objects: Vec<ObjectSnapshot>,
+    pub metrics: SessionMetrics,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaneSnapshot {
+    pub id: String,
+    pub center: [f32; 3],
+    pub extent: [f32; 2],
+    pub normal: [f32; 3],
+    #[serde(default)]
+    pub source: PlaneSource,
+    // Session time when the plane was first detected
+    #[serde(default)]
+    pub detected_at: f64,
+    #[serde(default)]
+    pub classification: PlaneClassification,
+    // Physics state is left out when absent, so builds with and without the physics
+    // feature write the same snapshot
+    #[cfg(feature = "physics")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<SurfaceMaterial>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectSnapshot {
+    pub id: String,
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    #[serde(default = "unit_scale")]
+    pub scale: f32,
+    pub object_type: String,
+    // Dimensions for primitive objects, whose object_type is the primitive's name
+    #[serde(default)]
+    pub primitive: Option<Primitive>,
+    // For text objects. Restoring rebuilds the mesh, so the font must be registered;
+    // without it the object comes back as a custom "text" object
+    #[cfg(feature = "text")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<TextSpec>,
+    // Motion in flight, so a restored session picks up where it left off instead of
+    // everything starting at rest
+    #[cfg(feature = "physics")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<BodySnapshot>,
+    #[serde(default)]
+    pub stabilizer: Option<StabilizerSnapshot>,
+}
+
+// A dynamic object's physics state (see physics.rs)
+#[cfg(feature = "physics")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodySnapshot {
+    pub velocity: [f32; 3],
+    pub angular_velocity: [f32; 3],
+    pub mass: f32,
+    pub material: SurfaceMaterial,
+    pub collider: ColliderShape,
+    #[serde(default)]
+    pub sleeping: bool,
+    #[serde(default)]
+    pub still_frames: u32,
+}
+
+// A stabilizer's settings and the pose it's easing toward, if any (see stabilizer.rs).
+// The rotation deadband is in radians
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StabilizerSnapshot {
+    pub position_deadband: f32,
+    pub rotation_deadband: f32,
+    pub time_constant: f32,
+    pub teleport_distance: f32,
+    #[serde(default)]
+    pub target_position: Option<[f32; 3]>,
+    #[serde(default)]
+    pub target_rotation: Option<[f32; 4]>,
+    #[serde(default)]
+    pub settling: bool,
+}
+
+#[cfg(feature = "physics")]
+impl From<&RigidBody> for BodySnapshot {
+    fn from(body: &RigidBody) -> Self {
+        BodySnapshot {
+            velocity: body.velocity,
+            angular_velocity: body.angular_velocity,
+            mass: body.mass,
+            material: body.material,
+            collider: body.shape,
+            sleeping: body.sleeping,
+            still_frames: body.still_frames,
+        }
+    }
+}
+
+#[cfg(feature = "physics")]
+impl From<&BodySnapshot> for RigidBody {
+    fn from(snapshot: &BodySnapshot) -> Self {
+        RigidBody {
+            velocity: snapshot.velocity,
+            angular_velocity: snapshot.angular_velocity,
+            mass: snapshot.mass,
+            material: snapshot.material,
+            shape: snapshot.collider,
+            sleeping: snapshot.sleeping,
+            still_frames: snapshot.still_frames,
+        }
+    }
+}
+
+fn identity_rotation() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+fn unit_scale() -> f32 {
+    1.0
+}
+
+impl SessionSnapshot {
+    // Capture the global session, or None if it hasn't been initialized
+    pub fn capture() -> Option<Self> {
+        crate::with_session(|session| Self::from(&*session))
+    }
+}
+
+impl From<&ARSession> for SessionSnapshot {
+    fn from(session: &ARSession) -> Self {
+        SessionSnapshot {
+            camera_position: session.camera_position,
+            camera_rotation: session.camera_rotation,
+            planes: session.detected_planes.iter().map(PlaneSnapshot::from).collect(),
+            objects: session.virtual_objects.iter().map(ObjectSnapshot::from).collect(),
+            metrics: session.metrics.clone(),
+        }
+    }
+}
+
+// Rebuild a session from a snapshot, e.g. for previewing an exported session
+impl From<&SessionSnapshot> for ARSession {
+    fn from(snapshot: &SessionSnapshot) -> Self {
+        let mut session = ARSession::new();
+        session.camera_position = snapshot.camera_position;
+        session.camera_rotation = snapshot.camera_rotation;
+        session.detected_planes = snapshot.planes.iter()
+            .map(|plane| ARPlane {
+                id: plane.id.clone(),
+                center: plane.center,
+                extent: plane.extent,
+                normal: plane.normal,
+                source: plane.source,
+                classification: plane.classification,
+                #[cfg(feature = "physics")]
+                material: plane.material,
+                detected_at: plane.detected_at,
+                updated_at: plane.detected_at,
+                observed_at: plane.detected_at,
+                stale: false,
+            })
+            .collect();
+        session.virtual_objects = snapshot.objects.iter()
+            .map(|object| {
+                let mut restored = ARObject::new(object.id.clone(), object.restored_type(), object.position, object.rotation);
+                restored.scale = object.scale;
+                #[cfg(feature = "physics")]
+                {
+                    restored.body = object.body.as_ref().map(RigidBody::from);
+                }
+                restored.stabilizer = object.stabilizer.as_ref().map(Stabilizer::from);
+                restored
+            })
+            .collect();
+        session.metrics = snapshot.metrics.clone();
+        session
+    }
+}
+
+impl From<&ARPlane> for PlaneSnapshot {
+    fn from(plane: &ARPlane) -> Self {
+        PlaneSnapshot {
+            id: plane.id.clone(),
+            center: plane.center,
+            extent: plane.extent,
+            normal: plane.normal,
+            source: plane.source,
+            detected_at: plane.detected_at,
+            classification: plane.classification,
+            #[cfg(feature = "physics")]
+            material: plane.material,
+        }
+    }
+}
+
+impl ObjectSnapshot {
+    // Text objects restore as custom "text" objects in builds without the text feature
+    fn restored_type(&self) -> ARObjectType {
+        if let Some(primitive) = self.primitive {
+            return ARObjectType::Primitive(primitive);
+        }
+        #[cfg(feature = "text")]
+        if let Some(label) = self.text.clone().and_then(TextLabel::new) {
+            return ARObjectType::Text(Box::new(label));
+        }
+        match self.object_type.as_str() {
+            "cube" => ARObjectType::Cube,
+            "sphere" => ARObjectType::Sphere,
+            name => ARObjectType::Custom(name.to_string()),
+        }
+    }
+}
+
+impl From<&ARObject> for ObjectSnapshot {
+    fn from(object: &ARObject) -> Self {
+        let object_type = match &object.object_type {
+            ARObjectType::Cube => "cube".to_string(),
+            ARObjectType::Sphere => "sphere".to_string(),
+            ARObjectType::Primitive(primitive) => primitive.name().to_string(),
+            #[cfg(feature = "text")]
+            ARObjectType::Text(_) => "text".to_string(),
+            ARObjectType::Custom(name) => name.clone(),
+        };
+        let primitive = match &object.object_type {
+            ARObjectType::Primitive(primitive) => Some(*primitive),
+            _ => None,
+        };
+        #[cfg(feature = "text")]
+        let text = match &object.object_type {
+            ARObjectType::Text(label) => Some(label.spec.clone()),
+            _ => None,
+        };
+
+        ObjectSnapshot {
+            id: object.id.clone(),
+            position: object.position,
+            rotation: object.rotation,
+            scale: object.scale,
+            object_type,
+            primitive,
+            #[cfg(feature = "text")]
+            text,
+            #[cfg(feature = "physics")]
+            body: object.body.as_ref().map(BodySnapshot::from),
+            stabilizer: object.stabilizer.as_ref().map(StabilizerSnapshot::from),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "physics"))]
+mod tests {
+    use super::*;
+    use crate::stabilizer::StabilizerConfig;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+    const DT: f32 = 1.0 / 60.0;
+
+    // A session with a thrown body in flight and a stabilizer partway to its target
+    fn session_in_motion() -> ARSession {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".into()), [0.0; 3], [4.0, 4.0], [0.0, 1.0, 0.0]);
+
+        let thrown = session.place_object(ARObjectType::Cube, [0.0, 1.0, 0.0], IDENTITY).unwrap();
+        let mut body = RigidBody::for_object(&session.virtual_objects[thrown], 0.5);
+        body.velocity = [0.5, 1.0, 0.0];
+        body.angular_velocity = [0.0, 2.0, 0.0];
+        session.virtual_objects[thrown].body = Some(body);
+
+        let label = session.place_object(ARObjectType::Sphere, [1.0, 1.0, 1.0], IDENTITY).unwrap();
+        let object = &mut session.virtual_objects[label];
+        object.stabilizer = Some(Stabilizer::new(StabilizerConfig {
+            position_deadband: 0.01,
+            rotation_deadband: 0.02,
+            time_constant: 0.2,
+            teleport_distance: 1.0,
+        }));
+        object.set_target_pose([1.2, 1.0, 1.0], IDENTITY);
+
+        advance(&mut session, 3);
+        session
+    }
+
+    fn advance(session: &mut ARSession, frames: usize) {
+        for _ in 0..frames {
+            session.step_physics(DT);
+            for object in session.virtual_objects.iter_mut() {
+                object.stabilize(DT);
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot_keeps_motion_in_flight() {
+        let snapshot = SessionSnapshot::from(&session_in_motion());
+
+        let body = snapshot.objects[0].body.as_ref().unwrap();
+        assert_ne!(body.velocity, [0.0; 3]);
+        assert_eq!(body.mass, 0.5);
+        assert!(!body.sleeping);
+        let stabilizer = snapshot.objects[1].stabilizer.as_ref().unwrap();
+        assert_eq!(stabilizer.target_position, Some([1.2, 1.0, 1.0]));
+        assert!(stabilizer.settling);
+    }
+
+    #[test]
+    fn restored_session_picks_up_where_it_left_off() {
+        let mut session = session_in_motion();
+        let snapshot = SessionSnapshot::from(&session);
+        let mut restored = ARSession::from(&snapshot);
+        assert_eq!(SessionSnapshot::from(&restored), snapshot);
+
+        advance(&mut session, 30);
+        advance(&mut restored, 30);
+        assert_eq!(SessionSnapshot::from(&restored), SessionSnapshot::from(&session));
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_json() {
+        let snapshot = SessionSnapshot::from(&session_in_motion());
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<SessionSnapshot>(&json).unwrap(), snapshot);
+    }
+}
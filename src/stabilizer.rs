@@ -0,0 +1,168 @@
+// Per-object pose stabilization. Anchor corrections arrive as small, frequent
+// adjustments that make attached content visibly "swim". A stabilized object ignores
+// corrections inside a deadband, eases toward larger ones over a time constant, and
+// snaps immediately when a correction is big enough to be a genuine relocation
+
+use crate::analytics::{self, Feature};
+use crate::math::{add, length, quat_delta_axis_angle, quat_slerp, scale, sub};
+use crate::snapshot::StabilizerSnapshot;
+use crate::{with_session, ARObject};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StabilizerConfig {
+    // Corrections smaller than this (meters / radians) are ignored
+    pub position_deadband: f32,
+    pub rotation_deadband: f32,
+    // Seconds to close ~63% of the remaining gap
+    pub time_constant: f32,
+    // Corrections larger than this (meters) are applied at once
+    pub teleport_distance: f32,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Stabilizer {
+    pub config: StabilizerConfig,
+    target: Option<([f32; 3], [f32; 4])>,
+    // Once a correction leaves the deadband we ease all the way to the target
+    settling: bool,
+}
+
+impl Stabilizer {
+    pub fn new(config: StabilizerConfig) -> Self {
+        Stabilizer { config, target: None, settling: false }
+    }
+
+    // Forget any target in flight, e.g. after the object was moved directly
+    pub fn reset(&mut self) {
+        self.target = None;
+        self.settling = false;
+    }
+
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        if let Some((position, _)) = self.target.as_mut() {
+            *position = add(*position, offset);
+        }
+    }
+}
+
+impl From<&Stabilizer> for StabilizerSnapshot {
+    fn from(stabilizer: &Stabilizer) -> Self {
+        let config = stabilizer.config;
+        StabilizerSnapshot {
+            position_deadband: config.position_deadband,
+            rotation_deadband: config.rotation_deadband,
+            time_constant: config.time_constant,
+            teleport_distance: config.teleport_distance,
+            target_position: stabilizer.target.map(|(position, _)| position),
+            target_rotation: stabilizer.target.map(|(_, rotation)| rotation),
+            settling: stabilizer.settling,
+        }
+    }
+}
+
+impl From<&StabilizerSnapshot> for Stabilizer {
+    fn from(snapshot: &StabilizerSnapshot) -> Self {
+        Stabilizer {
+            config: StabilizerConfig {
+                position_deadband: snapshot.position_deadband,
+                rotation_deadband: snapshot.rotation_deadband,
+                time_constant: snapshot.time_constant,
+                teleport_distance: snapshot.teleport_distance,
+            },
+            target: snapshot.target_position.zip(snapshot.target_rotation),
+            settling: snapshot.settling,
+        }
+    }
+}
+
+impl ARObject {
+    // Move the object to a new pose driven by its anchor, through the stabilizer
+    // if it has one
+    pub(crate) fn set_target_pose(&mut self, position: [f32; 3], rotation: [f32; 4]) {
+        match &mut self.stabilizer {
+            Some(stabilizer) => {
+                if length(sub(position, self.position)) > stabilizer.config.teleport_distance {
+                    self.position = position;
+                    self.rotation = rotation;
+                    stabilizer.target = None;
+                    stabilizer.settling = false;
+                } else {
+                    stabilizer.target = Some((position, rotation));
+                }
+            }
+            None => {
+                self.position = position;
+                self.rotation = rotation;
+            }
+        }
+    }
+
+    // Advance the stabilizer by `dt` seconds
+    pub(crate) fn stabilize(&mut self, dt: f32) {
+        let Some(stabilizer) = &mut self.stabilizer else {
+            return;
+        };
+        let Some((target_position, target_rotation)) = stabilizer.target else {
+            return;
+        };
+        let config = stabilizer.config;
+
+        let offset = sub(target_position, self.position);
+        let angle = length(quat_delta_axis_angle(self.rotation, target_rotation));
+        let distance = length(offset);
+
+        if !stabilizer.settling {
+            if distance <= config.position_deadband && angle <= config.rotation_deadband {
+                return;
+            }
+            stabilizer.settling = true;
+        }
+
+        // Close enough to call it done; avoids creeping forever
+        if distance < 1e-4 && angle < 1e-3 {
+            self.position = target_position;
+            self.rotation = target_rotation;
+            stabilizer.target = None;
+            stabilizer.settling = false;
+            return;
+        }
+
+        let blend = if config.time_constant > 0.0 { 1.0 - (-dt / config.time_constant).exp() } else { 1.0 };
+        self.position = add(self.position, scale(offset, blend));
+        self.rotation = quat_slerp(self.rotation, target_rotation, blend);
+    }
+}
+
+// Enable or disable stabilization for one object. Deadbands are in meters and
+// degrees, the time constant in seconds, and the teleport distance in meters
+#[no_mangle]
+pub extern "C" fn set_object_stabilization(
+    object_id: i32,
+    enabled: bool,
+    position_deadband: f32,
+    rotation_deadband_degrees: f32,
+    time_constant: f32,
+    teleport_distance: f32,
+) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+
+        if enabled {
+            analytics::feature_used(Feature::Stabilization);
+        }
+        object.stabilizer = enabled.then(|| Stabilizer::new(StabilizerConfig {
+            position_deadband: position_deadband.max(0.0),
+            rotation_deadband: rotation_deadband_degrees.max(0.0).to_radians(),
+            time_constant: time_constant.max(0.0),
+            teleport_distance: teleport_distance.max(0.0),
+        }));
+        true
+    })
+    .unwrap_or(false)
+}
@@ -0,0 +1,214 @@
+// Stair detection. ARKit finds each tread of a staircase as its own small horizontal
+// plane; a stair is a run of such planes climbing at a regular rise and run in a
+// consistent direction. Detection is recomputed from the current planes whenever it's
+// queried, so stairs appear and grow as treads are found.
+//
+// Step count is the number of treads found. The floor below and the landing above
+// are usually too large to pass as treads, so they aren't counted
+
+use crate::math::{dot, length, normalize, scale, sub};
+use crate::pose_filter::write_out;
+use crate::{with_session, ARPlane, ARSession};
+
+// Minimum normal y for a tread (~10 degrees from horizontal)
+const TREAD_MIN_NORMAL_Y: f32 = 0.985;
+
+// Treads are shallow; a plane deeper than this along its shorter side is a floor or table
+const MAX_TREAD_DEPTH: f32 = 0.6;
+
+// Ranges covering common building codes with some slack for plane noise, in meters
+const MIN_RISE: f32 = 0.08;
+const MAX_RISE: f32 = 0.25;
+const MIN_RUN: f32 = 0.15;
+const MAX_RUN: f32 = 0.5;
+
+// Allowed deviation of a step from the stair's average so far
+const RISE_TOLERANCE: f32 = 0.04;
+const RUN_TOLERANCE: f32 = 0.1;
+
+// Cosine of the largest turn between consecutive steps (~20 degrees)
+const MIN_DIRECTION_COSINE: f32 = 0.94;
+
+const MIN_STEPS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Stair {
+    pub step_count: usize,
+    // Average height and depth of a step, in meters
+    pub rise: f32,
+    pub run: f32,
+    // Horizontal unit vector pointing up the stairs
+    pub direction: [f32; 3],
+    // Centers of the lowest and highest treads
+    pub bottom: [f32; 3],
+    pub top: [f32; 3],
+    pub plane_ids: Vec<String>,
+}
+
+fn is_tread(plane: &ARPlane) -> bool {
+    plane.normal[1] >= TREAD_MIN_NORMAL_Y && plane.extent[0].min(plane.extent[1]) <= MAX_TREAD_DEPTH
+}
+
+fn horizontal(v: [f32; 3]) -> [f32; 3] {
+    [v[0], 0.0, v[2]]
+}
+
+// Rise, run and direction from one tread to the next, if they're a plausible step
+fn step_between(lower: &ARPlane, upper: &ARPlane) -> Option<(f32, f32, [f32; 3])> {
+    let offset = sub(upper.center, lower.center);
+    let rise = offset[1];
+    let run = length(horizontal(offset));
+    if !(MIN_RISE..=MAX_RISE).contains(&rise) || !(MIN_RUN..=MAX_RUN).contains(&run) {
+        return None;
+    }
+    Some((rise, run, scale(horizontal(offset), 1.0 / run)))
+}
+
+// Planes as a stair, averaging over the whole flight
+fn stair_from(planes: &[&ARPlane]) -> Stair {
+    let bottom = planes[0].center;
+    let top = planes[planes.len() - 1].center;
+    let steps = (planes.len() - 1) as f32;
+    let climb = sub(top, bottom);
+    Stair {
+        step_count: planes.len(),
+        rise: climb[1] / steps,
+        run: length(horizontal(climb)) / steps,
+        direction: normalize(horizontal(climb)),
+        bottom,
+        top,
+        plane_ids: planes.iter().map(|plane| plane.id.clone()).collect(),
+    }
+}
+
+impl ARSession {
+    // Stairs among the detected planes, lowest first. Each plane belongs to at most one
+    pub(crate) fn detect_stairs(&self) -> Vec<Stair> {
+        let mut treads: Vec<&ARPlane> = self.detected_planes.iter().filter(|plane| is_tread(plane)).collect();
+        treads.sort_by(|a, b| a.center[1].total_cmp(&b.center[1]));
+
+        let mut used = vec![false; treads.len()];
+        let mut stairs = Vec::new();
+        for start in 0..treads.len() {
+            if used[start] {
+                continue;
+            }
+
+            // Climb greedily from this tread, taking the nearest plausible step each time
+            let mut chain = vec![start];
+            loop {
+                let flight: Vec<&ARPlane> = chain.iter().map(|&index| treads[index]).collect();
+                let average = (chain.len() > 1).then(|| stair_from(&flight));
+                let last = treads[chain[chain.len() - 1]];
+
+                let next = (0..treads.len())
+                    .filter(|&index| !used[index] && !chain.contains(&index))
+                    .filter_map(|index| step_between(last, treads[index]).map(|step| (index, step)))
+                    .filter(|(_, (rise, run, direction))| match &average {
+                        Some(stair) => {
+                            (rise - stair.rise).abs() <= RISE_TOLERANCE
+                                && (run - stair.run).abs() <= RUN_TOLERANCE
+                                && dot(*direction, stair.direction) >= MIN_DIRECTION_COSINE
+                        }
+                        None => true,
+                    })
+                    .min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+                match next {
+                    Some((index, _)) => chain.push(index),
+                    None => break,
+                }
+            }
+
+            if chain.len() >= MIN_STEPS {
+                for &index in &chain {
+                    used[index] = true;
+                }
+                let flight: Vec<&ARPlane> = chain.iter().map(|&index| treads[index]).collect();
+                stairs.push(stair_from(&flight));
+            }
+        }
+        stairs
+    }
+}
+
+// Number of stairs among the detected planes, or -1 without a session
+#[no_mangle]
+pub extern "C" fn get_stair_count() -> i32 {
+    with_session(|session| session.detect_stairs().len() as i32).unwrap_or(-1)
+}
+
+// Stair at `index` (lowest first): tread count, average rise and run (2 floats), the
+// horizontal direction up the stairs, and the centers of the bottom and top treads.
+// Outputs may be null. Returns false for a bad index
+#[no_mangle]
+pub extern "C" fn get_stair_info(
+    index: i32,
+    out_step_count: *mut u32,
+    out_rise_run: *mut f32,
+    out_direction: *mut f32,
+    out_bottom: *mut f32,
+    out_top: *mut f32,
+) -> bool {
+    let Ok(index) = usize::try_from(index) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let stair = session.detect_stairs().into_iter().nth(index)?;
+        unsafe {
+            if !out_step_count.is_null() {
+                *out_step_count = stair.step_count as u32;
+            }
+            write_out(out_rise_run, [stair.rise, stair.run]);
+            write_out(out_direction, stair.direction);
+            write_out(out_bottom, stair.bottom);
+            write_out(out_top, stair.top);
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: [f32; 3] = [0.0, 1.0, 0.0];
+
+    // Treads 1 m wide and 0.28 m deep climbing along +z, one per rise
+    fn add_treads(session: &mut ARSession, rises: &[f32]) {
+        let mut center = [0.0, 0.0, 0.0];
+        for (index, rise) in rises.iter().enumerate() {
+            center = [0.0, center[1] + rise, center[2] + 0.28];
+            session.add_plane(Some(format!("tread_{}", index)), center, [1.0, 0.28], UP);
+        }
+    }
+
+    #[test]
+    fn regular_treads_make_a_stair() {
+        let mut session = ARSession::new();
+        // The floor is too deep to be a tread
+        session.add_plane(Some("floor".to_string()), [0.0, 0.0, -1.0], [3.0, 2.0], UP);
+        add_treads(&mut session, &[0.17; 4]);
+
+        let stairs = session.detect_stairs();
+        assert_eq!(stairs.len(), 1);
+        let stair = &stairs[0];
+        assert_eq!(stair.step_count, 4);
+        assert!((stair.rise - 0.17).abs() < 1e-5);
+        assert!((stair.run - 0.28).abs() < 1e-5);
+        assert!(length(sub(stair.direction, [0.0, 0.0, 1.0])) < 1e-5);
+        assert!(length(sub(stair.bottom, [0.0, 0.17, 0.28])) < 1e-5);
+        assert!(length(sub(stair.top, [0.0, 0.68, 1.12])) < 1e-5);
+        assert_eq!(stair.plane_ids, ["tread_0", "tread_1", "tread_2", "tread_3"]);
+    }
+
+    #[test]
+    fn irregular_rise_is_not_a_stair() {
+        let mut session = ARSession::new();
+        // The third step rises far more than the first two
+        add_treads(&mut session, &[0.1, 0.1, 0.22]);
+        assert!(session.detect_stairs().is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+// Plane classifications and physical materials for detected planes. Each plane
+// responds to collisions with a friction and restitution, either set explicitly or
+// taken from a per-classification default, so a ball rolls further on a table than on
+// a sofa. ARKit can't tell carpet from hardwood, so the host can re-tune a
+// classification's default (e.g. the floor) from its own guess. A contact combines the
+// plane's and the object's values by averaging them. Classifications are always kept;
+// materials need the physics feature
+
+#[cfg(feature = "physics")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "physics")]
+use crate::ARSession;
+use crate::{with_session, PlaneClassification};
+
+#[cfg(feature = "physics")]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SurfaceMaterial {
+    pub friction: f32,
+    pub restitution: f32,
+}
+
+#[cfg(feature = "physics")]
+impl SurfaceMaterial {
+    pub(crate) fn new(friction: f32, restitution: f32) -> Self {
+        SurfaceMaterial { friction: friction.max(0.0), restitution: restitution.clamp(0.0, 1.0) }
+    }
+
+    pub(crate) fn combine(self, other: SurfaceMaterial) -> SurfaceMaterial {
+        SurfaceMaterial {
+            friction: (self.friction + other.friction) * 0.5,
+            restitution: (self.restitution + other.restitution) * 0.5,
+        }
+    }
+}
+
+// Defaults indexed by PlaneClassification
+#[cfg(feature = "physics")]
+pub(crate) fn default_materials() -> [SurfaceMaterial; PlaneClassification::COUNT] {
+    [
+        SurfaceMaterial::new(0.5, 0.3), // none
+        SurfaceMaterial::new(0.6, 0.3), // floor, assumed hard
+        SurfaceMaterial::new(0.5, 0.4), // wall
+        SurfaceMaterial::new(0.5, 0.4), // ceiling
+        SurfaceMaterial::new(0.4, 0.5), // table
+        SurfaceMaterial::new(0.9, 0.1), // seat, upholstered
+        SurfaceMaterial::new(0.2, 0.6), // window
+        SurfaceMaterial::new(0.4, 0.5), // door
+    ]
+}
+
+#[cfg(feature = "physics")]
+impl ARSession {
+    // Material a plane collides with
+    pub(crate) fn plane_material(&self, index: usize) -> SurfaceMaterial {
+        let plane = &self.detected_planes[index];
+        plane.material.unwrap_or(self.physics.classification_materials[plane.classification as usize])
+    }
+}
+
+unsafe fn with_plane(plane_id: *const libc::c_char, f: impl FnOnce(&mut crate::ARPlane)) -> bool {
+    if plane_id.is_null() {
+        return false;
+    }
+    let id = std::ffi::CStr::from_ptr(plane_id).to_string_lossy();
+    with_session(|session| {
+        let id = session.qualify_id(&id).into_owned();
+        let Some(plane) = session.detected_planes.iter_mut().find(|plane| plane.id == id) else {
+            return false;
+        };
+        f(plane);
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Record what a plane is (AR_PLANE_CLASS_*), as ARKit reports it. Planes without an
+// explicit material use their classification's default. Returns false for an unknown
+// plane or classification
+#[no_mangle]
+pub extern "C" fn set_plane_classification(plane_id: *const libc::c_char, classification: i32) -> bool {
+    let Some(classification) = PlaneClassification::from_code(classification) else {
+        return false;
+    };
+    unsafe { with_plane(plane_id, |plane| plane.classification = classification) }
+}
+
+// Give one plane its own friction and restitution. Returns false for an unknown plane
+#[cfg(feature = "physics")]
+#[no_mangle]
+pub extern "C" fn set_plane_material(plane_id: *const libc::c_char, friction: f32, restitution: f32) -> bool {
+    unsafe { with_plane(plane_id, |plane| plane.material = Some(SurfaceMaterial::new(friction, restitution))) }
+}
+
+// Revert a plane to its classification's default material
+#[cfg(feature = "physics")]
+#[no_mangle]
+pub extern "C" fn clear_plane_material(plane_id: *const libc::c_char) -> bool {
+    unsafe { with_plane(plane_id, |plane| plane.material = None) }
+}
+
+// Change the default material for a classification, e.g. softer floors once the app
+// has decided the room is carpeted. Returns false for an unknown classification
+#[cfg(feature = "physics")]
+#[no_mangle]
+pub extern "C" fn set_classification_material(classification: i32, friction: f32, restitution: f32) -> bool {
+    let Some(classification) = PlaneClassification::from_code(classification) else {
+        return false;
+    };
+    with_session(|session| {
+        session.physics.classification_materials[classification as usize] = SurfaceMaterial::new(friction, restitution);
+    })
+    .is_some()
+}
@@ -0,0 +1,435 @@
+// 3D text. Strings are laid out with a registered TrueType/OpenType font, their glyph
+// outlines flattened and triangulated, and the result extruded into a solid mesh, so a
+// label is real geometry that can be lit, occluded and walked around instead of a
+// billboarded texture. Hosts fetch the mesh with get_object_mesh like a primitive's.
+//
+// Fonts are registered once per process by name and outlive sessions. Layout is simple:
+// glyph advances without kerning or shaping, lines split on '\n' and centered, so
+// scripts that need shaping (Arabic, Devanagari) won't come out right. The text reads
+// along +x facing +z, with the mesh's bounding box centered on the object's origin.
+// Text objects collide as that box
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use ttf_parser::{Face, GlyphId, OutlineBuilder};
+
+#[cfg(feature = "physics")]
+use crate::contacts::ColliderShape;
+use crate::primitives::Mesh;
+use crate::quotas::{Resource, AR_QUOTA_EXCEEDED};
+use crate::{with_session, ARObjectType};
+
+// Straight segments per quadratic or cubic outline curve
+const CURVE_SEGMENTS: usize = 6;
+
+// Longer strings are refused rather than stalling the caller's thread
+const MAX_TEXT_CHARS: usize = 256;
+
+fn fonts() -> &'static Mutex<HashMap<String, Arc<[u8]>>> {
+    static FONTS: OnceLock<Mutex<HashMap<String, Arc<[u8]>>>> = OnceLock::new();
+    FONTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Bytes of font data registered, which count against the asset quota
+pub(crate) fn registered_font_bytes() -> usize {
+    fonts().lock().map_or(0, |fonts| fonts.values().map(|data| data.len()).sum())
+}
+
+// What a text object shows; enough to rebuild its mesh while the font is registered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextSpec {
+    pub text: String,
+    pub font: String,
+    // Em size in meters
+    pub size: f32,
+    // Extrusion in meters; 0 gives a single-sided flat label
+    pub depth: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TextLabel {
+    pub spec: TextSpec,
+    // At unit scale, centered on the origin
+    pub mesh: Mesh,
+    pub half_extents: [f32; 3],
+}
+
+impl TextLabel {
+    // None if the font isn't registered or the text has nothing to draw
+    pub fn new(spec: TextSpec) -> Option<Self> {
+        if spec.text.chars().count() > MAX_TEXT_CHARS {
+            return None;
+        }
+        let data = fonts().lock().ok()?.get(&spec.font)?.clone();
+        let face = Face::parse(&data, 0).ok()?;
+
+        let glyphs = layout(&face, &spec.text, spec.size / face.units_per_em() as f32);
+        let mut mesh = Mesh::default();
+        for contours in glyphs {
+            extrude(&mut mesh, contours, spec.depth);
+        }
+        if mesh.indices.is_empty() {
+            return None;
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for position in &mesh.positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+        let center: [f32; 3] = std::array::from_fn(|axis| (min[axis] + max[axis]) * 0.5);
+        for position in mesh.positions.iter_mut() {
+            *position = std::array::from_fn(|axis| position[axis] - center[axis]);
+        }
+        let half_extents = std::array::from_fn(|axis| (max[axis] - min[axis]) * 0.5);
+
+        Some(TextLabel { spec, mesh, half_extents })
+    }
+}
+
+// Collects a glyph's outline as closed polylines
+struct Outline {
+    contours: Vec<Vec<[f32; 2]>>,
+    current: Vec<[f32; 2]>,
+    // Applied to every point: scale, then offset
+    scale: f32,
+    offset: [f32; 2],
+}
+
+impl Outline {
+    fn point(&mut self, x: f32, y: f32) {
+        let point = [self.offset[0] + x * self.scale, self.offset[1] + y * self.scale];
+        if self.current.last() != Some(&point) {
+            self.current.push(point);
+        }
+    }
+
+    fn last(&self) -> [f32; 2] {
+        let [x, y] = self.current.last().copied().unwrap_or_default();
+        [(x - self.offset[0]) / self.scale, (y - self.offset[1]) / self.scale]
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+        self.point(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.point(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let [x0, y0] = self.last();
+        for step in 1..=CURVE_SEGMENTS {
+            let t = step as f32 / CURVE_SEGMENTS as f32;
+            let u = 1.0 - t;
+            self.point(u * u * x0 + 2.0 * u * t * x1 + t * t * x, u * u * y0 + 2.0 * u * t * y1 + t * t * y);
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let [x0, y0] = self.last();
+        for step in 1..=CURVE_SEGMENTS {
+            let t = step as f32 / CURVE_SEGMENTS as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            self.point(a * x0 + b * x1 + c * x2 + d * x, a * y0 + b * y1 + c * y2 + d * y);
+        }
+    }
+
+    fn close(&mut self) {
+        let mut contour = std::mem::take(&mut self.current);
+        if contour.len() > 1 && contour.first() == contour.last() {
+            contour.pop();
+        }
+        if contour.len() >= 3 {
+            self.contours.push(contour);
+        }
+    }
+}
+
+// Outlines of each glyph in meters, lines centered on x = 0 and stacked downward from
+// a first baseline at y = 0
+fn layout(face: &Face, text: &str, scale: f32) -> Vec<Vec<Vec<[f32; 2]>>> {
+    let line_height = (face.ascender() - face.descender() + face.line_gap()) as f32 * scale;
+    let advance = |glyph| face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale;
+
+    let mut glyphs = Vec::new();
+    for (line_index, line) in text.split('\n').enumerate() {
+        let line_glyphs: Vec<GlyphId> = line.chars().map(|c| face.glyph_index(c).unwrap_or(GlyphId(0))).collect();
+        let width: f32 = line_glyphs.iter().map(|&glyph| advance(glyph)).sum();
+        let mut pen = [-width * 0.5, -(line_index as f32) * line_height];
+        for glyph in line_glyphs {
+            let mut outline = Outline { contours: Vec::new(), current: Vec::new(), scale, offset: pen };
+            if face.outline_glyph(glyph, &mut outline).is_some() {
+                outline.close();
+                glyphs.push(outline.contours);
+            }
+            pen[0] += advance(glyph);
+        }
+    }
+    glyphs
+}
+
+fn signed_area(polygon: &[[f32; 2]]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        area += a[0] * b[1] - b[0] * a[1];
+    }
+    area * 0.5
+}
+
+fn cross2(o: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+fn contains(polygon: &[[f32; 2]], point: [f32; 2]) -> bool {
+    let mut inside = false;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        if (a[1] > point[1]) != (b[1] > point[1]) && point[0] < a[0] + (point[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// Whether segments ab and cd cross at a point interior to both
+fn segments_cross(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2]) -> bool {
+    let (d1, d2) = (cross2(a, b, c), cross2(a, b, d));
+    let (d3, d4) = (cross2(c, d, a), cross2(c, d, b));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+fn edges(polygon: &[[f32; 2]]) -> impl Iterator<Item = ([f32; 2], [f32; 2])> + '_ {
+    polygon.iter().enumerate().map(|(i, &a)| (a, polygon[(i + 1) % polygon.len()]))
+}
+
+// Splice a hole into an outer polygon through a bridge from the hole's rightmost vertex
+// to the nearest outer vertex it can see, giving one polygon with no holes
+fn bridge_hole(outer: &mut Vec<[f32; 2]>, hole: &[[f32; 2]], remaining: &[Vec<[f32; 2]>]) {
+    let start = (0..hole.len()).max_by(|&a, &b| hole[a][0].total_cmp(&hole[b][0])).unwrap_or(0);
+    let m = hole[start];
+    let distance = |p: [f32; 2]| (p[0] - m[0]).powi(2) + (p[1] - m[1]).powi(2);
+    let mut candidates: Vec<usize> = (0..outer.len()).collect();
+    candidates.sort_by(|&a, &b| distance(outer[a]).total_cmp(&distance(outer[b])));
+
+    let visible = |p: [f32; 2]| {
+        let blocked = |(a, b): ([f32; 2], [f32; 2])| segments_cross(m, p, a, b);
+        !edges(outer).any(blocked) && !edges(hole).any(blocked) && !remaining.iter().any(|other| edges(other).any(blocked))
+    };
+    let target = candidates.iter().copied().find(|&i| visible(outer[i])).unwrap_or(candidates[0]);
+
+    let mut spliced = Vec::with_capacity(outer.len() + hole.len() + 2);
+    spliced.extend_from_slice(&outer[..=target]);
+    spliced.extend(hole[start..].iter().chain(&hole[..=start]));
+    spliced.extend_from_slice(&outer[target..]);
+    *outer = spliced;
+}
+
+// Ear clipping of a counter-clockwise polygon; triangles index into it
+fn triangulate(polygon: &[[f32; 2]]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = Vec::with_capacity(polygon.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let is_ear = |i: usize| {
+            let (a, b, c) = (polygon[remaining[(i + n - 1) % n]], polygon[remaining[i]], polygon[remaining[(i + 1) % n]]);
+            if cross2(a, b, c) <= 0.0 {
+                return false;
+            }
+            // No other vertex inside or on it; bridge duplicates of its corners don't count
+            !remaining.iter().map(|&j| polygon[j]).any(|p| {
+                p != a && p != b && p != c && cross2(a, b, p) >= 0.0 && cross2(b, c, p) >= 0.0 && cross2(c, a, p) >= 0.0
+            })
+        };
+        // Degenerate input can leave no ear; clip the first vertex anyway so this ends
+        let ear = (0..n).find(|&i| is_ear(i)).unwrap_or(0);
+        let (a, b, c) = (remaining[(ear + n - 1) % n], remaining[ear], remaining[(ear + 1) % n]);
+        if cross2(polygon[a], polygon[b], polygon[c]) > 0.0 {
+            triangles.push([a, b, c]);
+        }
+        remaining.remove(ear);
+    }
+    if let [a, b, c] = remaining[..] {
+        if cross2(polygon[a], polygon[b], polygon[c]) > 0.0 {
+            triangles.push([a, b, c]);
+        }
+    }
+    triangles
+}
+
+// Add one glyph's solid to the mesh: front face at z = depth / 2, back face and sides
+// only with depth
+fn extrude(mesh: &mut Mesh, mut contours: Vec<Vec<[f32; 2]>>, depth: f32) {
+    // Nesting decides what's filled whatever the font's winding convention: contours
+    // inside an odd number of others are holes
+    let nesting: Vec<usize> = contours.iter().enumerate()
+        .map(|(i, contour)| (0..contours.len()).filter(|&j| j != i && contains(&contours[j], contour[0])).count())
+        .collect();
+    for (contour, &level) in contours.iter_mut().zip(&nesting) {
+        let hole = level % 2 == 1;
+        if (signed_area(contour) > 0.0) == hole {
+            contour.reverse();
+        }
+    }
+
+    let front = depth * 0.5;
+    for (i, outer) in contours.iter().enumerate().filter(|&(i, _)| nesting[i].is_multiple_of(2)) {
+        // Holes directly inside this outline, rightmost first
+        let mut holes: Vec<&Vec<[f32; 2]>> = (0..contours.len())
+            .filter(|&j| nesting[j] == nesting[i] + 1 && contains(outer, contours[j][0]))
+            .map(|j| &contours[j])
+            .collect();
+        let rightmost = |hole: &Vec<[f32; 2]>| hole.iter().map(|p| p[0]).fold(f32::MIN, f32::max);
+        holes.sort_by(|a, b| rightmost(b).total_cmp(&rightmost(a)));
+
+        let mut polygon = outer.clone();
+        for (k, hole) in holes.iter().enumerate() {
+            let remaining: Vec<Vec<[f32; 2]>> = holes[k + 1..].iter().map(|&h| h.clone()).collect();
+            bridge_hole(&mut polygon, hole, &remaining);
+        }
+
+        let triangles = triangulate(&polygon);
+        let first = mesh.positions.len() as u32;
+        for &[x, y] in &polygon {
+            mesh.vertex([x, y, front], [0.0, 0.0, 1.0]);
+        }
+        for &[a, b, c] in &triangles {
+            mesh.triangle(first + a as u32, first + b as u32, first + c as u32);
+        }
+        if depth <= 0.0 {
+            continue;
+        }
+        let first = mesh.positions.len() as u32;
+        for &[x, y] in &polygon {
+            mesh.vertex([x, y, -front], [0.0, 0.0, -1.0]);
+        }
+        for &[a, b, c] in &triangles {
+            mesh.triangle(first + a as u32, first + c as u32, first + b as u32);
+        }
+    }
+
+    if depth <= 0.0 {
+        return;
+    }
+    // Sides, flat shaded per edge. Outlines run counter-clockwise and holes clockwise,
+    // so the right of each edge is always outside the solid
+    for contour in &contours {
+        for (a, b) in edges(contour) {
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            let length = (dx * dx + dy * dy).sqrt();
+            if length < 1e-9 {
+                continue;
+            }
+            let normal = [dy / length, -dx / length, 0.0];
+            let quad = [[a[0], a[1], front], [a[0], a[1], -front], [b[0], b[1], -front], [b[0], b[1], front]]
+                .map(|position| mesh.vertex(position, normal));
+            mesh.quad(quad[0], quad[1], quad[2], quad[3]);
+        }
+    }
+}
+
+// Register font data (TrueType or OpenType) under `name` for place_text, replacing any
+// font of that name. The data is copied. Returns false if it can't be parsed or would
+// take registered font data over the asset quota (see quotas.rs)
+#[no_mangle]
+pub extern "C" fn register_font(name_ptr: *const libc::c_char, data: *const u8, length: u32) -> bool {
+    if name_ptr.is_null() || data.is_null() {
+        return false;
+    }
+    let name = unsafe { CStr::from_ptr(name_ptr) }.to_string_lossy().into_owned();
+    let bytes: Arc<[u8]> = unsafe { std::slice::from_raw_parts(data, length as usize) }.into();
+    is_font(&bytes) && insert_font(name, bytes)
+}
+
+pub(crate) fn is_font(data: &[u8]) -> bool {
+    Face::parse(data, 0).is_ok()
+}
+
+// Register font data already checked with is_font. Returns false if it would take
+// registered font data over the asset quota
+pub(crate) fn insert_font(name: String, bytes: Arc<[u8]>) -> bool {
+    let replaced = fonts().lock().ok().and_then(|fonts| fonts.get(&name).map(|data| data.len())).unwrap_or(0);
+    let used = (registered_font_bytes() - replaced + bytes.len()) as u64;
+    // Without a session there's no quota to check
+    if with_session(|session| session.admit(Resource::AssetBytes, used)) == Some(false) {
+        return false;
+    }
+
+    fonts().lock().map(|mut fonts| fonts.insert(name, bytes)).is_ok()
+}
+
+// Place a text label: `text` (UTF-8, '\n' for line breaks) in the registered font
+// `font_name`, `size` meters per em, extruded `depth` meters (0 for a flat label).
+// Returns the object id, AR_QUOTA_EXCEEDED if the object quota is full, or -1 for an
+// unregistered font, non-positive size, negative depth, or text with nothing to draw or
+// over 256 characters
+#[no_mangle]
+pub extern "C" fn place_text(
+    text_ptr: *const libc::c_char,
+    font_name_ptr: *const libc::c_char,
+    size: f32,
+    depth: f32,
+    pos_x: f32, pos_y: f32, pos_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32
+) -> i32 {
+    if text_ptr.is_null() || font_name_ptr.is_null() || !(size > 0.0 && size.is_finite()) || !(depth >= 0.0 && depth.is_finite()) {
+        return -1;
+    }
+    let text = unsafe { CStr::from_ptr(text_ptr) }.to_string_lossy().into_owned();
+    let font = unsafe { CStr::from_ptr(font_name_ptr) }.to_string_lossy().into_owned();
+    let Some(label) = TextLabel::new(TextSpec { text, font, size, depth }) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        session.place_object(ARObjectType::Text(Box::new(label)), [pos_x, pos_y, pos_z], [rot_x, rot_y, rot_z, rot_w])
+            .map_or(AR_QUOTA_EXCEEDED, |index| index as i32)
+    })
+    .unwrap_or(-1)
+}
+
+// Change a text object's string, keeping its font, size and depth. Returns false for
+// an invalid id, an object that isn't text, or text place_text would refuse
+#[no_mangle]
+pub extern "C" fn set_object_text(object_id: i32, text_ptr: *const libc::c_char) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+    if text_ptr.is_null() {
+        return false;
+    }
+    let text = unsafe { CStr::from_ptr(text_ptr) }.to_string_lossy().into_owned();
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        let ARObjectType::Text(label) = &mut object.object_type else {
+            return false;
+        };
+        let Some(updated) = TextLabel::new(TextSpec { text, ..label.spec.clone() }) else {
+            return false;
+        };
+        // A dynamic label's collider follows its new size
+        #[cfg(feature = "physics")]
+        if let Some(body) = object.body.as_mut() {
+            body.shape = ColliderShape::Box(updated.half_extents);
+        }
+        **label = updated;
+        #[cfg(feature = "physics")]
+        session.wake_object(index);
+        true
+    })
+    .unwrap_or(false)
+}
@@ -0,0 +1,207 @@
+// Pose histories. The camera's path is always kept, and objects can opt in; each is a
+// ring buffer holding the last `duration` seconds of poses, so the host can draw the
+// path walked during a scan or measure how far the user has moved. Camera samples are
+// stamped with their reported timestamps; object samples are taken once per frame in
+// advance_frame, stamped with the session clock.
+//
+// The pose filter keeps its own short history for interpolation; this one is longer
+// and meant for queries, so the two are sized independently
+
+use std::collections::VecDeque;
+
+use crate::math::{add, length, sub};
+use crate::pose_filter::PoseSample;
+use crate::{with_session, ARSession};
+
+// Object id that selects the camera in trajectory queries
+pub const AR_TRAJECTORY_CAMERA: i32 = -1;
+
+const DEFAULT_DURATION: f32 = 60.0;
+const MAX_DURATION: f32 = 600.0;
+
+// Hard cap per trajectory, regardless of duration (10 minutes at 100 Hz)
+const MAX_SAMPLES: usize = 60_000;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Trajectory {
+    samples: VecDeque<PoseSample>,
+}
+
+impl Trajectory {
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    // Append a sample and drop those older than `duration` before it. Samples that
+    // don't move time forward are ignored
+    fn push(&mut self, sample: PoseSample, duration: f32) {
+        if self.samples.back().is_some_and(|last| last.timestamp >= sample.timestamp) {
+            return;
+        }
+        self.samples.push_back(sample);
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.trim(duration);
+    }
+
+    // Samples after `timestamp`, oldest first
+    fn since(&self, timestamp: f64) -> impl Iterator<Item = &PoseSample> {
+        let start = self.samples.partition_point(|sample| sample.timestamp <= timestamp);
+        self.samples.range(start..)
+    }
+
+    // Distance travelled along the path after `timestamp`
+    fn length_since(&self, timestamp: f64) -> f32 {
+        let mut samples = self.since(timestamp);
+        let Some(mut previous) = samples.next() else {
+            return 0.0;
+        };
+        let mut total = 0.0;
+        for sample in samples {
+            total += length(sub(sample.position, previous.position));
+            previous = sample;
+        }
+        total
+    }
+
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        for sample in self.samples.iter_mut() {
+            sample.position = add(sample.position, offset);
+        }
+    }
+
+    fn trim(&mut self, duration: f32) {
+        let Some(newest) = self.samples.back().map(|sample| sample.timestamp) else {
+            return;
+        };
+        let oldest = newest - duration as f64;
+        while self.samples.front().is_some_and(|first| first.timestamp < oldest) {
+            self.samples.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TrajectoryState {
+    // Seconds of history kept
+    pub duration: f32,
+    pub camera: Trajectory,
+}
+
+impl Default for TrajectoryState {
+    fn default() -> Self {
+        TrajectoryState {
+            duration: DEFAULT_DURATION,
+            camera: Trajectory::default(),
+        }
+    }
+}
+
+impl ARSession {
+    pub(crate) fn record_camera_trajectory(&mut self, sample: PoseSample) {
+        let duration = self.trajectories.duration;
+        self.trajectories.camera.push(sample, duration);
+    }
+
+    // Sample every tracked object, once per frame
+    pub(crate) fn record_object_trajectories(&mut self) {
+        let timestamp = self.clock.now();
+        let duration = self.trajectories.duration;
+        for object in self.virtual_objects.iter_mut() {
+            if let Some(trajectory) = object.trajectory.as_mut() {
+                trajectory.push(PoseSample { timestamp, position: object.position, rotation: object.rotation }, duration);
+            }
+        }
+    }
+
+    fn trajectory(&self, id: i32) -> Option<&Trajectory> {
+        if id == AR_TRAJECTORY_CAMERA {
+            return Some(&self.trajectories.camera);
+        }
+        self.virtual_objects.get(usize::try_from(id).ok()?)?.trajectory.as_ref()
+    }
+}
+
+// How many seconds of poses trajectories keep (at most 600). Shortening it trims
+// existing histories
+#[no_mangle]
+pub extern "C" fn set_trajectory_duration(seconds: f32) {
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return;
+    }
+    with_session(|session| {
+        let duration = seconds.min(MAX_DURATION);
+        session.trajectories.duration = duration;
+        session.trajectories.camera.trim(duration);
+        for trajectory in session.virtual_objects.iter_mut().filter_map(|object| object.trajectory.as_mut()) {
+            trajectory.trim(duration);
+        }
+    });
+}
+
+// Start or stop keeping an object's trajectory. Stopping discards it. Returns false
+// for an invalid id
+#[no_mangle]
+pub extern "C" fn set_object_trajectory_tracking(object_id: i32, enabled: bool) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(object) = session.virtual_objects.get_mut(index) else {
+            return false;
+        };
+        match (enabled, object.trajectory.is_some()) {
+            (true, false) => object.trajectory = Some(Trajectory::default()),
+            (false, _) => object.trajectory = None,
+            _ => {}
+        }
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Poses of the camera (AR_TRAJECTORY_CAMERA) or a tracked object recorded after
+// `since_timestamp`, oldest first. Writes up to `capacity` samples; each output may be
+// null, positions take 3 floats and rotations 4 per sample. Returns the total number of
+// samples after the timestamp, or -1 for an invalid or untracked id. To read a long
+// path in pieces, query again from the last timestamp written
+#[no_mangle]
+pub extern "C" fn get_trajectory(
+    id: i32,
+    since_timestamp: f64,
+    out_timestamps: *mut f64,
+    out_positions: *mut f32,
+    out_rotations: *mut f32,
+    capacity: u32,
+) -> i32 {
+    with_session(|session| {
+        let trajectory = session.trajectory(id)?;
+        for (i, sample) in trajectory.since(since_timestamp).take(capacity as usize).enumerate() {
+            unsafe {
+                if !out_timestamps.is_null() {
+                    *out_timestamps.add(i) = sample.timestamp;
+                }
+                if !out_positions.is_null() {
+                    std::slice::from_raw_parts_mut(out_positions.add(i * 3), 3).copy_from_slice(&sample.position);
+                }
+                if !out_rotations.is_null() {
+                    std::slice::from_raw_parts_mut(out_rotations.add(i * 4), 4).copy_from_slice(&sample.rotation);
+                }
+            }
+        }
+        Some(trajectory.since(since_timestamp).count() as i32)
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// Distance in meters travelled along a trajectory after `since_timestamp`, e.g. how far
+// the user has walked during a scan. Returns -1 for an invalid or untracked id
+#[no_mangle]
+pub extern "C" fn get_trajectory_length(id: i32, since_timestamp: f64) -> f32 {
+    with_session(|session| session.trajectory(id).map(|trajectory| trajectory.length_since(since_timestamp)))
+        .flatten()
+        .unwrap_or(-1.0)
+}
@@ -0,0 +1,209 @@
+// Rust side of the UniFFI interface in arlens.udl. The generated ARLensSession
+// wraps the same global session as the C API, so both can be mixed in one app
+
+use std::fmt;
+
+use crate::{with_session, ARObjectType};
+
+uniffi::include_scaffolding!("arlens");
+
+#[derive(Debug)]
+pub enum SessionError {
+    NotInitialized,
+    InvalidObject,
+    QuotaExceeded,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::NotInitialized => write!(f, "AR session is not initialized"),
+            SessionError::InvalidObject => write!(f, "no object with that index"),
+            SessionError::QuotaExceeded => write!(f, "session quota exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+pub enum ObjectKind {
+    Cube,
+    Sphere,
+    Custom { name: String },
+}
+
+pub struct Plane {
+    pub id: String,
+    pub center: Vec3,
+    pub width: f32,
+    pub height: f32,
+    pub normal: Vec3,
+}
+
+pub struct PlacedObject {
+    pub index: u32,
+    pub id: String,
+    pub kind: ObjectKind,
+    pub position: Vec3,
+    pub rotation: Quat,
+}
+
+pub struct SessionStats {
+    pub planes: u32,
+    pub objects: u32,
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from(v: [f32; 3]) -> Self {
+        Vec3 { x: v[0], y: v[1], z: v[2] }
+    }
+}
+
+impl From<Quat> for [f32; 4] {
+    fn from(q: Quat) -> Self {
+        [q.x, q.y, q.z, q.w]
+    }
+}
+
+impl From<[f32; 4]> for Quat {
+    fn from(q: [f32; 4]) -> Self {
+        Quat { x: q[0], y: q[1], z: q[2], w: q[3] }
+    }
+}
+
+impl From<ObjectKind> for ARObjectType {
+    fn from(kind: ObjectKind) -> Self {
+        match kind {
+            ObjectKind::Cube => ARObjectType::Cube,
+            ObjectKind::Sphere => ARObjectType::Sphere,
+            ObjectKind::Custom { name } => ARObjectType::Custom(name),
+        }
+    }
+}
+
+impl From<&ARObjectType> for ObjectKind {
+    fn from(object_type: &ARObjectType) -> Self {
+        match object_type {
+            ARObjectType::Cube => ObjectKind::Cube,
+            ARObjectType::Sphere => ObjectKind::Sphere,
+            // Primitives and text are placed through the C API; here they're reported by name
+            ARObjectType::Primitive(primitive) => ObjectKind::Custom { name: primitive.name().to_string() },
+            #[cfg(feature = "text")]
+            ARObjectType::Text(_) => ObjectKind::Custom { name: "text".to_string() },
+            ARObjectType::Custom(name) => ObjectKind::Custom { name: name.clone() },
+        }
+    }
+}
+
+fn session<R>(f: impl FnOnce(&mut crate::ARSession) -> R) -> Result<R, SessionError> {
+    with_session(f).ok_or(SessionError::NotInitialized)
+}
+
+pub struct ARLensSession;
+
+impl ARLensSession {
+    // Creating a session (re)initializes the global session, like `ios_main`
+    pub fn new() -> Self {
+        crate::initialize_ar_session();
+        ARLensSession
+    }
+
+    pub fn update_camera_position(&self, position: Vec3) -> Result<(), SessionError> {
+        session(|s| s.set_camera_position(position.into()))
+    }
+
+    pub fn add_plane(
+        &self,
+        id: Option<String>,
+        center: Vec3,
+        width: f32,
+        height: f32,
+        normal: Vec3,
+    ) -> Result<(), SessionError> {
+        if session(|s| s.add_plane(id, center.into(), [width, height], normal.into()))? {
+            Ok(())
+        } else {
+            Err(SessionError::QuotaExceeded)
+        }
+    }
+
+    pub fn place_object(&self, kind: ObjectKind, position: Vec3, rotation: Quat) -> Result<u32, SessionError> {
+        session(|s| s.place_object(kind.into(), position.into(), rotation.into()))?
+            .map(|index| index as u32)
+            .ok_or(SessionError::QuotaExceeded)
+    }
+
+    pub fn remove_object(&self, index: u32) -> Result<(), SessionError> {
+        if session(|s| s.remove_object(index as usize))? {
+            Ok(())
+        } else {
+            Err(SessionError::InvalidObject)
+        }
+    }
+
+    pub fn object_distance(&self, a: u32, b: u32) -> Result<f32, SessionError> {
+        session(|s| s.object_distance(a as usize, b as usize))?
+            .ok_or(SessionError::InvalidObject)
+    }
+
+    pub fn planes(&self) -> Result<Vec<Plane>, SessionError> {
+        session(|s| {
+            s.detected_planes.iter()
+                .map(|plane| Plane {
+                    id: plane.id.clone(),
+                    center: plane.center.into(),
+                    width: plane.extent[0],
+                    height: plane.extent[1],
+                    normal: plane.normal.into(),
+                })
+                .collect()
+        })
+    }
+
+    pub fn objects(&self) -> Result<Vec<PlacedObject>, SessionError> {
+        session(|s| {
+            s.virtual_objects.iter()
+                .enumerate()
+                .map(|(index, object)| PlacedObject {
+                    index: index as u32,
+                    id: object.id.clone(),
+                    kind: (&object.object_type).into(),
+                    position: object.position.into(),
+                    rotation: object.rotation.into(),
+                })
+                .collect()
+        })
+    }
+
+    pub fn stats(&self) -> Result<SessionStats, SessionError> {
+        session(|s| SessionStats {
+            planes: s.detected_planes.len() as u32,
+            objects: s.virtual_objects.len() as u32,
+        })
+    }
+}
+
+impl Default for ARLensSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,227 @@
+// Object visibility from the world tracking camera. Objects are treated as their
+// bounding spheres and tested against the camera image, so UI such as "point toward
+// the object when it's off-screen" (including where to draw that arrow) can be driven
+// from the session's own state. The
+// camera image stands in for the screen; an on-screen view that crops the image will
+// see slightly less. Objects entirely behind people (see occlusion.rs) aren't visible,
+// and the part people cover doesn't count toward screen coverage
+
+use std::ffi::CStr;
+
+use crate::cameras::{CameraFeature, CameraStream};
+use crate::math::{length, quat_conjugate, quat_rotate, sub};
+use crate::pose_filter::write_out;
+use crate::{with_session, ARSession};
+
+// Samples per side when integrating an object's projected footprint
+const COVERAGE_GRID: usize = 16;
+
+// An object's bounding sphere projected into the image
+pub(crate) struct Footprint {
+    pub center: [f32; 2],
+    pub radius: f32,
+}
+
+// Where a HUD should draw a target: at its projection if on screen, otherwise pinned to
+// the screen edge and pointing toward it. Coordinates are normalized to the camera
+// image (0-1, y down); the angle is in radians from +x toward +y
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Indicator {
+    pub on_screen: bool,
+    pub position: [f32; 2],
+    pub angle: f32,
+}
+
+impl ARSession {
+    // World tracking camera, with default intrinsics if the host hasn't sent any
+    pub(crate) fn view_camera(&self) -> Option<CameraStream> {
+        let camera = self.camera_stream(CameraFeature::WorldTracking.stream()).or_default_intrinsics();
+        camera.pose.is_some().then_some(camera)
+    }
+
+    // Projected bounding circle of an object. None if it's entirely behind the camera
+    pub(crate) fn object_footprint(&self, camera: &CameraStream, index: usize) -> Option<Footprint> {
+        let object = self.virtual_objects.get(index)?;
+        let radius = object.bounding_radius();
+        let pose = camera.pose?;
+
+        // The camera is inside the object, which then fills the view
+        let distance = length(sub(object.position, pose.position));
+        if distance <= radius {
+            let [width, height] = camera.resolution.map(|v| v as f32);
+            return Some(Footprint { center: [width * 0.5, height * 0.5], radius: width.max(height) });
+        }
+
+        let ([x, y], _) = camera.project(object.position)?;
+        let [fx, ..] = camera.intrinsics?;
+        // Tangent of the sphere's angular radius, scaled to pixels
+        let pixel_radius = fx * radius / (distance * distance - radius * radius).sqrt();
+        Some(Footprint { center: [x, y], radius: pixel_radius })
+    }
+
+    pub(crate) fn object_visible(&self, index: usize) -> Option<bool> {
+        let camera = self.view_camera()?;
+        self.virtual_objects.get(index)?;
+        let Some(footprint) = self.object_footprint(&camera, index) else {
+            return Some(false);
+        };
+
+        // Circle against image rectangle: closest point on the rectangle to the center
+        let [width, height] = camera.resolution.map(|v| v as f32);
+        let closest_x = footprint.center[0].clamp(0.0, width);
+        let closest_y = footprint.center[1].clamp(0.0, height);
+        let (dx, dy) = (footprint.center[0] - closest_x, footprint.center[1] - closest_y);
+        Some(dx * dx + dy * dy <= footprint.radius * footprint.radius && !self.hidden_by_people(index))
+    }
+
+    // Fraction of the image covered by an object's bounding circle and not by people
+    pub(crate) fn object_coverage(&self, index: usize) -> Option<f32> {
+        let camera = self.view_camera()?;
+        self.virtual_objects.get(index)?;
+        let Some(Footprint { center, radius }) = self.object_footprint(&camera, index) else {
+            return Some(0.0);
+        };
+
+        let [width, height] = camera.resolution.map(|v| v as f32);
+        let (left, right) = ((center[0] - radius).max(0.0), (center[0] + radius).min(width));
+        let (top, bottom) = ((center[1] - radius).max(0.0), (center[1] + radius).min(height));
+        if left >= right || top >= bottom {
+            return Some(0.0);
+        }
+
+        // Sample the on-screen part of the circle's bounding box
+        let (cell_width, cell_height) = ((right - left) / COVERAGE_GRID as f32, (bottom - top) / COVERAGE_GRID as f32);
+        let mut inside = 0;
+        for row in 0..COVERAGE_GRID {
+            for column in 0..COVERAGE_GRID {
+                let x = left + (column as f32 + 0.5) * cell_width - center[0];
+                let y = top + (row as f32 + 0.5) * cell_height - center[1];
+                if x * x + y * y <= radius * radius {
+                    inside += 1;
+                }
+            }
+        }
+        let covered = inside as f32 * cell_width * cell_height;
+        let unoccluded = 1.0 - self.object_occlusion(index).unwrap_or(0.0);
+        Some((covered / (width * height)).min(1.0) * unoccluded)
+    }
+
+    // Indicator for a world point. `margin` insets the screen edge, as a fraction of
+    // the image size
+    pub(crate) fn indicator(&self, point: [f32; 3], margin: f32) -> Option<Indicator> {
+        let camera = self.view_camera()?;
+        let pose = camera.pose?;
+        let [fx, fy, ..] = camera.intrinsics?;
+        let [width, height] = camera.resolution.map(|v| v as f32);
+        let margin = margin.clamp(0.0, 0.49);
+        let (margin_x, margin_y) = (width * margin, height * margin);
+
+        if let Some(([x, y], _)) = camera.project(point) {
+            if x >= margin_x && x <= width - margin_x && y >= margin_y && y <= height - margin_y {
+                let center = [width * 0.5, height * 0.5];
+                return Some(Indicator {
+                    on_screen: true,
+                    position: [x / width, y / height],
+                    angle: (y - center[1]).atan2(x - center[0]),
+                });
+            }
+        }
+
+        // Direction on the image plane. Using the camera-space offset rather than the
+        // projection keeps it correct for targets behind the camera, whose projection
+        // would be mirrored
+        let local = quat_rotate(quat_conjugate(pose.rotation), sub(point, pose.position));
+        let mut direction = [fx * local[0], -fy * local[1]];
+        if direction == [0.0, 0.0] {
+            // Directly behind: point down, toward "turn around"
+            direction = [0.0, 1.0];
+        }
+
+        // Walk from the center along the direction to the inset edge
+        let half = [width * 0.5 - margin_x, height * 0.5 - margin_y];
+        let t = (0..2)
+            .filter(|&axis| direction[axis] != 0.0)
+            .map(|axis| half[axis] / direction[axis].abs())
+            .fold(f32::INFINITY, f32::min);
+        let position = [width * 0.5 + direction[0] * t, height * 0.5 + direction[1] * t];
+
+        Some(Indicator {
+            on_screen: false,
+            position: [position[0] / width, position[1] / height],
+            angle: direction[1].atan2(direction[0]),
+        })
+    }
+}
+
+unsafe fn write_indicator(indicator: Indicator, out_position: *mut f32, out_angle: *mut f32) -> i32 {
+    write_out(out_position, indicator.position);
+    if !out_angle.is_null() {
+        *out_angle = indicator.angle;
+    }
+    i32::from(!indicator.on_screen)
+}
+
+// Screen-edge indicator for an object: writes its normalized image position (2
+// floats) and pointing angle. Returns 0 if the object is on screen (position is its
+// projection), 1 if off screen (position is on the inset edge), or -1 for an invalid
+// id or before the first camera update. Outputs may be null
+#[no_mangle]
+pub extern "C" fn get_object_indicator(object_id: i32, margin: f32, out_position: *mut f32, out_angle: *mut f32) -> i32 {
+    let Ok(index) = usize::try_from(object_id) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        let point = session.virtual_objects.get(index)?.position;
+        session.indicator(point, margin)
+    })
+    .flatten()
+    .map_or(-1, |indicator| unsafe { write_indicator(indicator, out_position, out_angle) })
+}
+
+// As get_object_indicator, for an anchor
+#[no_mangle]
+pub extern "C" fn get_anchor_indicator(
+    anchor_id_ptr: *const libc::c_char,
+    margin: f32,
+    out_position: *mut f32,
+    out_angle: *mut f32,
+) -> i32 {
+    if anchor_id_ptr.is_null() {
+        return -1;
+    }
+    let anchor_id = unsafe { CStr::from_ptr(anchor_id_ptr) }.to_string_lossy();
+
+    with_session(|session| {
+        let point = session.anchor(&session.qualify_id(&anchor_id))?.position;
+        session.indicator(point, margin)
+    })
+    .flatten()
+    .map_or(-1, |indicator| unsafe { write_indicator(indicator, out_position, out_angle) })
+}
+
+// Whether any part of an object is within the camera's view and not entirely behind
+// people. False for an invalid id or before the first camera update
+#[no_mangle]
+pub extern "C" fn is_object_visible(object_id: i32) -> bool {
+    let Ok(index) = usize::try_from(object_id) else {
+        return false;
+    };
+
+    with_session(|session| session.object_visible(index))
+        .flatten()
+        .unwrap_or(false)
+}
+
+// Fraction (0-1) of the camera image covered by an object, or -1 for an invalid id or
+// before the first camera update
+#[no_mangle]
+pub extern "C" fn object_screen_coverage(object_id: i32) -> f32 {
+    let Ok(index) = usize::try_from(object_id) else {
+        return -1.0;
+    };
+
+    with_session(|session| session.object_coverage(index))
+        .flatten()
+        .unwrap_or(-1.0)
+}
@@ -0,0 +1,92 @@
+// JS-facing API for the browser scene preview. Runs the same session logic as the
+// device build against a standalone session loaded from an exported snapshot
+
+use wasm_bindgen::prelude::*;
+
+use crate::render::RenderSnapshot;
+use crate::snapshot::SessionSnapshot;
+use crate::{ARObjectType, ARSession};
+
+#[wasm_bindgen]
+pub struct ScenePreview {
+    session: ARSession,
+}
+
+impl Default for ScenePreview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl ScenePreview {
+    // Empty scene
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ScenePreview {
+        ScenePreview { session: ARSession::new() }
+    }
+
+    // Load a session snapshot, e.g. the `final_state` of an `arlens-sim` report
+    #[wasm_bindgen(js_name = fromSnapshot)]
+    pub fn from_snapshot(json: &str) -> Result<ScenePreview, JsError> {
+        let snapshot: SessionSnapshot = serde_json::from_str(json)?;
+        Ok(ScenePreview { session: ARSession::from(&snapshot) })
+    }
+
+    #[wasm_bindgen(js_name = toSnapshot)]
+    pub fn to_snapshot(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&SessionSnapshot::from(&self.session))?)
+    }
+
+    // Drawables for the current state, for the JS renderer
+    #[wasm_bindgen(js_name = renderSnapshot)]
+    pub fn render_snapshot(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&RenderSnapshot::from(&self.session))?)
+    }
+
+    #[wasm_bindgen(getter, js_name = planeCount)]
+    pub fn plane_count(&self) -> u32 {
+        self.session.detected_planes.len() as u32
+    }
+
+    #[wasm_bindgen(getter, js_name = objectCount)]
+    pub fn object_count(&self) -> u32 {
+        self.session.virtual_objects.len() as u32
+    }
+
+    // Move the session timeline to `milliseconds` (e.g. performance.now()). There's no
+    // clock in wasm32, so time only advances when this is called; see clock.rs
+    #[wasm_bindgen(js_name = setTime)]
+    pub fn set_time(&mut self, milliseconds: f64) {
+        self.session.clock.sync(milliseconds / 1000.0);
+    }
+
+    // Stamped with the session time, so call setTime first: a position no newer than the
+    // last one is dropped as a late sample
+    #[wasm_bindgen(js_name = setCameraPosition)]
+    pub fn set_camera_position(&mut self, x: f32, y: f32, z: f32) {
+        self.session.set_camera_position([x, y, z]);
+    }
+
+    // Returns the new object's index, using the same type codes as the C API, or
+    // undefined if the object quota is full
+    #[wasm_bindgen(js_name = placeObject)]
+    pub fn place_object(&mut self, object_type: i32, x: f32, y: f32, z: f32) -> Option<u32> {
+        self.session.place_object(
+            ARObjectType::from_code(object_type),
+            [x, y, z],
+            [0.0, 0.0, 0.0, 1.0],
+        ).map(|index| index as u32)
+    }
+
+    #[wasm_bindgen(js_name = removeObject)]
+    pub fn remove_object(&mut self, index: u32) -> bool {
+        self.session.remove_object(index as usize)
+    }
+
+    // Distance in meters between two objects, or undefined if either index is invalid
+    #[wasm_bindgen(js_name = distanceBetween)]
+    pub fn distance_between(&self, a: u32, b: u32) -> Option<f32> {
+        self.session.object_distance(a as usize, b as usize)
+    }
+}
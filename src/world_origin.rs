@@ -0,0 +1,201 @@
+// Floor detection and world re-basing. ARKit puts the world origin wherever the device
+// was when tracking started, so "up from the floor" differs every session. Once the
+// floor is found the session can be shifted so y=0 is the floor: every stored position
+// moves with it and a world_origin_changed event tells the host to shift ARKit's origin
+// the same way (ARSession.setWorldOrigin), after which poses arrive in the new frame.
+//
+// The floor is the plane ARKit classified as floor if there is one, otherwise the
+// largest upward-facing horizontal plane near the lowest one, so a stray patch found
+// under a table doesn't win over the room's floor
+
+use crate::events::SessionEvent;
+use crate::math::add;
+use crate::pose_filter::PoseSample;
+use crate::{with_session, ARSession, PlaneClassification};
+
+// Minimum normal y for a plane to count as horizontal and facing up (~10 degrees)
+const FLOOR_MIN_NORMAL_Y: f32 = 0.985;
+
+// Planes this far above the lowest horizontal plane still count as floor candidates
+const FLOOR_HEIGHT_TOLERANCE: f32 = 0.15;
+
+// Offsets smaller than this aren't worth re-basing for
+const MIN_REBASE_DISTANCE: f32 = 1e-4;
+
+impl ARSession {
+    // Index of the dominant floor plane
+    pub(crate) fn floor_plane(&self) -> Option<usize> {
+        let area = |index: &usize| {
+            let plane = &self.detected_planes[*index];
+            plane.extent[0] * plane.extent[1]
+        };
+        let horizontal: Vec<usize> = (0..self.detected_planes.len())
+            .filter(|&index| self.detected_planes[index].normal[1] >= FLOOR_MIN_NORMAL_Y)
+            .collect();
+
+        let classified = horizontal.iter()
+            .copied()
+            .filter(|&index| self.detected_planes[index].classification == PlaneClassification::Floor)
+            .max_by(|a, b| area(a).total_cmp(&area(b)));
+        if classified.is_some() {
+            return classified;
+        }
+
+        let lowest = horizontal.iter().map(|&index| self.detected_planes[index].center[1]).min_by(f32::total_cmp)?;
+        horizontal.into_iter()
+            .filter(|&index| self.detected_planes[index].center[1] <= lowest + FLOOR_HEIGHT_TOLERANCE)
+            .max_by(|a, b| area(a).total_cmp(&area(b)))
+    }
+
+    // Shift everything the session stores in world coordinates by `offset`
+    pub(crate) fn translate_world(&mut self, offset: [f32; 3]) {
+        self.camera_position = add(self.camera_position, offset);
+        self.camera_filter.translate(offset);
+        if let Some(latest) = self.camera_filter.history().last() {
+            self.publish_camera(PoseSample { position: self.camera_position, ..*latest });
+        }
+        self.trajectories.camera.translate(offset);
+        self.pose_graph.translate(offset);
+        for stream in self.cameras.iter_mut() {
+            if let Some(pose) = stream.pose.as_mut() {
+                pose.position = add(pose.position, offset);
+            }
+        }
+        for plane in self.detected_planes.iter_mut() {
+            plane.center = add(plane.center, offset);
+        }
+        for coverage in self.coverage.values_mut() {
+            coverage.translate(offset);
+        }
+        #[cfg(feature = "reconstruction")]
+        self.mesh_coverage.translate(offset);
+        for object in self.virtual_objects.iter_mut() {
+            object.position = add(object.position, offset);
+            if let Some(stabilizer) = object.stabilizer.as_mut() {
+                stabilizer.translate(offset);
+            }
+            if let Some(trajectory) = object.trajectory.as_mut() {
+                trajectory.translate(offset);
+            }
+        }
+        for anchor in self.anchors.iter_mut() {
+            anchor.translate(offset);
+        }
+        #[cfg(feature = "reconstruction")]
+        for point in self.point_cloud.iter_mut() {
+            point.position = add(point.position, offset);
+        }
+        #[cfg(feature = "reconstruction")]
+        self.mesh.translate(offset);
+        #[cfg(feature = "physics")]
+        for field in self.physics.force_fields.iter_mut() {
+            field.center = add(field.center, offset);
+        }
+        for decal in self.decals.decals.iter_mut() {
+            decal.position = add(decal.position, offset);
+        }
+        for zone in self.zones.zones.iter_mut() {
+            zone.center = add(zone.center, offset);
+        }
+        self.gestures.translate(offset);
+    }
+
+    // Re-base the world so the floor is at y=0. Returns false without a floor
+    pub(crate) fn normalize_world_to_floor(&mut self) -> bool {
+        let Some(floor) = self.floor_plane() else {
+            return false;
+        };
+        let height = self.detected_planes[floor].center[1];
+        if height.abs() < MIN_REBASE_DISTANCE {
+            return true;
+        }
+
+        let translation = [0.0, -height, 0.0];
+        self.translate_world(translation);
+        self.events.push(SessionEvent::WorldOriginChanged {
+            translation,
+            floor_plane_id: self.detected_planes[floor].id.clone(),
+        });
+        true
+    }
+}
+
+// Height (y) of the dominant floor plane. Returns false if no floor has been detected.
+// `out_height` may be null
+#[no_mangle]
+pub extern "C" fn get_floor_height(out_height: *mut f32) -> bool {
+    with_session(|session| {
+        let floor = session.floor_plane()?;
+        if !out_height.is_null() {
+            unsafe { *out_height = session.detected_planes[floor].center[1] };
+        }
+        Some(())
+    })
+    .flatten()
+    .is_some()
+}
+
+// Move the session's coordinate system so the floor is at y=0, updating every stored
+// position. Emits world_origin_changed when anything moved; the host must apply the
+// same shift to ARKit before reporting further poses. Returns false if no floor has
+// been detected
+#[no_mangle]
+pub extern "C" fn normalize_world_to_floor() -> bool {
+    with_session(|session| session.normalize_world_to_floor()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    const UP: [f32; 3] = [0.0, 1.0, 0.0];
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    #[test]
+    fn classified_floor_wins_over_a_lower_patch() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("patch".to_string()), [0.0, -1.6, 0.0], [0.4, 0.4], UP);
+        session.add_plane(Some("floor".to_string()), [1.0, -1.4, 0.0], [0.3, 0.3], UP);
+        // Unclassified, the lowest plane's neighbourhood decides
+        assert_eq!(session.floor_plane(), Some(0));
+
+        session.detected_planes[1].classification = PlaneClassification::Floor;
+        assert_eq!(session.floor_plane(), Some(1));
+    }
+
+    #[test]
+    fn largest_plane_near_the_lowest_is_the_floor() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("patch".to_string()), [0.0, -1.5, 0.0], [0.3, 0.3], UP);
+        session.add_plane(Some("room".to_string()), [0.0, -1.4, 2.0], [4.0, 3.0], UP);
+        session.add_plane(Some("table".to_string()), [0.0, -0.7, 0.0], [5.0, 5.0], UP);
+        assert_eq!(session.floor_plane(), Some(1));
+    }
+
+    #[test]
+    fn rebasing_moves_planes_objects_and_anchors() {
+        let mut session = ARSession::new();
+        session.add_plane(Some("floor".to_string()), [0.0, -1.5, 0.0], [4.0, 4.0], UP);
+        session.add_plane(Some("wall".to_string()), [0.0, 0.0, -2.0], [4.0, 3.0], [0.0, 0.0, 1.0]);
+        let object = session.place_object(ARObjectType::Cube, [0.5, -1.4, 0.5], IDENTITY).unwrap();
+        session.upsert_anchor("marker", [1.0, -1.0, 0.0], IDENTITY);
+        std::iter::from_fn(|| session.events.pop()).for_each(drop);
+
+        assert!(session.normalize_world_to_floor());
+        assert_eq!(session.detected_planes[0].center, [0.0, 0.0, 0.0]);
+        assert_eq!(session.detected_planes[1].center, [0.0, 1.5, -2.0]);
+        let position = session.virtual_objects[object].position;
+        assert!((position[1] - 0.1).abs() < 1e-6);
+        assert_eq!(session.anchor("marker").unwrap().position, [1.0, 0.5, 0.0]);
+
+        let events: Vec<SessionEvent> = std::iter::from_fn(|| session.events.pop()).collect();
+        assert!(events.iter().any(|event| matches!(event,
+            SessionEvent::WorldOriginChanged { translation, floor_plane_id }
+                if *translation == [0.0, 1.5, 0.0] && floor_plane_id == "floor")));
+
+        // Already on the floor: nothing moves again
+        assert!(session.normalize_world_to_floor());
+        assert!(session.events.pop().is_none());
+    }
+}
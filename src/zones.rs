@@ -0,0 +1,321 @@
+// Visibility zones: box volumes that gate whether the content inside them is shown.
+// A portal zone is the classic AR doorway to another world. Its doorway is the box's
+// +z face, and what's inside the box can only be seen through that doorway: from
+// outside, an object shows only while the line from the camera to it passes through the
+// opening. A room zone hides its content whenever the camera is outside the box.
+// Stepping into either zone shows all of its content, and emits zone_entered /
+// zone_exited events so the host can swap lighting or audio.
+//
+// Membership is by position and re-evaluated every frame in advance_frame, so objects
+// can move in and out. An object in several zones follows the first one created. The
+// test is per object, so an object seen partly through a doorway still needs clipping
+// to the opening. The render snapshot marks such objects with the portal id and carries
+// the doorway corners, so the host can stencil them
+
+use crate::events::SessionEvent;
+use crate::math::{add, quat_conjugate, quat_normalize, quat_rotate, scale, sub};
+use crate::pose_filter::write_out;
+use crate::{with_session, ARSession};
+
+pub const AR_ZONE_PORTAL: i32 = 0;
+pub const AR_ZONE_ROOM: i32 = 1;
+
+pub const AR_ZONE_VISIBLE: i32 = 0;
+pub const AR_ZONE_THROUGH_PORTAL: i32 = 1;
+pub const AR_ZONE_HIDDEN: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneKind {
+    Portal,
+    Room,
+}
+
+impl ZoneKind {
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            AR_ZONE_PORTAL => Some(ZoneKind::Portal),
+            AR_ZONE_ROOM => Some(ZoneKind::Room),
+            _ => None,
+        }
+    }
+}
+
+// An object's visibility as of the last frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZoneVisibility {
+    // Outside every zone, or in one the camera is inside
+    #[default]
+    Visible,
+    // Seen through a portal's doorway; should be clipped to it
+    ThroughPortal(i32),
+    Hidden,
+}
+
+impl ZoneVisibility {
+    fn code(self) -> i32 {
+        match self {
+            ZoneVisibility::Visible => AR_ZONE_VISIBLE,
+            ZoneVisibility::ThroughPortal(_) => AR_ZONE_THROUGH_PORTAL,
+            ZoneVisibility::Hidden => AR_ZONE_HIDDEN,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct VisibilityZone {
+    pub id: i32,
+    pub kind: ZoneKind,
+    pub center: [f32; 3],
+    pub rotation: [f32; 4],
+    pub half_extents: [f32; 3],
+    // Whether the camera was inside as of the last frame, for enter/exit events
+    camera_inside: bool,
+}
+
+impl VisibilityZone {
+    fn local(&self, point: [f32; 3]) -> [f32; 3] {
+        quat_rotate(quat_conjugate(self.rotation), sub(point, self.center))
+    }
+
+    fn contains(&self, point: [f32; 3]) -> bool {
+        let local = self.local(point);
+        (0..3).all(|axis| local[axis].abs() <= self.half_extents[axis])
+    }
+
+    // Whether a sphere at `point` can be seen from `eye`, outside the box, through the
+    // doorway
+    fn seen_through_doorway(&self, eye: [f32; 3], point: [f32; 3], radius: f32) -> bool {
+        let (eye, point) = (self.local(eye), self.local(point));
+        let [half_x, half_y, doorway] = self.half_extents;
+        // The doorway only opens onto its front side
+        if eye[2] <= doorway || point[2] >= eye[2] {
+            return false;
+        }
+        // Where the sight line crosses the doorway's plane, with the sphere's radius
+        // shrunk to the scale it appears at there
+        let t = (eye[2] - doorway) / (eye[2] - point[2]);
+        let crossing = add(eye, scale(sub(point, eye), t));
+        let margin = radius * t;
+        crossing[0].abs() <= half_x + margin && crossing[1].abs() <= half_y + margin
+    }
+
+    // Corners of the doorway in world space, counter-clockwise seen from the front
+    pub fn doorway(&self) -> [[f32; 3]; 4] {
+        let [x, y, z] = self.half_extents;
+        [[-x, -y, z], [x, -y, z], [x, y, z], [-x, y, z]]
+            .map(|corner| add(self.center, quat_rotate(self.rotation, corner)))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ZoneState {
+    pub zones: Vec<VisibilityZone>,
+    next_id: i32,
+}
+
+impl ARSession {
+    // Track the camera entering and leaving zones, then classify every object. Once
+    // per frame
+    pub(crate) fn update_zones(&mut self) {
+        let camera = self.camera_position;
+        for zone in self.zones.zones.iter_mut() {
+            let inside = zone.contains(camera);
+            match (zone.camera_inside, inside) {
+                (false, true) => self.events.push(SessionEvent::ZoneEntered { zone_id: zone.id }),
+                (true, false) => self.events.push(SessionEvent::ZoneExited { zone_id: zone.id }),
+                _ => {}
+            }
+            zone.camera_inside = inside;
+        }
+
+        for index in 0..self.virtual_objects.len() {
+            let object = &self.virtual_objects[index];
+            let visibility = match self.zones.zones.iter().find(|zone| zone.contains(object.position)) {
+                None => ZoneVisibility::Visible,
+                Some(zone) if zone.camera_inside => ZoneVisibility::Visible,
+                Some(zone) => match zone.kind {
+                    ZoneKind::Portal if zone.seen_through_doorway(camera, object.position, object.bounding_radius()) => {
+                        ZoneVisibility::ThroughPortal(zone.id)
+                    }
+                    _ => ZoneVisibility::Hidden,
+                },
+            };
+            self.virtual_objects[index].zone_visibility = visibility;
+        }
+    }
+
+    pub(crate) fn add_zone(&mut self, kind: ZoneKind, center: [f32; 3], rotation: [f32; 4], half_extents: [f32; 3]) -> i32 {
+        self.zones.next_id += 1;
+        let id = self.zones.next_id;
+        self.zones.zones.push(VisibilityZone { id, kind, center, rotation, half_extents, camera_inside: false });
+        id
+    }
+}
+
+// Normalized rotation for a zone, or None for a degenerate one
+fn zone_shape(rotation: [f32; 4], half_extents: [f32; 3]) -> Option<[f32; 4]> {
+    let extents_valid = half_extents.iter().all(|h| h.is_finite() && *h > 0.0);
+    let rotation_valid = rotation.iter().all(|q| q.is_finite()) && rotation.iter().any(|&q| q != 0.0);
+    (extents_valid && rotation_valid).then(|| quat_normalize(rotation))
+}
+
+// Add a visibility zone (AR_ZONE_*): a box at `center` with `rotation`, spanning
+// `half_x` x `half_y` x `half_z` meters either side. A portal's doorway is its local +z
+// face, 2 * half_x wide and 2 * half_y tall. Takes effect from the next frame. Returns
+// the zone id, or -1 for bad input
+#[no_mangle]
+pub extern "C" fn add_visibility_zone(
+    kind: i32,
+    center_x: f32, center_y: f32, center_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32,
+    half_x: f32, half_y: f32, half_z: f32
+) -> i32 {
+    let half_extents = [half_x, half_y, half_z];
+    let (Some(kind), Some(rotation)) = (ZoneKind::from_code(kind), zone_shape([rot_x, rot_y, rot_z, rot_w], half_extents)) else {
+        return -1;
+    };
+
+    with_session(|session| session.add_zone(kind, [center_x, center_y, center_z], rotation, half_extents)).unwrap_or(-1)
+}
+
+// Move or resize a zone. Returns false for an unknown id or bad input
+#[no_mangle]
+pub extern "C" fn update_visibility_zone(
+    zone_id: i32,
+    center_x: f32, center_y: f32, center_z: f32,
+    rot_x: f32, rot_y: f32, rot_z: f32, rot_w: f32,
+    half_x: f32, half_y: f32, half_z: f32
+) -> bool {
+    let half_extents = [half_x, half_y, half_z];
+    let Some(rotation) = zone_shape([rot_x, rot_y, rot_z, rot_w], half_extents) else {
+        return false;
+    };
+
+    with_session(|session| {
+        let Some(zone) = session.zones.zones.iter_mut().find(|zone| zone.id == zone_id) else {
+            return false;
+        };
+        zone.center = [center_x, center_y, center_z];
+        zone.rotation = rotation;
+        zone.half_extents = half_extents;
+        true
+    })
+    .unwrap_or(false)
+}
+
+// Remove a zone; its content is shown normally from the next frame
+#[no_mangle]
+pub extern "C" fn remove_visibility_zone(zone_id: i32) -> bool {
+    with_session(|session| {
+        let Some(position) = session.zones.zones.iter().position(|zone| zone.id == zone_id) else {
+            return false;
+        };
+        session.zones.zones.remove(position);
+        true
+    })
+    .unwrap_or(false)
+}
+
+// An object's zone visibility as of the last frame: AR_ZONE_VISIBLE, AR_ZONE_HIDDEN, or
+// AR_ZONE_THROUGH_PORTAL with the portal's id in `out_zone_id` (may be null). Returns
+// -1 for an invalid id
+#[no_mangle]
+pub extern "C" fn get_object_zone_visibility(object_id: i32, out_zone_id: *mut i32) -> i32 {
+    let Ok(index) = usize::try_from(object_id) else {
+        return -1;
+    };
+
+    with_session(|session| {
+        let visibility = session.virtual_objects.get(index)?.zone_visibility;
+        if let (ZoneVisibility::ThroughPortal(zone_id), false) = (visibility, out_zone_id.is_null()) {
+            unsafe { *out_zone_id = zone_id };
+        }
+        Some(visibility.code())
+    })
+    .flatten()
+    .unwrap_or(-1)
+}
+
+// A portal's doorway corners in world space (4 x 3 floats, counter-clockwise seen from
+// the front), for stencilling content seen through it. Returns false for an unknown id
+// or a room zone
+#[no_mangle]
+pub extern "C" fn get_portal_doorway(zone_id: i32, out_corners: *mut f32) -> bool {
+    with_session(|session| {
+        let Some(zone) = session.zones.zones.iter().find(|zone| zone.id == zone_id && zone.kind == ZoneKind::Portal) else {
+            return false;
+        };
+        let corners = zone.doorway();
+        let flat: [f32; 12] = std::array::from_fn(|i| corners[i / 3][i % 3]);
+        unsafe { write_out(out_corners, flat) };
+        true
+    })
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARObjectType;
+
+    const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn drain(session: &mut ARSession) -> Vec<SessionEvent> {
+        std::iter::from_fn(|| session.events.pop()).collect()
+    }
+
+    #[test]
+    fn portal_content_shows_only_through_the_doorway() {
+        let mut session = ARSession::new();
+        // Doorway on the z=1 face, 2 m square
+        let portal = session.add_zone(ZoneKind::Portal, [0.0; 3], IDENTITY, [1.0, 1.0, 1.0]);
+        let inside = session.place_object(ARObjectType::Cube, [0.0, 0.0, -0.5], IDENTITY).unwrap();
+        let outside = session.place_object(ARObjectType::Cube, [0.0, 0.0, 3.0], IDENTITY).unwrap();
+
+        // In front of the doorway, looking through it
+        session.set_camera_pose(0.0, [0.0, 0.0, 4.0], IDENTITY);
+        session.update_zones();
+        assert_eq!(session.virtual_objects[inside].zone_visibility, ZoneVisibility::ThroughPortal(portal));
+        assert_eq!(session.virtual_objects[outside].zone_visibility, ZoneVisibility::Visible);
+
+        // In front but off to the side: the sight line misses the opening
+        session.set_camera_pose(0.1, [6.0, 0.0, 2.0], IDENTITY);
+        session.update_zones();
+        assert_eq!(session.virtual_objects[inside].zone_visibility, ZoneVisibility::Hidden);
+
+        // Behind the doorway's plane
+        session.set_camera_pose(0.2, [0.0, 0.0, -4.0], IDENTITY);
+        session.update_zones();
+        assert_eq!(session.virtual_objects[inside].zone_visibility, ZoneVisibility::Hidden);
+        assert!(drain(&mut session).iter().all(|event| !matches!(event, SessionEvent::ZoneEntered { .. } | SessionEvent::ZoneExited { .. })));
+    }
+
+    #[test]
+    fn stepping_in_and_out_emits_events() {
+        let mut session = ARSession::new();
+        let room = session.add_zone(ZoneKind::Room, [0.0; 3], IDENTITY, [2.0, 2.0, 2.0]);
+        let object = session.place_object(ARObjectType::Cube, [1.0, 0.0, 1.0], IDENTITY).unwrap();
+
+        session.set_camera_pose(0.0, [0.0, 0.0, 5.0], IDENTITY);
+        session.update_zones();
+        assert_eq!(session.virtual_objects[object].zone_visibility, ZoneVisibility::Hidden);
+        drain(&mut session);
+
+        session.set_camera_pose(0.1, [0.0, 0.0, 1.5], IDENTITY);
+        session.update_zones();
+        assert_eq!(session.virtual_objects[object].zone_visibility, ZoneVisibility::Visible);
+        let events = drain(&mut session);
+        assert!(events.iter().any(|event| matches!(event, SessionEvent::ZoneEntered { zone_id } if *zone_id == room)));
+
+        // Staying inside emits nothing further
+        session.set_camera_pose(0.2, [0.0, 0.0, 1.0], IDENTITY);
+        session.update_zones();
+        assert!(drain(&mut session).iter().all(|event| !matches!(event, SessionEvent::ZoneEntered { .. })));
+
+        session.set_camera_pose(0.3, [0.0, 0.0, 5.0], IDENTITY);
+        session.update_zones();
+        assert_eq!(session.virtual_objects[object].zone_visibility, ZoneVisibility::Hidden);
+        let events = drain(&mut session);
+        assert!(events.iter().any(|event| matches!(event, SessionEvent::ZoneExited { zone_id } if *zone_id == room)));
+    }
+}
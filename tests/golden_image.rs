@@ -0,0 +1,37 @@
+// Renders the final frame of each scenario that has a golden image in goldens/ and
+// compares it, as `arlens-sim <scenario> --render <frame> --golden <image>` does. Needs
+// the offscreen feature: cargo test --features offscreen
+
+#![cfg(feature = "offscreen")]
+
+use std::path::Path;
+
+use ARLens::offscreen::{self, Image};
+use ARLens::render::RenderSnapshot;
+use ARLens::sim::{self, Scenario};
+
+// Same size and tolerance as the README's arlens-sim example
+const RENDER_SIZE: (u32, u32) = (640, 480);
+const TOLERANCE: u8 = 2;
+
+#[test]
+fn scenarios_match_golden_images() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut compared = 0;
+    for entry in std::fs::read_dir(root.join("goldens")).unwrap() {
+        let path = entry.unwrap().path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()?.strip_suffix(".png")) else {
+            continue;
+        };
+        let scenario = Scenario::load(&root.join("scenarios").join(format!("{}.json", name))).unwrap();
+        let report = sim::run(&scenario);
+        assert!(report.passed(), "{}: {}", name, report);
+
+        let snapshot = RenderSnapshot::capture().unwrap();
+        let image = offscreen::render(&snapshot, RENDER_SIZE.0, RENDER_SIZE.1);
+        let diff = image.compare(&Image::load_png(&path).unwrap(), TOLERANCE).unwrap();
+        assert!(diff.is_match(), "{}: {} pixels differ (max channel delta {})", name, diff.differing_pixels, diff.max_channel_delta);
+        compared += 1;
+    }
+    assert!(compared > 0, "no golden images found");
+}
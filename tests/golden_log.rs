@@ -0,0 +1,32 @@
+// Replays each scenario that has a golden event log in goldens/ against it, as
+// `arlens-sim <scenario> --golden-log <log>` does. Runs as its own test binary because
+// the simulator drives the process-wide session. Feature-specific state is left out of
+// snapshots when absent, so the same logs hold with any feature set
+
+use std::path::Path;
+
+use ARLens::sim::{self, EventLog, Scenario};
+
+// Plane detection times follow the wall clock between scenario steps, which is off by
+// microseconds from run to run
+const LOG_TOLERANCE: f64 = 1e-4;
+
+#[test]
+fn scenarios_match_golden_logs() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut replayed = 0;
+    for entry in std::fs::read_dir(root.join("goldens")).unwrap() {
+        let path = entry.unwrap().path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()?.strip_suffix(".log.json")) else {
+            continue;
+        };
+        let scenario = Scenario::load(&root.join("scenarios").join(format!("{}.json", name))).unwrap();
+        let golden = EventLog::load(&path).unwrap();
+
+        let mut report = sim::run(&scenario);
+        report.check_event_log(&golden, LOG_TOLERANCE);
+        assert!(report.passed(), "{}: {}", name, report);
+        replayed += 1;
+    }
+    assert!(replayed > 0, "no golden logs found");
+}
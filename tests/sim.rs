@@ -0,0 +1,55 @@
+// Runs scenarios through the headless simulator, as `arlens-sim <scenario>` does. Runs
+// as its own test binary because the simulator drives the process-wide session; the
+// tests here take turns with it
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use ARLens::sim::{self, Action, Expectations, PlaneEvent, Scenario, TimedAction};
+
+static SESSION: Mutex<()> = Mutex::new(());
+
+#[test]
+fn basic_placement_runs_to_its_expected_state() {
+    let _session = SESSION.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/basic_placement.json");
+    let scenario = Scenario::load(&path).unwrap();
+
+    let report = sim::run(&scenario);
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.scenario, "basic placement");
+    assert_eq!(report.steps_run, 8);
+    assert_eq!(report.duration, 2.0);
+
+    let state = &report.final_state;
+    assert_eq!(state.planes.len(), 2);
+    assert_eq!(state.objects.len(), 1);
+    assert_eq!(state.camera_position, [0.4, 1.4, -0.6]);
+    assert_eq!(state.metrics.camera_updates, 3);
+    assert_eq!(state.metrics.planes_added, 2);
+    assert_eq!(state.metrics.objects_placed, 2);
+    assert_eq!(state.metrics.objects_removed, 1);
+    assert!(report.to_string().ends_with("result: ok"));
+}
+
+#[test]
+fn unmet_expectations_fail_the_run() {
+    let _session = SESSION.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let scenario = Scenario {
+        name: "unmet".to_string(),
+        planes: vec![PlaneEvent { t: 0.0, id: None, center: [0.0; 3], extent: [1.0, 1.0], normal: [0.0, 1.0, 0.0] }],
+        actions: vec![
+            TimedAction { t: 0.5, action: Action::Place { object_type: 0, position: [0.0, 0.05, 0.0], rotation: [0.0, 0.0, 0.0, 1.0] } },
+            // Nothing has this id; the removal fails and is counted
+            TimedAction { t: 1.0, action: Action::Remove { object_id: 7 } },
+        ],
+        expect: Some(Expectations { planes: Some(1), objects: Some(2) }),
+        ..Scenario::default()
+    };
+
+    let report = sim::run(&scenario);
+    assert!(!report.passed());
+    assert_eq!(report.failures, vec!["expected 2 objects, found 1".to_string()]);
+    assert_eq!(report.final_state.metrics.failed_removals, 1);
+    assert!(report.to_string().ends_with("result: failed"));
+}